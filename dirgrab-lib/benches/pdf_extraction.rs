@@ -0,0 +1,68 @@
+// --- FILE: dirgrab-lib/benches/pdf_extraction.rs ---
+
+//! Compares sequential and parallel PDF extraction (`GrabConfig::pdf_jobs`)
+//! on a directory of copies of the test fixture.
+//!
+//! Run with `cargo bench -p dirgrab-lib --bench pdf_extraction`; set
+//! `DIRGRAB_BENCH_PDFS` to change the number of documents (default 16).
+
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use dirgrab_lib::{grab_contents, GrabConfig};
+
+const ROUNDS: u32 = 3;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let count: usize = match std::env::var("DIRGRAB_BENCH_PDFS") {
+        Ok(value) => value.parse()?,
+        Err(_) => 16,
+    };
+    let fixture = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/sample.pdf");
+    let dir = tempfile::tempdir()?;
+    for index in 0..count {
+        fs::copy(&fixture, dir.path().join(format!("doc{:03}.pdf", index)))?;
+    }
+    let config = GrabConfig {
+        target_path: dir.path().to_path_buf(),
+        add_headers: true,
+        no_git: true,
+        convert_pdf: true,
+        ..Default::default()
+    };
+
+    let parallel_jobs = thread::available_parallelism().map_or(1, usize::from);
+    let (sequential, expected) = time_grab(&config, 1)?;
+    let (parallel, output) = time_grab(&config, parallel_jobs)?;
+    assert_eq!(output, expected, "parallel extraction changed the output");
+
+    println!("{} PDFs, best of {} rounds:", count, ROUNDS);
+    println!("  --pdf-jobs 1: {:>8.1?}", sequential);
+    println!("  --pdf-jobs {}: {:>8.1?}", parallel_jobs, parallel);
+    println!(
+        "  speedup: {:.2}x",
+        sequential.as_secs_f64() / parallel.as_secs_f64()
+    );
+    Ok(())
+}
+
+/// Best wall-clock time of `ROUNDS` grabs with `jobs` threads, plus the output.
+fn time_grab(
+    config: &GrabConfig,
+    jobs: usize,
+) -> Result<(Duration, String), Box<dyn std::error::Error>> {
+    let config = GrabConfig {
+        pdf_jobs: Some(jobs),
+        ..config.clone()
+    };
+    let mut best = Duration::MAX;
+    let mut output = String::new();
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+        output = grab_contents(&config)?;
+        best = best.min(start.elapsed());
+    }
+    Ok((best, output))
+}
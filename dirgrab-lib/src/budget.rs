@@ -0,0 +1,77 @@
+// --- FILE: dirgrab-lib/src/budget.rs ---
+
+use log::debug;
+
+/// Computes per-file byte limits so that `sizes` fit into `available` bytes.
+/// Each file first keeps its `floors` bytes (its minimum lines), then the rest
+/// of `available` is shared by shrinking every file by the same fraction. If
+/// the floors alone do not fit, they are dropped and every whole file shrinks
+/// instead. Returns `None` if everything already fits.
+pub(crate) fn proportional_limits(
+    sizes: &[usize],
+    floors: &[usize],
+    available: usize,
+) -> Option<Vec<usize>> {
+    let total: usize = sizes.iter().sum();
+    if total <= available {
+        return None;
+    }
+    let floor_total: usize = floors.iter().sum();
+    let floors: Vec<usize> = if floor_total < available {
+        floors.to_vec()
+    } else {
+        debug!(
+            "Context budget: minimum lines ({} bytes) do not fit into {}; ignoring them",
+            floor_total, available
+        );
+        vec![0; sizes.len()]
+    };
+    let floor_total: usize = floors.iter().sum();
+    let fraction = (available - floor_total) as f64 / (total - floor_total) as f64;
+    debug!(
+        "Context budget: {} body bytes must fit into {} (keeping {:.1}% beyond the minimum lines)",
+        total,
+        available,
+        fraction * 100.0
+    );
+    Some(
+        sizes
+            .iter()
+            .zip(&floors)
+            .map(|(&size, &floor)| floor + ((size - floor) as f64 * fraction).floor() as usize)
+            .collect(),
+    )
+}
+
+/// Rendered size of the first `lines` lines of `body`, where `line_cost(i)`
+/// is the bytes added in front of line `i` (zero-based), e.g. a line prefix.
+pub(crate) fn rendered_len(body: &str, lines: usize, line_cost: impl Fn(usize) -> usize) -> usize {
+    body.split_inclusive('\n')
+        .take(lines)
+        .enumerate()
+        .map(|(index, line)| line_cost(index) + line.len())
+        .sum()
+}
+
+/// Returns the longest prefix of `body` that ends on a line boundary and
+/// whose rendered size (see `rendered_len`) fits in `max_bytes`. Returns
+/// `None` if the whole body fits.
+pub(crate) fn truncate_body(
+    body: &str,
+    max_bytes: usize,
+    line_cost: impl Fn(usize) -> usize,
+) -> Option<&str> {
+    if rendered_len(body, usize::MAX, &line_cost) <= max_bytes {
+        return None;
+    }
+    let mut end = 0;
+    let mut used = 0;
+    for (index, line) in body.split_inclusive('\n').enumerate() {
+        used += line_cost(index) + line.len();
+        if used > max_bytes {
+            break;
+        }
+        end += line.len();
+    }
+    Some(&body[..end])
+}
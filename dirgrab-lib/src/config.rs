@@ -2,17 +2,25 @@
 
 use std::path::PathBuf; // Needed for the struct definition
 
+use serde::{Deserialize, Serialize};
+
 /// Configuration for the dirgrab operation.
 ///
 /// This struct holds all the settings needed to control how `dirgrab`
 /// finds and processes files within the specified target directory.
 /// It is typically constructed by the calling application (e.g., the CLI)
 /// based on user input.
-#[derive(Debug, Clone)]
+///
+/// The struct implements `Serialize`/`Deserialize` so a resolved configuration
+/// can be persisted and reloaded later. Missing fields deserialize to their
+/// `Default` values, and paths are written as forward-slash strings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct GrabConfig {
     // Made public here
     /// The starting path for the operation (directory or Git repository).
     /// `dirgrab` will operate within this path. It will be canonicalized internally.
+    #[serde(with = "portable_path")]
     pub target_path: PathBuf,
 
     /// If true, adds `'--- FILE: <filename> ---'` headers before each file's content
@@ -20,13 +28,167 @@ pub struct GrabConfig {
     /// Git repository root (if applicable) or the target path.
     pub add_headers: bool,
 
+    /// If true, a file whose content matches an earlier file's after
+    /// normalizing whitespace (CRLF/CR line endings, trailing whitespace, trailing
+    /// blank lines) is emitted as a header-only
+    /// `(duplicate of <path>, whitespace-insensitive)` entry. The first
+    /// occurrence is kept verbatim.
+    pub dedupe_normalized: bool,
+
+    /// If true, every body is hashed as read (before any transformation) and
+    /// files with byte-identical content are reported in
+    /// `GrabOutput::duplicates`, whether or not `dedupe_normalized` is set.
+    /// The output itself is unaffected.
+    pub find_duplicates: bool,
+
+    /// If true, each body is normalized per the `.editorconfig` files above it
+    /// (up to one marked `root = true`): `end_of_line`, `trim_trailing_whitespace`,
+    /// and `insert_final_newline` are applied before any other transformation.
+    /// Files no section matches, and unset properties, are left as they are.
+    pub respect_editorconfig: bool,
+
+    /// If true, the import block at the top of Rust (`use`, `extern crate`),
+    /// Python (`import`, `from ... import`), and JS/TS (`import`, `require`)
+    /// files is removed from the body. Only leading imports are touched; other
+    /// languages pass through unchanged.
+    pub strip_imports: bool,
+
+    /// If true, the same leading import block is replaced by a one-line
+    /// comment naming the imported modules, e.g.
+    /// `// imports: serde, tokio, anyhow (+3 more)`. Ignored when
+    /// `strip_imports` is set.
+    pub fold_imports: bool,
+
+    /// If true, files that are symlinks are read through to their target.
+    /// Otherwise (the default) a symlink is emitted as
+    /// `--- FILE: link (symlink -> target) ---` with the target path as its body,
+    /// so a link pointing outside the tree is never read.
+    pub follow_symlinks: bool,
+
+    /// If true, symlink targets shown in headers and bodies are rewritten
+    /// relative to the repo root (target path outside Git mode) when they
+    /// resolve inside it, and as absolute paths otherwise.
+    pub relativize_symlink_targets: bool,
+
+    /// If true, files are separated by a single newline instead of a blank
+    /// line: each body ends with exactly one `\n` and no separator follows.
+    pub tight: bool,
+
+    /// If true, every file is emitted as a header only, annotated with the size
+    /// and line count of its (decoded or extracted) content, e.g.
+    /// `--- FILE: src/main.rs (2048 bytes, 64 lines) ---`. Bodies are dropped, so
+    /// `GrabbedFile::body_range` is empty; the tree is unaffected.
+    pub no_body: bool,
+
+    /// If set, the files of a directory whose selected direct files are all
+    /// smaller than this many bytes are emitted together under one
+    /// `--- DIR: dir/ ---` header, each introduced by a short `--- name ---`
+    /// separator instead of a full `FILE:` header. Needs at least two files per
+    /// directory; only applies to plain (non-link, non-`prefix_lines`) headers.
+    pub group_under: Option<usize>,
+
+    /// If true, `GrabOutput::language_summary` holds a
+    /// `Primary language: Rust (68% of files)` line, computed from the
+    /// extensions of the selected files and shown before the tree.
+    pub detect_language: bool,
+
+    /// If true, `GrabOutput::selection` holds a `SELECTION` block describing
+    /// the configuration behind the grab (target, Git mode, untracked files,
+    /// excludes, change filters) and the number of selected files.
+    pub explain_selection: bool,
+
+    /// If true, prefixes every body line with `path:linenum: ` (like `grep -rn`)
+    /// instead of emitting a header line per file, so the output can be grepped
+    /// line by line. Replaces `add_headers`/`header_style`; `context_budget`
+    /// sizes bodies before the prefixes are added.
+    pub prefix_lines: bool,
+
+    /// If true, emits a YAML front-matter block (`path`, `bytes`, `modified`)
+    /// before each file body, after the header line if headers are enabled.
+    /// Its byte range is reported separately in `GrabbedFile::frontmatter_range`.
+    pub frontmatter: bool,
+
+    /// Controls how file header lines are wrapped (e.g. as `//` or `<!-- -->`
+    /// comments) so the combined output can stay valid in a given language.
+    pub header_style: HeaderStyle,
+
+    /// If set, file headers become Markdown headings linking to the file, e.g.
+    /// `### [src/lib.rs](https://github.com/org/repo/blob/main/src/lib.rs)`.
+    /// The display path is appended to this base (with a `/` inserted if
+    /// missing). Replaces `header_style`.
+    pub link_base: Option<String>,
+
+    /// If set, a line emitted before each file (ahead of its header), with
+    /// `{path}` replaced by the display path, e.g. `<file path='{path}'>`.
+    /// Counted in `GrabbedFile::full_range` but not in the header range.
+    pub wrap_open: Option<String>,
+
+    /// If set, a line emitted after each file's body (before the blank
+    /// separator), with `{path}` substituted, e.g. `</file>`. With a closing
+    /// wrapper the body range stops before it, separator excluded.
+    pub wrap_close: Option<String>,
+
+    /// If true, file headers name the file's language after the path, e.g.
+    /// `--- FILE: main.rs [Rust] ---`, based on its extension. Files with an
+    /// unknown extension get no tag.
+    pub label_language: bool,
+
+    /// If true, file headers carry a short SHA-256 of the file's text as read
+    /// (before import stripping, redaction, or trimming), e.g.
+    /// `--- FILE: main.rs [sha256:3f2a9c01b7de] ---`, so a reviewer can check
+    /// it against the repository later. `wrap_open`/`wrap_close` accept a
+    /// `{hash}` placeholder for the same value.
+    pub hash_headers: bool,
+
     /// A list of glob patterns (using .gitignore syntax) to exclude files or directories.
     /// These patterns are applied *in addition* to any `.gitignore` rules if operating
     /// in Git mode.
-    /// In Git mode, they are passed to `git ls-files` as `:!<pattern>` pathspecs.
-    /// In non-Git mode, they are used to filter the results from walking the directory.
+    /// In every mode they are matched against paths relative to the target
+    /// directory (the repository root with `all_repo`), so the same pattern
+    /// selects the same files with and without Git.
     pub exclude_patterns: Vec<String>,
 
+    /// Extra gitignore-style files (e.g. `.aiignore`) whose patterns are added
+    /// to `exclude_patterns`. Relative paths are resolved against `target_path`;
+    /// missing files are skipped. In Git mode the patterns become exclude
+    /// pathspecs, so negated (`!`) lines only take effect outside Git mode.
+    pub ignore_files: Vec<PathBuf>,
+
+    /// An explicit list of files to grab instead of listing the target.
+    /// Relative entries are resolved against the repository root in Git mode
+    /// (matching `git ls-files` / `git diff --name-only` output) and otherwise
+    /// against the path base (the `root_marker` directory, else `target_path`),
+    /// so paths copied from headers resolve. Entries outside that root or
+    /// missing on disk are skipped with a warning; exclude patterns still apply.
+    pub explicit_files: Option<Vec<PathBuf>>,
+
+    /// Files injected ahead of every selected file regardless of selection
+    /// filters (excludes, `.gitignore`, MIME and per-directory limits), then
+    /// processed, and headed like any other file. Paths resolve like
+    /// `explicit_files`, but may lie outside that root: such a file is shown
+    /// under its full path and left out of the tree. Entries missing on disk
+    /// are skipped with a warning.
+    pub prepend_files: Vec<PathBuf>,
+
+    /// Like `prepend_files`, but injected after every selected file.
+    pub append_files: Vec<PathBuf>,
+
+    /// If true, determine which `exclude_patterns` excluded no files and report
+    /// them in `GrabOutput::unused_excludes` / `FileListing::unused_excludes`.
+    /// This costs one extra, unfiltered listing pass.
+    pub warn_unused_excludes: bool,
+
+    /// If set, keep at most this many files from each directory (the first ones
+    /// in output order). Omitted files are reported in `GrabOutput::skipped`
+    /// and summarized in a trailer after the file contents.
+    pub max_per_dir: Option<usize>,
+
+    /// If non-empty, an allowlist: only files matching at least one pattern are
+    /// kept, after excludes apply. Patterns use `.gitignore` syntax relative to
+    /// the directory excludes are relative to; a pattern with a `max_depth`
+    /// matches only files at most that many levels below it.
+    pub include_patterns: Vec<IncludePattern>,
+
     /// If operating in Git mode, set this to true to include untracked files
     /// (files present in the working directory but not added to the index).
     /// This still respects `.gitignore` and the `exclude_patterns`.
@@ -44,13 +206,434 @@ pub struct GrabConfig {
     /// patterns (`-e`) are still respected.
     pub no_git: bool,
 
+    /// If true, select files by walking the target with the full `.gitignore`
+    /// ruleset (nested files, `.git/info/exclude`, global excludes) without
+    /// running `git`. Selection matches Git mode with untracked files on a clean
+    /// tree; display paths are relative to `target_path`.
+    pub gitignore_only: bool,
+
+    /// Outside Git mode, the name of a file or directory marking the project
+    /// root (e.g. `.project-root`). The nearest directory at or above the
+    /// target that contains it becomes the base for headers, the tree, and
+    /// listings, instead of the target itself. Ignored in Git mode.
+    pub root_marker: Option<String>,
+
     /// If true, prepend an indented directory tree structure to the output,
     /// showing the files and directories included in the grab operation.
     pub include_tree: bool,
 
+    /// If true (and `include_tree` is set), the tree also lists files removed by
+    /// `exclude_patterns`, marked `(excluded)`. Costs one extra, unfiltered
+    /// listing pass; the contents section still only holds selected files.
+    pub tree_show_excluded: bool,
+
+    /// If set, the directory tree renders at most this many levels; anything
+    /// deeper is collapsed into a `…/ (K files)` node under its ancestor at
+    /// the limit. File contents are unaffected. `0` is treated as unlimited.
+    pub tree_max_depth: Option<usize>,
+
+    /// If set, the rendered directory tree is cut after this many lines and
+    /// ends with a `… (M more entries)` marker, whatever its depth or style.
+    /// File contents are unaffected. `0` is treated as unlimited.
+    pub tree_max_entries: Option<usize>,
+
+    /// Layout of the directory tree: `Indented` (one line per entry, nested) or
+    /// `Compact` (one `dir/: a, b` line per directory, for very wide repos).
+    pub tree_style: TreeStyle,
+
+    /// If true, the indented tree renders chains of single-child directories on
+    /// one line (`- com/example/app/`), splitting only where a directory has
+    /// several children. Ignored by the compact style.
+    pub tree_collapse_chains: bool,
+
     /// If true, attempt to extract text content from PDF files.
     pub convert_pdf: bool, // <-- Field added here
 
+    /// If set, PDF extraction stops after this many pages and the header notes
+    /// `first N of M pages`. `0` is treated as unlimited.
+    pub pdf_max_pages: Option<usize>,
+
+    /// If set, PDFs larger than this many bytes on disk are not parsed at all;
+    /// they get a header-only entry noting `PDF too large, skipped`.
+    pub pdf_max_bytes: Option<u64>,
+
+    /// Number of threads PDF text is extracted on before the files are
+    /// processed in order. `None` uses the available parallelism; `Some(1)`
+    /// extracts each PDF when it is reached, as before.
+    pub pdf_jobs: Option<usize>,
+
+    /// What to do with binary (non-UTF-8) files: skip them, or include them
+    /// base64-encoded so they can be reconstructed. `mirror_contents` always
+    /// treats them as binaries.
+    pub binary_mode: BinaryMode,
+
+    /// How many times a file read is retried after a transient error
+    /// (`Interrupted`, `WouldBlock`, `TimedOut`), e.g. on network filesystems.
+    /// Other errors are never retried. `0` (the default) reads once.
+    pub read_retries: usize,
+
+    /// External commands used as text extractors for specific extensions.
+    /// A matching extractor takes precedence over built-in PDF handling; if it
+    /// fails, the raw file content is used instead.
+    pub extractors: Vec<Extractor>,
+
+    /// If true, files with a recognized image extension get a header noting
+    /// their dimensions and format (e.g. `shot.png (image: 1920x1080 PNG)`)
+    /// and no body. Unreadable images are skipped like other binary files.
+    pub image_metadata: bool,
+
+    /// If true, skip files whose first kilobyte contains one of the
+    /// `generated_markers` (e.g. protobuf or codegen output). Skipped files are
+    /// reported in `GrabOutput::skipped` and do not appear in the tree.
+    pub skip_generated: bool,
+
+    /// Markers that identify generated files when `skip_generated` is set.
+    /// When empty, defaults to `@generated` and `DO NOT EDIT`.
+    pub generated_markers: Vec<String>,
+
+    /// Regexes that drop a file when its content matches any of them. Content is
+    /// checked after decoding and PDF/extractor conversion, so every candidate
+    /// file is read; binary files are skipped (or base64-encoded) before this
+    /// check and never match. Dropped files are reported in `GrabOutput::skipped`
+    /// with `SkipReason::ContentExcluded` but still appear in the tree. An
+    /// invalid pattern fails the run with `GrabError::InvalidContentPattern`.
+    pub exclude_matching: Vec<String>,
+
+    /// Glob-style MIME patterns (`image/*`, `application/pdf`, `*`); when
+    /// non-empty, only files whose sniffed MIME type matches one are selected.
+    /// Types come from the first 8 KiB of each file: known signatures first,
+    /// then `text/plain` for UTF-8 and `application/octet-stream` otherwise.
+    /// Dropped files are reported with `SkipReason::MimeExcluded` and left out
+    /// of the tree. Either MIME option reads every candidate file's head.
+    pub include_mime: Vec<String>,
+
+    /// Glob-style MIME patterns whose files are dropped; checked before
+    /// `include_mime`. See `include_mime` for how types are detected.
+    pub exclude_mime: Vec<String>,
+
+    /// If set, `include_mime`/`exclude_mime` only sniff files larger than this
+    /// many bytes; smaller files are assumed to be `text/plain` without being
+    /// opened. Saves a read per small file on huge trees, at the cost of letting
+    /// small binaries (an icon, say) pass as text. `None` sniffs every file.
+    pub sniff_only_above: Option<u64>,
+
+    /// Regex rules applied to every file body before it is emitted, e.g. to
+    /// mask secrets. Rules run in order; an invalid pattern fails the run with
+    /// `GrabError::InvalidRedactionPattern`.
+    pub redactions: Vec<RedactionRule>,
+
+    /// Regex rules applied to every display path in the output (file headers,
+    /// the tree, trailers, symlink targets, and the paths in `GrabOutput`),
+    /// matched against whole relative paths, e.g. to replace an
+    /// internal project name before sharing a grab. Uses the same rule format as
+    /// `redactions`; see `anonymize_content` for file bodies.
+    pub anonymize: Vec<RedactionRule>,
+
+    /// If true, the `anonymize` rules also run over file bodies, before
+    /// `redactions`.
+    pub anonymize_content: bool,
+
+    /// Order in which selected files are emitted. Defaults to path order.
+    pub sort: FileOrder,
+
+    /// If true, reverse the primary `sort` key (ties still fall back to path order).
+    pub sort_desc: bool,
+
+    /// Glob patterns (exclude syntax, relative to the same base) whose files
+    /// are moved to the end of the output after sorting, keeping their order.
+    /// Applied before `max_per_dir`, so deprioritized files are omitted first.
+    pub deprioritize_patterns: Vec<String>,
+
+    /// Glob patterns (exclude syntax, relative to the same base) selecting the
+    /// files whose content is emitted. If non-empty, every other selected file
+    /// still appears in the tree but gets no header or body.
+    pub content_for: Vec<String>,
+
+    /// If true, each directory's `README*` files come right before the rest of
+    /// that directory's files (after sorting and `deprioritize_patterns`), and
+    /// their headers are annotated `directory overview`.
+    pub readme_first: bool,
+
+    /// Named sections for the output: each file goes to the first partition
+    /// whose pattern (exclude syntax, relative to the same base) matches it,
+    /// and files that match none go to a final `Other` section. Sections are
+    /// emitted in this order, each opened by a `===== Label =====` banner;
+    /// files keep their relative order within a section.
+    pub partitions: Vec<Partition>,
+
+    /// If true (and `partitions` or `collate` is set), the tree is split into
+    /// one subtree per section under the same banners.
+    pub tree_partitioned: bool,
+
+    /// If set, files are grouped into generated sections instead of
+    /// `partitions` (which are then ignored), e.g. one `===== .rs =====`
+    /// section per extension. Files are path-sorted within each section.
+    pub collate: Option<Collate>,
+
+    /// If set (Git mode only), keep only files touched by the last N commits
+    /// (`git log -n N --name-only`). The result is intersected with the normal
+    /// listing, so excludes still apply and deleted files are dropped.
+    pub changed_in_last: Option<usize>,
+
+    /// If set (Git mode only), append a `--- HISTORY: path ---` block after each
+    /// file's content listing its last N commit subjects
+    /// (`git log -n N --format='%h %s' -- path`). Costs one git call per file.
+    pub git_history: Option<usize>,
+
+    /// If true (Git mode only), tracked files that match an ignore rule (i.e.
+    /// were force-added with `git add -f`) get a `force-added` header note and
+    /// `GrabbedFile::force_added` set. One extra `git ls-files` call.
+    pub show_force_added: bool,
+
+    /// If true, append each file's permissions to its header: the octal Unix
+    /// mode (`mode 755`), or `read-only` on other platforms for read-only
+    /// files. `GrabbedFile::mode` is filled in either way.
+    pub show_mode: bool,
+
+    /// If set (Git mode only), keep only files that differ between the two
+    /// revisions, as listed by `git diff --name-only --diff-filter=d FROM..TO`.
+    /// This is the two-dot form: a direct comparison of both trees, not the
+    /// changes since their merge base (`...`). Both revisions are verified
+    /// first and an unknown one fails with `GrabError::UnknownGitRef`.
+    pub between: Option<(String, String)>,
+
+    /// If set (Git mode only), keep only files changed since this commit, as
+    /// listed by `git diff --name-only --diff-filter=d COMMIT..HEAD`, plus
+    /// untracked files when `include_untracked` is set. The range excludes
+    /// COMMIT and includes HEAD: the commit's own changes are not counted, and
+    /// uncommitted edits to tracked files are not either. Verified like `between`.
+    pub since_commit: Option<String>,
+
+    /// If set (Git mode only), keep only files whose `.gitattributes` set this
+    /// attribute (`path license` or `path license=MIT`, but not `-license`),
+    /// per `git check-attr`. Applied after excludes; ignored outside Git mode.
+    pub git_attr: Option<String>,
+
     /// If true, operate on the entire Git repository even when the target path is a subdirectory.
     pub all_repo: bool,
+
+    /// If set, fail with `GrabError::TooFewFiles` when fewer than this many files
+    /// are selected. The check runs right after listing, before any content is read.
+    pub min_files: Option<usize>,
+
+    /// If set, the total output size in bytes that file bodies are trimmed to
+    /// fit. Every file stays in the output; bodies are cut at line boundaries
+    /// according to `budget_strategy` and listed in a `TRUNCATED FILES` trailer.
+    pub context_budget: Option<usize>,
+
+    /// How `context_budget` is shared between files.
+    pub budget_strategy: BudgetStrategy,
+
+    /// Lines always kept from the start of each file, even when that exceeds
+    /// its share of `context_budget`. If these lines alone do not fit the
+    /// budget, they are not kept.
+    pub budget_min_lines: usize,
+}
+
+/// How `GrabConfig::context_budget` is split across the selected files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BudgetStrategy {
+    /// Shrink every body by the same fraction of its size.
+    #[default]
+    Proportional,
+}
+
+/// How `GrabConfig::collate` groups files into sections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Collate {
+    /// One section per file extension, in alphabetical order; files without
+    /// an extension go to a final `Other` section.
+    Extension,
+}
+
+/// A named output section (`GrabConfig::partitions`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Partition {
+    /// Glob pattern selecting the section's files, e.g. `tests/**`.
+    pub pattern: String,
+    /// Section name shown in the banner, e.g. `Tests`.
+    pub label: String,
+}
+
+/// An allowlist pattern (`GrabConfig::include_patterns`), written
+/// `PATTERN[:depth=N]` on the command line, e.g. `src/*.rs:depth=2`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IncludePattern {
+    /// `.gitignore`-style glob; a directory pattern covers the files below it.
+    pub pattern: String,
+    /// If set, only files at most this deep match (1 is the top level).
+    pub max_depth: Option<usize>,
+}
+
+/// An external command that converts files with a given extension to text.
+/// The command's stdout becomes the file body and the header is annotated with
+/// `(via <program>)`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Extractor {
+    /// File extension without the leading dot, matched case-insensitively.
+    pub extension: String,
+    /// Program followed by its arguments. Each `{path}` is replaced with the
+    /// absolute path of the file being extracted. No shell is involved.
+    pub command: Vec<String>,
+}
+
+impl Extractor {
+    /// Placeholder substituted with the file path in `command`.
+    pub const PATH_PLACEHOLDER: &'static str = "{path}";
+
+    /// Returns true if this extractor handles `path`.
+    pub fn matches(&self, path: &std::path::Path) -> bool {
+        path.extension()
+            .is_some_and(|ext| ext.to_string_lossy().eq_ignore_ascii_case(&self.extension))
+    }
+
+    /// Short program name used in headers (the file name of the program).
+    pub fn name(&self) -> String {
+        let program = self.command.first().map(String::as_str).unwrap_or_default();
+        std::path::Path::new(program)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| program.to_string())
+    }
+}
+
+/// A single redaction rule: every match of `pattern` is replaced with `replacement`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RedactionRule {
+    /// A regular expression (Rust `regex` syntax).
+    pub pattern: String,
+    /// Replacement text; may reference capture groups as `$1` or `${name}`.
+    #[serde(default = "RedactionRule::default_replacement")]
+    pub replacement: String,
+}
+
+impl RedactionRule {
+    /// Placeholder used when a rule does not specify its own replacement.
+    pub const DEFAULT_REPLACEMENT: &'static str = "[REDACTED]";
+
+    /// Creates a rule that replaces matches with `DEFAULT_REPLACEMENT`.
+    pub fn new(pattern: impl Into<String>) -> Self {
+        Self {
+            pattern: pattern.into(),
+            replacement: Self::DEFAULT_REPLACEMENT.to_string(),
+        }
+    }
+
+    fn default_replacement() -> String {
+        Self::DEFAULT_REPLACEMENT.to_string()
+    }
+}
+
+/// Ordering applied to the selected files before they are processed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FileOrder {
+    /// Lexicographic path order (deterministic; the default).
+    #[default]
+    Path,
+    /// File size in bytes, smallest first.
+    Size,
+    /// Last modification time, oldest first.
+    Mtime,
+    /// File extension, then path.
+    Ext,
+    /// Timestamp of the file's last commit, oldest first, so recently changed
+    /// files come last. Files without commits use their mtime, as does every
+    /// file outside Git mode.
+    GitRecency,
+}
+
+/// Layout of the directory tree section.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TreeStyle {
+    /// One line per file and directory, indented by depth (the default).
+    #[default]
+    Indented,
+    /// One line per directory holding files: the directory path followed by its
+    /// comma-separated file names.
+    Compact,
+}
+
+/// How files that are not valid text (see `SkipReason::NonUtf8`) are handled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BinaryMode {
+    /// Leave them out and list them in `GrabOutput::skipped` (the default).
+    #[default]
+    Skip,
+    /// Include them with a `(base64)` header note and the bytes base64-encoded
+    /// in 76-character lines. Encoding inflates the size by about a third.
+    Base64,
+}
+
+/// The wrapping applied to `FILE:` header lines.
+///
+/// Every style renders as `<prefix>FILE: <path><suffix>`; the presets only differ
+/// in their prefix and suffix.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HeaderStyle {
+    /// `--- FILE: path ---` (the historical default).
+    #[default]
+    Plain,
+    /// `// --- FILE: path ---`, valid in Rust, C, JavaScript, Go, etc.
+    Rust,
+    /// `# --- FILE: path ---`, valid in Python, shell, TOML, YAML, etc.
+    Python,
+    /// `<!-- FILE: path -->`, valid in HTML, XML, and Markdown.
+    Html,
+    /// A user-supplied prefix and suffix.
+    Custom { prefix: String, suffix: String },
+}
+
+impl HeaderStyle {
+    /// Text emitted before `FILE: `.
+    pub fn prefix(&self) -> &str {
+        match self {
+            HeaderStyle::Plain => "--- ",
+            HeaderStyle::Rust => "// --- ",
+            HeaderStyle::Python => "# --- ",
+            HeaderStyle::Html => "<!-- ",
+            HeaderStyle::Custom { prefix, .. } => prefix,
+        }
+    }
+
+    /// Text emitted after the file label.
+    pub fn suffix(&self) -> &str {
+        match self {
+            HeaderStyle::Plain | HeaderStyle::Rust | HeaderStyle::Python => " ---",
+            HeaderStyle::Html => " -->",
+            HeaderStyle::Custom { suffix, .. } => suffix,
+        }
+    }
+
+    /// Renders a header line (without the trailing newline) for `label`.
+    pub fn render(&self, label: &str) -> String {
+        format!("{}FILE: {}{}", self.prefix(), label, self.suffix())
+    }
+}
+
+/// Serializes `PathBuf` values as plain strings with `/` separators so configs
+/// written on one platform can be read on another.
+mod portable_path {
+    use std::path::{Path, PathBuf};
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+        let raw = path.to_string_lossy();
+        if std::path::MAIN_SEPARATOR == '\\' {
+            serializer.serialize_str(&raw.replace('\\', "/"))
+        } else {
+            serializer.serialize_str(&raw)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        Ok(PathBuf::from(raw))
+    }
 }
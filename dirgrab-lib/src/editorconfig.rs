@@ -0,0 +1,360 @@
+// --- FILE: dirgrab-lib/src/editorconfig.rs ---
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::{debug, warn};
+use regex::Regex;
+
+use crate::utils::posix_path;
+
+/// Numeric `{n..m}` ranges wider than this are matched literally.
+const MAX_NUMERIC_RANGE: i64 = 1000;
+
+/// Line ending requested by an `end_of_line` property.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineEnding {
+    Lf,
+    Crlf,
+    Cr,
+}
+
+impl LineEnding {
+    fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+}
+
+/// The normalization properties `.editorconfig` sets for one file. `None`
+/// means the property is unset, so that aspect of the file is left alone.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Normalization {
+    end_of_line: Option<LineEnding>,
+    trim_trailing_whitespace: Option<bool>,
+    insert_final_newline: Option<bool>,
+}
+
+impl Normalization {
+    fn set(&mut self, key: &str, value: &str) {
+        let value = value.to_ascii_lowercase();
+        let flag = match value.as_str() {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        };
+        match key {
+            "end_of_line" => {
+                self.end_of_line = match value.as_str() {
+                    "lf" => Some(LineEnding::Lf),
+                    "crlf" => Some(LineEnding::Crlf),
+                    "cr" => Some(LineEnding::Cr),
+                    _ => None,
+                }
+            }
+            "trim_trailing_whitespace" => self.trim_trailing_whitespace = flag,
+            "insert_final_newline" => self.insert_final_newline = flag,
+            _ => {}
+        }
+    }
+
+    /// Rewrites `text` to match the properties, borrowing it when nothing changes.
+    pub(crate) fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if *self == Normalization::default() || text.is_empty() {
+            return Cow::Borrowed(text);
+        }
+        let trim = self.trim_trailing_whitespace == Some(true);
+        let mut normalized = String::with_capacity(text.len());
+        // The file's own line ending, used for a missing final newline if
+        // `end_of_line` is unset.
+        let mut first_ending = None;
+        let mut ends_with_newline = false;
+        for (line, ending) in split_lines(text) {
+            normalized.push_str(if trim { line.trim_end() } else { line });
+            ends_with_newline = !ending.is_empty();
+            if ends_with_newline {
+                first_ending.get_or_insert(ending);
+                normalized.push_str(self.end_of_line.map_or(ending, |eol| eol.as_str()));
+            }
+        }
+        match self.insert_final_newline {
+            Some(true) if !ends_with_newline => {
+                let ending = self
+                    .end_of_line
+                    .map(|eol| eol.as_str())
+                    .or(first_ending)
+                    .unwrap_or("\n");
+                normalized.push_str(ending);
+            }
+            Some(false) if ends_with_newline => {
+                let ending = if normalized.ends_with("\r\n") { 2 } else { 1 };
+                normalized.truncate(normalized.len() - ending);
+            }
+            _ => {}
+        }
+        if normalized == text {
+            Cow::Borrowed(text)
+        } else {
+            Cow::Owned(normalized)
+        }
+    }
+}
+
+/// Splits `text` into lines paired with their terminator (`\n`, `\r\n`, `\r`,
+/// or empty for a final line without one).
+fn split_lines(text: &str) -> impl Iterator<Item = (&str, &str)> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let Some(at) = rest.find(['\r', '\n']) else {
+            let line = rest;
+            rest = "";
+            return Some((line, ""));
+        };
+        let len = if rest[at..].starts_with("\r\n") { 2 } else { 1 };
+        let (line, ending) = (&rest[..at], &rest[at..at + len]);
+        rest = &rest[at + len..];
+        Some((line, ending))
+    })
+}
+
+/// One parsed `.editorconfig` file.
+struct EditorConfigFile {
+    root: bool,
+    sections: Vec<(Regex, Vec<(String, String)>)>,
+}
+
+/// Resolves `.editorconfig` properties per file, parsing each directory's file
+/// at most once per run.
+#[derive(Default)]
+pub(crate) struct EditorConfigs {
+    parsed: HashMap<PathBuf, Option<EditorConfigFile>>,
+}
+
+impl EditorConfigs {
+    /// Collects the properties matching `file` from every `.editorconfig` between
+    /// its directory and the nearest one marked `root = true`; nearer files and
+    /// later sections win.
+    pub(crate) fn normalization_for(&mut self, file: &Path) -> Normalization {
+        let mut chain = Vec::new();
+        let mut dir = file.parent();
+        while let Some(current) = dir {
+            let parsed = self
+                .parsed
+                .entry(current.to_path_buf())
+                .or_insert_with(|| parse_file(current));
+            if let Some(config) = parsed {
+                chain.push(current);
+                if config.root {
+                    break;
+                }
+            }
+            dir = current.parent();
+        }
+
+        let mut normalization = Normalization::default();
+        for dir in chain.into_iter().rev() {
+            let Some(Some(config)) = self.parsed.get(dir) else {
+                continue;
+            };
+            let Ok(relative) = file.strip_prefix(dir) else {
+                continue;
+            };
+            let relative = posix_path(relative);
+            for (glob, properties) in &config.sections {
+                if glob.is_match(&relative) {
+                    for (key, value) in properties {
+                        normalization.set(key, value);
+                    }
+                }
+            }
+        }
+        normalization
+    }
+}
+
+/// Reads `dir/.editorconfig`, or returns `None` if there is none. Lines that
+/// are not sections or `key = value` pairs are ignored, like editors do.
+fn parse_file(dir: &Path) -> Option<EditorConfigFile> {
+    let path = dir.join(".editorconfig");
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) => {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!("Could not read {:?}: {}", path, e);
+            }
+            return None;
+        }
+    };
+    debug!("Parsing {:?}", path);
+    let mut config = EditorConfigFile {
+        root: false,
+        sections: Vec::new(),
+    };
+    // Properties of a section whose glob failed to compile are dropped.
+    let (mut seen_section, mut in_valid_section) = (false, false);
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(glob) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            seen_section = true;
+            match Regex::new(&glob_to_regex(glob)) {
+                Ok(regex) => {
+                    config.sections.push((regex, Vec::new()));
+                    in_valid_section = true;
+                }
+                Err(e) => {
+                    warn!("Ignoring section [{}] in {:?}: {}", glob, path, e);
+                    in_valid_section = false;
+                }
+            }
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_ascii_lowercase();
+        let value = value.trim().to_string();
+        match config.sections.last_mut() {
+            Some((_, properties)) if in_valid_section => properties.push((key, value)),
+            _ if !seen_section && key == "root" => config.root = value.eq_ignore_ascii_case("true"),
+            _ => {}
+        }
+    }
+    Some(config)
+}
+
+/// Translates an EditorConfig section glob into an anchored regex over paths
+/// relative to the `.editorconfig` directory. A glob without `/` matches the
+/// file name in any subdirectory.
+fn glob_to_regex(glob: &str) -> String {
+    let anchored = glob.contains('/');
+    let glob = glob.strip_prefix('/').unwrap_or(glob);
+    let chars: Vec<char> = glob.chars().collect();
+    let mut regex = String::from(if anchored { "^" } else { "^(?:.*/)?" });
+    translate(&chars, &mut regex);
+    regex.push('$');
+    regex
+}
+
+fn translate(chars: &[char], out: &mut String) {
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                out.push_str(".*");
+                i += 2;
+                continue;
+            }
+            '*' => out.push_str("[^/]*"),
+            '?' => out.push_str("[^/]"),
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                push_literal(chars[i], out);
+            }
+            '[' => match chars[i + 1..].iter().position(|&c| c == ']') {
+                Some(len) => {
+                    let class: String = chars[i + 1..i + 1 + len].iter().collect();
+                    let (negated, class) = match class.strip_prefix('!') {
+                        Some(rest) => (true, rest.to_string()),
+                        None => (false, class),
+                    };
+                    out.push('[');
+                    if negated {
+                        out.push('^');
+                    }
+                    out.push_str(&class.replace('\\', "\\\\").replace('[', "\\["));
+                    out.push(']');
+                    i += len + 2;
+                    continue;
+                }
+                None => push_literal('[', out),
+            },
+            '{' => match matching_brace(&chars[i..]) {
+                Some(len) => {
+                    translate_braces(&chars[i + 1..i + len], out);
+                    i += len + 1;
+                    continue;
+                }
+                None => push_literal('{', out),
+            },
+            c => push_literal(c, out),
+        }
+        i += 1;
+    }
+}
+
+/// Translates the inside of `{...}`: a `n..m` integer range, a comma-separated
+/// alternation, or (with neither) the literal braces.
+fn translate_braces(inner: &[char], out: &mut String) {
+    let text: String = inner.iter().collect();
+    if let Some((start, end)) = text.split_once("..") {
+        if let (Ok(start), Ok(end)) = (start.parse::<i64>(), end.parse::<i64>()) {
+            let (low, high) = (start.min(end), start.max(end));
+            if high - low <= MAX_NUMERIC_RANGE {
+                let numbers: Vec<String> = (low..=high).map(|n| n.to_string()).collect();
+                out.push_str(&format!("(?:{})", numbers.join("|")));
+                return;
+            }
+        }
+    }
+    let mut alternatives = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (index, &c) in inner.iter().enumerate() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                alternatives.push(&inner[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    if alternatives.is_empty() {
+        out.push_str("\\{");
+        translate(inner, out);
+        out.push_str("\\}");
+        return;
+    }
+    alternatives.push(&inner[start..]);
+    out.push_str("(?:");
+    for (index, alternative) in alternatives.iter().enumerate() {
+        if index > 0 {
+            out.push('|');
+        }
+        translate(alternative, out);
+    }
+    out.push(')');
+}
+
+/// Offset of the `}` closing the `{` at the start of `chars`, if any.
+fn matching_brace(chars: &[char]) -> Option<usize> {
+    let mut depth = 0;
+    for (index, &c) in chars.iter().enumerate() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn push_literal(c: char, out: &mut String) {
+    out.push_str(&regex::escape(&c.to_string()));
+}
@@ -54,6 +54,38 @@ pub enum GrabError {
     /// Error specifically for path stripping issues during tree generation.
     #[error("Failed to strip prefix '{prefix}' from path '{path}' during tree generation")]
     PathStripError { prefix: PathBuf, path: PathBuf },
+
+    /// Fewer files were selected than required by `GrabConfig::min_files`.
+    #[error("Only {found} file(s) selected, but at least {minimum} were required (--min-files)")]
+    TooFewFiles { found: usize, minimum: usize },
+
+    /// A pattern in `GrabConfig::redactions` is not a valid regular expression.
+    #[error("Invalid redaction pattern '{pattern}': {source}")]
+    InvalidRedactionPattern {
+        pattern: String,
+        #[source]
+        source: regex::Error,
+    },
+
+    /// A pattern in `GrabConfig::exclude_matching` is not a valid regular expression.
+    #[error("Invalid --exclude-matching pattern '{pattern}': {source}")]
+    InvalidContentPattern {
+        pattern: String,
+        #[source]
+        source: regex::Error,
+    },
+
+    /// A revision given in `GrabConfig::between` or `GrabConfig::since_commit`
+    /// does not name a commit.
+    #[error("Unknown Git revision '{reference}'")]
+    UnknownGitRef { reference: String },
+
+    /// The mirror output directory contains the files being mirrored.
+    #[error("Mirror directory '{out_dir}' must not contain the source tree '{source_root}'")]
+    MirrorOverlapsSource {
+        out_dir: PathBuf,
+        source_root: PathBuf,
+    },
 }
 
 /// A convenience type alias for `Result<T, GrabError>`.
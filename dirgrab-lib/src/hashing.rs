@@ -0,0 +1,23 @@
+// --- FILE: dirgrab-lib/src/hashing.rs ---
+
+use sha2::{Digest, Sha256};
+
+/// Returns the SHA-256 digest of `data` as lowercase hex, e.g. for verifying
+/// that a grab's output has not changed.
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Hex digits kept by `short_sha256`.
+const SHORT_HASH_LEN: usize = 12;
+
+/// The first `SHORT_HASH_LEN` hex digits of `sha256_hex(data)`, as shown in
+/// `GrabConfig::hash_headers` headers.
+pub(crate) fn short_sha256(data: &[u8]) -> String {
+    let mut hash = sha256_hex(data);
+    hash.truncate(SHORT_HASH_LEN);
+    hash
+}
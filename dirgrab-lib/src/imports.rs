@@ -0,0 +1,359 @@
+// --- FILE: dirgrab-lib/src/imports.rs ---
+
+use std::borrow::Cow;
+use std::path::Path;
+
+use crate::language::language_name;
+
+/// Module names listed by `fold_imports` before the `(+N more)` count.
+const FOLDED_NAMES: usize = 3;
+
+/// Languages whose leading import block `strip_imports` understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Language {
+    Rust,
+    Python,
+    JavaScript,
+}
+
+impl Language {
+    fn for_path(path: &Path) -> Option<Self> {
+        match language_name(path)? {
+            "Rust" => Some(Self::Rust),
+            "Python" => Some(Self::Python),
+            "JavaScript" | "TypeScript" => Some(Self::JavaScript),
+            _ => None,
+        }
+    }
+
+    fn comment_prefix(self) -> &'static str {
+        match self {
+            Self::Python => "#",
+            Self::Rust | Self::JavaScript => "//",
+        }
+    }
+}
+
+/// Removes the import statements at the top of `text` (`GrabConfig::strip_imports`):
+/// Rust `use`/`extern crate`, Python `import`/`from ... import`, and JS/TS
+/// `import`/`require`. Blank lines, comments, shebangs, Rust inner attributes,
+/// Python docstrings, and JS directives may precede or separate the imports and
+/// are kept; blank lines right after a removed statement are dropped. Scanning
+/// stops at the first other line, so nothing below the leading block is
+/// touched. Files in other languages are returned unchanged.
+pub(crate) fn strip_imports<'a>(path: &Path, text: &'a str) -> Cow<'a, str> {
+    match Language::for_path(path) {
+        Some(language) => rewrite_imports(language, text, false),
+        None => Cow::Borrowed(text),
+    }
+}
+
+/// Like `strip_imports`, but replaces the removed block with one comment line
+/// naming the imported modules (`GrabConfig::fold_imports`), e.g.
+/// `// imports: serde, tokio, anyhow (+3 more)`, followed by a blank line.
+/// Names are the crate, package, or module specifier of each statement, in
+/// order and without duplicates.
+pub(crate) fn fold_imports<'a>(path: &Path, text: &'a str) -> Cow<'a, str> {
+    match Language::for_path(path) {
+        Some(language) => rewrite_imports(language, text, true),
+        None => Cow::Borrowed(text),
+    }
+}
+
+fn rewrite_imports(language: Language, text: &str, fold: bool) -> Cow<'_, str> {
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let mut kept = String::with_capacity(text.len());
+    let mut removed_any = false;
+    let mut after_removed = false;
+    // Where the first removed statement was, and every removed module name.
+    let mut fold_at = 0;
+    let mut names: Vec<String> = Vec::new();
+    let mut index = 0;
+
+    while index < lines.len() {
+        let trimmed = lines[index].trim();
+        if trimmed.is_empty() {
+            if !after_removed {
+                kept.push_str(lines[index]);
+            }
+            index += 1;
+            continue;
+        }
+        if let Some(len) = import_len(language, &lines[index..]) {
+            if !removed_any {
+                fold_at = kept.len();
+            }
+            if fold {
+                for name in imported_names(language, &lines[index..index + len].concat()) {
+                    if !names.contains(&name) {
+                        names.push(name);
+                    }
+                }
+            }
+            removed_any = true;
+            after_removed = true;
+            index += len;
+            continue;
+        }
+        let Some(len) = preamble_len(language, &lines[index..], index == 0) else {
+            break;
+        };
+        for line in &lines[index..index + len] {
+            kept.push_str(line);
+        }
+        after_removed = false;
+        index += len;
+    }
+
+    if !removed_any {
+        return Cow::Borrowed(text);
+    }
+    for line in &lines[index..] {
+        kept.push_str(line);
+    }
+    if fold {
+        let mut summary = format!(
+            "{} imports: {}",
+            language.comment_prefix(),
+            names
+                .iter()
+                .take(FOLDED_NAMES)
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        if names.len() > FOLDED_NAMES {
+            summary.push_str(&format!(" (+{} more)", names.len() - FOLDED_NAMES));
+        }
+        summary.push('\n');
+        let rest = &kept[fold_at..];
+        if !rest.is_empty() && !rest.starts_with('\n') && !rest.starts_with("\r\n") {
+            summary.push('\n');
+        }
+        kept.insert_str(fold_at, &summary);
+    }
+    Cow::Owned(kept)
+}
+
+/// Module names imported by one statement found by `import_len`: the root
+/// crate of a Rust path, the top-level package of an absolute Python import
+/// (relative ones as written), or a JS/TS module specifier.
+fn imported_names(language: Language, statement: &str) -> Vec<String> {
+    let statement = statement.trim();
+    match language {
+        Language::Rust => {
+            let rest = strip_visibility(statement);
+            let path = rest
+                .strip_prefix("use ")
+                .or_else(|| rest.strip_prefix("extern crate "))
+                .unwrap_or(rest)
+                .trim_start()
+                .trim_start_matches("::");
+            let end = path
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(path.len());
+            vec![path[..end].to_string()]
+        }
+        Language::Python => {
+            let modules: Vec<&str> = match statement.strip_prefix("from ") {
+                Some(rest) => rest.split_whitespace().take(1).collect(),
+                None => statement
+                    .trim_start_matches("import ")
+                    .split(',')
+                    .filter_map(|part| part.split_whitespace().next())
+                    .collect(),
+            };
+            modules
+                .into_iter()
+                .map(|module| {
+                    if module.starts_with('.') {
+                        module.to_string()
+                    } else {
+                        module.split('.').next().unwrap_or(module).to_string()
+                    }
+                })
+                .filter(|module| !module.is_empty())
+                .collect()
+        }
+        Language::JavaScript => {
+            // The specifier is the first string after `require(` or the `from`
+            // keyword; a side-effect `import 'x'` has only the one string.
+            let start = statement
+                .find("require(")
+                .or_else(|| last_from_keyword(statement))
+                .unwrap_or(0);
+            statement[start..]
+                .split(['\'', '"', '`'])
+                .nth(1)
+                .map(str::to_string)
+                .into_iter()
+                .collect()
+        }
+    }
+}
+
+/// Byte offset of the last `from` keyword in a JavaScript statement, skipping
+/// string literals so `import x from './from'` finds the keyword, not the path.
+fn last_from_keyword(statement: &str) -> Option<usize> {
+    let bytes = statement.as_bytes();
+    let is_word = |byte: u8| byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'$';
+    let mut quote = None;
+    let mut found = None;
+    for (index, &byte) in bytes.iter().enumerate() {
+        match quote {
+            Some(open) if byte == open && bytes[index - 1] != b'\\' => quote = None,
+            Some(_) => {}
+            None if matches!(byte, b'\'' | b'"' | b'`') => quote = Some(byte),
+            None if bytes[index..].starts_with(b"from")
+                && (index == 0 || !is_word(bytes[index - 1]))
+                && !bytes.get(index + 4).is_some_and(|&next| is_word(next)) =>
+            {
+                found = Some(index)
+            }
+            None => {}
+        }
+    }
+    found
+}
+
+/// Number of lines taken by the import statement starting at `lines[0]`, or
+/// `None` if it does not start one.
+fn import_len(language: Language, lines: &[&str]) -> Option<usize> {
+    let first = lines[0].trim_start();
+    match language {
+        Language::Rust => {
+            let rest = strip_visibility(first);
+            if !(rest.starts_with("use ") || first.starts_with("extern crate ")) {
+                return None;
+            }
+            statement_len(lines, |line, _| line.trim_end().ends_with(';'))
+        }
+        Language::Python => {
+            if !(first.starts_with("import ") || first.starts_with("from ")) {
+                return None;
+            }
+            let len = statement_len(lines, |line, depth| {
+                depth == 0 && !line.trim_end().ends_with('\\')
+            })?;
+            let is_import = first.starts_with("import ")
+                || lines[..len].iter().any(|line| line.contains(" import"));
+            is_import.then_some(len)
+        }
+        Language::JavaScript => {
+            if first.starts_with("import ") || first.starts_with("import{") {
+                return statement_len(lines, |line, depth| {
+                    depth == 0 && (line.trim_end().ends_with(';') || has_quote(line))
+                });
+            }
+            let is_declaration = ["const ", "let ", "var "]
+                .iter()
+                .any(|keyword| first.starts_with(keyword));
+            if !(is_declaration || first.starts_with("require(")) {
+                return None;
+            }
+            let len = statement_len(lines, |_, depth| depth == 0)?;
+            let statement: String = lines[..len].concat();
+            let call = statement.find("require(")?;
+            // Only a plain `require(...)` binding, not e.g. `require(x).setup()`.
+            let tail = statement[call..].trim_end().trim_end_matches(';');
+            (tail.ends_with(')') && !tail[..tail.len() - 1].contains(')')).then_some(len)
+        }
+    }
+}
+
+/// Number of lines taken by a kept non-import line at the top of the file
+/// (comment, attribute, docstring, directive), or `None` to end the block.
+fn preamble_len(language: Language, lines: &[&str], first_line: bool) -> Option<usize> {
+    let first = lines[0].trim();
+    match language {
+        Language::Rust => {
+            if first.starts_with("//") || first.starts_with("#!") {
+                Some(1)
+            } else if first.starts_with("/*") {
+                closing_len(lines, "*/", 2)
+            } else {
+                None
+            }
+        }
+        Language::Python => {
+            if first.starts_with('#') {
+                Some(1)
+            } else if let Some(quote) = ["\"\"\"", "'''"]
+                .into_iter()
+                .find(|quote| first.starts_with(quote))
+            {
+                closing_len(lines, quote, quote.len())
+            } else {
+                None
+            }
+        }
+        Language::JavaScript => {
+            let is_directive = matches!(
+                first.trim_end_matches(';'),
+                "'use strict'" | "\"use strict\"" | "'use client'" | "\"use client\""
+            );
+            if first.starts_with("//") || is_directive || (first_line && first.starts_with("#!")) {
+                Some(1)
+            } else if first.starts_with("/*") {
+                closing_len(lines, "*/", 2)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Counts lines until `is_end(line, depth)` holds, where `depth` is the
+/// bracket nesting after that line. `None` if the statement never ends.
+fn statement_len(lines: &[&str], is_end: impl Fn(&str, i32) -> bool) -> Option<usize> {
+    let mut depth = 0;
+    for (index, line) in lines.iter().enumerate() {
+        for ch in line.chars() {
+            match ch {
+                '(' | '{' | '[' => depth += 1,
+                ')' | '}' | ']' => depth -= 1,
+                _ => {}
+            }
+        }
+        if is_end(line, depth) {
+            return Some(index + 1);
+        }
+    }
+    None
+}
+
+/// Lines up to and including the one containing `closing`, searching the
+/// first line only after its opening `skip` bytes.
+fn closing_len(lines: &[&str], closing: &str, skip: usize) -> Option<usize> {
+    let first = lines[0].trim_start();
+    if first.get(skip..).is_some_and(|rest| rest.contains(closing)) {
+        return Some(1);
+    }
+    lines
+        .iter()
+        .skip(1)
+        .position(|line| line.contains(closing))
+        .map(|position| position + 2)
+}
+
+fn strip_visibility(line: &str) -> &str {
+    let Some(rest) = line.strip_prefix("pub") else {
+        return line;
+    };
+    let rest = match rest.strip_prefix('(') {
+        Some(scoped) => match scoped.find(')') {
+            Some(end) => &scoped[end + 1..],
+            None => return line,
+        },
+        None => rest,
+    };
+    if rest.starts_with(char::is_whitespace) {
+        rest.trim_start()
+    } else {
+        line
+    }
+}
+
+fn has_quote(line: &str) -> bool {
+    line.contains(['\'', '"', '`'])
+}
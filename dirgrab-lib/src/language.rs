@@ -0,0 +1,94 @@
+// --- FILE: dirgrab-lib/src/language.rs ---
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// File extension (lowercase) to language name, shared by every feature that
+/// needs to know what language a file is written in.
+const LANGUAGES: &[(&str, &str)] = &[
+    ("bash", "Shell"),
+    ("c", "C"),
+    ("cc", "C++"),
+    ("cjs", "JavaScript"),
+    ("clj", "Clojure"),
+    ("cpp", "C++"),
+    ("cs", "C#"),
+    ("css", "CSS"),
+    ("cts", "TypeScript"),
+    ("cxx", "C++"),
+    ("dart", "Dart"),
+    ("erl", "Erlang"),
+    ("ex", "Elixir"),
+    ("exs", "Elixir"),
+    ("go", "Go"),
+    ("h", "C"),
+    ("hh", "C++"),
+    ("hpp", "C++"),
+    ("hs", "Haskell"),
+    ("htm", "HTML"),
+    ("html", "HTML"),
+    ("java", "Java"),
+    ("js", "JavaScript"),
+    ("json", "JSON"),
+    ("jsx", "JavaScript"),
+    ("kt", "Kotlin"),
+    ("kts", "Kotlin"),
+    ("lua", "Lua"),
+    ("md", "Markdown"),
+    ("mjs", "JavaScript"),
+    ("mts", "TypeScript"),
+    ("nix", "Nix"),
+    ("php", "PHP"),
+    ("ps1", "PowerShell"),
+    ("py", "Python"),
+    ("pyi", "Python"),
+    ("r", "R"),
+    ("rb", "Ruby"),
+    ("rs", "Rust"),
+    ("scala", "Scala"),
+    ("scss", "SCSS"),
+    ("sh", "Shell"),
+    ("sql", "SQL"),
+    ("svelte", "Svelte"),
+    ("swift", "Swift"),
+    ("toml", "TOML"),
+    ("ts", "TypeScript"),
+    ("tsx", "TypeScript"),
+    ("vue", "Vue"),
+    ("xml", "XML"),
+    ("yaml", "YAML"),
+    ("yml", "YAML"),
+    ("zig", "Zig"),
+    ("zsh", "Shell"),
+];
+
+/// The language name for `path` based on its extension (case-insensitive), or
+/// `None` for unknown or missing extensions.
+pub(crate) fn language_name(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_string_lossy().to_ascii_lowercase();
+    LANGUAGES
+        .binary_search_by_key(&ext.as_str(), |&(ext, _)| ext)
+        .ok()
+        .map(|index| LANGUAGES[index].1)
+}
+
+/// `Primary language: Rust (68% of files)` followed by a blank line, for the
+/// most common language among `files` by extension (ties go to the name that
+/// sorts first). The percentage counts every file, recognised or not. `None`
+/// when no file has a known language.
+pub(crate) fn primary_language_summary(files: &[PathBuf]) -> Option<String> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for file in files {
+        if let Some(language) = language_name(file) {
+            *counts.entry(language).or_default() += 1;
+        }
+    }
+    // `max_by_key` keeps the last maximum; iterate in reverse so ties resolve
+    // to the alphabetically first language.
+    let (language, count) = counts.into_iter().rev().max_by_key(|&(_, count)| count)?;
+    let percent = (count * 100 + files.len() / 2) / files.len();
+    Some(format!(
+        "Primary language: {} ({}% of files)\n\n",
+        language, percent
+    ))
+}
@@ -1,43 +1,333 @@
 #![doc = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/README.md"))]
 
 // Declare modules
+mod budget;
 mod config;
+mod editorconfig;
 mod errors;
+mod hashing;
+mod imports;
+mod language;
 mod listing;
+mod mime;
+mod ordering;
 mod processing;
+mod redact;
 mod tree;
 mod utils;
 
 // Necessary imports for lib.rs itself
 use log::{debug, error, info, warn};
+use ordering::Sections;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
 use std::io; // For io::ErrorKind // For logging within grab_contents
 use std::ops::Range;
 use std::path::{Path, PathBuf};
+use tree::TreeEntry;
 
 // Re-export public API components
-pub use config::GrabConfig;
+pub use config::{
+    BinaryMode, BudgetStrategy, Collate, Extractor, FileOrder, GrabConfig, HeaderStyle,
+    IncludePattern, Partition, RedactionRule, TreeStyle,
+};
 pub use errors::{GrabError, GrabResult};
-pub use listing::normalize_glob;
+pub use hashing::sha256_hex;
+pub use listing::{detect_git_repo, git_version, normalize_glob};
+pub use redact::validate_redaction_pattern;
 
 #[derive(Debug, Clone)]
 pub struct GrabbedFile {
     pub display_path: String,
     pub full_range: Range<usize>,
     pub header_range: Option<Range<usize>>,
+    /// The YAML front-matter block, if `GrabConfig::frontmatter` is set.
+    pub frontmatter_range: Option<Range<usize>>,
     pub body_range: Range<usize>,
+    /// Number of lines in the file body (excluding the trailing blank separator line).
+    pub lines: usize,
+    /// Tracked despite matching an ignore rule; only set with `GrabConfig::show_force_added`.
+    pub force_added: bool,
+    /// Unix permission bits (e.g. `0o755`), read for every file regardless of
+    /// `GrabConfig::show_mode`; `None` on other platforms.
+    pub mode: Option<u32>,
 }
 
 #[derive(Debug, Clone)]
 pub struct GrabOutput {
+    /// The `SELECTION` block describing the configuration that produced the
+    /// file set (`GrabConfig::explain_selection`), shown first.
+    pub selection: Option<String>,
+    /// The `Primary language: ...` line plus a blank line
+    /// (`GrabConfig::detect_language`), shown before the tree.
+    pub language_summary: Option<String>,
+    /// The directory tree section (`GrabConfig::include_tree`), kept apart from
+    /// `content` so callers can decide per destination whether to show it.
+    /// `full_text` joins the two as `grab_contents` returns them.
+    pub tree: Option<String>,
+    /// File sections and trailers; every range in `files` points into this.
     pub content: String,
     pub files: Vec<GrabbedFile>,
+    /// Files that were selected by listing but left out of `content`.
+    pub skipped: Vec<SkippedFile>,
+    /// Exclude patterns that excluded no files (see `GrabConfig::warn_unused_excludes`).
+    pub unused_excludes: Vec<String>,
+    /// Files shortened to fit `GrabConfig::context_budget`.
+    pub truncated: Vec<TruncatedFile>,
+    /// Bytes removed by each enabled content transformation, in pipeline order.
+    pub savings: Vec<TransformSavings>,
+    /// Files collapsed by `GrabConfig::dedupe_normalized`; `None` when dedupe is off.
+    pub dedupe_stats: Option<DedupeStats>,
+    /// Groups of files with identical content (`GrabConfig::find_duplicates`),
+    /// in order of first occurrence; empty when the search is off.
+    pub duplicates: Vec<DuplicateGroup>,
+    /// Matches replaced by `GrabConfig::anonymize` across paths in the output
+    /// (each file's path counts once) and, with `anonymize_content`, bodies.
+    pub anonymized: usize,
+}
+
+impl GrabOutput {
+    /// The complete output: the selection block, language summary, and tree
+    /// section (if any) followed by `content`.
+    pub fn full_text(&self) -> String {
+        format!(
+            "{}{}{}{}",
+            self.selection.as_deref().unwrap_or(""),
+            self.language_summary.as_deref().unwrap_or(""),
+            self.tree.as_deref().unwrap_or(""),
+            self.content
+        )
+    }
+}
+
+/// How much `GrabConfig::dedupe_normalized` removed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DedupeStats {
+    /// Files emitted as `duplicate of` references instead of their content.
+    pub files: usize,
+    /// Body bytes those files would otherwise have contributed.
+    pub bytes_saved: usize,
+}
+
+/// Files whose content was byte-identical as read (`GrabConfig::find_duplicates`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    /// Display paths of the copies, in output order.
+    pub paths: Vec<String>,
+    /// Size of one copy in bytes.
+    pub bytes: usize,
+}
+
+/// Display paths of the selected files plus listing diagnostics.
+#[derive(Debug, Clone)]
+pub struct FileListing {
+    pub files: Vec<String>,
+    /// Exclude patterns that excluded no files (see `GrabConfig::warn_unused_excludes`).
+    pub unused_excludes: Vec<String>,
+}
+
+/// A selected file with the metadata reported by `list_files_with_metadata`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListedFile {
+    pub display_path: String,
+    /// Size on disk in bytes (0 if the file vanished after listing).
+    pub bytes: u64,
+    /// Whether Git tracks the file. Always false outside Git mode.
+    pub tracked: bool,
+}
+
+/// Selected files with per-file metadata plus listing diagnostics.
+#[derive(Debug, Clone)]
+pub struct MetadataListing {
+    pub files: Vec<ListedFile>,
+    /// Exclude patterns that excluded no files (see `GrabConfig::warn_unused_excludes`).
+    pub unused_excludes: Vec<String>,
+}
+
+/// A size-annotated directory tree of the selection, from `plan_contents`.
+#[derive(Debug, Clone)]
+pub struct GrabPlan {
+    /// The directory tree with each file's size and each directory's subtotal.
+    pub tree: String,
+    pub file_count: usize,
+    pub total_bytes: u64,
+}
+
+/// How far `grab_contents_with_progress` has got through the selected files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GrabProgress {
+    /// Files whose contents have been read so far.
+    pub processed: usize,
+    /// Files selected for the grab.
+    pub total: usize,
+}
+
+/// An untracked path Git ignores, with the rule responsible (see
+/// `explain_ignored`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IgnoredPath {
+    /// Path relative to the repository root. An ignored directory is listed
+    /// once, with a trailing `/`, and stands for everything beneath it.
+    pub display_path: String,
+    /// File that defines the rule, e.g. `.gitignore` or `.git/info/exclude`.
+    pub source: String,
+    /// 1-based line of the rule in `source`.
+    pub line: usize,
+    /// The matching pattern as written, e.g. `*.log` or `!keep.log`.
+    pub pattern: String,
+}
+
+/// A selected file that was not included in the output, and why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedFile {
+    pub display_path: String,
+    pub reason: SkipReason,
+    /// Sniffed MIME type, set for binary (`NonUtf8`) and `MimeExcluded` skips.
+    pub mime: Option<String>,
+}
+
+/// Summary of a `mirror_contents` run.
+#[derive(Debug, Clone, Default)]
+pub struct MirrorReport {
+    /// Text files written after transformations.
+    pub written: usize,
+    /// Binary files copied verbatim (`copy_binaries`).
+    pub copied: usize,
+    /// Selected files that were not written.
+    pub skipped: Vec<SkippedFile>,
+    /// Matches replaced by `GrabConfig::anonymize` in mirrored paths and, with
+    /// `anonymize_content`, bodies.
+    pub anonymized: usize,
+}
+
+/// One file's size as counted by `measure_contents`: its body as a grab emits
+/// it, after content transformations and `context_budget` trimming.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MeasuredFile {
+    pub display_path: String,
+    pub bytes: usize,
+    pub chars: usize,
+    /// Whitespace-separated words.
+    pub words: usize,
+}
+
+/// Summary of a `measure_contents` run.
+#[derive(Debug, Clone, Default)]
+pub struct ContentMeasure {
+    /// Measured files, in output order.
+    pub files: Vec<MeasuredFile>,
+    /// Selected files that would be left out of the output.
+    pub skipped: Vec<SkippedFile>,
+}
+
+/// A file whose body was shortened to fit `GrabConfig::context_budget`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TruncatedFile {
+    pub display_path: String,
+    /// Lines kept in the output.
+    pub kept_lines: usize,
+    /// Lines in the full body.
+    pub total_lines: usize,
+}
+
+/// How many bytes one content transformation removed from the file bodies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransformSavings {
+    /// Transformation name, e.g. `redact` or `context-budget`.
+    pub name: &'static str,
+    /// Bytes removed; negative if the transformation grew the output (e.g. a
+    /// redaction replacement longer than the secret it masks).
+    pub bytes_saved: isize,
+}
+
+impl TransformSavings {
+    pub const DEDUPE: &'static str = "dedupe";
+    pub const EDITORCONFIG: &'static str = "editorconfig";
+    pub const STRIP_IMPORTS: &'static str = "strip-imports";
+    pub const FOLD_IMPORTS: &'static str = "fold-imports";
+    pub const REDACT: &'static str = "redact";
+    pub const CONTEXT_BUDGET: &'static str = "context-budget";
+}
+
+/// Why a selected file was left out of the output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The file's header contained a generated-code marker (`skip_generated`).
+    Generated { marker: String },
+    /// The file is not valid UTF-8 (typically a binary file).
+    NonUtf8,
+    /// The file could not be read.
+    ReadError(String),
+    /// The file was listed but no longer existed when its content was read.
+    DisappearedDuringRun,
+    /// The file's directory already contributed `limit` files (`max_per_dir`).
+    DirectoryLimit { limit: usize },
+    /// The file's content matched `pattern` from `exclude_matching`.
+    ContentExcluded { pattern: String },
+    /// The file's sniffed MIME type matched `pattern` from `exclude_mime`, or
+    /// (when `pattern` is `None`) no `include_mime` pattern.
+    MimeExcluded { pattern: Option<String> },
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkipReason::Generated { marker } => write!(f, "generated (matched '{}')", marker),
+            SkipReason::NonUtf8 => write!(f, "not valid UTF-8"),
+            SkipReason::ReadError(msg) => write!(f, "read error: {}", msg),
+            SkipReason::DisappearedDuringRun => write!(f, "removed during the run"),
+            SkipReason::DirectoryLimit { limit } => {
+                write!(f, "directory limit reached (--max-per-dir {})", limit)
+            }
+            SkipReason::ContentExcluded { pattern } => {
+                write!(f, "content matched '{}' (--exclude-matching)", pattern)
+            }
+            SkipReason::MimeExcluded {
+                pattern: Some(pattern),
+            } => write!(f, "MIME type matched '{}' (--exclude-mime)", pattern),
+            SkipReason::MimeExcluded { pattern: None } => {
+                write!(f, "MIME type not matched by --include-mime")
+            }
+        }
+    }
 }
 
 // --- Internal helpers ---
 
-/// Shared file-discovery logic: canonicalizes target, detects git repo,
-/// lists files. Returns (absolute file paths, repo root if any, canonical target).
-fn discover_files(config: &GrabConfig) -> GrabResult<(Vec<PathBuf>, Option<PathBuf>, PathBuf)> {
+/// Result of the shared discovery phase.
+struct Discovery {
+    /// Absolute paths of the selected files, in output order.
+    files: Vec<PathBuf>,
+    /// Repository root when operating in Git mode.
+    repo_root: Option<PathBuf>,
+    /// Sub-path of the repository the Git listing was limited to.
+    scope_subdir: Option<PathBuf>,
+    /// Canonicalized target path.
+    target_path: PathBuf,
+    /// Directory display paths are relative to outside Git mode: the target
+    /// path, or the nearest ancestor holding `GrabConfig::root_marker`.
+    base_path: PathBuf,
+    /// User exclude patterns that excluded nothing (only computed when
+    /// `warn_unused_excludes` is set).
+    unused_excludes: Vec<String>,
+    /// Files removed by exclude patterns (only computed when
+    /// `tree_show_excluded` applies).
+    excluded: Vec<PathBuf>,
+    /// Files dropped by `max_per_dir`, in output order.
+    omitted: Vec<PathBuf>,
+    /// Files dropped by `include_mime`/`exclude_mime`.
+    mime_skipped: Vec<mime::MimeSkip>,
+    /// Section of every selected and excluded file (empty without
+    /// `GrabConfig::partitions` or `GrabConfig::collate`).
+    sections: Sections,
+    /// Selected files shown in the tree without a body (those `content_for`
+    /// does not match; empty without it).
+    tree_only: HashSet<PathBuf>,
+}
+
+/// Canonicalizes `GrabConfig::target_path`, mapping a missing target to
+/// `GrabError::TargetPathNotFound`.
+fn canonical_target(config: &GrabConfig) -> GrabResult<PathBuf> {
     let target_path = config.target_path.canonicalize().map_err(|e| {
         if e.kind() == io::ErrorKind::NotFound {
             GrabError::TargetPathNotFound(config.target_path.clone())
@@ -49,51 +339,260 @@ fn discover_files(config: &GrabConfig) -> GrabResult<(Vec<PathBuf>, Option<PathB
         }
     })?;
     debug!("Canonical target path: {:?}", target_path);
+    Ok(target_path)
+}
+
+/// Shared file-discovery logic: canonicalizes target, detects git repo,
+/// lists files, and applies selection guardrails and ordering.
+fn discover_files(config: &GrabConfig) -> GrabResult<Discovery> {
+    let target_path = canonical_target(config)?;
 
-    let (files, maybe_repo_root) = if config.no_git {
+    let git_repo_root = if config.no_git {
         info!("Ignoring Git context due to --no-git flag.");
-        let files = listing::list_files_walkdir(&target_path, config)?;
-        (files, None)
+        None
+    } else if config.gitignore_only {
+        info!("Applying .gitignore rules without running git (--gitignore-only).");
+        None
     } else {
-        let git_repo_root = listing::detect_git_repo(&target_path)?;
-        let scope_subdir = git_repo_root
-            .as_ref()
-            .and_then(|root| derive_scope_subdir(root, &target_path, config));
-
-        let files = match &git_repo_root {
+        listing::detect_git_repo(&target_path)?
+    };
+    let scope_subdir = git_repo_root
+        .as_ref()
+        .and_then(|root| derive_scope_subdir(root, &target_path, config));
+    let base_path = match (&config.root_marker, &git_repo_root) {
+        (Some(_), Some(_)) => {
+            debug!("--root-marker has no effect in Git mode; paths are relative to the repo root.");
+            target_path.clone()
+        }
+        (Some(marker), None) => match listing::find_root_marker(&target_path, marker) {
             Some(root) => {
-                info!("Operating in Git mode. Repo root: {:?}", root);
-                if let Some(scope) = scope_subdir.as_deref() {
-                    info!("Limiting Git file listing to sub-path: {:?}", scope);
-                } else if !config.all_repo {
-                    debug!(
-                        "Scope calculation yielded full repository; processing entire repo contents."
-                    );
-                }
-                listing::list_files_git(root, config, scope_subdir.as_deref())?
+                info!("Using {:?} (contains '{}') as the path base.", root, marker);
+                root
             }
             None => {
-                info!("Operating in Non-Git mode. Target path: {:?}", target_path);
-                listing::list_files_walkdir(&target_path, config)?
+                warn!(
+                    "--root-marker '{}' not found at or above {:?}; paths stay relative to the target.",
+                    marker, target_path
+                );
+                target_path.clone()
+            }
+        },
+        (None, _) => target_path.clone(),
+    };
+
+    // Lists files for `config` in the detected mode; reused for the unfiltered
+    // pass behind --warn-unused-excludes.
+    let list = |config: &GrabConfig| -> GrabResult<Vec<PathBuf>> {
+        if let Some(entries) = &config.explicit_files {
+            let base = git_repo_root.as_deref().unwrap_or(&base_path);
+            return listing::resolve_explicit_files(base, entries, config);
+        }
+        match &git_repo_root {
+            Some(root) => listing::list_files_git(root, config, scope_subdir.as_deref()),
+            None if config.gitignore_only => listing::list_files_gitignore(&target_path, config),
+            None => listing::list_files_walkdir(&target_path, config),
+        }
+    };
+
+    match &git_repo_root {
+        Some(root) => {
+            info!("Operating in Git mode. Repo root: {:?}", root);
+            if let Some(scope) = scope_subdir.as_deref() {
+                info!("Limiting Git file listing to sub-path: {:?}", scope);
+            } else if !config.all_repo {
+                debug!(
+                    "Scope calculation yielded full repository; processing entire repo contents."
+                );
             }
+        }
+        None if !config.no_git && !config.gitignore_only => {
+            info!("Operating in Non-Git mode. Target path: {:?}", target_path);
+        }
+        None => {}
+    }
+    if git_repo_root.is_none() && config.changed_in_last.is_some() {
+        warn!("--changed-in-last has no effect outside Git mode; ignoring it.");
+    }
+    if git_repo_root.is_none() && config.between.is_some() {
+        warn!("--between has no effect outside Git mode; ignoring it.");
+    }
+    if git_repo_root.is_none() && config.since_commit.is_some() {
+        warn!("--since-commit has no effect outside Git mode; ignoring it.");
+    }
+    if git_repo_root.is_none() && config.git_attr.is_some() {
+        warn!("--git-attr has no effect outside Git mode; ignoring it.");
+    }
+    if git_repo_root.is_none() && config.git_history.is_some() {
+        warn!("--git-history has no effect outside Git mode; ignoring it.");
+    }
+    if git_repo_root.is_none() && config.show_force_added {
+        warn!("--show-force-added has no effect outside Git mode; ignoring it.");
+    }
+    let mut files = list(config)?;
+    if !config.include_patterns.is_empty() {
+        let base = match &git_repo_root {
+            Some(root) => listing::exclude_root(root, scope_subdir.as_deref()),
+            None => target_path.clone(),
         };
-        (files, git_repo_root)
+        listing::apply_include_patterns(&mut files, &base, &config.include_patterns)?;
+    }
+    let mime_skipped = if config.include_mime.is_empty() && config.exclude_mime.is_empty() {
+        Vec::new()
+    } else {
+        let (kept, skipped) = mime::filter_files(
+            files,
+            &config.include_mime,
+            &config.exclude_mime,
+            config.sniff_only_above,
+        );
+        files = kept;
+        skipped
     };
 
     info!("Found {} files.", files.len());
-    Ok((files, maybe_repo_root, target_path))
+
+    // One unfiltered pass serves both --warn-unused-excludes and --tree-show-excluded.
+    let want_unused = config.warn_unused_excludes;
+    let want_excluded = config.include_tree && config.tree_show_excluded;
+    let has_excludes = !config.exclude_patterns.is_empty() || !config.ignore_files.is_empty();
+    let unfiltered = if (want_unused || want_excluded) && has_excludes {
+        let mut unfiltered_config = config.clone();
+        unfiltered_config.exclude_patterns.clear();
+        unfiltered_config.ignore_files.clear();
+        Some(list(&unfiltered_config)?)
+    } else {
+        None
+    };
+
+    let unused_excludes = match &unfiltered {
+        Some(unfiltered) if want_unused => {
+            let base = match &git_repo_root {
+                Some(root) => listing::exclude_root(root, scope_subdir.as_deref()),
+                None => target_path.clone(),
+            };
+            listing::find_unused_excludes(&base, unfiltered, &config.exclude_patterns)
+        }
+        _ => Vec::new(),
+    };
+
+    let excluded = match unfiltered {
+        Some(unfiltered) if want_excluded => {
+            let selected: HashSet<&PathBuf> = files.iter().collect();
+            unfiltered
+                .into_iter()
+                .filter(|path| !selected.contains(path))
+                .collect()
+        }
+        _ => Vec::new(),
+    };
+
+    if let Some(minimum) = config.min_files {
+        if files.len() < minimum {
+            return Err(GrabError::TooFewFiles {
+                found: files.len(),
+                minimum,
+            });
+        }
+    }
+
+    if config.sort != FileOrder::Path || config.sort_desc {
+        ordering::sort_files(
+            &mut files,
+            config.sort,
+            config.sort_desc,
+            git_repo_root.as_deref(),
+        );
+    }
+    if !config.deprioritize_patterns.is_empty() {
+        let base = match &git_repo_root {
+            Some(root) => listing::exclude_root(root, scope_subdir.as_deref()),
+            None => target_path.clone(),
+        };
+        ordering::deprioritize(&mut files, &base, &config.deprioritize_patterns)?;
+    }
+
+    if config.readme_first {
+        files = ordering::readme_first(files);
+    }
+
+    // Partitions regroup the ordered files by section; the sort is stable.
+    // Collation also path-sorts each section.
+    let mut paths = files.clone();
+    paths.extend(excluded.iter().cloned());
+    let mut sections = match config.collate {
+        Some(Collate::Extension) => {
+            let sections = Sections::by_extension(&paths);
+            files.sort_by(|a, b| sections.index[a].cmp(&sections.index[b]).then(a.cmp(b)));
+            sections
+        }
+        None if config.partitions.is_empty() => Sections::default(),
+        None => {
+            let base = match &git_repo_root {
+                Some(root) => listing::exclude_root(root, scope_subdir.as_deref()),
+                None => target_path.clone(),
+            };
+            let sections = Sections::partitioned(&paths, &base, &config.partitions)?;
+            files.sort_by_key(|path| sections.index[path]);
+            sections
+        }
+    };
+
+    let omitted = match config.max_per_dir {
+        Some(limit) => {
+            let (kept, omitted) = ordering::limit_per_directory(files, limit);
+            files = kept;
+            omitted
+        }
+        None => Vec::new(),
+    };
+
+    let mut tree_only = if config.content_for.is_empty() {
+        HashSet::new()
+    } else {
+        let base = match &git_repo_root {
+            Some(root) => listing::exclude_root(root, scope_subdir.as_deref()),
+            None => target_path.clone(),
+        };
+        ordering::tree_only(&files, &base, &config.content_for)?
+    };
+
+    // Injected files bypass every filter above and take no partition section.
+    let mut excluded = excluded;
+    if !config.prepend_files.is_empty() || !config.append_files.is_empty() {
+        let base = git_repo_root.as_deref().unwrap_or(&base_path);
+        let prepend = listing::resolve_injected_files(base, &config.prepend_files);
+        let append: Vec<PathBuf> = listing::resolve_injected_files(base, &config.append_files)
+            .into_iter()
+            .filter(|path| !prepend.contains(path))
+            .collect();
+        let injected: HashSet<&PathBuf> = prepend.iter().chain(&append).collect();
+        files.retain(|path| !injected.contains(path));
+        excluded.retain(|path| !injected.contains(path));
+        sections.index.retain(|path, _| !injected.contains(path));
+        tree_only.retain(|path| !injected.contains(path));
+        files = prepend.into_iter().chain(files).chain(append).collect();
+    }
+
+    Ok(Discovery {
+        files,
+        repo_root: git_repo_root,
+        scope_subdir,
+        target_path,
+        base_path,
+        unused_excludes,
+        excluded,
+        omitted,
+        mime_skipped,
+        sections,
+        tree_only,
+    })
 }
 
 /// Computes a display path for a file (relative to repo root or target path).
 fn display_path(file_path: &Path, repo_root: Option<&Path>, target_path: &Path) -> String {
     let base = repo_root.unwrap_or(target_path);
     let rel = file_path.strip_prefix(base).unwrap_or(file_path);
-    let raw = rel.to_string_lossy();
-    if std::path::MAIN_SEPARATOR == '\\' && raw.contains('\\') {
-        raw.replace('\\', "/")
-    } else {
-        raw.into_owned()
-    }
+    utils::posix_path(rel).into_owned()
 }
 
 // --- Main Public Functions ---
@@ -101,85 +600,214 @@ fn display_path(file_path: &Path, repo_root: Option<&Path>, target_path: &Path)
 /// Lists the files that would be included by `dirgrab` without reading their contents.
 /// Returns display paths (relative to repo root in Git mode, or target path otherwise).
 pub fn list_files(config: &GrabConfig) -> GrabResult<Vec<String>> {
+    list_files_detailed(config).map(|listing| listing.files)
+}
+
+/// Lists the selected files along with listing diagnostics, without reading contents.
+pub fn list_files_detailed(config: &GrabConfig) -> GrabResult<FileListing> {
     info!("Listing files with config: {:?}", config);
-    let (files, maybe_repo_root, target_path) = discover_files(config)?;
-    Ok(files
+    let discovery = discover_files(config)?;
+    let anonymizer = redact::Redactor::new(&config.anonymize)?;
+    Ok(FileListing {
+        files: discovery
+            .files
+            .iter()
+            .map(|f| {
+                let path = display_path(f, discovery.repo_root.as_deref(), &discovery.base_path);
+                anonymizer.apply(&path).into_owned()
+            })
+            .collect(),
+        unused_excludes: discovery.unused_excludes,
+    })
+}
+
+/// Lists the selected files with their size and Git tracked status, without
+/// reading contents. Costs one stat per file plus, in Git mode, one `git ls-files`.
+pub fn list_files_with_metadata(config: &GrabConfig) -> GrabResult<MetadataListing> {
+    info!("Listing files with metadata, config: {:?}", config);
+    let discovery = discover_files(config)?;
+    let anonymizer = redact::Redactor::new(&config.anonymize)?;
+    let tracked = match &discovery.repo_root {
+        Some(root) => listing::list_tracked_files(root, discovery.scope_subdir.as_deref())?,
+        None => HashSet::new(),
+    };
+    let files = discovery
+        .files
+        .iter()
+        .map(|path| {
+            let bytes = file_size(path);
+            let shown = display_path(path, discovery.repo_root.as_deref(), &discovery.base_path);
+            ListedFile {
+                display_path: anonymizer.apply(&shown).into_owned(),
+                bytes,
+                tracked: tracked.contains(path),
+            }
+        })
+        .collect();
+    Ok(MetadataListing {
+        files,
+        unused_excludes: discovery.unused_excludes,
+    })
+}
+
+/// Explains, for Git mode, why files on disk were left out by Git's ignore
+/// rules: every untracked ignored path within the target's scope along with
+/// the `.gitignore` (or other exclude source) line that matched it, per `git
+/// check-ignore -v`. Selection is unaffected. Returns an empty list, with a
+/// warning, outside Git mode.
+pub fn explain_ignored(config: &GrabConfig) -> GrabResult<Vec<IgnoredPath>> {
+    let target_path = canonical_target(config)?;
+    let git_repo_root = if config.no_git || config.gitignore_only {
+        None
+    } else {
+        listing::detect_git_repo(&target_path)?
+    };
+    match &git_repo_root {
+        Some(root) => {
+            let scope_subdir = derive_scope_subdir(root, &target_path, config);
+            listing::explain_ignored(root, scope_subdir.as_deref())
+        }
+        None => {
+            warn!("Ignore-rule diagnostics need Git mode; nothing to explain.");
+            Ok(Vec::new())
+        }
+    }
+}
+
+/// Renders the directory tree of the selected files annotated with file sizes
+/// and per-directory subtotals, for deciding what to exclude before a grab.
+/// Only stats files; contents are never read.
+pub fn plan_contents(config: &GrabConfig) -> GrabResult<GrabPlan> {
+    info!("Planning grab with config: {:?}", config);
+    let discovery = discover_files(config)?;
+    let base = discovery
+        .repo_root
+        .as_deref()
+        .unwrap_or(&discovery.base_path);
+    let entries: Vec<TreeEntry> = discovery
+        .files
         .iter()
-        .map(|f| display_path(f, maybe_repo_root.as_deref(), &target_path))
-        .collect())
+        .map(|path| TreeEntry::sized(path.clone(), file_size(path)))
+        .collect();
+    let total_bytes = entries.iter().filter_map(|entry| entry.bytes).sum();
+    let anonymizer = redact::Redactor::new(&config.anonymize)?;
+    let tree = tree::truncate_tree(
+        tree::generate_tree(
+            &anonymize_tree_entries(&entries, base, &anonymizer, &mut 0),
+            base,
+            config.tree_max_depth,
+            config.tree_style,
+            config.tree_collapse_chains,
+        )?,
+        config.tree_max_entries,
+    );
+    Ok(GrabPlan {
+        tree,
+        file_count: entries.len(),
+        total_bytes,
+    })
+}
+
+/// Size of `path` in bytes, or 0 (with a warning) if it cannot be stat'ed.
+fn file_size(path: &Path) -> u64 {
+    match fs::metadata(path) {
+        Ok(metadata) => metadata.len(),
+        Err(e) => {
+            warn!("Could not stat {:?}: {}", path, e);
+            0
+        }
+    }
 }
 
 /// Performs the main `dirgrab` operation based on the provided configuration.
 pub fn grab_contents(config: &GrabConfig) -> GrabResult<String> {
-    grab_contents_detailed(config).map(|output| output.content)
+    grab_contents_detailed(config).map(|output| output.full_text())
 }
 
 /// Performs the main `dirgrab` operation and returns file-level metadata along with the content.
 pub fn grab_contents_detailed(config: &GrabConfig) -> GrabResult<GrabOutput> {
+    grab_contents_with_progress(config, |_| {})
+}
+
+/// Like `grab_contents_detailed`, but calls `on_progress` as file contents are
+/// read: before each file, and once more when all files are done. Nothing is
+/// reported for `no_files` runs or for discovery, which has no file count yet.
+pub fn grab_contents_with_progress(
+    config: &GrabConfig,
+    mut on_progress: impl FnMut(GrabProgress),
+) -> GrabResult<GrabOutput> {
     info!("Starting dirgrab operation with config: {:?}", config);
 
-    let (files_to_process, maybe_repo_root, target_path) = discover_files(config)?;
+    let GrabPreamble {
+        files: files_to_process,
+        selected,
+        repo_root: maybe_repo_root,
+        base_path,
+        sections,
+        unused_excludes,
+        mut skipped,
+        selection,
+        language_summary,
+        tree,
+        trailer,
+        budget,
+        anonymizer,
+        mut anonymized,
+    } = prepare_grab(config)?;
+
+    if config.include_tree && selected == 0 {
+        return Ok(GrabOutput {
+            selection,
+            language_summary: None,
+            tree,
+            content: String::new(),
+            files: Vec::new(),
+            skipped,
+            unused_excludes,
+            truncated: Vec::new(),
+            savings: Vec::new(),
+            dedupe_stats: None,
+            duplicates: Vec::new(),
+            anonymized,
+        });
+    }
 
-    // Initialize output buffer
     let mut output_buffer = String::new();
     let mut file_segments = Vec::new();
 
-    // Generate and prepend tree if requested
-    if config.include_tree {
-        if files_to_process.is_empty() {
-            warn!("--include-tree specified, but no files were selected for processing. Tree will be empty.");
-            // Keep explicit tree header even if empty
-            output_buffer.push_str("---\nDIRECTORY STRUCTURE (No files selected)\n---\n\n");
-            return Ok(GrabOutput {
-                content: output_buffer,
-                files: Vec::new(),
-            });
-        } else {
-            // Determine base path for tree (repo root if git mode, target path otherwise)
-            let base_path_for_tree = if !config.no_git && maybe_repo_root.is_some() {
-                maybe_repo_root.as_deref().unwrap() // Safe unwrap due to is_some() check
-            } else {
-                &target_path
-            };
-            debug!(
-                "Generating directory tree relative to: {:?}",
-                base_path_for_tree
-            );
-
-            match tree::generate_indented_tree(&files_to_process, base_path_for_tree) {
-                Ok(tree_str) => {
-                    output_buffer.push_str("---\nDIRECTORY STRUCTURE\n---\n");
-                    output_buffer.push_str(&tree_str);
-                    output_buffer.push_str("\n---\nFILE CONTENTS\n---\n\n");
-                }
-                Err(e) => {
-                    error!("Failed to generate directory tree: {}", e);
-                    // Still add header indicating failure
-                    output_buffer.push_str("---\nERROR GENERATING DIRECTORY STRUCTURE\n---\n\n");
-                }
-            }
-        }
-    }
-
     // Process files and append content (only if files exist)
+    let mut truncated = Vec::new();
+    let mut savings = Vec::new();
+    let mut dedupe_stats = None;
+    let mut duplicates = Vec::new();
     if !files_to_process.is_empty() {
         // Updated call to process_files to pass the whole config struct
         let processed = processing::process_files(
             &files_to_process,
             config, // Pass config struct
             maybe_repo_root.as_deref(),
-            &target_path,
+            &base_path,
+            budget,
+            &sections,
+            &mut on_progress,
         )?;
-        let base_offset = output_buffer.len();
         output_buffer.push_str(&processed.content);
+        skipped.extend(processed.skipped);
+        truncated = processed.truncated;
+        savings = processed.savings;
+        dedupe_stats = processed.dedupe_stats;
+        duplicates = processed.duplicates;
+        anonymized += processed.anonymized;
         for segment in processed.files {
             file_segments.push(GrabbedFile {
                 display_path: segment.display_path,
-                full_range: offset_range(&segment.full_range, base_offset),
-                header_range: segment
-                    .header_range
-                    .map(|range| offset_range(&range, base_offset)),
-                body_range: offset_range(&segment.body_range, base_offset),
+                full_range: segment.full_range,
+                header_range: segment.header_range,
+                frontmatter_range: segment.frontmatter_range,
+                body_range: segment.body_range,
+                lines: segment.lines,
+                force_added: segment.force_added,
+                mode: segment.mode,
             });
         }
     } else if !config.include_tree {
@@ -187,50 +815,597 @@ pub fn grab_contents_detailed(config: &GrabConfig) -> GrabResult<GrabOutput> {
         warn!("No files selected for processing based on current configuration.");
         // Return empty string only if no files were found AND tree wasn't requested/generated.
         return Ok(GrabOutput {
+            selection,
+            language_summary: None,
+            tree: None,
             content: String::new(),
             files: Vec::new(),
+            skipped,
+            unused_excludes,
+            truncated: Vec::new(),
+            savings: Vec::new(),
+            dedupe_stats: None,
+            duplicates: Vec::new(),
+            anonymized,
         });
     }
 
+    if !truncated.is_empty() {
+        output_buffer.push_str(&render_truncated_trailer(&truncated));
+    }
+    if let Some(trailer) = trailer {
+        let (trailer, replaced) = anonymizer.apply_counted(&trailer);
+        anonymized += replaced;
+        output_buffer.push_str(&trailer);
+    }
+
     // Return the combined buffer (might contain only tree, or tree + content, or just content)
     Ok(GrabOutput {
+        selection,
+        language_summary,
+        tree,
         content: output_buffer,
         files: file_segments,
+        skipped,
+        unused_excludes,
+        truncated,
+        savings,
+        dedupe_stats,
+        duplicates,
+        anonymized,
     })
 }
 
-fn derive_scope_subdir(
-    repo_root: &Path,
-    target_path: &Path,
-    config: &GrabConfig,
-) -> Option<PathBuf> {
-    if config.all_repo {
-        return None;
+/// Everything a grab settles before reading file contents: the files that get
+/// bodies, the blocks emitted around them, and the bytes of `context_budget`
+/// left for the file sections. Shared by `grab_contents_with_progress` and
+/// `measure_contents`, so both read the same files under the same budget.
+struct GrabPreamble {
+    /// Files that get a body, in output order.
+    files: Vec<PathBuf>,
+    /// Selected files including tree-only ones (`GrabConfig::content_for`).
+    selected: usize,
+    repo_root: Option<PathBuf>,
+    base_path: PathBuf,
+    sections: Sections,
+    unused_excludes: Vec<String>,
+    /// Files dropped before reading: `max_per_dir`, MIME filters, generated code.
+    skipped: Vec<SkippedFile>,
+    selection: Option<String>,
+    language_summary: Option<String>,
+    tree: Option<String>,
+    /// Omitted-files trailer, not yet anonymized.
+    trailer: Option<String>,
+    budget: Option<usize>,
+    anonymizer: redact::Redactor,
+    anonymized: usize,
+}
+
+fn prepare_grab(config: &GrabConfig) -> GrabResult<GrabPreamble> {
+    let Discovery {
+        files: files_to_process,
+        repo_root: maybe_repo_root,
+        target_path,
+        base_path,
+        unused_excludes,
+        excluded,
+        omitted,
+        mime_skipped,
+        sections,
+        tree_only,
+        ..
+    } = discover_files(config)?;
+
+    let anonymizer = redact::Redactor::new(&config.anonymize)?;
+    let mut anonymized = 0;
+
+    let mut tree = None;
+    let mut skipped = Vec::new();
+
+    let trailer = config
+        .max_per_dir
+        .filter(|_| !omitted.is_empty())
+        .map(|limit| {
+            let base = maybe_repo_root.as_deref().unwrap_or(&base_path);
+            render_omitted_trailer(&omitted, base, limit)
+        });
+    if let Some(limit) = config.max_per_dir {
+        skipped.extend(omitted.iter().map(|path| {
+            SkippedFile {
+                display_path: anonymizer
+                    .apply(&display_path(path, maybe_repo_root.as_deref(), &base_path))
+                    .into_owned(),
+                reason: SkipReason::DirectoryLimit { limit },
+                mime: None,
+            }
+        }));
     }
+    skipped.extend(mime_skipped.into_iter().map(|entry| {
+        SkippedFile {
+            display_path: anonymizer
+                .apply(&display_path(
+                    &entry.path,
+                    maybe_repo_root.as_deref(),
+                    &base_path,
+                ))
+                .into_owned(),
+            reason: SkipReason::MimeExcluded {
+                pattern: entry.pattern,
+            },
+            mime: Some(entry.mime.to_string()),
+        }
+    }));
 
-    match target_path.strip_prefix(repo_root) {
-        Ok(rel) => {
-            if rel.as_os_str().is_empty() {
-                None
+    // Drop generated files before the tree is built so they never show up.
+    let files_to_process = if config.skip_generated {
+        let (kept, generated) = processing::partition_generated(
+            files_to_process,
+            config,
+            maybe_repo_root.as_deref(),
+            &base_path,
+        );
+        skipped.extend(generated.into_iter().map(|entry| SkippedFile {
+            display_path: anonymizer.apply(&entry.display_path).into_owned(),
+            ..entry
+        }));
+        kept
+    } else {
+        files_to_process
+    };
+
+    let selection = config.explain_selection.then(|| {
+        let block = render_selection_block(
+            config,
+            !config.no_git && maybe_repo_root.is_some(),
+            &target_path,
+            files_to_process.len(),
+        );
+        let (block, replaced) = anonymizer.apply_counted(&block);
+        anonymized += replaced;
+        block.into_owned()
+    });
+    let language_summary = if config.detect_language {
+        language::primary_language_summary(&files_to_process)
+    } else {
+        None
+    };
+
+    // Generate and prepend tree if requested
+    if config.include_tree {
+        if files_to_process.is_empty() {
+            warn!("--include-tree specified, but no files were selected for processing. Tree will be empty.");
+            // Keep explicit tree header even if empty
+            tree = Some("---\nDIRECTORY STRUCTURE (No files selected)\n---\n\n".to_string());
+        } else {
+            // Determine base path for tree (repo root if git mode, target path otherwise)
+            let base_path_for_tree = if !config.no_git && maybe_repo_root.is_some() {
+                maybe_repo_root.as_deref().unwrap() // Safe unwrap due to is_some() check
             } else {
-                Some(rel.to_path_buf())
+                &base_path
+            };
+            debug!(
+                "Generating directory tree relative to: {:?}",
+                base_path_for_tree
+            );
+
+            // Injected files from outside the tree's root have no place in it.
+            let tree_entries: Vec<TreeEntry> = files_to_process
+                .iter()
+                .filter(|path| path.starts_with(base_path_for_tree))
+                .cloned()
+                .map(TreeEntry::selected)
+                .chain(excluded.into_iter().map(TreeEntry::excluded))
+                .collect();
+            let generated = if config.tree_partitioned && !sections.is_empty() {
+                generate_section_trees(
+                    &tree_entries,
+                    base_path_for_tree,
+                    config,
+                    &sections,
+                    &anonymizer,
+                    &mut anonymized,
+                )
+            } else {
+                tree::generate_tree(
+                    &anonymize_tree_entries(
+                        &tree_entries,
+                        base_path_for_tree,
+                        &anonymizer,
+                        &mut anonymized,
+                    ),
+                    base_path_for_tree,
+                    config.tree_max_depth,
+                    config.tree_style,
+                    config.tree_collapse_chains,
+                )
+            };
+            match generated {
+                Ok(tree_str) => {
+                    let tree_str = tree::truncate_tree(tree_str, config.tree_max_entries);
+                    tree = Some(format!(
+                        "---\nDIRECTORY STRUCTURE\n---\n{}\n---\nFILE CONTENTS\n---\n\n",
+                        tree_str
+                    ));
+                }
+                Err(e) => {
+                    error!("Failed to generate directory tree: {}", e);
+                    // Still add header indicating failure
+                    tree = Some("---\nERROR GENERATING DIRECTORY STRUCTURE\n---\n\n".to_string());
+                }
             }
         }
-        Err(_) => None,
     }
-}
 
-fn offset_range(range: &Range<usize>, offset: usize) -> Range<usize> {
-    (range.start + offset)..(range.end + offset)
+    // Tree-only files (`content_for`) were drawn in the tree above; only the
+    // rest get bodies.
+    let selected = files_to_process.len();
+    let files_to_process: Vec<PathBuf> = if tree_only.is_empty() {
+        files_to_process
+    } else {
+        files_to_process
+            .into_iter()
+            .filter(|path| !tree_only.contains(path))
+            .collect()
+    };
+
+    // The tree and omitted-files trailer are not trimmable; the rest of the
+    // budget is shared between file sections.
+    let budget = config.context_budget.map(|budget| {
+        budget
+            .saturating_sub(selection.as_ref().map_or(0, String::len))
+            .saturating_sub(language_summary.as_ref().map_or(0, String::len))
+            .saturating_sub(tree.as_ref().map_or(0, String::len))
+            .saturating_sub(trailer.as_ref().map_or(0, String::len))
+    });
+
+    Ok(GrabPreamble {
+        files: files_to_process,
+        selected,
+        repo_root: maybe_repo_root,
+        base_path,
+        sections,
+        unused_excludes,
+        skipped,
+        selection,
+        language_summary,
+        tree,
+        trailer,
+        budget,
+        anonymizer,
+        anonymized,
+    })
 }
 
-// --- FILE: dirgrab-lib/src/lib.rs ---
-// (Showing only the tests module and its necessary imports)
+/// Renders one tree per section (in section order), each under its banner;
+/// sections without entries are left out. Paths are anonymized per section,
+/// as membership is keyed by the real path.
+fn generate_section_trees(
+    entries: &[TreeEntry],
+    base_path: &Path,
+    config: &GrabConfig,
+    sections: &Sections,
+    anonymizer: &redact::Redactor,
+    anonymized: &mut usize,
+) -> GrabResult<String> {
+    let mut output = String::new();
+    // Injected files (`prepend_files`/`append_files`) belong to no section.
+    let unsectioned: Vec<TreeEntry> = entries
+        .iter()
+        .filter(|entry| !sections.index.contains_key(&entry.path))
+        .cloned()
+        .collect();
+    if !unsectioned.is_empty() {
+        output.push_str(&tree::generate_tree(
+            &anonymize_tree_entries(&unsectioned, base_path, anonymizer, anonymized),
+            base_path,
+            config.tree_max_depth,
+            config.tree_style,
+            config.tree_collapse_chains,
+        )?);
+    }
+    for index in 0..sections.labels.len() {
+        let members: Vec<TreeEntry> = entries
+            .iter()
+            .filter(|entry| sections.index.get(&entry.path) == Some(&index))
+            .cloned()
+            .collect();
+        if members.is_empty() {
+            continue;
+        }
+        output.push_str(&ordering::partition_banner(sections.label(index)));
+        output.push_str(&tree::generate_tree(
+            &anonymize_tree_entries(&members, base_path, anonymizer, anonymized),
+            base_path,
+            config.tree_max_depth,
+            config.tree_style,
+            config.tree_collapse_chains,
+        )?);
+    }
+    Ok(output)
+}
 
-// ... (rest of lib.rs code above) ...
+/// Rewrites tree entries with the `anonymize` rules, matched against each path
+/// relative to `base` just like file headers, so a multi-segment pattern hides
+/// the same paths in both. Directory entries are dropped first: the tree can
+/// no longer stat a rewritten path to tell. Adds the replacements to `count`.
+fn anonymize_tree_entries(
+    entries: &[TreeEntry],
+    base: &Path,
+    anonymizer: &redact::Redactor,
+    count: &mut usize,
+) -> Vec<TreeEntry> {
+    entries
+        .iter()
+        .filter(|entry| !entry.path.is_dir())
+        .map(|entry| {
+            let Ok(relative) = entry.path.strip_prefix(base) else {
+                return entry.clone();
+            };
+            let relative = utils::posix_path(relative);
+            let (shown, replaced) = anonymizer.apply_counted(&relative);
+            if replaced == 0 {
+                return entry.clone();
+            }
+            *count += replaced;
+            TreeEntry {
+                path: base.join(shown.as_ref()),
+                ..entry.clone()
+            }
+        })
+        .collect()
+}
 
-// --- Tests ---
-#[cfg(test)]
+/// Writes a transformed copy of every selected file below `out_dir`, preserving
+/// the directory structure relative to the repository root (Git mode) or the
+/// target path, with `anonymize` applied to the copied paths. Text files get
+/// the same treatment as in `grab_contents` (PDF and extractor conversion,
+/// UTF-16 decoding, `.editorconfig` normalization, import stripping or folding,
+/// content anonymization, redaction); headers, the tree, and `context_budget`
+/// do not apply. Binary files are copied verbatim when
+/// `copy_binaries` is set and reported as skipped otherwise. Files already
+/// inside `out_dir` are never mirrored.
+pub fn mirror_contents(
+    config: &GrabConfig,
+    out_dir: &Path,
+    copy_binaries: bool,
+) -> GrabResult<MirrorReport> {
+    info!(
+        "Mirroring files into {:?} with config: {:?}",
+        out_dir, config
+    );
+
+    let Discovery {
+        files,
+        repo_root,
+        base_path,
+        omitted,
+        mime_skipped,
+        ..
+    } = discover_files(config)?;
+    let base = repo_root.as_deref().unwrap_or(&base_path);
+
+    fs::create_dir_all(out_dir).map_err(|e| GrabError::IoError {
+        path: out_dir.to_path_buf(),
+        source: e,
+    })?;
+    let out_dir = out_dir.canonicalize().map_err(|e| GrabError::IoError {
+        path: out_dir.to_path_buf(),
+        source: e,
+    })?;
+    if base.starts_with(&out_dir) {
+        return Err(GrabError::MirrorOverlapsSource {
+            out_dir,
+            source_root: base.to_path_buf(),
+        });
+    }
+
+    let mut skipped = Vec::new();
+    if let Some(limit) = config.max_per_dir {
+        skipped.extend(omitted.iter().map(|path| SkippedFile {
+            display_path: display_path(path, repo_root.as_deref(), &base_path),
+            reason: SkipReason::DirectoryLimit { limit },
+            mime: None,
+        }));
+    }
+    skipped.extend(mime_skipped.into_iter().map(|entry| SkippedFile {
+        display_path: display_path(&entry.path, repo_root.as_deref(), &base_path),
+        reason: SkipReason::MimeExcluded {
+            pattern: entry.pattern,
+        },
+        mime: Some(entry.mime.to_string()),
+    }));
+    let files: Vec<PathBuf> = files
+        .into_iter()
+        .filter(|path| !path.starts_with(&out_dir))
+        .collect();
+    let files = if config.skip_generated {
+        let (kept, generated) =
+            processing::partition_generated(files, config, repo_root.as_deref(), &base_path);
+        skipped.extend(generated);
+        kept
+    } else {
+        files
+    };
+
+    let anonymizer = redact::Redactor::new(&config.anonymize)?;
+    for entry in &mut skipped {
+        entry.display_path = anonymizer.apply(&entry.display_path).into_owned();
+    }
+
+    let mut report = processing::mirror_files(&files, config, base, &out_dir, copy_binaries)?;
+    skipped.append(&mut report.skipped);
+    report.skipped = skipped;
+    Ok(report)
+}
+
+/// Reads every file `grab_contents` would emit and counts the bytes, characters,
+/// and words of its body without building the output: a middle ground between
+/// `plan_contents` (sizes on disk, nothing read) and a full grab. Bodies go
+/// through the same transformations and `context_budget` trimming as in a grab;
+/// headers, the tree, and other blocks around the bodies are not counted.
+pub fn measure_contents(config: &GrabConfig) -> GrabResult<ContentMeasure> {
+    info!("Measuring grab with config: {:?}", config);
+    let GrabPreamble {
+        files,
+        repo_root,
+        base_path,
+        sections,
+        mut skipped,
+        budget,
+        ..
+    } = prepare_grab(config)?;
+    let mut measure = processing::measure_files(
+        &files,
+        config,
+        repo_root.as_deref(),
+        &base_path,
+        budget,
+        &sections,
+    )?;
+    skipped.append(&mut measure.skipped);
+    measure.skipped = skipped;
+    Ok(measure)
+}
+
+fn derive_scope_subdir(
+    repo_root: &Path,
+    target_path: &Path,
+    config: &GrabConfig,
+) -> Option<PathBuf> {
+    if config.all_repo {
+        return None;
+    }
+
+    match target_path.strip_prefix(repo_root) {
+        Ok(rel) => {
+            if rel.as_os_str().is_empty() {
+                None
+            } else {
+                Some(rel.to_path_buf())
+            }
+        }
+        Err(_) => None,
+    }
+}
+
+/// Summarizes files dropped by `max_per_dir`, one line per directory.
+fn render_omitted_trailer(omitted: &[PathBuf], base: &Path, limit: usize) -> String {
+    let mut per_dir: BTreeMap<String, usize> = BTreeMap::new();
+    for path in omitted {
+        let dir = display_path(path.parent().unwrap_or(base), None, base);
+        let label = if dir.is_empty() {
+            "./".to_string()
+        } else {
+            format!("{}/", dir)
+        };
+        *per_dir.entry(label).or_insert(0) += 1;
+    }
+
+    let mut trailer = format!("---\nOMITTED FILES (--max-per-dir {})\n---\n", limit);
+    for (dir, count) in per_dir {
+        trailer.push_str(&format!("- {}: {} more file(s)\n", dir, count));
+    }
+    trailer
+}
+
+/// The `SELECTION` block for `GrabConfig::explain_selection`: the target,
+/// listing mode, and filters from `config`, plus the number of selected files.
+fn render_selection_block(
+    config: &GrabConfig,
+    git_mode: bool,
+    target_path: &Path,
+    file_count: usize,
+) -> String {
+    let mut lines = vec![format!("Target: {}", target_path.display())];
+
+    let mode = if let Some(files) = &config.explicit_files {
+        format!("explicit file list ({} given)", files.len())
+    } else if git_mode {
+        format!(
+            "Git ({}, {})",
+            if config.include_untracked {
+                "tracked + untracked"
+            } else {
+                "tracked only"
+            },
+            if config.all_repo {
+                "whole repository"
+            } else {
+                "scoped to target"
+            }
+        )
+    } else if config.gitignore_only {
+        "directory walk (no Git, .gitignore rules only)".to_string()
+    } else {
+        "directory walk (no Git)".to_string()
+    };
+    lines.push(format!("Mode: {}", mode));
+
+    if git_mode {
+        if let Some(commit) = &config.since_commit {
+            lines.push(format!("Changes: since {} (plus untracked)", commit));
+        } else if let Some((from, to)) = &config.between {
+            lines.push(format!("Changes: between {} and {}", from, to));
+        } else if let Some(count) = config.changed_in_last {
+            lines.push(format!("Changes: touched by the last {} commit(s)", count));
+        }
+        if let Some(attr) = &config.git_attr {
+            lines.push(format!("Git attribute: {}", attr));
+        }
+    }
+
+    let excludes = if config.exclude_patterns.is_empty() {
+        "none".to_string()
+    } else {
+        config.exclude_patterns.join(", ")
+    };
+    lines.push(format!("Exclude patterns: {}", excludes));
+    if !config.ignore_files.is_empty() {
+        let ignore_files: Vec<String> = config
+            .ignore_files
+            .iter()
+            .map(|path| utils::posix_path(path).into_owned())
+            .collect();
+        lines.push(format!("Ignore files: {}", ignore_files.join(", ")));
+    }
+    if !config.exclude_matching.is_empty() {
+        lines.push(format!(
+            "Content excludes: {}",
+            config.exclude_matching.join(", ")
+        ));
+    }
+    if config.skip_generated {
+        lines.push("Generated files: skipped".to_string());
+    }
+    if let Some(limit) = config.max_per_dir {
+        lines.push(format!("Per-directory limit: {} file(s)", limit));
+    }
+    lines.push(format!("Files: {} selected", file_count));
+
+    format!("---\nSELECTION\n---\n{}\n\n", lines.join("\n"))
+}
+
+/// Opens the trailer listing files cut by `GrabConfig::context_budget`.
+pub(crate) const TRUNCATED_TRAILER_HEADING: &str = "---\nTRUNCATED FILES (--context-budget)\n---\n";
+
+fn render_truncated_trailer(truncated: &[TruncatedFile]) -> String {
+    let mut trailer = String::from(TRUNCATED_TRAILER_HEADING);
+    for file in truncated {
+        trailer.push_str(&format!(
+            "- {}: kept {} of {} lines\n",
+            file.display_path, file.kept_lines, file.total_lines
+        ));
+    }
+    trailer
+}
+
+// --- FILE: dirgrab-lib/src/lib.rs ---
+// (Showing only the tests module and its necessary imports)
+
+// ... (rest of lib.rs code above) ...
+
+// --- Tests ---
+#[cfg(test)]
 mod tests {
     // Use super::* to bring everything from lib.rs into scope for tests
     // This now includes GrabConfig, GrabError, GrabResult because they are re-exported.
@@ -368,6 +1543,7 @@ mod tests {
             include_tree: false,
             convert_pdf: false,
             all_repo: false,
+            ..Default::default()
         };
         let files = crate::listing::list_files_walkdir(&path, &config)?; // Use crate:: path
         let expected_set = get_expected_set(
@@ -398,6 +1574,7 @@ mod tests {
             include_tree: false,
             convert_pdf: false,
             all_repo: false,
+            ..Default::default()
         };
         let files = crate::listing::list_files_walkdir(&path, &config)?; // Use crate:: path
         let expected_set = get_expected_set(
@@ -431,6 +1608,7 @@ mod tests {
             include_tree: false,
             convert_pdf: false,
             all_repo: false,
+            ..Default::default()
         };
         let files = crate::listing::list_files_git(&path, &config, None)?; // Use crate:: path, pass repo root
         let expected_set = get_expected_set(
@@ -469,6 +1647,7 @@ mod tests {
             include_tree: false,
             convert_pdf: false,
             all_repo: false,
+            ..Default::default()
         };
         let files = crate::listing::list_files_git(&path, &config, None)?; // Use crate:: path
         let expected_set = get_expected_set(
@@ -511,6 +1690,7 @@ mod tests {
             include_tree: false,
             convert_pdf: false,
             all_repo: false,
+            ..Default::default()
         };
         let files = crate::listing::list_files_git(&path, &config, None)?; // Use crate:: path
         let expected_set = get_expected_set(&path, &[".gitignore"]); // Only .gitignore remains
@@ -535,6 +1715,7 @@ mod tests {
             include_tree: false,
             convert_pdf: false,
             all_repo: false,
+            ..Default::default()
         };
         let files = crate::listing::list_files_git(&path, &config, None)?; // Use crate:: path
         let expected_set = get_expected_set(
@@ -565,6 +1746,7 @@ mod tests {
             include_tree: false,
             convert_pdf: false,
             all_repo: false,
+            ..Default::default()
         };
         let files = crate::listing::list_files_walkdir(&path, &config)?; // Use crate:: path
         let expected_set = get_expected_set(
@@ -604,6 +1786,7 @@ mod tests {
             include_tree: false,
             convert_pdf: false,
             all_repo: false,
+            ..Default::default()
         };
         let files = crate::listing::list_files_git(&path, &config, None)?; // Use crate:: path
         let expected_set = get_expected_set(
@@ -638,6 +1821,7 @@ mod tests {
             include_tree: false,
             convert_pdf: false,
             all_repo: false,
+            ..Default::default()
         };
         let files = crate::listing::list_files_git(&path, &config, None)?; // Use crate:: path
         let expected_set = get_expected_set(
@@ -672,6 +1856,7 @@ mod tests {
             include_tree: false,
             convert_pdf: false,
             all_repo: false,
+            ..Default::default()
         };
         let files = crate::listing::list_files_git(&path, &config, None)?; // Use crate:: path
         let expected_set = get_expected_set(
@@ -709,6 +1894,7 @@ mod tests {
             include_tree: false,
             convert_pdf: false,
             all_repo: false,
+            ..Default::default()
         };
         let scope = Path::new("deep");
         let files = crate::listing::list_files_git(&path, &config, Some(scope))?;
@@ -735,6 +1921,7 @@ mod tests {
             include_tree: false,           // No tree for easier content check
             convert_pdf: false,
             all_repo: false,
+            ..Default::default()
         };
         let result_string = grab_contents(&config)?;
 
@@ -785,6 +1972,7 @@ mod tests {
             include_tree: false,
             convert_pdf: false,
             all_repo: false,
+            ..Default::default()
         };
         let result_string = grab_contents(&config)?;
 
@@ -830,6 +2018,7 @@ mod tests {
             include_tree: false,
             convert_pdf: false,
             all_repo: false,
+            ..Default::default()
         };
         let result_string = grab_contents(&config)?;
         assert!(
@@ -860,6 +2049,7 @@ mod tests {
             include_tree: false, // No tree
             convert_pdf: false,
             all_repo: false,
+            ..Default::default()
         };
         let result_string = grab_contents(&config)?;
 
@@ -914,6 +2104,7 @@ mod tests {
             include_tree: false, // No tree
             convert_pdf: false,
             all_repo: false,
+            ..Default::default()
         };
         let result_string = grab_contents(&config)?; // Should still find files relative to repo root
 
@@ -971,6 +2162,7 @@ mod tests {
             include_tree: true,            // THE flag to test
             convert_pdf: false,
             all_repo: false,
+            ..Default::default()
         };
         let result = grab_contents(&config)?;
 
@@ -1050,6 +2242,7 @@ DIRECTORY STRUCTURE
             include_tree: true,                               // Include tree
             convert_pdf: false,
             all_repo: false,
+            ..Default::default()
         };
         let result = grab_contents(&config)?;
 
@@ -1115,6 +2308,7 @@ DIRECTORY STRUCTURE
             include_tree: true, // Ask for tree
             convert_pdf: false,
             all_repo: false,
+            ..Default::default()
         };
         let result = grab_contents(&config)?;
         // Expect only the empty tree message
@@ -1149,10 +2343,11 @@ DIRECTORY STRUCTURE
         let files_in_tmp = files_logical
             .iter()
             .map(|p| tmp_dir.path().join(p.strip_prefix("/").unwrap()))
+            .map(crate::tree::TreeEntry::selected)
             .collect::<Vec<_>>();
         let base_in_tmp = tmp_dir.path().join("project"); // The actual base path
 
-        let tree = crate::tree::generate_indented_tree(&files_in_tmp, &base_in_tmp)?; // Use crate:: path
+        let tree = crate::tree::generate_indented_tree(&files_in_tmp, &base_in_tmp, None, false)?; // Use crate:: path
         let expected = "\
 - README.md
 - src/
@@ -1187,10 +2382,11 @@ DIRECTORY STRUCTURE
         let files_in_tmp = files_logical
             .iter()
             .map(|p| tmp_dir.path().join(p.strip_prefix("/").unwrap()))
+            .map(crate::tree::TreeEntry::selected)
             .collect::<Vec<_>>();
         let base_in_tmp = tmp_dir.path().join("project"); // Actual base
 
-        let tree = crate::tree::generate_indented_tree(&files_in_tmp, &base_in_tmp)?; // Use crate:: path
+        let tree = crate::tree::generate_indented_tree(&files_in_tmp, &base_in_tmp, None, false)?; // Use crate:: path
         let expected = "\
 - a/
   - b/
@@ -1225,8 +2421,17 @@ DIRECTORY STRUCTURE
             include_tree: false,
             convert_pdf: false, // PDF conversion off
             all_repo: false,
+            ..Default::default()
         };
-        let result = crate::processing::process_files(&files_to_process, &config, None, &path)?;
+        let result = crate::processing::process_files(
+            &files_to_process,
+            &config,
+            None,
+            &path,
+            None,
+            &Sections::default(),
+            &mut |_| {},
+        )?;
         let expected_content = "Content of file 1.\n\nfn main() {}\n\n";
         assert_eq!(result.content, expected_content);
         assert_eq!(result.files.len(), 2);
@@ -1259,9 +2464,17 @@ DIRECTORY STRUCTURE
             include_tree: false,
             convert_pdf: false,
             all_repo: false,
+            ..Default::default()
         };
-        let result =
-            crate::processing::process_files(&files_to_process, &config, repo_root, &path)?;
+        let result = crate::processing::process_files(
+            &files_to_process,
+            &config,
+            repo_root,
+            &path,
+            None,
+            &Sections::default(),
+            &mut |_| {},
+        )?;
         let expected_content = format!(
             "--- FILE: {} ---\nContent of file 1.\n\n--- FILE: {} ---\nfn main() {{}}\n\n",
             Path::new("file1.txt").display(), // Paths relative to repo_root (which is path)
@@ -1283,6 +2496,61 @@ DIRECTORY STRUCTURE
         Ok(())
     }
 
+    #[test]
+    fn test_process_files_with_comment_header_styles() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        let files_to_process = vec![path.join("file2.rs")];
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            header_style: HeaderStyle::Rust,
+            ..Default::default()
+        };
+        let result = crate::processing::process_files(
+            &files_to_process,
+            &config,
+            None,
+            &path,
+            None,
+            &Sections::default(),
+            &mut |_| {},
+        )?;
+        assert_eq!(
+            result.content,
+            "// --- FILE: file2.rs ---\nfn main() {}\n\n"
+        );
+
+        config.header_style = HeaderStyle::Html;
+        let result = crate::processing::process_files(
+            &files_to_process,
+            &config,
+            None,
+            &path,
+            None,
+            &Sections::default(),
+            &mut |_| {},
+        )?;
+        let header = &result.content[result.files[0].header_range.clone().unwrap()];
+        assert_eq!(header, "<!-- FILE: file2.rs -->\n");
+
+        config.header_style = HeaderStyle::Custom {
+            prefix: "/* ".to_string(),
+            suffix: " */".to_string(),
+        };
+        let result = crate::processing::process_files(
+            &files_to_process,
+            &config,
+            None,
+            &path,
+            None,
+            &Sections::default(),
+            &mut |_| {},
+        )?;
+        assert!(result.content.starts_with("/* FILE: file2.rs */\n"));
+        Ok(())
+    }
+
     #[test]
     fn test_process_files_headers_no_git_mode() -> Result<()> {
         let (_dir, path) = setup_test_dir()?;
@@ -1297,8 +2565,17 @@ DIRECTORY STRUCTURE
             include_tree: false,
             convert_pdf: false,
             all_repo: false,
+            ..Default::default()
         };
-        let result = crate::processing::process_files(&files_to_process, &config, None, &path)?;
+        let result = crate::processing::process_files(
+            &files_to_process,
+            &config,
+            None,
+            &path,
+            None,
+            &Sections::default(),
+            &mut |_| {},
+        )?;
         let expected_content = format!(
             "--- FILE: {} ---\nContent of file 1.\n\n--- FILE: {} ---\nAnother text file.\n\n",
             Path::new("file1.txt").display(), // Paths relative to target_path
@@ -1350,6 +2627,7 @@ DIRECTORY STRUCTURE
             include_tree: false,
             convert_pdf: true,
             all_repo: false,
+            ..Default::default()
         };
 
         let result_string = grab_contents(&config)?;
@@ -1449,6 +2727,7 @@ DIRECTORY STRUCTURE
             include_tree: false,
             convert_pdf: false, // Disable PDF conversion
             all_repo: false,
+            ..Default::default()
         };
 
         let result_string = grab_contents(&config)?;
@@ -1517,6 +2796,7 @@ DIRECTORY STRUCTURE
             include_tree: false,
             convert_pdf: false,
             all_repo: false,
+            ..Default::default()
         };
         let paths = list_files(&config)?;
 
@@ -1551,6 +2831,7 @@ DIRECTORY STRUCTURE
             include_tree: false,
             convert_pdf: false,
             all_repo: false,
+            ..Default::default()
         };
         let files = crate::listing::list_files_walkdir(&path, &config)?;
         let filenames: Vec<String> = files
@@ -1594,6 +2875,7 @@ DIRECTORY STRUCTURE
             include_tree: false,
             convert_pdf: false,
             all_repo: false,
+            ..Default::default()
         };
         let files = crate::listing::list_files_walkdir(&target, &config)?;
         let filenames: Vec<String> = files
@@ -1612,6 +2894,124 @@ DIRECTORY STRUCTURE
         Ok(())
     }
 
+    #[test]
+    fn test_min_files_rejects_small_selection() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: false,
+            exclude_patterns: vec!["subdir/".to_string()],
+            no_git: true,
+            min_files: Some(10),
+            ..Default::default()
+        };
+
+        match grab_contents(&config) {
+            Err(GrabError::TooFewFiles { found, minimum }) => {
+                assert_eq!(found, 3);
+                assert_eq!(minimum, 10);
+            }
+            other => panic!("Expected TooFewFiles error, got {:?}", other),
+        }
+        assert!(matches!(
+            list_files(&config),
+            Err(GrabError::TooFewFiles { .. })
+        ));
+
+        config.min_files = Some(3);
+        assert!(grab_contents(&config).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_skip_generated_removes_marked_files_from_content_and_tree() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::create_dir_all(path.join("gen"))?;
+        fs::write(path.join("main.rs"), "fn main() {}")?;
+        fs::write(
+            path.join("gen/api.pb.rs"),
+            "// @generated by protoc\npub struct Api;",
+        )?;
+        fs::write(path.join("schema.rs"), "// Code generated. DO NOT EDIT.\n")?;
+        fs::write(path.join("custom.rs"), "// AUTOGEN\nconst X: u8 = 1;")?;
+
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            include_tree: true,
+            skip_generated: true,
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        assert!(output.content.contains("fn main() {}"));
+        assert!(output.content.contains("AUTOGEN"));
+        assert!(!output.content.contains("api.pb.rs"));
+        assert!(!output.content.contains("schema.rs"));
+        assert!(!output.content.contains("- gen/"));
+
+        let mut skipped: Vec<_> = output
+            .skipped
+            .iter()
+            .map(|s| (s.display_path.as_str(), s.reason.clone()))
+            .collect();
+        skipped.sort_by(|a, b| a.0.cmp(b.0));
+        assert_eq!(
+            skipped,
+            vec![
+                (
+                    "gen/api.pb.rs",
+                    SkipReason::Generated {
+                        marker: "@generated".to_string()
+                    }
+                ),
+                (
+                    "schema.rs",
+                    SkipReason::Generated {
+                        marker: "DO NOT EDIT".to_string()
+                    }
+                ),
+            ]
+        );
+
+        // Custom markers replace the defaults.
+        config.generated_markers = vec!["AUTOGEN".to_string()];
+        let output = grab_contents_detailed(&config)?;
+        assert!(!output.content.contains("AUTOGEN"));
+        assert!(output.content.contains("DO NOT EDIT"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort_orders_files() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::write(path.join("a.txt"), "12345")?;
+        fs::write(path.join("b.md"), "1")?;
+        fs::write(path.join("c.rs"), "123")?;
+
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            no_git: true,
+            sort: FileOrder::Size,
+            ..Default::default()
+        };
+        assert_eq!(list_files(&config)?, vec!["b.md", "c.rs", "a.txt"]);
+
+        config.sort_desc = true;
+        assert_eq!(list_files(&config)?, vec!["a.txt", "c.rs", "b.md"]);
+
+        config.sort = FileOrder::Ext;
+        config.sort_desc = false;
+        assert_eq!(list_files(&config)?, vec!["b.md", "c.rs", "a.txt"]);
+
+        config.sort = FileOrder::Path;
+        config.sort_desc = true;
+        assert_eq!(list_files(&config)?, vec!["c.rs", "b.md", "a.txt"]);
+        Ok(())
+    }
+
     #[test]
     fn test_pdf_failure_segment_consistency() -> Result<()> {
         let (_dir, path) = setup_test_dir()?;
@@ -1630,9 +3030,18 @@ DIRECTORY STRUCTURE
             include_tree: false,
             convert_pdf: true, // Enable PDF extraction (will fail on bad.pdf)
             all_repo: false,
+            ..Default::default()
         };
 
-        let result = crate::processing::process_files(&files, &config, None, &path)?;
+        let result = crate::processing::process_files(
+            &files,
+            &config,
+            None,
+            &path,
+            None,
+            &Sections::default(),
+            &mut |_| {},
+        )?;
 
         // Both files should produce segments
         assert_eq!(result.files.len(), 2, "Expected 2 file segments");
@@ -1670,4 +3079,3056 @@ DIRECTORY STRUCTURE
 
         Ok(())
     }
+
+    #[test]
+    fn test_warn_unused_excludes_walkdir() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        let config = GrabConfig {
+            target_path: path.clone(),
+            no_git: true,
+            exclude_patterns: vec![
+                "*.log".to_string(),
+                "old_dir/".to_string(),
+                "*.nothing".to_string(),
+            ],
+            warn_unused_excludes: true,
+            ..Default::default()
+        };
+        let listing = list_files_detailed(&config)?;
+        assert!(!listing.files.iter().any(|p| p.ends_with(".log")));
+        assert_eq!(listing.unused_excludes, vec!["old_dir/", "*.nothing"]);
+
+        let output = grab_contents_detailed(&config)?;
+        assert_eq!(output.unused_excludes, vec!["old_dir/", "*.nothing"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_warn_unused_excludes_git() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        if !setup_git_repo(&path)? {
+            println!("Skipping Git test: git not found or setup failed.");
+            return Ok(());
+        }
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            exclude_patterns: vec![
+                "deep/".to_string(),
+                "*.rs".to_string(),
+                "*.log".to_string(), // already gitignored, so excludes nothing extra
+            ],
+            include_untracked: true,
+            warn_unused_excludes: true,
+            ..Default::default()
+        };
+        let listing = list_files_detailed(&config)?;
+        assert_eq!(listing.unused_excludes, vec!["*.log"]);
+
+        config.warn_unused_excludes = false;
+        let listing = list_files_detailed(&config)?;
+        assert!(listing.unused_excludes.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_explicit_files_resolve_against_repo_root_in_git_mode() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        if !setup_git_repo(&path)? {
+            println!("Skipping Git test: git not found or setup failed.");
+            return Ok(());
+        }
+        // Paths as `git diff --name-only` would print them, even though the
+        // target is a subdirectory.
+        let config = GrabConfig {
+            target_path: path.join("deep"),
+            exclude_patterns: vec!["*.log".to_string()],
+            explicit_files: Some(vec![
+                PathBuf::from("deep/sub/nested.txt"),
+                PathBuf::from("file2.rs"),
+                PathBuf::from("subdir/file3.log"), // excluded by pattern
+                PathBuf::from("missing.txt"),      // not on disk
+                PathBuf::from("../outside.txt"),   // escapes the repo root
+            ]),
+            ..Default::default()
+        };
+        let files = list_files(&config)?;
+        assert_eq!(files, vec!["deep/sub/nested.txt", "file2.rs"]);
+
+        // Without git, the same entries resolve against the target path.
+        let config = GrabConfig {
+            target_path: path.join("deep"),
+            no_git: true,
+            explicit_files: Some(vec![
+                PathBuf::from("sub/nested.txt"),
+                PathBuf::from("deep/sub/nested.txt"),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(list_files(&config)?, vec!["sub/nested.txt"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_redactions_apply_in_order_with_capture_groups() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::write(
+            path.join("config.env"),
+            "API_KEY=sk-abc123\nPASSWORD=hunter2\nUSER=alice",
+        )?;
+
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            redactions: vec![
+                RedactionRule::new(r"sk-[a-z0-9]+"),
+                RedactionRule {
+                    pattern: r"(PASSWORD)=\S+".to_string(),
+                    replacement: "$1=<password>".to_string(),
+                },
+            ],
+            ..Default::default()
+        };
+        let output = grab_contents(&config)?;
+        assert!(output.contains("API_KEY=[REDACTED]"));
+        assert!(output.contains("PASSWORD=<password>"));
+        assert!(output.contains("USER=alice"));
+        assert!(!output.contains("sk-abc123"));
+        assert!(!output.contains("hunter2"));
+        assert!(output.contains("--- FILE: config.env ---"));
+
+        config.redactions = vec![RedactionRule::new("(unclosed")];
+        let err = grab_contents(&config).unwrap_err();
+        assert!(matches!(err, GrabError::InvalidRedactionPattern { .. }));
+        Ok(())
+    }
+
+    #[test]
+    fn test_tree_show_excluded_marks_filtered_files() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        fs::create_dir_all(path.join("logs"))?;
+        fs::write(path.join("logs/app.txt"), "log line")?;
+
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            exclude_patterns: vec!["*.log".to_string(), "logs/".to_string()],
+            no_git: true,
+            include_tree: true,
+            tree_show_excluded: true,
+            ..Default::default()
+        };
+        let output = grab_contents(&config)?;
+        let tree = output.split("---\nFILE CONTENTS\n---").next().unwrap();
+        assert!(tree.contains("- file1.txt\n"), "{}", tree);
+        assert!(tree.contains("  - file3.log (excluded)\n"), "{}", tree);
+        assert!(tree.contains("- logs/ (excluded)\n"), "{}", tree);
+        assert!(tree.contains("  - app.txt (excluded)\n"), "{}", tree);
+        // Directories with at least one selected file are not marked.
+        assert!(tree.contains("- subdir/\n"), "{}", tree);
+        assert!(!output.contains("log line"));
+        assert!(!output.contains("--- FILE: subdir/file3.log ---"));
+
+        config.tree_show_excluded = false;
+        let output = grab_contents(&config)?;
+        assert!(!output.contains("(excluded)"));
+        assert!(!output.contains("app.txt"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_per_dir_keeps_first_files_per_directory() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::create_dir_all(path.join("migrations"))?;
+        for i in 1..=4 {
+            fs::write(path.join(format!("migrations/{:03}.sql", i)), "-- sql")?;
+        }
+        fs::write(path.join("a.txt"), "a")?;
+        fs::write(path.join("b.txt"), "b")?;
+        fs::write(path.join("c.txt"), "c")?;
+
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            include_tree: true,
+            max_per_dir: Some(2),
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        let kept: Vec<_> = output
+            .files
+            .iter()
+            .map(|f| f.display_path.as_str())
+            .collect();
+        assert_eq!(
+            kept,
+            vec!["a.txt", "b.txt", "migrations/001.sql", "migrations/002.sql"]
+        );
+        assert!(!output.content.contains("- 003.sql"));
+        assert!(output
+            .content
+            .ends_with("---\nOMITTED FILES (--max-per-dir 2)\n---\n- ./: 1 more file(s)\n- migrations/: 2 more file(s)\n"));
+        assert_eq!(output.skipped.len(), 3);
+        assert!(output
+            .skipped
+            .iter()
+            .all(|s| s.reason == SkipReason::DirectoryLimit { limit: 2 }));
+        Ok(())
+    }
+
+    #[test]
+    fn test_image_metadata_emits_header_only() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        image::RgbImage::new(3, 2).save(path.join("shot.png"))?;
+        fs::write(path.join("broken.png"), [0x89, 0xFF, 0x00, 0x01])?;
+        fs::write(path.join("notes.txt"), "see shot.png")?;
+
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            image_metadata: true,
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        assert!(output
+            .content
+            .ends_with("--- FILE: shot.png (image: 3x2 PNG) ---\n\n"));
+        assert!(!output.content.contains("broken.png"));
+        assert_eq!(
+            output.skipped,
+            vec![SkippedFile {
+                display_path: "broken.png".to_string(),
+                reason: SkipReason::NonUtf8,
+                mime: Some("application/octet-stream".to_string()),
+            }]
+        );
+
+        config.image_metadata = false;
+        let output = grab_contents(&config)?;
+        assert!(!output.contains("shot.png (image"));
+        assert!(!output.contains("--- FILE: shot.png"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_changed_in_last_limits_to_recent_commits() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        if !setup_git_repo(&path)? {
+            println!("Skipping Git test: git not found or setup failed.");
+            return Ok(());
+        }
+        fs::write(path.join("file2.rs"), "fn main() { println!(); }")?;
+        fs::write(path.join("gone.txt"), "short-lived")?;
+        run_test_command("git", &["add", "file2.rs", "gone.txt"], &path)?;
+        run_test_command("git", &["commit", "-m", "Touch file2, add gone"], &path)?;
+        run_test_command("git", &["rm", "-q", "gone.txt"], &path)?;
+        run_test_command("git", &["commit", "-m", "Remove gone"], &path)?;
+
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            include_untracked: false,
+            changed_in_last: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(list_files(&config)?, vec!["file2.rs"]);
+
+        config.changed_in_last = Some(3);
+        assert_eq!(
+            list_files(&config)?,
+            vec!["deep/sub/nested.txt", "file2.rs"]
+        );
+
+        config.exclude_patterns = vec!["*.rs".to_string()];
+        assert_eq!(list_files(&config)?, vec!["deep/sub/nested.txt"]);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extractor_output_replaces_body_and_falls_back_on_failure() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::write(path.join("guide.rst"), "hello rst\n")?;
+        fs::write(path.join("notes.adoc"), "raw adoc\n")?;
+
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            extractors: vec![
+                Extractor {
+                    extension: "rst".to_string(),
+                    command: vec![
+                        "sh".to_string(),
+                        "-c".to_string(),
+                        "tr a-z A-Z < \"$1\"".to_string(),
+                        "sh".to_string(),
+                        "{path}".to_string(),
+                    ],
+                },
+                Extractor {
+                    extension: "ADOC".to_string(),
+                    command: vec!["false".to_string(), "{path}".to_string()],
+                },
+            ],
+            ..Default::default()
+        };
+        let output = grab_contents(&config)?;
+        assert!(output.contains("--- FILE: guide.rst (via sh) ---\nHELLO RST\n"));
+        assert!(output.contains("--- FILE: notes.adoc ---\nraw adoc\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_removed_after_listing_is_reported_as_disappeared() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::write(path.join("keep.txt"), "still here")?;
+        fs::write(path.join("doomed.rs"), "fn gone() {}")?;
+        fs::write(path.join("doomed.pdf"), "not really a pdf")?;
+
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            convert_pdf: true,
+            explicit_files: Some(vec![
+                PathBuf::from("keep.txt"),
+                PathBuf::from("doomed.rs"),
+                PathBuf::from("doomed.pdf"),
+            ]),
+            ..Default::default()
+        };
+        let listed = discover_files(&config)?;
+        assert_eq!(listed.files.len(), 3);
+
+        // Simulate concurrent deletion between listing and processing.
+        fs::remove_file(path.join("doomed.rs"))?;
+        fs::remove_file(path.join("doomed.pdf"))?;
+
+        let processed = crate::processing::process_files(
+            &listed.files,
+            &config,
+            None,
+            &listed.target_path,
+            None,
+            &Sections::default(),
+            &mut |_| {},
+        )?;
+        assert!(processed
+            .content
+            .contains("--- FILE: keep.txt ---\nstill here"));
+        assert!(!processed.content.contains("doomed"));
+        let mut skipped: Vec<_> = processed
+            .skipped
+            .iter()
+            .map(|s| (s.display_path.as_str(), s.reason.clone()))
+            .collect();
+        skipped.sort_by(|a, b| a.0.cmp(b.0));
+        assert_eq!(
+            skipped,
+            vec![
+                ("doomed.pdf", SkipReason::DisappearedDuringRun),
+                ("doomed.rs", SkipReason::DisappearedDuringRun),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_grabbed_files_report_line_counts() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::write(path.join("a.txt"), "one\ntwo\nthree\n")?;
+        fs::write(path.join("b.txt"), "no trailing newline")?;
+        fs::write(path.join("c.txt"), "")?;
+
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        let lines: Vec<_> = output
+            .files
+            .iter()
+            .map(|f| (f.display_path.as_str(), f.lines))
+            .collect();
+        assert_eq!(lines, vec![("a.txt", 3), ("b.txt", 1), ("c.txt", 0)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_gitignore_only_matches_git_listing_without_git() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        if !setup_git_repo(&path)? {
+            println!("Skipping Git test: git not found or setup failed.");
+            return Ok(());
+        }
+        // Nested rules, info/exclude, and dotfiles must all be honoured.
+        fs::write(path.join("deep/.gitignore"), "*.tmp\n")?;
+        fs::write(path.join("deep/scratch.tmp"), "scratch")?;
+        fs::write(path.join(".env.example"), "KEY=")?;
+        fs::write(path.join(".git/info/exclude"), "local-only.txt\n")?;
+        fs::write(path.join("local-only.txt"), "mine")?;
+
+        let git_config = GrabConfig {
+            target_path: path.clone(),
+            include_untracked: true,
+            exclude_patterns: vec!["*.rs".to_string()],
+            ..Default::default()
+        };
+        let git_files = list_files(&git_config)?;
+
+        let walk_config = GrabConfig {
+            gitignore_only: true,
+            ..git_config.clone()
+        };
+        let walk_files = list_files(&walk_config)?;
+        assert_eq!(walk_files, git_files);
+        assert!(walk_files.contains(&".env.example".to_string()));
+        assert!(walk_files.contains(&"deep/.gitignore".to_string()));
+        assert!(!walk_files.iter().any(|f| f.ends_with(".tmp")));
+        assert!(!walk_files.iter().any(|f| f.starts_with(".git/")));
+
+        // --no-git alone ignores .gitignore; adding --gitignore-only restores it.
+        let no_git = GrabConfig {
+            no_git: true,
+            ..git_config.clone()
+        };
+        assert!(list_files(&no_git)?.contains(&"file1.txt".to_string()));
+        let no_git_with_rules = GrabConfig {
+            gitignore_only: true,
+            ..no_git
+        };
+        assert_eq!(list_files(&no_git_with_rules)?, git_files);
+        Ok(())
+    }
+
+    #[test]
+    fn test_context_budget_trims_every_file_proportionally() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        let numbered =
+            |count: usize| -> String { (0..count).map(|i| format!("line {:03}\n", i)).collect() };
+        fs::write(path.join("big.txt"), numbered(100))?;
+        fs::write(path.join("small.txt"), numbered(20))?;
+
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            context_budget: Some(600),
+            budget_min_lines: 3,
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        let summary: Vec<_> = output
+            .truncated
+            .iter()
+            .map(|t| (t.display_path.as_str(), t.kept_lines, t.total_lines))
+            .collect();
+        assert_eq!(summary, vec![("big.txt", 39, 100), ("small.txt", 9, 20)]);
+        assert_eq!(output.files[0].lines, 39);
+        assert!(output.content.len() <= 600);
+
+        let (files_part, trailer) = output
+            .content
+            .split_once("---\nTRUNCATED FILES (--context-budget)\n---\n")
+            .expect("truncation trailer");
+        assert!(files_part.contains("line 038\n\n--- FILE: small.txt"));
+        assert_eq!(
+            trailer,
+            "- big.txt: kept 39 of 100 lines\n- small.txt: kept 9 of 20 lines\n"
+        );
+
+        // The minimum line count wins over a file's proportional share.
+        let config = GrabConfig {
+            budget_min_lines: 15,
+            ..config
+        };
+        let output = grab_contents_detailed(&config)?;
+        assert_eq!(output.truncated[1].kept_lines, 16);
+        assert!(output.content.len() <= 600);
+
+        // ...but not over the budget itself, nor do line prefixes overflow it.
+        for prefix_lines in [false, true] {
+            let config = GrabConfig {
+                budget_min_lines: 60,
+                prefix_lines,
+                ..config.clone()
+            };
+            let output = grab_contents_detailed(&config)?;
+            assert_eq!(output.truncated.len(), 2);
+            assert!(output.content.len() <= 600, "{}", output.content.len());
+        }
+
+        // Nothing is trimmed when the output already fits.
+        let config = GrabConfig {
+            context_budget: Some(10_000),
+            ..config
+        };
+        let output = grab_contents_detailed(&config)?;
+        assert!(output.truncated.is_empty());
+        assert!(!output.content.contains("TRUNCATED FILES"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_ignore_files_exclude_in_git_and_walkdir_modes() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        if !setup_git_repo(&path)? {
+            println!("Skipping Git test: git not found or setup failed.");
+            return Ok(());
+        }
+        // Untracked on purpose: the ignore file is not committed.
+        fs::write(path.join(".aiignore"), "# AI-specific\nsubdir/\n*.rs\n")?;
+
+        let git_config = GrabConfig {
+            target_path: path.clone(),
+            include_untracked: true,
+            ignore_files: vec![PathBuf::from(".aiignore"), PathBuf::from("missing")],
+            ..Default::default()
+        };
+        let git_files = list_files(&git_config)?;
+        assert!(git_files.contains(&".aiignore".to_string()));
+        assert!(!git_files.iter().any(|f| f.ends_with(".rs")));
+        assert!(!git_files.iter().any(|f| f.starts_with("subdir/")));
+
+        let walk_config = GrabConfig {
+            no_git: true,
+            ..git_config
+        };
+        let walk_files = list_files(&walk_config)?;
+        assert!(walk_files.contains(&"file1.txt".to_string()));
+        assert!(!walk_files.iter().any(|f| f.ends_with(".rs")));
+        assert!(!walk_files.iter().any(|f| f.starts_with("subdir/")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_savings_track_bytes_removed_per_transformation() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::write(path.join("a.txt"), "token=abcdefghij\n".repeat(20))?;
+
+        let config = GrabConfig {
+            target_path: path.clone(),
+            no_git: true,
+            ..Default::default()
+        };
+        assert!(grab_contents_detailed(&config)?.savings.is_empty());
+
+        let config = GrabConfig {
+            // "abcdefghij" (10 bytes) -> "***" (3 bytes), 20 times.
+            redactions: vec![RedactionRule {
+                pattern: "abcdefghij".to_string(),
+                replacement: "***".to_string(),
+            }],
+            context_budget: Some(100),
+            budget_min_lines: 1,
+            ..config
+        };
+        let output = grab_contents_detailed(&config)?;
+        let names: Vec<_> = output.savings.iter().map(|s| s.name).collect();
+        assert_eq!(
+            names,
+            vec![TransformSavings::REDACT, TransformSavings::CONTEXT_BUDGET]
+        );
+        assert_eq!(output.savings[0].bytes_saved, 140);
+        let kept = output.files[0].body_range.len() - 1; // minus the blank separator line
+        assert_eq!(
+            output.savings[1].bytes_saved,
+            (20 * "token=***\n".len() - kept) as isize
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_utf16_files_with_bom_are_decoded() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        let text = "héllo\r\nwörld 🦀\n";
+        let utf16 = |le: bool| -> Vec<u8> {
+            std::iter::once(0xFEFF)
+                .chain(text.encode_utf16())
+                .flat_map(|unit| {
+                    if le {
+                        unit.to_le_bytes()
+                    } else {
+                        unit.to_be_bytes()
+                    }
+                })
+                .collect()
+        };
+        fs::write(path.join("be.txt"), utf16(false))?;
+        fs::write(path.join("le.txt"), utf16(true))?;
+        // A BOM followed by an odd number of bytes is not valid UTF-16.
+        fs::write(path.join("odd.txt"), [0xFF, 0xFE, 0x41])?;
+
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        let expected = format!(
+            "--- FILE: be.txt (decoded from utf-16) ---\n{text}\n--- FILE: le.txt (decoded from utf-16) ---\n{text}\n"
+        );
+        assert_eq!(output.content, expected);
+        assert_eq!(output.skipped.len(), 1);
+        assert_eq!(output.skipped[0].display_path, "odd.txt");
+        assert_eq!(output.skipped[0].reason, SkipReason::NonUtf8);
+        Ok(())
+    }
+
+    #[test]
+    fn test_frontmatter_block_precedes_body_with_its_own_range() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::create_dir_all(path.join("docs"))?;
+        let file = path.join("docs/a: b.md");
+        fs::write(&file, "# Title\n")?;
+        let mtime = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_709_210_096);
+        fs::File::options()
+            .write(true)
+            .open(&file)?
+            .set_modified(mtime)?;
+
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            frontmatter: true,
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        let frontmatter =
+            "---\npath: \"docs/a: b.md\"\nbytes: 8\nmodified: 2024-02-29T12:34:56Z\n---\n";
+        assert_eq!(
+            output.content,
+            format!("--- FILE: docs/a: b.md ---\n{frontmatter}# Title\n\n")
+        );
+        let grabbed = &output.files[0];
+        let range = grabbed
+            .frontmatter_range
+            .clone()
+            .expect("frontmatter range");
+        assert_eq!(&output.content[range.clone()], frontmatter);
+        assert_eq!(
+            grabbed.header_range.as_ref().map(|r| r.end),
+            Some(range.start)
+        );
+        assert_eq!(grabbed.body_range.start, range.end);
+        assert_eq!(&output.content[grabbed.body_range.clone()], "# Title\n\n");
+
+        // Without headers, the front matter leads each file.
+        let config = GrabConfig {
+            add_headers: false,
+            ..config
+        };
+        assert!(grab_contents(&config)?.starts_with("---\npath: "));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_frontmatter_escapes_control_characters_in_paths() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::write(path.join("a\nb\t\u{1b}\u{85}\u{2028}.md"), "x\n")?;
+        let config = GrabConfig {
+            target_path: path.clone(),
+            no_git: true,
+            frontmatter: true,
+            ..Default::default()
+        };
+        let output = grab_contents(&config)?;
+        assert!(
+            output.starts_with("---\npath: \"a\\nb\\t\\x1B\\x85\\u2028.md\"\nbytes: 2\n"),
+            "{}",
+            output
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_mirror_writes_transformed_copies_and_handles_binaries() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        fs::write(path.join("secrets.env"), "API_KEY=sk-12345\n")?;
+        // An earlier mirror inside the target must not be mirrored again.
+        fs::create_dir_all(path.join("out/subdir"))?;
+        fs::write(path.join("out/stale.txt"), "old")?;
+
+        let config = GrabConfig {
+            target_path: path.clone(),
+            no_git: true,
+            redactions: vec![RedactionRule::new("sk-[0-9]+")],
+            ..Default::default()
+        };
+        let out = path.join("out");
+        let report = mirror_contents(&config, &out, false)?;
+        assert_eq!(report.written, 5);
+        assert_eq!(report.copied, 0);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].display_path, "binary.dat");
+        assert_eq!(
+            fs::read_to_string(out.join("secrets.env"))?,
+            "API_KEY=[REDACTED]\n"
+        );
+        assert_eq!(
+            fs::read_to_string(out.join("subdir/another.txt"))?,
+            "Another text file."
+        );
+        assert!(!out.join("binary.dat").exists());
+        assert!(!out.join("out").exists());
+
+        let report = mirror_contents(&config, &out, true)?;
+        assert_eq!(report.copied, 1);
+        assert!(report.skipped.is_empty());
+        assert_eq!(fs::read(out.join("binary.dat"))?, vec![0x80, 0x81, 0x82]);
+
+        // Mirroring into a parent of the source would overwrite it.
+        let nested = GrabConfig {
+            target_path: path.join("subdir"),
+            ..config
+        };
+        assert!(matches!(
+            mirror_contents(&nested, &path, false),
+            Err(GrabError::MirrorOverlapsSource { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_mirror_anonymizes_paths_and_content() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("src");
+        fs::create_dir_all(path.join("acme-billing"))?;
+        fs::write(
+            path.join("acme-billing/acme.txt"),
+            "Acme Corp owns acme-billing\n",
+        )?;
+        let out = dir.path().join("out");
+        let config = GrabConfig {
+            target_path: path.clone(),
+            no_git: true,
+            anonymize: vec![RedactionRule {
+                pattern: "(?i)acme".to_string(),
+                replacement: "vendor".to_string(),
+            }],
+            ..Default::default()
+        };
+        let report = mirror_contents(&config, &out, false)?;
+        assert_eq!(report.written, 1);
+        assert_eq!(report.anonymized, 2);
+        assert!(!out.join("acme-billing").exists());
+        assert_eq!(
+            fs::read_to_string(out.join("vendor-billing/vendor.txt"))?,
+            "Acme Corp owns acme-billing\n"
+        );
+
+        let config = GrabConfig {
+            anonymize_content: true,
+            ..config
+        };
+        let report = mirror_contents(&config, &out, false)?;
+        assert_eq!(report.anonymized, 4);
+        assert_eq!(
+            fs::read_to_string(out.join("vendor-billing/vendor.txt"))?,
+            "vendor Corp owns vendor-billing\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_mirror_applies_body_transforms() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("src");
+        fs::create_dir_all(&path)?;
+        fs::write(
+            path.join("main.rs"),
+            "use std::fs;\nuse std::path::Path;\n\nfn main() {}\n",
+        )?;
+        let out = dir.path().join("out");
+        let config = GrabConfig {
+            target_path: path.clone(),
+            no_git: true,
+            strip_imports: true,
+            ..Default::default()
+        };
+        let report = mirror_contents(&config, &out, false)?;
+        assert_eq!(report.written, 1);
+        assert_eq!(fs::read_to_string(out.join("main.rs"))?, "fn main() {}\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_between_limits_to_files_changed_between_refs() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        if !setup_git_repo(&path)? {
+            println!("Skipping Git test: git not found or setup failed.");
+            return Ok(());
+        }
+        run_test_command("git", &["tag", "v1.0"], &path)?;
+        fs::write(path.join("file2.rs"), "fn main() { println!(); }")?;
+        fs::write(path.join("added.txt"), "new in v2")?;
+        run_test_command("git", &["add", "file2.rs", "added.txt"], &path)?;
+        run_test_command("git", &["rm", "-q", ".gitignore"], &path)?;
+        run_test_command("git", &["commit", "-m", "Release 2"], &path)?;
+        run_test_command("git", &["tag", "v2.0"], &path)?;
+
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            include_untracked: false,
+            between: Some(("v1.0".to_string(), "v2.0".to_string())),
+            ..Default::default()
+        };
+        assert_eq!(list_files(&config)?, vec!["added.txt", "file2.rs"]);
+
+        // Reversed, the comparison still lists files present on both sides.
+        config.between = Some(("v2.0".to_string(), "v1.0".to_string()));
+        assert_eq!(list_files(&config)?, vec!["file2.rs"]);
+
+        config.between = Some(("v1.0".to_string(), "no-such-tag".to_string()));
+        match list_files(&config) {
+            Err(GrabError::UnknownGitRef { reference }) => assert_eq!(reference, "no-such-tag"),
+            other => panic!("expected UnknownGitRef, got {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_tree_max_depth_collapses_deep_entries() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::create_dir_all(path.join("node_modules/pkg/lib/deep"))?;
+        fs::write(path.join("node_modules/pkg/index.js"), "a")?;
+        fs::write(path.join("node_modules/pkg/lib/util.js"), "b")?;
+        fs::write(path.join("node_modules/pkg/lib/deep/x.js"), "c")?;
+        fs::write(path.join("node_modules/top.js"), "d")?;
+        fs::write(path.join("README.md"), "e")?;
+
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            include_tree: true,
+            tree_max_depth: Some(2),
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        let tree = "---\nDIRECTORY STRUCTURE\n---\n- README.md\n- node_modules/\n  - pkg/\n    - …/ (3 files)\n  - top.js\n";
+        let rendered = output.tree.as_deref().unwrap_or_default();
+        assert!(rendered.starts_with(tree), "unexpected tree:\n{}", rendered);
+        // Contents still include every file.
+        assert_eq!(output.files.len(), 5);
+        assert!(output
+            .content
+            .contains("--- FILE: node_modules/pkg/lib/deep/x.js ---"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_prefix_lines_replaces_headers_with_grep_style_prefixes() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::create_dir_all(path.join("src"))?;
+        fs::write(path.join("src/lib.rs"), "fn a() {}\nfn b() {}")?;
+        fs::write(path.join("notes.txt"), "todo\n")?;
+
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            prefix_lines: true,
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        assert_eq!(
+            output.content,
+            "notes.txt:1: todo\n\nsrc/lib.rs:1: fn a() {}\nsrc/lib.rs:2: fn b() {}\n\n"
+        );
+        let lib = &output.files[1];
+        assert!(lib.header_range.is_none());
+        assert_eq!(lib.lines, 2);
+        assert_eq!(
+            &output.content[lib.body_range.clone()],
+            "src/lib.rs:1: fn a() {}\nsrc/lib.rs:2: fn b() {}\n\n"
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks_emit_target_unless_followed() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::write(path.join("real_file.txt"), "real content\n")?;
+        std::os::unix::fs::symlink("real_file.txt", path.join("link.txt"))?;
+
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            ..Default::default()
+        };
+        let output = grab_contents(&config)?;
+        assert!(
+            output.contains("--- FILE: link.txt (symlink -> real_file.txt) ---\nreal_file.txt\n"),
+            "unexpected output: {output}"
+        );
+        assert!(output.contains("--- FILE: real_file.txt ---\nreal content\n"));
+
+        config.follow_symlinks = true;
+        let output = grab_contents(&config)?;
+        assert!(output.contains("--- FILE: link.txt ---\nreal content\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_files_with_metadata_reports_size_and_tracked_status() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        if !setup_git_repo(&path)? {
+            println!("Skipping Git test: git not found or setup failed.");
+            return Ok(());
+        }
+        let config = GrabConfig {
+            target_path: path.clone(),
+            include_untracked: true,
+            ..Default::default()
+        };
+        let listing = list_files_with_metadata(&config)?;
+        let entry = |name: &str| {
+            listing
+                .files
+                .iter()
+                .find(|f| f.display_path == name)
+                .cloned()
+                .unwrap_or_else(|| panic!("{name} missing from {:?}", listing.files))
+        };
+        assert_eq!(
+            entry("file2.rs"),
+            ListedFile {
+                display_path: "file2.rs".to_string(),
+                bytes: 12,
+                tracked: true,
+            }
+        );
+        let untracked = entry("untracked.txt");
+        assert!(!untracked.tracked);
+        assert_eq!(untracked.bytes, 25);
+        assert_eq!(
+            listing
+                .files
+                .iter()
+                .map(|f| f.display_path.clone())
+                .collect::<Vec<_>>(),
+            list_files(&config)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_linked_worktrees_list_correctly() -> Result<()> {
+        let (dir, path) = setup_test_dir()?;
+        if !setup_git_repo(&path)? {
+            println!("Skipping Git test: git not found or setup failed.");
+            return Ok(());
+        }
+        let linked = dir.path().join("linked");
+        let linked_arg = linked.to_string_lossy().to_string();
+        run_test_command("git", &["worktree", "add", "-q", &linked_arg], &path)?;
+        run_test_command("git", &["worktree", "add", "-q", "inner"], &path)?;
+        fs::write(linked.join("scratch.txt"), "untracked in worktree")?;
+
+        assert!(linked.join(".git").is_file());
+        assert_eq!(
+            crate::listing::detect_git_repo(&linked)?,
+            Some(linked.canonicalize()?)
+        );
+
+        let git_config = GrabConfig {
+            target_path: linked.clone(),
+            include_untracked: true,
+            ..Default::default()
+        };
+        let expected = vec![
+            ".gitignore".to_string(),
+            "deep/sub/nested.txt".to_string(),
+            "file2.rs".to_string(),
+            "scratch.txt".to_string(),
+            "subdir/another.txt".to_string(),
+        ];
+        assert_eq!(list_files(&git_config)?, expected);
+
+        // `.git` is a file in a linked worktree and must still be excluded.
+        let walk_config = GrabConfig {
+            no_git: true,
+            ..git_config.clone()
+        };
+        assert_eq!(list_files(&walk_config)?, expected);
+
+        // A worktree nested in the main checkout is not listed as an entry.
+        let main_config = GrabConfig {
+            target_path: path.clone(),
+            include_untracked: true,
+            ..Default::default()
+        };
+        let main_files = list_files(&main_config)?;
+        assert!(
+            main_files.iter().all(|f| !f.starts_with("inner")),
+            "nested worktree leaked into listing: {:?}",
+            main_files
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_dedupe_normalized_ignores_whitespace_differences() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::write(path.join("a.txt"), "alpha  \nbeta\n")?;
+        fs::write(path.join("b.txt"), "alpha\r\nbeta\t\r\n\r\n")?;
+        fs::write(path.join("c.txt"), "alpha\nbeta gamma\n")?;
+
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            dedupe_normalized: true,
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        assert!(output
+            .content
+            .contains("--- FILE: a.txt ---\nalpha  \nbeta\n"));
+        assert!(output
+            .content
+            .contains("--- FILE: b.txt (duplicate of a.txt, whitespace-insensitive) ---\n\n"));
+        assert!(output
+            .content
+            .contains("--- FILE: c.txt ---\nalpha\nbeta gamma\n"));
+        assert_eq!(
+            output.savings,
+            vec![TransformSavings {
+                name: TransformSavings::DEDUPE,
+                bytes_saved: 16,
+            }]
+        );
+        assert_eq!(
+            output.dedupe_stats,
+            Some(DedupeStats {
+                files: 1,
+                bytes_saved: 16,
+            })
+        );
+
+        config.dedupe_normalized = false;
+        let output = grab_contents(&config)?;
+        assert!(output.contains("--- FILE: b.txt ---\nalpha\r\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_duplicates_hashes_content_as_read() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::write(path.join("a.txt"), "same\n")?;
+        fs::write(path.join("b.txt"), "same\n")?;
+        fs::write(path.join("c.txt"), "same \n")?;
+        fs::write(path.join("d.txt"), "other\n")?;
+
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            ..Default::default()
+        };
+        assert!(grab_contents_detailed(&config)?.duplicates.is_empty());
+
+        config.find_duplicates = true;
+        let expected = vec![DuplicateGroup {
+            paths: vec!["a.txt".to_string(), "b.txt".to_string()],
+            bytes: 5,
+        }];
+        assert_eq!(grab_contents_detailed(&config)?.duplicates, expected);
+        // Neither collapsed bodies nor per-line path prefixes change the groups.
+        config.dedupe_normalized = true;
+        assert_eq!(grab_contents_detailed(&config)?.duplicates, expected);
+        config.dedupe_normalized = false;
+        config.prefix_lines = true;
+        assert_eq!(grab_contents_detailed(&config)?.duplicates, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_git_history_appends_commit_subjects() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        if !setup_git_repo(&path)? {
+            println!("Skipping Git test: git not found or setup failed.");
+            return Ok(());
+        }
+        fs::write(path.join("file2.rs"), "fn main() { println!(); }")?;
+        run_test_command("git", &["commit", "-am", "Print something"], &path)?;
+
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            include_untracked: true,
+            git_history: Some(5),
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        let file2 = output
+            .files
+            .iter()
+            .find(|f| f.display_path == "file2.rs")
+            .expect("file2.rs should be grabbed");
+        let segment = &output.content[file2.full_range.clone()];
+        let history = segment
+            .split_once("--- HISTORY: file2.rs ---\n")
+            .map(|(_, history)| history)
+            .expect("history block should follow the content");
+        let subjects: Vec<&str> = history
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| line.split_once(' ').map_or("", |(_, subject)| subject))
+            .collect();
+        assert_eq!(subjects, vec!["Print something", "Initial commit"]);
+        assert!(!output.content[file2.body_range.clone()].contains("HISTORY"));
+        assert!(
+            !output.content.contains("HISTORY: untracked.txt"),
+            "untracked files have no history"
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_git_history_matches_file_names_literally() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        if !setup_git_repo(&path)? {
+            println!("Skipping Git test: git not found or setup failed.");
+            return Ok(());
+        }
+        fs::write(path.join("[ab].txt"), "brackets\n")?;
+        run_test_command("git", &["add", "[ab].txt"], &path)?;
+        run_test_command("git", &["commit", "-m", "Add brackets"], &path)?;
+        fs::write(path.join("a.txt"), "a\n")?;
+        run_test_command("git", &["add", "a.txt"], &path)?;
+        run_test_command("git", &["commit", "-m", "Add a"], &path)?;
+
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            git_history: Some(5),
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        let history = output
+            .content
+            .split_once("--- HISTORY: [ab].txt ---\n")
+            .map(|(_, history)| history)
+            .expect("history block for [ab].txt");
+        let subjects: Vec<&str> = history
+            .lines()
+            .take_while(|line| !line.is_empty())
+            .map(|line| line.split_once(' ').map_or("", |(_, subject)| subject))
+            .collect();
+        assert_eq!(subjects, vec!["Add brackets"]);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_relativize_symlink_targets_inside_and_outside_repo() -> Result<()> {
+        let dir = tempdir()?;
+        let repo = dir.path().join("repo");
+        fs::create_dir_all(repo.join("docs"))?;
+        fs::create_dir_all(repo.join("nested/deeper"))?;
+        fs::write(repo.join("docs/guide.md"), "guide\n")?;
+        fs::write(dir.path().join("outside.txt"), "outside\n")?;
+        std::os::unix::fs::symlink("../../docs/guide.md", repo.join("nested/deeper/guide.md"))?;
+        std::os::unix::fs::symlink("../outside.txt", repo.join("external.txt"))?;
+        if Command::new("git").arg("--version").output().is_err() {
+            println!("Skipping Git test: git not found.");
+            return Ok(());
+        }
+        run_test_command("git", &["init", "-b", "main"], &repo)?;
+
+        let mut config = GrabConfig {
+            target_path: repo.clone(),
+            add_headers: true,
+            include_untracked: true,
+            ..Default::default()
+        };
+        let output = grab_contents(&config)?;
+        assert!(output
+            .contains("--- FILE: nested/deeper/guide.md (symlink -> ../../docs/guide.md) ---"));
+
+        config.relativize_symlink_targets = true;
+        let output = grab_contents(&config)?;
+        assert!(
+            output.contains(
+                "--- FILE: nested/deeper/guide.md (symlink -> docs/guide.md) ---\ndocs/guide.md\n"
+            ),
+            "unexpected output: {output}"
+        );
+        let outside = dir.path().canonicalize()?.join("outside.txt");
+        assert!(output.contains(&format!(
+            "--- FILE: external.txt (symlink -> {}) ---",
+            outside.display()
+        )));
+        Ok(())
+    }
+
+    #[test]
+    fn test_excludes_select_identically_in_git_and_walkdir_modes() -> Result<()> {
+        let dir = tempdir()?;
+        let repo = dir.path().to_path_buf();
+        for file in [
+            "docs/root.md",
+            "sub/docs/a.md",
+            "sub/deep/docs/d.md",
+            "sub/notes/n.txt",
+            "sub/x.txt",
+            "sub/y.log",
+        ] {
+            let path = repo.join(file);
+            fs::create_dir_all(path.parent().unwrap())?;
+            fs::write(path, file)?;
+        }
+        if Command::new("git").arg("--version").output().is_err() {
+            println!("Skipping Git test: git not found.");
+            return Ok(());
+        }
+        run_test_command("git", &["init", "-b", "main"], &repo)?;
+        let sub = repo.join("sub").canonicalize()?;
+
+        for pattern in ["docs/*", "*.md", "notes/", "**/docs/**", "/x.txt"] {
+            let git_config = GrabConfig {
+                target_path: sub.clone(),
+                include_untracked: true,
+                exclude_patterns: vec![pattern.to_string(), "*.log".to_string()],
+                ..Default::default()
+            };
+            let walk_config = GrabConfig {
+                no_git: true,
+                ..git_config.clone()
+            };
+            let git_files = crate::discover_files(&git_config)?.files;
+            let walk_files = crate::discover_files(&walk_config)?.files;
+            assert_eq!(git_files, walk_files, "pattern {pattern:?}");
+            assert!(!git_files.iter().any(|p| p.ends_with("y.log")));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_link_base_renders_markdown_link_headers() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::create_dir_all(path.join("src"))?;
+        fs::write(path.join("src/lib.rs"), "pub fn a() {}\n")?;
+        fs::write(path.join("my notes.txt"), "todo\n")?;
+
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            link_base: Some("https://github.com/org/repo/blob/main/".to_string()),
+            ..Default::default()
+        };
+        let output = grab_contents(&config)?;
+        assert!(output.contains(
+            "### [src/lib.rs](https://github.com/org/repo/blob/main/src/lib.rs)\npub fn a() {}\n"
+        ));
+        assert!(output.contains(
+            "### [my notes.txt](https://github.com/org/repo/blob/main/my%20notes.txt)\n"
+        ));
+
+        config.link_base = Some("https://example.com/tree".to_string());
+        let output = grab_contents(&config)?;
+        assert!(output.contains("### [src/lib.rs](https://example.com/tree/src/lib.rs)\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_contents_annotates_tree_with_sizes() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::create_dir_all(path.join("src/nested"))?;
+        fs::write(path.join("README.md"), "x".repeat(100))?;
+        fs::write(path.join("src/lib.rs"), "y".repeat(2048))?;
+        fs::write(path.join("src/nested/mod.rs"), "z".repeat(1024))?;
+
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            no_git: true,
+            ..Default::default()
+        };
+        let plan = plan_contents(&config)?;
+        assert_eq!(plan.file_count, 3);
+        assert_eq!(plan.total_bytes, 3172);
+        assert_eq!(
+            plan.tree,
+            "- README.md (100 B)\n- src/ (3.0 KB)\n  - lib.rs (2.0 KB)\n  - nested/ (1.0 KB)\n    - mod.rs (1.0 KB)\n"
+        );
+
+        config.tree_max_depth = Some(1);
+        let plan = plan_contents(&config)?;
+        assert_eq!(
+            plan.tree,
+            "- README.md (100 B)\n- src/ (3.0 KB)\n  - …/ (2 files, 3.0 KB)\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_tight_separates_files_with_single_newline() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::write(path.join("a.txt"), "alpha\n")?;
+        fs::write(path.join("b.txt"), "beta")?;
+
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            tight: true,
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        assert_eq!(
+            output.content,
+            "--- FILE: a.txt ---\nalpha\n--- FILE: b.txt ---\nbeta\n"
+        );
+        let bodies: Vec<&str> = output
+            .files
+            .iter()
+            .map(|f| &output.content[f.body_range.clone()])
+            .collect();
+        assert_eq!(bodies, vec!["alpha\n", "beta\n"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_show_force_added_marks_ignored_tracked_files() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        if !setup_git_repo(&path)? {
+            println!("Skipping Git test: git not found or setup failed.");
+            return Ok(());
+        }
+        fs::write(path.join("forced.log"), "kept on purpose")?;
+        run_test_command("git", &["add", "-f", "forced.log"], &path)?;
+        run_test_command("git", &["commit", "-m", "Force-add log"], &path)?;
+
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            show_force_added: true,
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        let forced: Vec<&str> = output
+            .files
+            .iter()
+            .filter(|f| f.force_added)
+            .map(|f| f.display_path.as_str())
+            .collect();
+        assert_eq!(forced, vec!["forced.log"]);
+        assert!(output
+            .content
+            .contains("--- FILE: forced.log (force-added) ---\n"));
+        assert!(output.content.contains("--- FILE: file2.rs ---\n"));
+
+        let plain = grab_contents_detailed(&GrabConfig {
+            show_force_added: false,
+            ..config
+        })?;
+        assert!(plain.files.iter().all(|f| !f.force_added));
+        assert!(!plain.content.contains("force-added"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort_git_recency_orders_by_last_commit() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        if !setup_git_repo(&path)? {
+            println!("Skipping Git test: git not found or setup failed.");
+            return Ok(());
+        }
+        let commit_at = |file: &str, content: &str, date: &str| -> Result<()> {
+            fs::write(path.join(file), content)?;
+            run_test_command("git", &["add", file], &path)?;
+            let status = crate::utils::git_command(&path)
+                .args(["commit", "-q", "-m", file])
+                .env("GIT_COMMITTER_DATE", date)
+                .status()?;
+            anyhow::ensure!(status.success(), "commit of {} failed", file);
+            Ok(())
+        };
+        commit_at("a.txt", "a1", "2030-01-01T00:00:00Z")?;
+        commit_at("c.txt", "c1", "2030-01-02T00:00:00Z")?;
+        commit_at("b.txt", "b1", "2030-01-03T00:00:00Z")?;
+        commit_at("a.txt", "a2", "2030-01-04T00:00:00Z")?;
+
+        let config = GrabConfig {
+            target_path: path.clone(),
+            sort: FileOrder::GitRecency,
+            ..Default::default()
+        };
+        let listed = list_files(&config)?;
+        // Files from the setup commits are older and sort before these three.
+        let committed: Vec<&str> = listed
+            .iter()
+            .map(String::as_str)
+            .filter(|p| p.len() == 5 && p.ends_with(".txt"))
+            .collect();
+        assert_eq!(committed, vec!["c.txt", "b.txt", "a.txt"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_strip_imports_removes_only_leading_imports() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::write(
+            path.join("main.rs"),
+            "//! Crate docs.\n\nuse std::fs;\nuse std::io::{\n    self,\n    Read,\n};\nextern crate log;\n\nfn main() {\n    use std::path::Path;\n}\n",
+        )?;
+        fs::write(
+            path.join("app.py"),
+            "\"\"\"Module docs.\"\"\"\nimport os\nfrom typing import (\n    Any,\n)\n\ndef run():\n    import sys\n",
+        )?;
+        fs::write(
+            path.join("index.js"),
+            "'use strict';\nimport React from 'react';\nimport {\n  a,\n} from './a';\nconst fs = require('fs');\n\nconst x = require('x').setup();\n",
+        )?;
+        fs::write(path.join("notes.txt"), "import this\n")?;
+
+        let config = GrabConfig {
+            target_path: path.clone(),
+            no_git: true,
+            strip_imports: true,
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        let body = |name: &str| {
+            let file = output
+                .files
+                .iter()
+                .find(|f| f.display_path == name)
+                .expect("file should be grabbed");
+            output.content[file.body_range.clone()].to_string()
+        };
+        assert_eq!(
+            body("main.rs"),
+            "//! Crate docs.\n\nfn main() {\n    use std::path::Path;\n}\n\n"
+        );
+        assert_eq!(
+            body("app.py"),
+            "\"\"\"Module docs.\"\"\"\ndef run():\n    import sys\n\n"
+        );
+        assert_eq!(
+            body("index.js"),
+            "'use strict';\nconst x = require('x').setup();\n\n"
+        );
+        assert_eq!(body("notes.txt"), "import this\n\n");
+        let stripped = output
+            .savings
+            .iter()
+            .find(|s| s.name == TransformSavings::STRIP_IMPORTS)
+            .expect("strip-imports savings should be reported");
+        assert!(stripped.bytes_saved > 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_label_language_tags_known_extensions() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::write(path.join("main.RS"), "fn main() {}\n")?;
+        fs::write(path.join("notes.xyz"), "plain\n")?;
+
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            label_language: true,
+            ..Default::default()
+        };
+        let content = grab_contents(&config)?;
+        assert!(content.contains("--- FILE: main.RS [Rust] ---\n"));
+        assert!(content.contains("--- FILE: notes.xyz ---\n"));
+
+        let linked = grab_contents(&GrabConfig {
+            link_base: Some("https://example.com".to_string()),
+            ..config
+        })?;
+        assert!(linked.contains("### [main.RS](https://example.com/main.RS) [Rust]\n"));
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_long_paths_are_read_on_windows() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        let mut nested = path.clone();
+        while nested.as_os_str().len() < 300 {
+            nested.push("node_modules_package_directory");
+        }
+        let file = nested.join("index.js");
+        fs::create_dir_all(crate::utils::long_path(&nested))?;
+        fs::write(crate::utils::long_path(&file), "module.exports = 1;\n")?;
+        assert!(crate::utils::long_path(&file)
+            .to_string_lossy()
+            .starts_with(r"\\?\"));
+
+        let config = GrabConfig {
+            target_path: path.clone(),
+            no_git: true,
+            explicit_files: Some(vec![file.clone()]),
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        assert!(output.skipped.is_empty(), "skipped: {:?}", output.skipped);
+        assert!(output.content.contains("module.exports = 1;"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_compact_tree_lists_one_line_per_directory() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::create_dir_all(path.join("src/bin"))?;
+        fs::create_dir_all(path.join("vendor/pkg/lib"))?;
+        fs::write(path.join("src/main.rs"), "a")?;
+        fs::write(path.join("src/lib.rs"), "b")?;
+        fs::write(path.join("src/bin/tool.rs"), "c")?;
+        fs::write(path.join("vendor/pkg/lib/x.js"), "d")?;
+        fs::write(path.join("vendor/pkg/y.js"), "e")?;
+        fs::write(path.join("README.md"), "f")?;
+
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            include_tree: true,
+            tree_style: TreeStyle::Compact,
+            ..Default::default()
+        };
+        let output = grab_contents(&config)?;
+        let tree = "---\nDIRECTORY STRUCTURE\n---\n./: README.md\nsrc/: lib.rs, main.rs\nsrc/bin/: tool.rs\nvendor/pkg/: y.js\nvendor/pkg/lib/: x.js\n\n---\nFILE CONTENTS\n---\n";
+        assert!(output.starts_with(tree), "unexpected tree:\n{}", output);
+
+        config.tree_max_depth = Some(2);
+        let output = grab_contents(&config)?;
+        assert!(
+            output.contains(
+                "src/: lib.rs, main.rs\nsrc/bin/: …/ (1 file)\nvendor/pkg/: …/ (2 files)\n"
+            ),
+            "unexpected tree:\n{}",
+            output
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_tree_is_kept_apart_from_file_content() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            include_tree: true,
+            exclude_patterns: vec!["*.dat".to_string(), "dirgrab.txt".to_string()],
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        let tree = output.tree.as_deref().expect("tree should be rendered");
+        assert!(tree.starts_with("---\nDIRECTORY STRUCTURE\n---\n"));
+        assert!(tree.ends_with("---\nFILE CONTENTS\n---\n\n"));
+        assert!(output.content.starts_with("--- FILE: file1.txt ---\n"));
+        assert!(!output.content.contains("DIRECTORY STRUCTURE"));
+        // Ranges index into `content`, not the composed text.
+        let first = &output.files[0];
+        assert_eq!(
+            &output.content[first.header_range.clone().unwrap()],
+            "--- FILE: file1.txt ---\n"
+        );
+        assert_eq!(output.full_text(), grab_contents(&config)?);
+        assert_eq!(output.full_text(), format!("{}{}", tree, output.content));
+
+        let without_tree = grab_contents_detailed(&GrabConfig {
+            include_tree: false,
+            ..config
+        })?;
+        assert!(without_tree.tree.is_none());
+        assert_eq!(without_tree.content, output.content);
+        Ok(())
+    }
+
+    #[test]
+    fn test_anonymize_rewrites_display_paths() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::create_dir(path.join("acme-internal"))?;
+        fs::write(
+            path.join("acme-internal").join("a.txt"),
+            "see acme-internal docs",
+        )?;
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            include_tree: true,
+            anonymize: vec![RedactionRule {
+                pattern: "acme-internal".to_string(),
+                replacement: "project".to_string(),
+            }],
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        let tree = output.tree.as_deref().expect("tree should be rendered");
+        assert!(tree.contains("project/"));
+        assert!(!tree.contains("acme-internal"));
+        assert!(output
+            .content
+            .starts_with("--- FILE: project/a.txt ---\nsee acme-internal docs"));
+        assert_eq!(output.files[0].display_path, "project/a.txt");
+        assert_eq!(output.anonymized, 2);
+
+        let with_content = grab_contents_detailed(&GrabConfig {
+            anonymize_content: true,
+            ..config
+        })?;
+        assert!(with_content.content.contains("see project docs"));
+        assert_eq!(with_content.anonymized, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_anonymize_matches_full_paths_in_tree_and_symlink_targets() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::create_dir_all(path.join("src/secret"))?;
+        fs::write(path.join("src/secret/a.txt"), "a")?;
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("src/secret/a.txt", path.join("link"))?;
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            include_tree: true,
+            anonymize: vec![RedactionRule {
+                pattern: "src/secret".to_string(),
+                replacement: "src/hidden".to_string(),
+            }],
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        let tree = output.tree.as_deref().expect("tree should be rendered");
+        assert!(tree.contains("- src/\n  - hidden/\n    - a.txt\n"));
+        assert!(!output.full_text().contains("secret"));
+        #[cfg(unix)]
+        assert!(output
+            .content
+            .contains("--- FILE: link (symlink -> src/hidden/a.txt) ---"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_since_commit_lists_later_changes_and_untracked() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        if !setup_git_repo(&path)? {
+            println!("Skipping Git test: git not found or setup failed.");
+            return Ok(());
+        }
+        fs::write(path.join("base.txt"), "committed in the base commit")?;
+        run_test_command("git", &["add", "base.txt"], &path)?;
+        run_test_command("git", &["commit", "-m", "Base"], &path)?;
+        run_test_command("git", &["tag", "base"], &path)?;
+        fs::write(path.join("file2.rs"), "fn main() { println!(); }")?;
+        run_test_command("git", &["commit", "-am", "Later"], &path)?;
+        fs::write(path.join("new.txt"), "untracked")?;
+
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            include_untracked: true,
+            since_commit: Some("base".to_string()),
+            ..Default::default()
+        };
+        // `base..HEAD` leaves out the changes made in `base` itself.
+        assert_eq!(
+            list_files(&config)?,
+            vec!["file2.rs", "new.txt", "untracked.txt"]
+        );
+
+        config.include_untracked = false;
+        assert_eq!(list_files(&config)?, vec!["file2.rs"]);
+
+        config.since_commit = Some("no-such-commit".to_string());
+        match list_files(&config) {
+            Err(GrabError::UnknownGitRef { reference }) => {
+                assert_eq!(reference, "no-such-commit")
+            }
+            other => panic!("expected UnknownGitRef, got {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_pdf_max_pages_limits_extraction() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        let fixture = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/sample.pdf");
+        fs::copy(&fixture, path.join("sample.pdf"))?;
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            convert_pdf: true,
+            pdf_max_pages: Some(1),
+            ..Default::default()
+        };
+        let limited = grab_contents(&config)?;
+        assert!(
+            limited.starts_with("--- FILE: sample.pdf (extracted text, first 1 of 3 pages) ---\n"),
+            "unexpected header: {}",
+            limited
+        );
+
+        let full = grab_contents(&GrabConfig {
+            pdf_max_pages: None,
+            ..config
+        })?;
+        assert!(full.starts_with("--- FILE: sample.pdf (extracted text) ---\n"));
+        assert!(limited.len() < full.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parallel_pdf_extraction_keeps_order() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        let fixture = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/sample.pdf");
+        for name in ["a.pdf", "b.pdf", "c.pdf"] {
+            fs::copy(&fixture, path.join(name))?;
+        }
+        fs::write(path.join("b.txt"), "between the PDFs")?;
+        fs::write(path.join("broken.pdf"), "not a pdf")?;
+        let sequential = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            convert_pdf: true,
+            pdf_jobs: Some(1),
+            ..Default::default()
+        };
+        let expected = grab_contents(&sequential)?;
+        assert!(expected.contains("--- FILE: broken.pdf (PDF extraction failed) ---"));
+        let parallel = grab_contents(&GrabConfig {
+            pdf_jobs: Some(3),
+            ..sequential
+        })?;
+        assert_eq!(parallel, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_base64_includes_encoded_files() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::write(path.join("blob.bin"), [0x80, 0x81, 0x82])?;
+        fs::write(path.join("big.bin"), vec![0xff; 60])?;
+        fs::write(path.join("text.txt"), "a/b")?;
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            // Would also match inside the base64 bodies, which are left alone.
+            redactions: vec![RedactionRule::new("[/g]")],
+            ..Default::default()
+        };
+        let skipped = grab_contents_detailed(&config)?;
+        assert_eq!(skipped.skipped.len(), 2);
+
+        let output = grab_contents_detailed(&GrabConfig {
+            binary_mode: BinaryMode::Base64,
+            ..config
+        })?;
+        assert!(output.skipped.is_empty());
+        let line = "/".repeat(76);
+        assert_eq!(
+            output.content,
+            format!(
+                "--- FILE: big.bin (base64) ---\n{}\n{}\n\n\
+                 --- FILE: blob.bin (base64) ---\ngIGC\n\n\
+                 --- FILE: text.txt ---\na[REDACTED]b\n\n",
+                line,
+                "/".repeat(4)
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_exclude_matching_drops_files_by_content() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::write(path.join("gen.rs"), "// GENERATED by tool\nfn a() {}")?;
+        fs::write(path.join("late.txt"), "line\nline\nmarker: GENERATED\n")?;
+        fs::write(path.join("keep.rs"), "fn generated_by_hand() {}")?;
+        fs::write(path.join("blob.bin"), [0x80, b'G'])?;
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            exclude_matching: vec!["GENERATED".to_string()],
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        assert_eq!(
+            output.content,
+            "--- FILE: keep.rs ---\nfn generated_by_hand() {}\n\n"
+        );
+        let reasons: Vec<_> = output
+            .skipped
+            .iter()
+            .map(|entry| (entry.display_path.as_str(), entry.reason.clone()))
+            .collect();
+        let excluded = SkipReason::ContentExcluded {
+            pattern: "GENERATED".to_string(),
+        };
+        assert_eq!(
+            reasons,
+            vec![
+                ("blob.bin", SkipReason::NonUtf8),
+                ("gen.rs", excluded.clone()),
+                ("late.txt", excluded),
+            ]
+        );
+
+        config.exclude_matching = vec!["(unclosed".to_string()];
+        assert!(matches!(
+            grab_contents(&config),
+            Err(GrabError::InvalidContentPattern { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_body_emits_annotated_headers_only() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::write(path.join("a.rs"), "fn a() {}\nfn b() {}\n")?;
+        fs::write(path.join("b.txt"), "no newline")?;
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            no_body: true,
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        assert_eq!(
+            output.content,
+            "--- FILE: a.rs (20 bytes, 2 lines) ---\n\n--- FILE: b.txt (10 bytes, 1 line) ---\n\n"
+        );
+        assert!(output.files.iter().all(|file| file.body_range.is_empty()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_under_merges_directories_of_small_files() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::create_dir_all(path.join("util/deep"))?;
+        fs::create_dir(path.join("big"))?;
+        fs::write(path.join("util/a.rs"), "fn a() {}\n")?;
+        fs::write(path.join("util/deep/x.rs"), "fn x() {}\n")?;
+        fs::write(path.join("util/z.rs"), "fn z() {}\n")?;
+        fs::write(path.join("big/small.rs"), "fn s() {}\n")?;
+        fs::write(path.join("big/large.rs"), "x".repeat(100))?;
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            group_under: Some(50),
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        assert_eq!(
+            output.content,
+            format!(
+                "--- FILE: big/large.rs ---\n{}\n\n\
+                 --- FILE: big/small.rs ---\nfn s() {{}}\n\n\
+                 --- DIR: util/ ---\n\
+                 --- a.rs ---\nfn a() {{}}\n\n\
+                 --- z.rs ---\nfn z() {{}}\n\n\
+                 --- FILE: util/deep/x.rs ---\nfn x() {{}}\n\n",
+                "x".repeat(100)
+            )
+        );
+        let grouped = &output.files[2];
+        assert_eq!(grouped.display_path, "util/a.rs");
+        assert_eq!(
+            &output.content[grouped.full_range.clone()],
+            "--- a.rs ---\nfn a() {}\n\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_language_prepends_primary_language() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::write(path.join("a.rs"), "fn a() {}\n")?;
+        fs::write(path.join("b.rs"), "fn b() {}\n")?;
+        fs::write(path.join("c.py"), "c = 1\n")?;
+        fs::write(path.join("notes.unknownext"), "notes\n")?;
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            include_tree: true,
+            detect_language: true,
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        assert_eq!(
+            output.language_summary.as_deref(),
+            Some("Primary language: Rust (50% of files)\n\n")
+        );
+        assert!(output
+            .full_text()
+            .starts_with("Primary language: Rust (50% of files)\n\n---\nDIRECTORY STRUCTURE"));
+        assert!(!output.content.contains("Primary language"));
+
+        let plain = grab_contents_detailed(&GrabConfig {
+            detect_language: false,
+            ..config
+        })?;
+        assert_eq!(plain.language_summary, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_explain_selection_describes_config() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        if !setup_git_repo(&path)? {
+            println!("Skipping Git test: git not found or setup failed.");
+            return Ok(());
+        }
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            include_untracked: true,
+            exclude_patterns: vec!["*.rs".to_string()],
+            explain_selection: true,
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        let selection = output.selection.clone().context("selection block")?;
+        let target = path.canonicalize()?;
+        assert!(selection.starts_with("---\nSELECTION\n---\n"));
+        assert!(selection.contains("Mode: Git (tracked + untracked, scoped to target)\n"));
+        assert!(selection.contains("Exclude patterns: *.rs\n"));
+        let selected = list_files(&config)?.len();
+        assert!(selection.ends_with(&format!("Files: {} selected\n\n", selected)));
+        assert!(selection.contains(&format!("Target: {}\n", target.display())));
+        assert!(output.full_text().starts_with(&selection));
+        assert!(!output.content.contains("SELECTION"));
+
+        let plain = grab_contents_detailed(&GrabConfig {
+            no_git: true,
+            exclude_patterns: Vec::new(),
+            ..config
+        })?;
+        let selection = plain.selection.context("selection block")?;
+        assert!(selection.contains("Mode: directory walk (no Git)\n"));
+        assert!(selection.contains("Exclude patterns: none\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_deprioritize_moves_matching_files_last() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::create_dir(path.join("vendor"))?;
+        fs::write(path.join("Cargo.lock"), "lock")?;
+        fs::write(path.join("a.rs"), "12345")?;
+        fs::write(path.join("vendor/dep.rs"), "1")?;
+        fs::write(path.join("z.rs"), "123")?;
+
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            no_git: true,
+            deprioritize_patterns: vec!["*.lock".to_string(), "vendor/**".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            list_files(&config)?,
+            vec!["a.rs", "z.rs", "Cargo.lock", "vendor/dep.rs"]
+        );
+
+        config.sort = FileOrder::Size;
+        assert_eq!(
+            list_files(&config)?,
+            vec!["z.rs", "a.rs", "vendor/dep.rs", "Cargo.lock"]
+        );
+
+        config.max_per_dir = Some(2);
+        config.sort = FileOrder::Path;
+        assert_eq!(list_files(&config)?, vec!["a.rs", "z.rs", "vendor/dep.rs"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_root_marker_above_target_sets_path_base() -> Result<()> {
+        let dir = tempdir()?;
+        let root = dir.path().join("project");
+        fs::create_dir_all(root.join("pkg/src"))?;
+        fs::write(root.join(".project-root"), "")?;
+        fs::write(root.join("pkg/src/lib.rs"), "pub fn f() {}\n")?;
+        let config = GrabConfig {
+            target_path: root.join("pkg"),
+            add_headers: true,
+            include_tree: true,
+            no_git: true,
+            root_marker: Some(".project-root".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(list_files(&config)?, vec!["pkg/src/lib.rs"]);
+        let output = grab_contents_detailed(&config)?;
+        assert!(output.content.starts_with("--- FILE: pkg/src/lib.rs ---\n"));
+        assert!(output.tree.as_deref().unwrap_or("").contains("- pkg/\n"));
+
+        let unmarked = GrabConfig {
+            root_marker: Some(".missing-marker".to_string()),
+            ..config
+        };
+        assert_eq!(list_files(&unmarked)?, vec!["src/lib.rs"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_root_marker_resolves_listed_paths_like_headers() -> Result<()> {
+        let dir = tempdir()?;
+        let root = dir.path().join("project");
+        fs::create_dir_all(root.join("pkg/src"))?;
+        fs::write(root.join(".project-root"), "")?;
+        fs::write(root.join("pkg/src/lib.rs"), "pub fn f() {}\n")?;
+        fs::write(root.join("pkg/NOTES.md"), "notes\n")?;
+        let config = GrabConfig {
+            target_path: root.join("pkg"),
+            add_headers: true,
+            no_git: true,
+            root_marker: Some(".project-root".to_string()),
+            explicit_files: Some(vec![PathBuf::from("pkg/src/lib.rs")]),
+            prepend_files: vec![PathBuf::from("pkg/NOTES.md")],
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        assert!(output
+            .content
+            .starts_with("--- FILE: pkg/NOTES.md ---\nnotes\n"));
+        assert!(output.content.contains("--- FILE: pkg/src/lib.rs ---\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_root_marker_below_target_is_ignored() -> Result<()> {
+        let dir = tempdir()?;
+        let root = dir.path().join("project");
+        fs::create_dir_all(root.join("pkg"))?;
+        fs::write(root.join("pkg/.project-root"), "")?;
+        fs::write(root.join("pkg/a.txt"), "a")?;
+        let config = GrabConfig {
+            target_path: root.clone(),
+            no_git: true,
+            root_marker: Some(".project-root".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(list_files(&config)?, vec!["pkg/.project-root", "pkg/a.txt"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_retries_only_retry_transient_errors() {
+        let mut calls = 0;
+        let result = processing::retry_transient(2, || {
+            calls += 1;
+            if calls < 3 {
+                Err(io::Error::from(io::ErrorKind::Interrupted))
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(result.ok(), Some(3));
+
+        let mut calls = 0;
+        let result: io::Result<()> = processing::retry_transient(1, || {
+            calls += 1;
+            Err(io::Error::from(io::ErrorKind::TimedOut))
+        });
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+        assert_eq!(calls, 2);
+
+        let mut calls = 0;
+        let result: io::Result<()> = processing::retry_transient(3, || {
+            calls += 1;
+            Err(io::Error::from(io::ErrorKind::PermissionDenied))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_wrappers_surround_each_file() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::write(path.join("a.rs"), "fn a() {}\n")?;
+        fs::write(path.join("b.txt"), "b")?;
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            wrap_open: Some("<file path='{path}'>".to_string()),
+            wrap_close: Some("</file>".to_string()),
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        assert_eq!(
+            output.content,
+            "<file path='a.rs'>\n--- FILE: a.rs ---\nfn a() {}\n</file>\n\n\
+             <file path='b.txt'>\n--- FILE: b.txt ---\nb\n</file>\n\n"
+        );
+        let a = &output.files[0];
+        assert_eq!(
+            &output.content[a.full_range.clone()],
+            "<file path='a.rs'>\n--- FILE: a.rs ---\nfn a() {}\n</file>\n\n"
+        );
+        assert_eq!(
+            &output.content[a.header_range.clone().unwrap()],
+            "--- FILE: a.rs ---\n"
+        );
+        assert_eq!(&output.content[a.body_range.clone()], "fn a() {}\n");
+
+        let bare = grab_contents_detailed(&GrabConfig {
+            add_headers: false,
+            tight: true,
+            wrap_open: Some("<<{path}".to_string()),
+            ..config
+        })?;
+        assert_eq!(
+            bare.content,
+            "<<a.rs\nfn a() {}\n</file>\n<<b.txt\nb\n</file>\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_include_patterns_allowlist_files_within_their_depth() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::create_dir_all(path.join("src/bin"))?;
+        fs::create_dir_all(path.join("docs/deep"))?;
+        fs::write(path.join("main.rs"), "")?;
+        fs::write(path.join("Cargo.toml"), "")?;
+        fs::write(path.join("src/lib.rs"), "")?;
+        fs::write(path.join("src/bin/tool.rs"), "")?;
+        fs::write(path.join("docs/deep/guide.md"), "")?;
+        let include = |pattern: &str, max_depth: Option<usize>| IncludePattern {
+            pattern: pattern.to_string(),
+            max_depth,
+        };
+        let config = GrabConfig {
+            target_path: path.clone(),
+            no_git: true,
+            include_patterns: vec![include("*.md", None), include("*.rs", Some(2))],
+            ..Default::default()
+        };
+        assert_eq!(
+            list_files(&config)?,
+            vec!["docs/deep/guide.md", "main.rs", "src/lib.rs"]
+        );
+
+        // A directory pattern covers the files below it, down to its depth.
+        let config = GrabConfig {
+            include_patterns: vec![include("src/", Some(2))],
+            ..config
+        };
+        assert_eq!(list_files(&config)?, vec!["src/lib.rs"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_mime_patterns_filter_selection() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        // PNG signature followed by junk, plus a PDF header without an extension.
+        fs::write(path.join("logo.dat"), b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR")?;
+        fs::write(path.join("manual"), b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n")?;
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            no_git: true,
+            include_tree: false,
+            exclude_mime: vec!["image/*".to_string()],
+            ..Default::default()
+        };
+
+        let output = grab_contents_detailed(&config)?;
+        assert!(!output.content.contains("logo.dat"));
+        let reported: Vec<_> = output
+            .skipped
+            .iter()
+            .map(|entry| {
+                (
+                    entry.display_path.as_str(),
+                    &entry.reason,
+                    entry.mime.as_deref(),
+                )
+            })
+            .collect();
+        assert!(reported.contains(&(
+            "logo.dat",
+            &SkipReason::MimeExcluded {
+                pattern: Some("image/*".to_string())
+            },
+            Some("image/png")
+        )));
+        // Binaries skipped for not being text are classified too.
+        assert!(reported.contains(&("manual", &SkipReason::NonUtf8, Some("application/pdf"))));
+
+        config.exclude_mime.clear();
+        config.include_mime = vec!["text/*".to_string()];
+        let mut files = list_files(&config)?;
+        files.sort();
+        assert_eq!(
+            files,
+            vec![
+                "file1.txt",
+                "file2.rs",
+                "subdir/another.txt",
+                "subdir/file3.log"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_tree_collapse_chains_merges_single_child_dirs() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::create_dir_all(path.join("src/main/java/com/example/app/model"))?;
+        fs::write(path.join("src/main/java/com/example/app/Main.java"), "a")?;
+        fs::write(
+            path.join("src/main/java/com/example/app/model/User.java"),
+            "b",
+        )?;
+        fs::create_dir_all(path.join("docs/api"))?;
+        fs::write(path.join("docs/api/index.md"), "c")?;
+        fs::write(path.join("pom.xml"), "d")?;
+
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            no_git: true,
+            include_tree: true,
+            tree_collapse_chains: true,
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        let tree = "---\nDIRECTORY STRUCTURE\n---\n- docs/api/\n  - index.md\n- pom.xml\n- src/main/java/com/example/app/\n  - Main.java\n  - model/\n    - User.java\n";
+        let rendered = output.tree.as_deref().unwrap_or_default();
+        assert!(rendered.starts_with(tree), "unexpected tree:\n{}", rendered);
+        assert_eq!(output.files.len(), 4);
+
+        // A depth-limited node keeps its own line so the hidden count stays visible.
+        config.tree_max_depth = Some(3);
+        let output = grab_contents_detailed(&config)?;
+        let tree = "---\nDIRECTORY STRUCTURE\n---\n- docs/api/\n  - index.md\n- pom.xml\n- src/main/java/\n  - …/ (2 files)\n";
+        let rendered = output.tree.as_deref().unwrap_or_default();
+        assert!(rendered.starts_with(tree), "unexpected tree:\n{}", rendered);
+        Ok(())
+    }
+
+    #[test]
+    fn test_git_attr_keeps_only_tagged_files() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        if !setup_git_repo(&path)? {
+            println!("Skipping Git test: git not found or setup failed.");
+            return Ok(());
+        }
+        fs::write(
+            path.join(".gitattributes"),
+            "*.rs license\ndeep/** license=MIT\n*.txt -license\nuntracked.txt license\n",
+        )?;
+
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            include_untracked: true,
+            git_attr: Some("license".to_string()),
+            ..Default::default()
+        };
+        // Untracked files are checked too; `-license` unsets the attribute, and
+        // later lines win, so `deep/sub/nested.txt` is unset again.
+        assert_eq!(list_files(&config)?, vec!["file2.rs", "untracked.txt"]);
+
+        config.git_attr = Some("prompt".to_string());
+        assert!(list_files(&config)?.is_empty());
+
+        // Outside Git mode the attribute filter is ignored.
+        config.no_git = true;
+        config.git_attr = Some("license".to_string());
+        assert!(list_files(&config)?.len() > 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_grab_contents_with_progress_reports_each_file() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        let config = GrabConfig {
+            target_path: path.clone(),
+            no_git: true,
+            ..Default::default()
+        };
+        let mut events = Vec::new();
+        let output = grab_contents_with_progress(&config, |event| events.push(event))?;
+        let total = output.files.len() + output.skipped.len();
+        let processed: Vec<usize> = events.iter().map(|event| event.processed).collect();
+        assert_eq!(processed, (0..=total).collect::<Vec<_>>());
+        assert!(events.iter().all(|event| event.total == total));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sniff_only_above_treats_small_files_as_text() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        let png_header = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        fs::write(path.join("icon.png"), &png_header)?;
+        let mut large = png_header.clone();
+        large.resize(2_000, 0);
+        fs::write(path.join("photo.png"), &large)?;
+        fs::write(path.join("notes.txt"), "hello")?;
+
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            no_git: true,
+            exclude_mime: vec!["image/*".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(list_files(&config)?, vec!["notes.txt"]);
+
+        // The small icon is no longer sniffed, so it passes as text.
+        config.sniff_only_above = Some(1_000);
+        assert_eq!(list_files(&config)?, vec!["icon.png", "notes.txt"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tree_omits_directories_without_selected_files() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        fs::create_dir_all(path.join("empty/nested"))?;
+        let config = GrabConfig {
+            target_path: path.clone(),
+            include_tree: true,
+            exclude_patterns: vec!["subdir/*".to_string()],
+            no_git: true,
+            ..Default::default()
+        };
+        let tree = grab_contents_detailed(&config)?.tree.unwrap_or_default();
+        assert!(tree.contains("- file1.txt"), "tree: {}", tree);
+        assert!(!tree.contains("subdir"), "tree: {}", tree);
+        assert!(!tree.contains("empty"), "tree: {}", tree);
+
+        // A directory in the listing itself (e.g. a Git submodule) is not drawn.
+        let entries = vec![
+            crate::tree::TreeEntry::selected(path.join("file1.txt")),
+            crate::tree::TreeEntry::selected(path.join("empty")),
+        ];
+        let tree = crate::tree::generate_indented_tree(&entries, &path, None, false)?;
+        assert_eq!(tree, "- file1.txt\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_partitions_group_files_under_banners() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::create_dir_all(path.join("src"))?;
+        fs::create_dir_all(path.join("tests"))?;
+        fs::write(path.join("README.md"), "readme\n")?;
+        fs::write(path.join("src/lib.rs"), "lib\n")?;
+        fs::write(path.join("tests/basic.rs"), "test\n")?;
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            no_git: true,
+            add_headers: true,
+            include_tree: true,
+            partitions: vec![
+                Partition {
+                    pattern: "tests/**".to_string(),
+                    label: "Tests".to_string(),
+                },
+                Partition {
+                    pattern: "src/**".to_string(),
+                    label: "Source".to_string(),
+                },
+            ],
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        assert_eq!(
+            output.content,
+            "===== Tests =====\n\n--- FILE: tests/basic.rs ---\ntest\n\n\
+             ===== Source =====\n\n--- FILE: src/lib.rs ---\nlib\n\n\
+             ===== Other =====\n\n--- FILE: README.md ---\nreadme\n\n"
+        );
+        let paths: Vec<&str> = output
+            .files
+            .iter()
+            .map(|file| file.display_path.as_str())
+            .collect();
+        assert_eq!(paths, vec!["tests/basic.rs", "src/lib.rs", "README.md"]);
+        assert_eq!(
+            &output.content[output.files[1].body_range.clone()],
+            "lib\n\n"
+        );
+
+        config.tree_partitioned = true;
+        let tree = grab_contents_detailed(&config)?.tree.unwrap_or_default();
+        assert!(
+            tree.contains(
+                "===== Tests =====\n- tests/\n  - basic.rs\n\
+                 ===== Source =====\n- src/\n  - lib.rs\n\
+                 ===== Other =====\n- README.md\n"
+            ),
+            "tree: {}",
+            tree
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_git_dir_from_environment_is_ignored() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        if !setup_git_repo(&path)? {
+            println!("Skipping Git test: git not found or setup failed.");
+            return Ok(());
+        }
+        let other = tempdir()?;
+        run_test_command("git", &["init", "-q"], other.path())?;
+
+        // As exported by a hook running in another repository.
+        std::env::set_var("GIT_DIR", other.path().join(".git"));
+        std::env::set_var("GIT_WORK_TREE", other.path());
+        let config = GrabConfig {
+            target_path: path.clone(),
+            ..Default::default()
+        };
+        let result = list_files(&config);
+        std::env::remove_var("GIT_DIR");
+        std::env::remove_var("GIT_WORK_TREE");
+
+        assert_eq!(
+            result?,
+            vec![
+                ".gitignore",
+                "deep/sub/nested.txt",
+                "file2.rs",
+                "subdir/another.txt"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_fold_imports_summarizes_leading_imports() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::write(
+            path.join("main.rs"),
+            "//! Crate docs.\nuse serde::Deserialize;\nuse tokio::{fs, io};\nuse anyhow::Result;\nuse serde::Serialize;\npub use crate::config::Config;\nextern crate log;\nuse std::path::Path;\n\nfn main() {}\n",
+        )?;
+        fs::write(
+            path.join("app.py"),
+            "import os, sys as system\nfrom typing import Any\nfrom .models import User\ndef run():\n    pass\n",
+        )?;
+        fs::write(
+            path.join("index.js"),
+            "import React from 'react';\nimport from from './from';\nimport {\n  a,\n} from './a';\nconst fs = require(\"fs\");\nimport './styles.css';\n\nrun();\n",
+        )?;
+        fs::write(path.join("notes.txt"), "import this\n")?;
+
+        let config = GrabConfig {
+            target_path: path.clone(),
+            no_git: true,
+            fold_imports: true,
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        let body = |name: &str| {
+            let file = output
+                .files
+                .iter()
+                .find(|f| f.display_path == name)
+                .expect("file should be grabbed");
+            output.content[file.body_range.clone()].to_string()
+        };
+        assert_eq!(
+            body("main.rs"),
+            "//! Crate docs.\n// imports: serde, tokio, anyhow (+3 more)\n\nfn main() {}\n\n"
+        );
+        assert_eq!(
+            body("app.py"),
+            "# imports: os, sys, typing (+1 more)\n\ndef run():\n    pass\n\n"
+        );
+        assert_eq!(
+            body("index.js"),
+            "// imports: react, ./from, ./a (+2 more)\n\nrun();\n\n"
+        );
+        assert_eq!(body("notes.txt"), "import this\n\n");
+        assert!(output
+            .savings
+            .iter()
+            .any(|s| s.name == TransformSavings::FOLD_IMPORTS && s.bytes_saved > 0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_headers_hash_the_file_as_read() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        let text = "use std::fs;\n\nfn main() {}\n";
+        fs::write(path.join("main.rs"), text)?;
+        let hash = &sha256_hex(text.as_bytes())[..12];
+
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            no_git: true,
+            add_headers: true,
+            hash_headers: true,
+            strip_imports: true,
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        let file = &output.files[0];
+        let header = &output.content[file.header_range.clone().expect("header range")];
+        assert_eq!(header, format!("--- FILE: main.rs [sha256:{}] ---\n", hash));
+        assert_eq!(&output.content[file.body_range.clone()], "fn main() {}\n\n");
+
+        config.hash_headers = false;
+        config.wrap_open = Some("<file path='{path}' sha256='{hash}'>".to_string());
+        let output = grab_contents_detailed(&config)?;
+        assert!(output.content.starts_with(&format!(
+            "<file path='main.rs' sha256='{}'>\n--- FILE: main.rs ---\n",
+            hash
+        )));
+        Ok(())
+    }
+
+    #[test]
+    fn test_pdf_max_bytes_skips_large_pdfs_before_parsing() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::write(path.join("big.pdf"), "not really a pdf ".repeat(10))?;
+        fs::write(path.join("small.pdf"), "not a pdf")?;
+        let config = GrabConfig {
+            target_path: path.clone(),
+            no_git: true,
+            add_headers: true,
+            convert_pdf: true,
+            pdf_max_bytes: Some(100),
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        assert!(
+            output
+                .content
+                .contains("--- FILE: big.pdf (PDF too large, skipped) ---\n"),
+            "{}",
+            output.content
+        );
+        // Within the limit, extraction is still attempted.
+        assert!(
+            output
+                .content
+                .contains("--- FILE: small.pdf (PDF extraction failed) ---\n"),
+            "{}",
+            output.content
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_readme_first_leads_each_directory() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::create_dir_all(path.join("docs/api"))?;
+        fs::create_dir_all(path.join("src"))?;
+        fs::write(path.join("CHANGELOG.md"), "changes\n")?;
+        fs::write(path.join("README.md"), "top\n")?;
+        fs::write(path.join("docs/api/index.md"), "api\n")?;
+        fs::write(path.join("docs/guide.md"), "guide\n")?;
+        fs::write(path.join("docs/readme.txt"), "docs\n")?;
+        fs::write(path.join("src/lib.rs"), "lib\n")?;
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            no_git: true,
+            add_headers: true,
+            readme_first: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            list_files(&config)?,
+            vec![
+                "README.md",
+                "CHANGELOG.md",
+                "docs/readme.txt",
+                "docs/api/index.md",
+                "docs/guide.md",
+                "src/lib.rs",
+            ]
+        );
+        let output = grab_contents_detailed(&config)?;
+        assert!(output.content.starts_with(
+            "--- FILE: README.md (directory overview) ---\ntop\n\n--- FILE: CHANGELOG.md ---\n"
+        ));
+
+        config.readme_first = false;
+        assert_eq!(list_files(&config)?[0], "CHANGELOG.md");
+        Ok(())
+    }
+
+    #[test]
+    fn test_prepend_and_append_files_bypass_filters() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            exclude_patterns: vec!["*.log".to_string()],
+            no_git: true,
+            include_tree: true,
+            prepend_files: vec![PathBuf::from("dirgrab.txt"), PathBuf::from("missing.md")],
+            append_files: vec![PathBuf::from("subdir/file3.log"), path.join("file2.rs")],
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        let order: Vec<&str> = output
+            .files
+            .iter()
+            .map(|file| file.display_path.as_str())
+            .collect();
+        assert_eq!(
+            order,
+            [
+                "dirgrab.txt",
+                "file1.txt",
+                "subdir/another.txt",
+                "subdir/file3.log",
+                "file2.rs"
+            ]
+        );
+        assert!(output
+            .content
+            .starts_with("--- FILE: dirgrab.txt ---\nPrevious dirgrab output."));
+        let tree = output.tree.unwrap();
+        assert!(tree.contains("- dirgrab.txt"));
+        assert!(tree.contains("  - file3.log"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_prepend_file_outside_the_target_is_grabbed() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        let shared = tempdir()?;
+        let instructions = shared.path().join("AI.md");
+        fs::write(&instructions, "Be concise.")?;
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            include_tree: true,
+            prepend_files: vec![instructions.clone()],
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        let shown = crate::utils::posix_path(&instructions.canonicalize()?).into_owned();
+        assert_eq!(output.files[0].display_path, shown);
+        assert!(output
+            .content
+            .starts_with(&format!("--- FILE: {} ---\nBe concise.", shown)));
+        // The rest of the grab and its tree are unaffected.
+        assert_eq!(output.files[1].display_path, "file1.txt");
+        let tree = output.tree.unwrap();
+        assert!(!tree.contains("AI.md"));
+        assert!(tree.contains("- file1.txt"));
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_display_paths_use_forward_slashes_on_windows() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        fs::create_dir_all(path.join("deep").join("sub"))?;
+        fs::write(path.join("deep").join("sub").join("nested.txt"), "nested")?;
+        for tree_style in [crate::TreeStyle::Indented, crate::TreeStyle::Compact] {
+            let config = GrabConfig {
+                target_path: path.clone(),
+                add_headers: true,
+                no_git: true,
+                include_tree: true,
+                tree_style,
+                ..Default::default()
+            };
+            let output = grab_contents_detailed(&config)?;
+            assert!(output
+                .files
+                .iter()
+                .any(|file| file.display_path == "deep/sub/nested.txt"));
+            assert!(output.content.contains("--- FILE: subdir/another.txt ---"));
+            assert!(!output.content.contains('\\'));
+            assert!(!output.tree.unwrap_or_default().contains('\\'));
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_show_mode_annotates_headers_with_octal_mode() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        let (_dir, path) = setup_test_dir()?;
+        fs::set_permissions(path.join("file2.rs"), fs::Permissions::from_mode(0o755))?;
+        fs::set_permissions(path.join("file1.txt"), fs::Permissions::from_mode(0o644))?;
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            show_mode: true,
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        assert!(output
+            .content
+            .contains("--- FILE: file2.rs (mode 755) ---\n"));
+        assert!(output
+            .content
+            .contains("--- FILE: file1.txt (mode 644) ---\n"));
+
+        let plain = grab_contents_detailed(&GrabConfig {
+            show_mode: false,
+            ..config
+        })?;
+        assert!(plain.content.contains("--- FILE: file2.rs ---\n"));
+        let script = plain
+            .files
+            .iter()
+            .find(|file| file.display_path == "file2.rs")
+            .unwrap();
+        assert_eq!(script.mode, Some(0o755));
+        Ok(())
+    }
+
+    #[test]
+    fn test_collate_by_extension_groups_sections_and_keeps_tree() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        fs::write(path.join("subdir").join("lib.rs"), "pub fn lib() {}")?;
+        fs::write(path.join("Makefile"), "all:")?;
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            include_tree: true,
+            sort: FileOrder::Size,
+            collate: Some(Collate::Extension),
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        let order: Vec<&str> = output
+            .files
+            .iter()
+            .map(|file| file.display_path.as_str())
+            .collect();
+        assert_eq!(
+            order,
+            [
+                "subdir/file3.log",
+                "file2.rs",
+                "subdir/lib.rs",
+                "file1.txt",
+                "subdir/another.txt",
+                "Makefile"
+            ]
+        );
+        assert!(output
+            .content
+            .starts_with("===== .log =====\n\n--- FILE: subdir/file3.log ---\n"));
+        assert!(output
+            .content
+            .contains("===== .rs =====\n\n--- FILE: file2.rs ---\n"));
+        assert!(output
+            .content
+            .contains("===== Other =====\n\n--- FILE: Makefile ---\n"));
+        assert_eq!(output.content.matches("===== .rs =====").count(), 1);
+        let tree = output.tree.unwrap();
+        assert!(!tree.contains("====="));
+        assert!(tree.contains("- subdir/\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_explain_ignored_reports_matching_rule() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        if !setup_git_repo(&path)? {
+            println!("Skipping Git test: git not found or setup failed.");
+            return Ok(());
+        }
+        let config = GrabConfig {
+            target_path: path.clone(),
+            ..Default::default()
+        };
+        let ignored = explain_ignored(&config)?;
+        let rule_for = |display: &str| {
+            ignored
+                .iter()
+                .find(|entry| entry.display_path == display)
+                .map(|entry| (entry.source.as_str(), entry.line, entry.pattern.as_str()))
+        };
+        assert_eq!(
+            rule_for("subdir/file3.log"),
+            Some((".gitignore", 1, "*.log"))
+        );
+        assert_eq!(
+            rule_for("binary.dat"),
+            Some((".gitignore", 2, "binary.dat"))
+        );
+        assert_eq!(rule_for("file1.txt"), Some((".gitignore", 3, "file1.txt")));
+        assert_eq!(rule_for("untracked.txt"), None);
+
+        // Scoped to a subdirectory, only its ignored paths are explained.
+        let scoped = explain_ignored(&GrabConfig {
+            target_path: path.join("subdir"),
+            ..Default::default()
+        })?;
+        let paths: Vec<&str> = scoped.iter().map(|e| e.display_path.as_str()).collect();
+        assert_eq!(paths, vec!["subdir/file3.log"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tree_max_entries_truncates_tree() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            include_tree: true,
+            tree_max_entries: Some(3),
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        let tree = "---\nDIRECTORY STRUCTURE\n---\n- binary.dat\n- file1.txt\n- file2.rs\n… (3 more entries)\n\n---\nFILE CONTENTS";
+        let rendered = output.tree.as_deref().unwrap_or_default();
+        assert!(rendered.starts_with(tree), "unexpected tree:\n{}", rendered);
+        // Contents still include every file.
+        assert!(output.content.contains("--- FILE: subdir/file3.log ---"));
+
+        // A cap at or above the line count leaves the tree whole.
+        config.tree_max_entries = Some(6);
+        let output = grab_contents_detailed(&config)?;
+        assert!(!output.tree.unwrap_or_default().contains("more entr"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_respect_editorconfig_normalizes_matching_files() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::write(
+            path.join(".editorconfig"),
+            "root = true\n\n[*.{py,txt}]\nend_of_line = lf\ntrim_trailing_whitespace = true\ninsert_final_newline = true\n",
+        )?;
+        fs::create_dir_all(path.join("legacy"))?;
+        // The nearer file wins for `legacy/`.
+        fs::write(
+            path.join("legacy").join(".editorconfig"),
+            "[*.txt]\nend_of_line = crlf\ninsert_final_newline = false\n",
+        )?;
+        fs::write(path.join("app.py"), "x = 1   \r\ny = 2\t")?;
+        fs::write(path.join("legacy").join("notes.txt"), "a  \nb\n")?;
+        fs::write(path.join("data.csv"), "a,b  \r\n")?;
+
+        let mut config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: false,
+            no_git: true,
+            respect_editorconfig: true,
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        let body = |display: &str| {
+            let file = output
+                .files
+                .iter()
+                .find(|file| file.display_path == display)
+                .expect("file grabbed");
+            output.content[file.body_range.clone()].to_string()
+        };
+        // Each body range ends with the separator newline, after the newline
+        // the output adds to a body without one.
+        assert_eq!(body("app.py"), "x = 1\ny = 2\n\n");
+        assert_eq!(body("legacy/notes.txt"), "a\r\nb\n\n");
+        // No section covers CSV files.
+        assert_eq!(body("data.csv"), "a,b  \r\n\n");
+
+        config.respect_editorconfig = false;
+        let output = grab_contents_detailed(&config)?;
+        assert!(output.content.contains("x = 1   \r\ny = 2\t"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_content_for_keeps_other_files_tree_only() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::create_dir_all(path.join("src"))?;
+        fs::create_dir_all(path.join("tests"))?;
+        fs::write(path.join("src/lib.rs"), "pub fn lib() {}")?;
+        fs::write(path.join("tests/it.rs"), "#[test] fn it() {}")?;
+        fs::write(path.join("README.md"), "readme")?;
+
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            include_tree: true,
+            content_for: vec!["src/**".to_string()],
+            append_files: vec![PathBuf::from("README.md")],
+            ..Default::default()
+        };
+        let output = grab_contents_detailed(&config)?;
+        let tree = output.tree.as_deref().unwrap_or_default();
+        assert!(tree.contains("- src/\n  - lib.rs\n- tests/\n  - it.rs\n"));
+        let bodies: Vec<&str> = output
+            .files
+            .iter()
+            .map(|file| file.display_path.as_str())
+            .collect();
+        // Injected files keep their content.
+        assert_eq!(bodies, vec!["src/lib.rs", "README.md"]);
+        assert!(!output.content.contains("fn it()"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_measure_contents_matches_transformed_and_trimmed_bodies() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        fs::write(
+            path.join("main.rs"),
+            "use std::fs;\nuse std::path::Path;\n\nfn main() {}\n",
+        )?;
+        fs::write(path.join("secrets.env"), "API_KEY=sk-12345\n")?;
+        let config = GrabConfig {
+            target_path: path.clone(),
+            no_git: true,
+            strip_imports: true,
+            redactions: vec![RedactionRule::new("sk-[0-9]+")],
+            ..Default::default()
+        };
+        let bytes = |config: &GrabConfig| -> Result<Vec<(String, usize)>> {
+            Ok(measure_contents(config)?
+                .files
+                .into_iter()
+                .map(|file| (file.display_path, file.bytes))
+                .collect())
+        };
+        // "fn main() {}\n" and "API_KEY=[REDACTED]\n".
+        assert_eq!(
+            bytes(&config)?,
+            vec![("main.rs".to_string(), 13), ("secrets.env".to_string(), 19)]
+        );
+
+        let config = GrabConfig {
+            no_body: true,
+            ..config
+        };
+        assert_eq!(
+            bytes(&config)?,
+            vec![("main.rs".to_string(), 0), ("secrets.env".to_string(), 0)]
+        );
+
+        // Bodies are cut to the same lines a budgeted grab keeps.
+        let numbered =
+            |count: usize| -> String { (0..count).map(|i| format!("line {:03}\n", i)).collect() };
+        fs::remove_file(path.join("main.rs"))?;
+        fs::remove_file(path.join("secrets.env"))?;
+        fs::write(path.join("big.txt"), numbered(100))?;
+        fs::write(path.join("small.txt"), numbered(20))?;
+        let config = GrabConfig {
+            target_path: path.clone(),
+            add_headers: true,
+            no_git: true,
+            context_budget: Some(600),
+            budget_min_lines: 3,
+            ..Default::default()
+        };
+        assert_eq!(
+            bytes(&config)?,
+            vec![
+                ("big.txt".to_string(), 39 * 9),
+                ("small.txt".to_string(), 9 * 9)
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_measure_contents_counts_bodies_without_output() -> Result<()> {
+        let (_dir, path) = setup_test_dir()?;
+        let config = GrabConfig {
+            target_path: path.clone(),
+            no_git: true,
+            ..Default::default()
+        };
+        let measure = measure_contents(&config)?;
+        let measured = |display: &str| {
+            measure
+                .files
+                .iter()
+                .find(|file| file.display_path == display)
+                .map(|file| (file.bytes, file.chars, file.words))
+        };
+        assert_eq!(measured("file1.txt"), Some((18, 18, 4)));
+        assert_eq!(measured("subdir/file3.log"), Some((12, 12, 2)));
+        assert_eq!(measured("binary.dat"), None);
+        assert_eq!(measure.skipped.len(), 1);
+        assert_eq!(measure.skipped[0].display_path, "binary.dat");
+
+        // Same files, in the same order, as a full grab emits.
+        let output = grab_contents_detailed(&config)?;
+        let grabbed: Vec<&str> = output
+            .files
+            .iter()
+            .map(|file| file.display_path.as_str())
+            .collect();
+        let counted: Vec<&str> = measure
+            .files
+            .iter()
+            .map(|file| file.display_path.as_str())
+            .collect();
+        assert_eq!(counted, grabbed);
+        Ok(())
+    }
 } // End of mod tests
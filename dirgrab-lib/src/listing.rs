@@ -1,22 +1,39 @@
 // --- FILE: dirgrab-lib/src/listing.rs ---
 
 use std::collections::HashSet; // Needed for list_files_git
-use std::io; // Needed for io::ErrorKind::NotFound check indirectly via run_command/detect_git_repo
-use std::path::{Path, PathBuf};
+use std::io::{self, Write}; // Needed for io::ErrorKind::NotFound check indirectly via run_command/detect_git_repo
+use std::path::{Component, Path, PathBuf};
+use std::process::Stdio;
 
-use ignore::gitignore::GitignoreBuilder;
-use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::{Match, WalkBuilder};
 use log::{debug, error, info, warn};
 use walkdir::WalkDir;
 
 // Use crate:: paths for sibling modules
-use crate::config::GrabConfig;
+use crate::config::{GrabConfig, IncludePattern};
 use crate::errors::{GrabError, GrabResult};
-use crate::utils::run_command; // Use the utility function
+use crate::utils::{git_command, long_path, run_command};
+use crate::IgnoredPath;
+
+/// Returns the first line of `git --version` (e.g. `git version 2.43.0`), or
+/// `None` if `git` is not on `PATH` or does not run successfully.
+pub fn git_version() -> Option<String> {
+    let output = run_command("git", &["--version"], Path::new("."))
+        .map_err(|e| debug!("Could not run 'git --version': {}", e))
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .map(|line| line.trim().to_string())
+}
 
 /// Checks if the path is inside a Git repository and returns the repo root if true.
-/// Crate-public as it's only called by grab_contents in lib.rs.
-pub(crate) fn detect_git_repo(path: &Path) -> GrabResult<Option<PathBuf>> {
+/// Returns `Ok(None)` outside a repository or when `git` is not installed.
+pub fn detect_git_repo(path: &Path) -> GrabResult<Option<PathBuf>> {
     let command_str = "git rev-parse --show-toplevel";
     debug!(
         "Detecting git repo by running '{}' in path: {:?}",
@@ -97,13 +114,12 @@ pub(crate) fn list_files_git(
     );
 
     let mut combined_files = HashSet::new();
+    let mut untracked = HashSet::new();
 
-    let scope_specs = build_scope_pathspecs(repo_root, scope_subdir);
-    let exclude_specs = build_exclude_pathspecs(config);
+    let scope_specs = build_scope_pathspecs(scope_subdir);
 
     let mut tracked_args = vec!["ls-files".to_string(), "-z".to_string()];
     tracked_args.extend(scope_specs.iter().cloned());
-    tracked_args.extend(exclude_specs.iter().cloned());
 
     run_git_ls(repo_root, &tracked_args, "tracked", &mut combined_files)?;
 
@@ -115,18 +131,266 @@ pub(crate) fn list_files_git(
             "--exclude-standard".to_string(),
         ];
         untracked_args.extend(scope_specs.iter().cloned());
-        untracked_args.extend(exclude_specs.iter().cloned());
 
-        run_git_ls(repo_root, &untracked_args, "untracked", &mut combined_files)?;
+        run_git_ls(repo_root, &untracked_args, "untracked", &mut untracked)?;
+        // Nested repositories, including linked worktrees created inside this
+        // checkout, are reported as a single `dir/` entry rather than as files.
+        untracked.retain(|path| {
+            let nested = path.is_dir();
+            if nested {
+                debug!("Skipping nested repository or worktree {:?}", path);
+            }
+            !nested
+        });
+        combined_files.extend(untracked.iter().cloned());
     } else {
         debug!("Skipping untracked files per configuration.");
     }
 
+    if let Some(count) = config.changed_in_last {
+        let mut changed = HashSet::new();
+        // No `--diff-filter=d` here: it would make `-n` skip deletion-only
+        // commits. Deleted paths are dropped by the intersection below instead.
+        let mut log_args = vec![
+            "log".to_string(),
+            "-n".to_string(),
+            count.to_string(),
+            "--name-only".to_string(),
+            "--format=".to_string(),
+            "-z".to_string(),
+        ];
+        if !scope_specs.is_empty() {
+            log_args.push("--".to_string());
+            log_args.extend(scope_specs.iter().cloned());
+        }
+        run_git_ls(repo_root, &log_args, "recently changed", &mut changed)?;
+        // Intersect with the regular listing so excludes apply and files that
+        // no longer exist are dropped.
+        combined_files.retain(|path| changed.contains(path));
+        debug!(
+            "{} listed files were changed in the last {} commit(s)",
+            combined_files.len(),
+            count
+        );
+    }
+
+    if let Some((from, to)) = &config.between {
+        for reference in [from, to] {
+            verify_git_ref(repo_root, reference)?;
+        }
+        let mut changed = HashSet::new();
+        // Two-dot range: a straight comparison of the two trees, like `git diff A B`.
+        let mut diff_args = vec![
+            "diff".to_string(),
+            "--name-only".to_string(),
+            "--diff-filter=d".to_string(),
+            "-z".to_string(),
+            format!("{}..{}", from, to),
+        ];
+        if !scope_specs.is_empty() {
+            diff_args.push("--".to_string());
+            diff_args.extend(scope_specs.iter().cloned());
+        }
+        run_git_ls(repo_root, &diff_args, "changed between refs", &mut changed)?;
+        combined_files.retain(|path| changed.contains(path));
+        debug!(
+            "{} listed files changed between {} and {}",
+            combined_files.len(),
+            from,
+            to
+        );
+    }
+
+    if let Some(since) = &config.since_commit {
+        verify_git_ref(repo_root, since)?;
+        // Untracked files are new since any commit, so they always count.
+        let mut changed = untracked;
+        // `SINCE..HEAD` excludes SINCE itself: its own changes are the baseline.
+        let mut diff_args = vec![
+            "diff".to_string(),
+            "--name-only".to_string(),
+            "--diff-filter=d".to_string(),
+            "-z".to_string(),
+            format!("{}..HEAD", since),
+        ];
+        if !scope_specs.is_empty() {
+            diff_args.push("--".to_string());
+            diff_args.extend(scope_specs.iter().cloned());
+        }
+        run_git_ls(repo_root, &diff_args, "changed since commit", &mut changed)?;
+        combined_files.retain(|path| changed.contains(path));
+        debug!(
+            "{} listed files changed since {}",
+            combined_files.len(),
+            since
+        );
+    }
+
+    // Excludes are matched here rather than passed to git as pathspecs: pathspec
+    // globs differ from gitignore syntax, and git skips `**/` exclude pathspecs
+    // when a scope pathspec is present. Matching relative to the scope root
+    // gives the same selection as the walkdir modes for the same target.
+    let exclude_root = exclude_root(repo_root, scope_subdir);
+    let exclude_matcher = build_exclude_matcher(&exclude_root, config)?;
+    combined_files.retain(|path| {
+        let excluded = path.starts_with(&exclude_root)
+            && exclude_matcher
+                .matched_path_or_any_parents(path, false)
+                .is_ignore();
+        if excluded {
+            debug!("Excluding file due to pattern match (git): {:?}", path);
+        }
+        !excluded
+    });
+
+    if let Some(attr) = &config.git_attr {
+        filter_by_git_attr(repo_root, &mut combined_files, attr)?;
+        debug!(
+            "{} listed files have the '{}' attribute set",
+            combined_files.len(),
+            attr
+        );
+    }
+
     let mut files: Vec<PathBuf> = combined_files.into_iter().collect();
     files.sort();
     Ok(files)
 }
 
+/// Paths passed to one `git check-attr` call, keeping command lines short.
+const CHECK_ATTR_BATCH: usize = 500;
+
+/// Keeps only the files for which `.gitattributes` sets `attr` (to `set` or
+/// any value), as reported by `git check-attr`.
+fn filter_by_git_attr(
+    repo_root: &Path,
+    files: &mut HashSet<PathBuf>,
+    attr: &str,
+) -> GrabResult<()> {
+    let rel_paths: Vec<String> = files
+        .iter()
+        .filter_map(|path| path.strip_prefix(repo_root).ok())
+        .map(normalize_for_git)
+        .collect();
+    let mut tagged = HashSet::new();
+    for batch in rel_paths.chunks(CHECK_ATTR_BATCH) {
+        let mut args = vec!["check-attr", "-z", attr, "--"];
+        args.extend(batch.iter().map(String::as_str));
+        let output = run_command("git", &args, repo_root)?;
+        if !output.status.success() {
+            return Err(GrabError::GitCommandError {
+                command: format!("git check-attr -z {} -- <{} paths>", attr, batch.len()),
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+                stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            });
+        }
+        // `-z` output is a flat run of `<path> NUL <attr> NUL <info> NUL` records.
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let fields: Vec<&str> = stdout.split('\0').collect();
+        for record in fields.chunks_exact(3) {
+            if !matches!(record[2], "unspecified" | "unset") {
+                tagged.insert(repo_root.join(record[0]));
+            }
+        }
+    }
+    files.retain(|path| tagged.contains(path));
+    Ok(())
+}
+
+/// The directory exclude patterns are relative to in Git mode: the scoped
+/// sub-path (the target directory), or the repository root for whole-repo runs.
+pub(crate) fn exclude_root(repo_root: &Path, scope_subdir: Option<&Path>) -> PathBuf {
+    match scope_subdir {
+        Some(scope) if repo_root.join(scope).is_dir() => repo_root.join(scope),
+        Some(scope) => repo_root
+            .join(scope)
+            .parent()
+            .map_or_else(|| repo_root.to_path_buf(), Path::to_path_buf),
+        None => repo_root.to_path_buf(),
+    }
+}
+
+/// Keeps only files under `base` that match one of `patterns`
+/// (`GrabConfig::include_patterns`) within its depth limit, where a file
+/// directly in `base` has depth 1. Files outside `base` are kept; an empty
+/// `patterns` keeps everything.
+pub(crate) fn apply_include_patterns(
+    files: &mut Vec<PathBuf>,
+    base: &Path,
+    patterns: &[IncludePattern],
+) -> GrabResult<()> {
+    if patterns.is_empty() {
+        return Ok(());
+    }
+    let mut matchers = Vec::with_capacity(patterns.len());
+    for include in patterns {
+        let mut builder = GitignoreBuilder::new(base);
+        builder
+            .add_line(None, &normalize_glob(&include.pattern))
+            .map_err(GrabError::GlobMatcherBuildError)?;
+        let matcher = builder.build().map_err(GrabError::GlobMatcherBuildError)?;
+        matchers.push((matcher, include.max_depth.unwrap_or(usize::MAX)));
+    }
+    files.retain(|path| {
+        let Ok(relative) = path.strip_prefix(base) else {
+            return true;
+        };
+        let depth = relative.components().count();
+        let keep = matchers.iter().any(|(matcher, max_depth)| {
+            depth <= *max_depth && matcher.matched_path_or_any_parents(path, false).is_ignore()
+        });
+        if !keep {
+            debug!("Excluding {:?}: no include pattern matches it", path);
+        }
+        keep
+    });
+    Ok(())
+}
+
+/// The nearest directory at or above `target` that contains an entry named
+/// `marker` (`GrabConfig::root_marker`), or `None` if no ancestor has one.
+pub(crate) fn find_root_marker(target: &Path, marker: &str) -> Option<PathBuf> {
+    target
+        .ancestors()
+        .find(|dir| dir.join(marker).exists())
+        .map(Path::to_path_buf)
+}
+
+/// Returns the absolute paths of all files Git tracks within the scope,
+/// regardless of exclude patterns.
+pub(crate) fn list_tracked_files(
+    repo_root: &Path,
+    scope_subdir: Option<&Path>,
+) -> GrabResult<HashSet<PathBuf>> {
+    let mut tracked = HashSet::new();
+    let mut args = vec!["ls-files".to_string(), "-z".to_string()];
+    args.extend(build_scope_pathspecs(scope_subdir));
+    run_git_ls(repo_root, &args, "tracked", &mut tracked)?;
+    Ok(tracked)
+}
+
+/// Fails with `GrabError::UnknownGitRef` unless `reference` names a commit.
+fn verify_git_ref(repo_root: &Path, reference: &str) -> GrabResult<()> {
+    let unknown = || GrabError::UnknownGitRef {
+        reference: reference.to_string(),
+    };
+    // A leading dash would be parsed as an option rather than a revision.
+    if reference.is_empty() || reference.starts_with('-') {
+        return Err(unknown());
+    }
+    let spec = format!("{}^{{commit}}", reference);
+    let output = run_command(
+        "git",
+        &["rev-parse", "--verify", "--quiet", &spec],
+        repo_root,
+    )?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(unknown())
+    }
+}
+
 /// Lists files using `walkdir` when not in a Git repository. Applies command-line excludes.
 /// Crate-public as it's only called by grab_contents in lib.rs.
 pub(crate) fn list_files_walkdir(
@@ -136,43 +400,7 @@ pub(crate) fn list_files_walkdir(
     debug!("Listing files using walkdir starting at: {:?}", target_path);
     let mut files = Vec::new();
 
-    let mut exclude_builder = GitignoreBuilder::new(target_path);
-
-    // Add default exclusions for dirgrab.txt (conditionally) and .git/
-    if !config.include_default_output {
-        let pattern = normalize_glob("dirgrab.txt");
-        if let Err(e) = exclude_builder.add_line(None, &pattern) {
-            warn!("Failed to add default exclusion pattern 'dirgrab.txt': {}. This exclusion might not apply.", e);
-        } else {
-            debug!("Applying default exclusion for 'dirgrab.txt'");
-        }
-    } else {
-        info!("Default exclusion for 'dirgrab.txt' is disabled by --include-default-output flag.");
-    }
-    // Always exclude the .git directory when using walkdir
-    let git_dir_pattern = normalize_glob(".git/");
-    if let Err(e) = exclude_builder.add_line(None, &git_dir_pattern) {
-        warn!(
-            "Failed to add default exclusion pattern '.git/': {}. Git directory might be included.",
-            e
-        );
-    } else {
-        debug!("Applying default exclusion for '.git/'");
-    }
-
-    // Add user-provided exclusion patterns
-    for pattern in &config.exclude_patterns {
-        let normalized = normalize_glob(pattern);
-        if let Err(e) = exclude_builder.add_line(None, &normalized) {
-            error!(
-                "Failed to add exclude pattern '{}': {}. This pattern will be ignored.",
-                pattern, e
-            );
-        }
-    }
-    let exclude_matcher = exclude_builder
-        .build()
-        .map_err(GrabError::GlobMatcherBuildError)?;
+    let exclude_matcher = build_exclude_matcher(target_path, config)?;
 
     // Canonicalize the target to use as a boundary check for symlinks.
     let canonical_root = target_path
@@ -252,6 +480,246 @@ pub(crate) fn list_files_walkdir(
     Ok(files)
 }
 
+/// Resolves an explicit file list (see `GrabConfig::explicit_files`).
+/// Relative entries are resolved against `base`, which is the repo root in Git
+/// mode (matching `git ls-files` / `git diff --name-only` output) and the path
+/// base (`--root-marker` directory or target) otherwise, so header paths
+/// resolve. Entries outside `base` or not present on disk are skipped with a
+/// warning; entries matching an exclude pattern are dropped like any other file.
+pub(crate) fn resolve_explicit_files(
+    base: &Path,
+    entries: &[PathBuf],
+    config: &GrabConfig,
+) -> GrabResult<Vec<PathBuf>> {
+    debug!(
+        "Resolving {} explicitly listed files relative to {:?}",
+        entries.len(),
+        base
+    );
+    let exclude_matcher = build_exclude_matcher(base, config)?;
+    let mut files = HashSet::new();
+
+    for entry in entries {
+        let Some(resolved) = resolve_listed_path(base, entry) else {
+            continue;
+        };
+        if exclude_matcher
+            .matched_path_or_any_parents(&resolved, false)
+            .is_ignore()
+        {
+            debug!("Excluding listed file due to pattern match: {:?}", resolved);
+            continue;
+        }
+        files.insert(resolved);
+    }
+
+    let mut files: Vec<PathBuf> = files.into_iter().collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Resolves `GrabConfig::prepend_files` / `append_files` against `base` like
+/// an explicit file list, but without applying any exclude pattern, and
+/// accepting files outside `base` (a shared instruction file, say). Order is
+/// kept; repeats are dropped.
+pub(crate) fn resolve_injected_files(base: &Path, entries: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let resolved = resolve_entry(base, entry);
+        if !long_path(&resolved).is_file() {
+            warn!("Skipping injected path {:?}: not a file on disk", entry);
+            continue;
+        }
+        if !files.contains(&resolved) {
+            files.push(resolved);
+        }
+    }
+    files
+}
+
+/// Resolves one listed entry against `base`, or warns and returns `None` when
+/// it lies outside `base` or is not a file on disk.
+fn resolve_listed_path(base: &Path, entry: &Path) -> Option<PathBuf> {
+    let resolved = resolve_entry(base, entry);
+    if !resolved.starts_with(base) {
+        warn!("Skipping listed path {:?}: it is outside {:?}", entry, base);
+        return None;
+    }
+    if !long_path(&resolved).is_file() {
+        warn!("Skipping listed path {:?}: not a file on disk", entry);
+        return None;
+    }
+    Some(resolved)
+}
+
+/// Joins a relative `entry` to `base`; canonicalizes an absolute one so it
+/// compares equal to listed paths under a canonical `base`.
+fn resolve_entry(base: &Path, entry: &Path) -> PathBuf {
+    if entry.is_absolute() {
+        entry.canonicalize().unwrap_or_else(|_| entry.to_path_buf())
+    } else {
+        normalize_lexically(&base.join(entry))
+    }
+}
+
+/// Resolves `.` and `..` components without touching the filesystem.
+pub(crate) fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    normalized
+}
+
+/// Lists files by walking `target_path` with the `ignore` crate's full
+/// `.gitignore` ruleset (nested `.gitignore` files, `.git/info/exclude`, and the
+/// global excludes file) without spawning `git`. Applies command-line excludes.
+/// Crate-public as it's only called by grab_contents in lib.rs.
+pub(crate) fn list_files_gitignore(
+    target_path: &Path,
+    config: &GrabConfig,
+) -> GrabResult<Vec<PathBuf>> {
+    debug!(
+        "Listing files using gitignore rules starting at: {:?}",
+        target_path
+    );
+    let exclude_matcher = build_exclude_matcher(target_path, config)?;
+    let prune_matcher = exclude_matcher.clone();
+
+    let walker = WalkBuilder::new(target_path)
+        .hidden(false) // git lists dotfiles
+        .ignore(false) // `.ignore` files are not part of git's rules
+        .parents(true)
+        .git_ignore(true)
+        .git_global(true)
+        .git_exclude(true)
+        .require_git(false)
+        .filter_entry(move |entry| {
+            let is_dir = entry.file_type().is_some_and(|ft| ft.is_dir());
+            !(is_dir
+                && matches!(
+                    prune_matcher.matched_path_or_any_parents(entry.path(), true),
+                    Match::Ignore(_)
+                ))
+        })
+        .build();
+
+    let mut files = Vec::new();
+    for entry_result in walker {
+        let entry = match entry_result {
+            Ok(entry) => entry,
+            Err(e) => {
+                warn!("Skipping path due to error during gitignore walk: {}", e);
+                continue;
+            }
+        };
+        let path = entry.path();
+        // git lists symlinks to files as regular entries.
+        let is_file = entry.file_type().is_some_and(|ft| ft.is_file())
+            || (entry.path_is_symlink() && path.is_file());
+        if !is_file {
+            continue;
+        }
+        match exclude_matcher.matched_path_or_any_parents(path, false) {
+            Match::None | Match::Whitelist(_) => files.push(path.to_path_buf()),
+            Match::Ignore(_) => {
+                debug!(
+                    "Excluding file due to pattern match on path or parent (gitignore): {:?}",
+                    path
+                );
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Builds the gitignore-style matcher used to filter walked or explicitly
+/// listed files: the default `dirgrab.txt` and `.git` exclusions plus the
+/// user's `exclude_patterns` and `ignore_files` patterns, all relative to `root`.
+fn build_exclude_matcher(root: &Path, config: &GrabConfig) -> GrabResult<Gitignore> {
+    let mut exclude_builder = GitignoreBuilder::new(root);
+
+    // Add default exclusions for dirgrab.txt (conditionally) and .git
+    if !config.include_default_output {
+        let pattern = normalize_glob("dirgrab.txt");
+        if let Err(e) = exclude_builder.add_line(None, &pattern) {
+            warn!("Failed to add default exclusion pattern 'dirgrab.txt': {}. This exclusion might not apply.", e);
+        } else {
+            debug!("Applying default exclusion for 'dirgrab.txt'");
+        }
+    } else {
+        info!("Default exclusion for 'dirgrab.txt' is disabled by --include-default-output flag.");
+    }
+    // Always exclude .git. No trailing slash: in linked
+    // worktrees and submodules `.git` is a file pointing at the real git dir.
+    let git_dir_pattern = normalize_glob(".git");
+    if let Err(e) = exclude_builder.add_line(None, &git_dir_pattern) {
+        warn!(
+            "Failed to add default exclusion pattern '.git': {}. Git directory might be included.",
+            e
+        );
+    } else {
+        debug!("Applying default exclusion for '.git'");
+    }
+
+    // Add user-provided exclusion patterns, then those read from ignore files
+    let ignore_file_patterns = read_ignore_files(config)?;
+    for pattern in config.exclude_patterns.iter().chain(&ignore_file_patterns) {
+        let normalized = normalize_glob(pattern);
+        if let Err(e) = exclude_builder.add_line(None, &normalized) {
+            error!(
+                "Failed to add exclude pattern '{}': {}. This pattern will be ignored.",
+                pattern, e
+            );
+        }
+    }
+    exclude_builder
+        .build()
+        .map_err(GrabError::GlobMatcherBuildError)
+}
+
+/// Returns the patterns from `patterns` that match none of `candidates`.
+/// `candidates` should be the listing produced *without* the user excludes, and
+/// `base` the directory the patterns are relative to. Each pattern is checked on
+/// its own, so a pattern fully shadowed by another is still considered used.
+pub(crate) fn find_unused_excludes(
+    base: &Path,
+    candidates: &[PathBuf],
+    patterns: &[String],
+) -> Vec<String> {
+    let mut unused = Vec::new();
+    for pattern in patterns {
+        let mut builder = GitignoreBuilder::new(base);
+        if let Err(e) = builder.add_line(None, &normalize_glob(pattern)) {
+            debug!("Cannot check usage of exclude pattern '{}': {}", pattern, e);
+            continue;
+        }
+        let matcher = match builder.build() {
+            Ok(matcher) => matcher,
+            Err(e) => {
+                debug!("Cannot check usage of exclude pattern '{}': {}", pattern, e);
+                continue;
+            }
+        };
+        let used = candidates.iter().any(|path| {
+            path.starts_with(base) && matcher.matched_path_or_any_parents(path, false).is_ignore()
+        });
+        if !used {
+            debug!("Exclude pattern '{}' did not match any files", pattern);
+            unused.push(pattern.clone());
+        }
+    }
+    unused
+}
+
 fn run_git_ls(
     repo_root: &Path,
     args: &[String],
@@ -290,57 +758,128 @@ fn run_git_ls(
     Ok(())
 }
 
-fn build_scope_pathspecs(repo_root: &Path, scope_subdir: Option<&Path>) -> Vec<String> {
+/// Lists the untracked paths Git ignores within the scope (an ignored
+/// directory is reported once, as `dir/`) and asks `git check-ignore -v` for
+/// the rule behind each.
+pub(crate) fn explain_ignored(
+    repo_root: &Path,
+    scope_subdir: Option<&Path>,
+) -> GrabResult<Vec<IgnoredPath>> {
+    let mut args = vec![
+        "ls-files".to_string(),
+        "-z".to_string(),
+        "--others".to_string(),
+        "--ignored".to_string(),
+        "--exclude-standard".to_string(),
+        "--directory".to_string(),
+    ];
+    args.extend(build_scope_pathspecs(scope_subdir));
+    let ignored = git_stdout(repo_root, &args)?;
+    let paths: Vec<&str> = ignored.split('\0').filter(|s| !s.is_empty()).collect();
+    debug!("Git ignores {} untracked paths in scope", paths.len());
+
+    if paths.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // `-z` requires `--stdin`, which also keeps long path lists off the command line.
+    let command = "git check-ignore -v -z --stdin";
+    let execution_error = |source| GrabError::GitExecutionError {
+        command: command.to_string(),
+        source,
+    };
+    let mut child = git_command(repo_root)
+        .args(["check-ignore", "-v", "-z", "--stdin"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(execution_error)?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input: String = paths.iter().map(|path| format!("{}\0", path)).collect();
+    // Fed from another thread so a full stdout pipe cannot deadlock the write.
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output().map_err(execution_error)?;
+    if let Ok(Err(e)) = writer.join() {
+        return Err(execution_error(e));
+    }
+    // Exit status 1 means no path matched a rule.
+    if !output.status.success() && output.status.code() != Some(1) {
+        return Err(GrabError::GitCommandError {
+            command: command.to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        });
+    }
+
+    // Records are `source NUL line NUL pattern NUL path NUL`.
+    let verbose = String::from_utf8_lossy(&output.stdout);
+    let fields: Vec<&str> = verbose.split('\0').collect();
+    Ok(fields
+        .chunks_exact(4)
+        .map(|record| IgnoredPath {
+            display_path: record[3].to_string(),
+            source: record[0].to_string(),
+            line: record[1].parse().unwrap_or(0),
+            pattern: record[2].to_string(),
+        })
+        .collect())
+}
+
+/// Runs a read-only Git command in `repo_root` and returns its stdout.
+fn git_stdout(repo_root: &Path, args: &[String]) -> GrabResult<String> {
+    let arg_refs: Vec<&str> = args.iter().map(String::as_str).collect();
+    let output = run_command("git", &arg_refs, repo_root)?;
+    if !output.status.success() {
+        return Err(GrabError::GitCommandError {
+            command: format!("git {}", args.join(" ")),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// A literal pathspec for the scope, so directory names like `[x]` match only
+/// themselves; a directory pathspec also covers everything below it.
+fn build_scope_pathspecs(scope_subdir: Option<&Path>) -> Vec<String> {
     let mut specs = Vec::new();
     if let Some(rel_path) = scope_subdir {
         if rel_path.as_os_str().is_empty() {
             return specs;
         }
 
-        let absolute_path = repo_root.join(rel_path);
         let normalized = normalize_for_git(rel_path);
-        if absolute_path.is_dir() {
-            let suffix = if normalized.ends_with('/') {
-                "**"
-            } else {
-                "/**"
-            };
-            let spec = format!(":(glob){}{}", normalized, suffix);
-            specs.push(spec);
-        } else {
-            specs.push(format!(":(glob){}", normalized));
-        }
+        specs.push(format!(":(literal){}", normalized.trim_end_matches('/')));
     }
     specs
 }
 
-fn build_exclude_pathspecs(config: &GrabConfig) -> Vec<String> {
-    let mut specs = Vec::new();
-    let mut seen = HashSet::new();
-
-    if !config.include_default_output {
-        let normalized = normalize_glob("dirgrab.txt");
-        if seen.insert(normalized.clone()) {
-            debug!("Applying default exclusion for 'dirgrab.txt'");
-            specs.push(format!(":(glob,exclude){}", prefix_for_git(&normalized)));
-        }
-    } else {
-        info!("Default exclusion for 'dirgrab.txt' is disabled by configuration.");
-    }
-
-    for pattern in &config.exclude_patterns {
-        let normalized = normalize_glob(pattern);
-        if seen.insert(normalized.clone()) {
-            specs.push(format!(":(glob,exclude){}", prefix_for_git(&normalized)));
-        } else {
-            debug!(
-                "Skipping duplicate exclude pattern '{}' when building git pathspecs",
-                pattern
-            );
-        }
+/// Reads the patterns from every file in `GrabConfig::ignore_files`, skipping
+/// blank lines and `#` comments. Relative paths are resolved against the target
+/// path; missing files are skipped with a warning.
+fn read_ignore_files(config: &GrabConfig) -> GrabResult<Vec<String>> {
+    let mut patterns = Vec::new();
+    for file in &config.ignore_files {
+        let path = config.target_path.join(file);
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                warn!("Ignore file {:?} not found; skipping it", path);
+                continue;
+            }
+            Err(e) => return Err(GrabError::IoError { path, source: e }),
+        };
+        debug!("Loading exclude patterns from ignore file {:?}", path);
+        patterns.extend(
+            contents
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(String::from),
+        );
     }
-
-    specs
+    Ok(patterns)
 }
 
 fn normalize_for_git(path: &Path) -> String {
@@ -350,14 +889,6 @@ fn normalize_for_git(path: &Path) -> String {
         .join("/")
 }
 
-fn prefix_for_git(pattern: &str) -> String {
-    if pattern.contains('/') {
-        pattern.to_string()
-    } else {
-        format!("**/{}", pattern)
-    }
-}
-
 /// Normalizes a glob pattern by replacing backslashes with forward slashes.
 /// Used to ensure consistent pattern matching across platforms.
 pub fn normalize_glob(pattern: &str) -> String {
@@ -0,0 +1,103 @@
+// --- FILE: dirgrab-lib/src/mime.rs ---
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use log::{debug, info};
+
+/// How many leading bytes are read to classify a file.
+const SNIFF_LEN: usize = 8192;
+
+/// MIME type reported for files that are valid UTF-8 but carry no signature.
+const TEXT_PLAIN: &str = "text/plain";
+/// MIME type reported for unrecognized binary files.
+const OCTET_STREAM: &str = "application/octet-stream";
+
+/// Classifies a file from its first few KiB: a known signature (via `infer`)
+/// wins, otherwise UTF-8 text is `text/plain` and anything else is
+/// `application/octet-stream`. Returns `None` if the file cannot be read.
+pub(crate) fn sniff(path: &Path) -> Option<&'static str> {
+    let mut buf = Vec::with_capacity(SNIFF_LEN);
+    File::open(crate::utils::long_path(path))
+        .and_then(|file| file.take(SNIFF_LEN as u64).read_to_end(&mut buf))
+        .map_err(|e| debug!("Could not sniff MIME type of {:?}: {}", path, e))
+        .ok()?;
+    Some(classify(&buf))
+}
+
+fn classify(buf: &[u8]) -> &'static str {
+    if let Some(kind) = infer::get(buf) {
+        return kind.mime_type();
+    }
+    match std::str::from_utf8(buf) {
+        Ok(_) => TEXT_PLAIN,
+        // A multi-byte character cut off by the sniff window is still text.
+        Err(e) if e.error_len().is_none() => TEXT_PLAIN,
+        Err(_) => OCTET_STREAM,
+    }
+}
+
+/// Matches a MIME type against a glob-style pattern: `*` (or `*/*`) matches
+/// everything, `image/*` a whole top-level type, anything else exactly.
+/// Comparison is case-insensitive.
+pub(crate) fn matches(pattern: &str, mime: &str) -> bool {
+    let pattern = pattern.trim().to_ascii_lowercase();
+    let mime = mime.to_ascii_lowercase();
+    match pattern.split_once('/') {
+        _ if pattern == "*" => true,
+        Some((kind, "*")) => kind == "*" || mime.split('/').next() == Some(kind),
+        _ => pattern == mime,
+    }
+}
+
+/// A file dropped by `GrabConfig::include_mime`/`exclude_mime`.
+pub(crate) struct MimeSkip {
+    pub path: PathBuf,
+    pub mime: &'static str,
+    /// The exclude pattern that matched; `None` when no include pattern did.
+    pub pattern: Option<String>,
+}
+
+/// Keeps files whose sniffed MIME type matches an include pattern (when any are
+/// given) and no exclude pattern. Unreadable files are kept so the read error
+/// surfaces later. Reads the head of every candidate file, except that files of
+/// at most `sniff_above` bytes are assumed to be `text/plain` unread.
+pub(crate) fn filter_files(
+    files: Vec<PathBuf>,
+    include: &[String],
+    exclude: &[String],
+    sniff_above: Option<u64>,
+) -> (Vec<PathBuf>, Vec<MimeSkip>) {
+    let mut kept = Vec::with_capacity(files.len());
+    let mut skipped = Vec::new();
+    for path in files {
+        let small = sniff_above.is_some_and(|limit| {
+            fs::metadata(crate::utils::long_path(&path)).is_ok_and(|meta| meta.len() <= limit)
+        });
+        let mime = if small {
+            Some(TEXT_PLAIN)
+        } else {
+            sniff(&path)
+        };
+        let Some(mime) = mime else {
+            kept.push(path);
+            continue;
+        };
+        let pattern = if let Some(pattern) = exclude.iter().find(|p| matches(p, mime)) {
+            Some(pattern.clone())
+        } else if !include.is_empty() && !include.iter().any(|p| matches(p, mime)) {
+            None
+        } else {
+            kept.push(path);
+            continue;
+        };
+        info!("Skipping {:?} by MIME type ({})", path, mime);
+        skipped.push(MimeSkip {
+            path,
+            mime,
+            pattern,
+        });
+    }
+    (kept, skipped)
+}
@@ -0,0 +1,346 @@
+// --- FILE: dirgrab-lib/src/ordering.rs ---
+
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use log::{debug, error, warn};
+
+use crate::config::{FileOrder, Partition};
+use crate::errors::{GrabError, GrabResult};
+use crate::listing::normalize_glob;
+use crate::utils::run_command;
+
+/// File count above which `FileOrder::GitRecency` warns about its history walk.
+const GIT_RECENCY_WARN_FILES: usize = 1000;
+
+/// Marks commit lines in the `git log --name-only` output read by `git_commit_times`.
+const COMMIT_MARKER: char = '\u{1}';
+
+/// Sorts `files` according to `order`. Ties (and the default `Path` order) fall
+/// back to path order so the result stays deterministic.
+/// Metadata is read at most once per file via `sort_by_cached_key`.
+/// `repo_root` is only used by `FileOrder::GitRecency`, which falls back to
+/// `Mtime` without it.
+pub(crate) fn sort_files(
+    files: &mut [PathBuf],
+    order: FileOrder,
+    descending: bool,
+    repo_root: Option<&Path>,
+) {
+    debug!(
+        "Sorting {} files by {:?} (descending: {})",
+        files.len(),
+        order,
+        descending
+    );
+    match order {
+        FileOrder::Path => {
+            if descending {
+                files.sort_by(|a, b| b.cmp(a));
+            } else {
+                files.sort();
+            }
+        }
+        FileOrder::Size => sort_by_primary(files, descending, |path| {
+            fs::metadata(path).map(|meta| meta.len()).unwrap_or(0)
+        }),
+        FileOrder::Mtime => sort_by_primary(files, descending, |path| {
+            fs::metadata(path)
+                .and_then(|meta| meta.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH)
+        }),
+        FileOrder::GitRecency => {
+            let commit_times = match repo_root {
+                Some(root) => {
+                    if files.len() > GIT_RECENCY_WARN_FILES {
+                        warn!(
+                            "--sort git-recency walks the whole Git history; {} files may take a while.",
+                            files.len()
+                        );
+                    }
+                    git_commit_times(root)
+                }
+                None => {
+                    warn!("--sort git-recency has no effect outside Git mode; sorting by mtime.");
+                    HashMap::new()
+                }
+            };
+            // Files without commits (e.g. untracked ones) use their mtime.
+            sort_by_primary(files, descending, |path| {
+                commit_times
+                    .get(path)
+                    .copied()
+                    .unwrap_or_else(|| mtime_seconds(path))
+            })
+        }
+        FileOrder::Ext => sort_by_primary(files, descending, |path| {
+            path.extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .unwrap_or_default()
+        }),
+    }
+}
+
+/// Maps every path that appears in the history of `repo_root` to the committer
+/// timestamp of its most recent commit, from a single
+/// `git log --format=%ct --name-only` walk (newest commits come first).
+/// Failures are logged and yield an empty map.
+fn git_commit_times(repo_root: &Path) -> HashMap<PathBuf, i64> {
+    let format = format!("--format={}%ct", COMMIT_MARKER);
+    let args = ["-c", "core.quotePath=false", "log", &format, "--name-only"];
+    let stdout = match run_command("git", &args, repo_root) {
+        Ok(output) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).into_owned()
+        }
+        Ok(output) => {
+            warn!(
+                "git log failed while reading commit times: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            return HashMap::new();
+        }
+        Err(e) => {
+            warn!("Could not read commit times: {}", e);
+            return HashMap::new();
+        }
+    };
+
+    let mut times = HashMap::new();
+    let mut current = 0;
+    for line in stdout.lines().filter(|line| !line.is_empty()) {
+        match line.strip_prefix(COMMIT_MARKER) {
+            Some(timestamp) => current = timestamp.trim().parse().unwrap_or(0),
+            None => {
+                times.entry(repo_root.join(line)).or_insert(current);
+            }
+        }
+    }
+    debug!("Read last-commit times for {} paths", times.len());
+    times
+}
+
+fn mtime_seconds(path: &Path) -> i64 {
+    fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
+/// Moves files matching any of `patterns` (gitignore-style globs relative to
+/// `base`, like excludes) after all other files. The sort is stable, so both
+/// groups keep the order from `sort_files`. Invalid patterns are logged and
+/// ignored.
+pub(crate) fn deprioritize(
+    files: &mut [PathBuf],
+    base: &Path,
+    patterns: &[String],
+) -> GrabResult<()> {
+    let matcher = build_pattern_matcher(base, patterns, "deprioritize")?;
+    files.sort_by_cached_key(|path| {
+        path.starts_with(base) && matcher.matched_path_or_any_parents(path, false).is_ignore()
+    });
+    Ok(())
+}
+
+/// Files matching none of the `content_for` `patterns` (gitignore-style globs
+/// relative to `base`, like excludes): they stay in the tree but get no body.
+/// Invalid patterns are logged and ignored.
+pub(crate) fn tree_only(
+    files: &[PathBuf],
+    base: &Path,
+    patterns: &[String],
+) -> GrabResult<HashSet<PathBuf>> {
+    let matcher = build_pattern_matcher(base, patterns, "content-for")?;
+    Ok(files
+        .iter()
+        .filter(|path| {
+            !(path.starts_with(base)
+                && matcher.matched_path_or_any_parents(path, false).is_ignore())
+        })
+        .cloned()
+        .collect())
+}
+
+fn build_pattern_matcher(base: &Path, patterns: &[String], kind: &str) -> GrabResult<Gitignore> {
+    let mut builder = GitignoreBuilder::new(base);
+    for pattern in patterns {
+        if let Err(e) = builder.add_line(None, &normalize_glob(pattern)) {
+            error!(
+                "Failed to add {} pattern '{}': {}. This pattern will be ignored.",
+                kind, pattern, e
+            );
+        }
+    }
+    builder.build().map_err(GrabError::GlobMatcherBuildError)
+}
+
+/// True for `README*` files (case-insensitive), the overviews moved ahead by
+/// `readme_first`.
+pub(crate) fn is_readme(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.to_ascii_lowercase().starts_with("readme"))
+}
+
+/// Moves each directory's `README*` files to just before the first other file
+/// at or below that directory, keeping everything else in its current order,
+/// so a directory's overview also precedes its subdirectories.
+pub(crate) fn readme_first(files: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut readmes: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+    for path in files.iter().filter(|path| is_readme(path)) {
+        let parent = path.parent().map(PathBuf::from).unwrap_or_default();
+        readmes.entry(parent).or_default().push(path.clone());
+    }
+    if readmes.is_empty() {
+        return files;
+    }
+    let mut ordered = Vec::with_capacity(files.len());
+    for path in files {
+        // Outermost directory first, so parent overviews precede child ones.
+        let ancestors: Vec<&Path> = path.ancestors().skip(1).collect();
+        for dir in ancestors.into_iter().rev() {
+            if let Some(overviews) = readmes.remove(dir) {
+                ordered.extend(overviews);
+            }
+        }
+        if !is_readme(&path) {
+            ordered.push(path);
+        }
+    }
+    ordered
+}
+
+/// Banner label for files that match no `GrabConfig::partitions` pattern.
+pub(crate) const DEFAULT_PARTITION: &str = "Other";
+
+/// Assigns each of `paths` to the first partition whose pattern matches it
+/// (same matching as `deprioritize`); unmatched paths get `partitions.len()`,
+/// the default section. Invalid patterns are logged and match nothing.
+pub(crate) fn partition_indices(
+    paths: &[PathBuf],
+    base: &Path,
+    partitions: &[Partition],
+) -> GrabResult<HashMap<PathBuf, usize>> {
+    let matchers = partitions
+        .iter()
+        .map(|partition| {
+            build_pattern_matcher(base, std::slice::from_ref(&partition.pattern), "partition")
+        })
+        .collect::<GrabResult<Vec<_>>>()?;
+    Ok(paths
+        .iter()
+        .map(|path| {
+            let index = matchers
+                .iter()
+                .position(|matcher| {
+                    path.starts_with(base)
+                        && matcher.matched_path_or_any_parents(path, false).is_ignore()
+                })
+                .unwrap_or(partitions.len());
+            (path.clone(), index)
+        })
+        .collect())
+}
+
+/// Output sections, from `GrabConfig::partitions` or `GrabConfig::collate`.
+#[derive(Debug, Default)]
+pub(crate) struct Sections {
+    /// Section index of each file.
+    pub index: HashMap<PathBuf, usize>,
+    /// Banner label of each section index, the default section last.
+    pub labels: Vec<String>,
+}
+
+impl Sections {
+    /// Sections for `partitions`, assigned by `partition_indices`.
+    pub(crate) fn partitioned(
+        paths: &[PathBuf],
+        base: &Path,
+        partitions: &[Partition],
+    ) -> GrabResult<Self> {
+        let mut labels: Vec<String> = partitions.iter().map(|p| p.label.clone()).collect();
+        labels.push(DEFAULT_PARTITION.to_string());
+        Ok(Self {
+            index: partition_indices(paths, base, partitions)?,
+            labels,
+        })
+    }
+
+    /// One section per lowercased extension (`.rs`, `.sql`, ...) in
+    /// alphabetical order; files without an extension go to the default section.
+    pub(crate) fn by_extension(paths: &[PathBuf]) -> Self {
+        let extension = |path: &PathBuf| {
+            path.extension()
+                .map(|ext| format!(".{}", ext.to_string_lossy().to_lowercase()))
+        };
+        let mut labels: Vec<String> = paths.iter().filter_map(extension).collect();
+        labels.sort();
+        labels.dedup();
+        let index = paths
+            .iter()
+            .map(|path| {
+                let section = extension(path)
+                    .and_then(|ext| labels.iter().position(|label| *label == ext))
+                    .unwrap_or(labels.len());
+                (path.clone(), section)
+            })
+            .collect();
+        labels.push(DEFAULT_PARTITION.to_string());
+        Self { index, labels }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Banner label of section `index`.
+    pub(crate) fn label(&self, index: usize) -> &str {
+        self.labels
+            .get(index)
+            .map_or(DEFAULT_PARTITION, String::as_str)
+    }
+}
+
+/// The `===== Label =====` line that opens an output (or tree) section.
+pub(crate) fn partition_banner(label: &str) -> String {
+    format!("===== {} =====\n", label)
+}
+
+fn sort_by_primary<K, F>(files: &mut [PathBuf], descending: bool, key: F)
+where
+    K: Ord,
+    F: Fn(&PathBuf) -> K,
+{
+    if descending {
+        files.sort_by_cached_key(|path| (Reverse(key(path)), path.clone()));
+    } else {
+        files.sort_by_cached_key(|path| (key(path), path.clone()));
+    }
+}
+
+/// Keeps at most `limit` files per parent directory, preserving the current
+/// order. Returns `(kept, omitted)`.
+pub(crate) fn limit_per_directory(
+    files: Vec<PathBuf>,
+    limit: usize,
+) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let mut counts: HashMap<PathBuf, usize> = HashMap::new();
+    let (kept, omitted): (Vec<_>, Vec<_>) = files.into_iter().partition(|path| {
+        let parent = path.parent().map(PathBuf::from).unwrap_or_default();
+        let count = counts.entry(parent).or_insert(0);
+        *count += 1;
+        *count <= limit
+    });
+    debug!(
+        "Per-directory limit {} kept {} files, omitted {}",
+        limit,
+        kept.len(),
+        omitted.len()
+    );
+    (kept, omitted)
+}
@@ -1,20 +1,51 @@
 // --- FILE: dirgrab-lib/src/processing.rs ---
 
-use std::borrow::Cow;
-use std::fs;
+use std::collections::hash_map::{Entry, HashMap};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Read};
 use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
 
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use image::{ImageFormat, ImageReader};
 use log::{debug, info, warn};
+use regex::Regex;
 
 // Use crate:: paths for sibling modules
-use crate::config::GrabConfig; // Import GrabConfig
-use crate::errors::GrabResult;
+use crate::config::{BinaryMode, BudgetStrategy, Extractor, GrabConfig}; // Import GrabConfig
+use crate::editorconfig::EditorConfigs;
+use crate::errors::{GrabError, GrabResult};
+use crate::hashing::{sha256_hex, short_sha256};
+use crate::ordering::{is_readme, Sections};
+use crate::redact::Redactor;
+use crate::utils::{format_rfc3339_utc, long_path, posix_path, run_command};
+use crate::{
+    budget, imports, language, listing, ContentMeasure, DedupeStats, DuplicateGroup, GrabProgress,
+    MeasuredFile, MirrorReport, SkipReason, SkippedFile, TransformSavings, TruncatedFile,
+    TRUNCATED_TRAILER_HEADING,
+};
+
+/// Markers used when `GrabConfig::generated_markers` is empty.
+const DEFAULT_GENERATED_MARKERS: &[&str] = &["@generated", "DO NOT EDIT"];
+
+/// How much of each file is inspected for generated-code markers.
+const GENERATED_SNIFF_BYTES: u64 = 1024;
 
 #[derive(Debug, Clone)]
 pub(crate) struct ProcessedFiles {
     pub content: String,
     pub files: Vec<FileSegment>,
+    pub skipped: Vec<SkippedFile>,
+    pub truncated: Vec<TruncatedFile>,
+    pub savings: Vec<TransformSavings>,
+    pub dedupe_stats: Option<DedupeStats>,
+    pub duplicates: Vec<DuplicateGroup>,
+    /// Matches replaced by `GrabConfig::anonymize` in paths and bodies.
+    pub anonymized: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -22,23 +53,281 @@ pub(crate) struct FileSegment {
     pub display_path: String,
     pub full_range: Range<usize>,
     pub header_range: Option<Range<usize>>,
+    pub frontmatter_range: Option<Range<usize>>,
     pub body_range: Range<usize>,
+    pub lines: usize,
+    pub force_added: bool,
+    pub mode: Option<u32>,
+}
+
+/// The outcome of reading a single file, before any transformations.
+enum LoadedFile {
+    /// Text to emit as the body, with an optional annotation appended to the
+    /// header label (e.g. `extracted text`). `converted` is set when the text
+    /// came from a PDF or an extractor rather than the file's own bytes.
+    Text {
+        text: String,
+        annotation: Option<String>,
+        converted: bool,
+    },
+    /// Only a header is emitted, followed by a single blank line
+    /// (e.g. when PDF extraction fails).
+    HeaderOnly { annotation: String },
+    /// A binary file included as base64 (`BinaryMode::Base64`). The body is
+    /// emitted verbatim: content transformations would corrupt the encoding.
+    Encoded { text: String },
+    /// The file is left out of the output entirely.
+    Skipped(SkipReason),
+}
+
+/// A file that survived loading, ready to be emitted.
+struct PendingFile {
+    display_path: String,
+    /// `DIR:` header emitted before this file when it opens a
+    /// `GrabConfig::group_under` group (empty otherwise).
+    group_header: String,
+    /// Rendered header line (empty when headers are disabled).
+    header: String,
+    /// Label inside a plain header: the path plus language tag and annotation
+    /// (empty for link headers or when headers are disabled).
+    label: String,
+    /// Rendered YAML front-matter block (empty unless `GrabConfig::frontmatter`).
+    frontmatter: String,
+    /// Transformed body, or `None` for header-only entries.
+    body: Option<String>,
+    /// Header annotation such as `extracted text`.
+    annotation: Option<String>,
+    /// Rendered `HISTORY:` block (empty unless `GrabConfig::git_history`).
+    history: String,
+    force_added: bool,
+    /// Unix permission bits, see `GrabbedFile::mode`.
+    mode: Option<u32>,
+    /// Short SHA-256 of the body as read, when headers or wrappers show it.
+    hash: Option<String>,
+    /// `GrabConfig::partitions` section index, if partitions are in use.
+    section: Option<usize>,
 }
 
+/// Result of extracting one PDF: the text plus `(kept, total)` pages when
+/// `pdf_max_pages` cut it short, or the extraction error message.
+type PdfText = Result<(String, Option<(usize, usize)>), String>;
+
+/// Header annotation of `README*` files under `GrabConfig::readme_first`.
+const DIRECTORY_OVERVIEW: &str = "directory overview";
+
+/// Placeholder in `wrap_open`/`wrap_close` replaced by the file's short hash.
+const HASH_PLACEHOLDER: &str = "{hash}";
+
+/// File count above which `git_history` warns about its one-call-per-file cost.
+const GIT_HISTORY_WARN_FILES: usize = 200;
+
 /// Reads a list of files, concatenates their UTF-8 content, optionally adding headers.
-/// Handles PDF text extraction if configured.
+/// Handles PDF text extraction if configured and applies redaction rules to bodies.
 /// Skips non-UTF8 files and files with read errors, logging warnings.
+///
+/// Runs in two passes: every file is loaded and transformed first, so that
+/// `budget` (the bytes available for this section, see
+/// `GrabConfig::context_budget`) can be split across all bodies before emitting.
 pub(crate) fn process_files(
     files: &[PathBuf],
     config: &GrabConfig,
     repo_root: Option<&Path>,
     target_path: &Path,
+    budget: Option<usize>,
+    sections: &Sections,
+    on_progress: &mut dyn FnMut(GrabProgress),
 ) -> GrabResult<ProcessedFiles> {
     debug!("Processing {} files for content.", files.len());
+    let LoadedFiles {
+        pending,
+        skipped,
+        mut savings,
+        dedupe_stats,
+        duplicates,
+        anonymized,
+    } = load_files(files, config, repo_root, target_path, sections, on_progress)?;
+    if budget.is_some() {
+        savings.enable(TransformSavings::CONTEXT_BUDGET);
+    }
+    let limits = budget.and_then(|budget| budget_limits(&pending, config, budget));
+
     let mut combined_content = String::with_capacity(files.len() * 1024);
-    let mut segments = Vec::with_capacity(files.len());
+    let mut segments = Vec::with_capacity(pending.len());
+    let mut truncated = Vec::new();
 
-    for file_path in files {
+    for (index, file) in pending.into_iter().enumerate() {
+        combined_content.push_str(&file.group_header);
+        let file_start = combined_content.len();
+        combined_content.push_str(&render_wrapper(config.wrap_open.as_deref(), &file));
+        let mut header_range = None;
+        if !file.header.is_empty() {
+            let start = combined_content.len();
+            combined_content.push_str(&file.header);
+            header_range = Some(start..combined_content.len());
+        }
+        let mut frontmatter_range = None;
+        if !file.frontmatter.is_empty() {
+            let start = combined_content.len();
+            combined_content.push_str(&file.frontmatter);
+            frontmatter_range = Some(start..combined_content.len());
+        }
+
+        let close = render_wrapper(config.wrap_close.as_deref(), &file);
+        let body_start = combined_content.len();
+        let mut lines = 0;
+        match file.body {
+            Some(full_body) => {
+                let mut body = full_body.as_str();
+                if let Some(limit) = limits.as_ref().map(|limits| limits[index]) {
+                    if let Some(prefix) =
+                        budget::truncate_body(body, limit, line_cost(config, &file.display_path))
+                    {
+                        body = prefix;
+                        savings.record(
+                            TransformSavings::CONTEXT_BUDGET,
+                            full_body.len(),
+                            body.len(),
+                        );
+                        truncated.push(TruncatedFile {
+                            display_path: file.display_path.clone(),
+                            kept_lines: count_lines(body),
+                            total_lines: count_lines(&full_body),
+                        });
+                    }
+                }
+                lines = count_lines(body);
+                if config.prefix_lines {
+                    push_prefixed_lines(&mut combined_content, &file.display_path, body);
+                } else {
+                    combined_content.push_str(body);
+                }
+                if !body.ends_with('\n') {
+                    combined_content.push('\n');
+                }
+            }
+            None if config.prefix_lines => {
+                // Header-only entries (e.g. failed PDFs) still get one greppable line.
+                let note = file.annotation.as_deref().unwrap_or("no content");
+                combined_content.push_str(&format!("{}: ({})\n", file.display_path, note));
+            }
+            None => {}
+        }
+        let content_end = combined_content.len();
+        combined_content.push_str(&close);
+        if !config.tight {
+            combined_content.push('\n');
+        }
+        // With `no_body`, the blank separator is not a body; nor is it once a
+        // closing wrapper sits between them.
+        let body_end = if config.no_body {
+            body_start
+        } else if !close.is_empty() {
+            content_end
+        } else {
+            combined_content.len()
+        };
+        let body_range = body_start..body_end;
+        combined_content.push_str(&file.history);
+
+        let full_end = combined_content.len();
+        segments.push(FileSegment {
+            display_path: file.display_path,
+            full_range: file_start..full_end,
+            header_range,
+            frontmatter_range,
+            body_range,
+            lines,
+            force_added: file.force_added,
+            mode: file.mode,
+        });
+    }
+
+    Ok(ProcessedFiles {
+        content: combined_content,
+        files: segments,
+        skipped,
+        truncated,
+        savings: savings.0,
+        dedupe_stats,
+        duplicates,
+        anonymized,
+    })
+}
+
+/// Files loaded and transformed by the first pass of `process_files`, laid out
+/// in output order (`group_under` groups and partition banners included) but
+/// not yet fitted to a context budget.
+struct LoadedFiles {
+    pending: Vec<PendingFile>,
+    skipped: Vec<SkippedFile>,
+    savings: SavingsCounter,
+    dedupe_stats: Option<DedupeStats>,
+    duplicates: Vec<DuplicateGroup>,
+    anonymized: usize,
+}
+
+/// First pass of `process_files`: loads every file, applies `BodyTransforms`,
+/// and renders headers and the other per-file blocks.
+fn load_files(
+    files: &[PathBuf],
+    config: &GrabConfig,
+    repo_root: Option<&Path>,
+    target_path: &Path,
+    sections: &Sections,
+    on_progress: &mut dyn FnMut(GrabProgress),
+) -> GrabResult<LoadedFiles> {
+    let mut transforms = BodyTransforms::new(config)?;
+    let content_excludes = compile_content_excludes(&config.exclude_matching)?;
+    let mut anonymized = 0;
+    let mut savings = SavingsCounter::default();
+    if config.dedupe_normalized {
+        savings.enable(TransformSavings::DEDUPE);
+    }
+    if config.respect_editorconfig {
+        savings.enable(TransformSavings::EDITORCONFIG);
+    }
+    if config.strip_imports {
+        savings.enable(TransformSavings::STRIP_IMPORTS);
+    } else if config.fold_imports {
+        savings.enable(TransformSavings::FOLD_IMPORTS);
+    }
+    if !config.redactions.is_empty() {
+        savings.enable(TransformSavings::REDACT);
+    }
+    let mut pending = Vec::with_capacity(files.len());
+    let mut skipped = Vec::new();
+    let history_repo = repo_root.filter(|_| config.git_history.is_some());
+    if history_repo.is_some() && files.len() > GIT_HISTORY_WARN_FILES {
+        warn!(
+            "--git-history runs one git command per file; {} files may take a while.",
+            files.len()
+        );
+    }
+    let force_added = match repo_root {
+        Some(root) if config.show_force_added && !config.no_git => list_force_added(root),
+        _ => HashSet::new(),
+    };
+    let mut dedupe_stats = config.dedupe_normalized.then(DedupeStats::default);
+    // Normalized content -> display path of its first occurrence.
+    let mut first_seen: HashMap<String, String> = HashMap::new();
+    // Content hash -> index into `duplicates` (`GrabConfig::find_duplicates`).
+    let mut content_hashes: HashMap<String, usize> = HashMap::new();
+    let mut duplicates: Vec<DuplicateGroup> = Vec::new();
+    let mut pdf_texts = extract_pdfs(files, config);
+    let wants_hash = config.hash_headers
+        || [&config.wrap_open, &config.wrap_close]
+            .iter()
+            .any(|wrapper| {
+                wrapper
+                    .as_deref()
+                    .is_some_and(|w| w.contains(HASH_PLACEHOLDER))
+            });
+
+    for (index, file_path) in files.iter().enumerate() {
+        on_progress(GrabProgress {
+            processed: index,
+            total: files.len(),
+        });
         debug!("Processing file content for: {:?}", file_path);
 
         let display_path_result = if !config.no_git {
@@ -53,106 +342,1364 @@ pub(crate) fn process_files(
             file_path.strip_prefix(target_path) // Non-Git mode always strips from target_path
         };
         let display_path = display_path_result.unwrap_or(file_path);
-        let display_path_ref = normalized_path(display_path);
+        let display_path_ref = posix_path(display_path);
+        // What the output shows; `display_path_ref` stays the real path for Git lookups.
+        let (shown_path, replaced) = transforms.anonymizer.apply_counted(&display_path_ref);
+        anonymized += replaced;
+        let display_base = match repo_root {
+            Some(root) if !config.no_git => root,
+            _ => target_path,
+        };
 
-        let file_start = combined_content.len();
-        let mut header_range = None;
-        let body_range;
+        let prefetched = pdf_texts.remove(file_path);
+        let mut encoded = false;
+        let mut loaded = load_file(file_path, config, display_base, prefetched);
+        if !config.anonymize.is_empty() && is_symlink(file_path) {
+            anonymized += anonymize_symlink_target(&mut loaded, &transforms.anonymizer);
+        }
+        let loaded = exclude_by_content(loaded, &content_excludes);
+        if let LoadedFile::Text { text, .. } = &loaded {
+            if config.find_duplicates && !text.is_empty() {
+                let index = *content_hashes
+                    .entry(sha256_hex(text.as_bytes()))
+                    .or_insert_with(|| {
+                        duplicates.push(DuplicateGroup {
+                            paths: Vec::new(),
+                            bytes: text.len(),
+                        });
+                        duplicates.len() - 1
+                    });
+                duplicates[index].paths.push(shown_path.to_string());
+            }
+        }
+        let (text, annotation) = match loaded {
+            LoadedFile::Text {
+                text, annotation, ..
+            } if config.dedupe_normalized => match first_seen.entry(normalize_whitespace(&text)) {
+                Entry::Occupied(first) => {
+                    debug!("{:?} duplicates {}", file_path, first.get());
+                    savings.record(TransformSavings::DEDUPE, text.len(), 0);
+                    if let Some(stats) = dedupe_stats.as_mut() {
+                        stats.files += 1;
+                        stats.bytes_saved += text.len();
+                    }
+                    let note = format!("duplicate of {}, whitespace-insensitive", first.get());
+                    (None, Some(note))
+                }
+                Entry::Vacant(slot) => {
+                    slot.insert(shown_path.to_string());
+                    (Some(text), annotation)
+                }
+            },
+            LoadedFile::Text {
+                text, annotation, ..
+            } => (Some(text), annotation),
+            LoadedFile::HeaderOnly { annotation } => (None, Some(annotation)),
+            LoadedFile::Encoded { text } => {
+                encoded = true;
+                (Some(text), Some("base64".to_string()))
+            }
+            LoadedFile::Skipped(reason) => {
+                skipped.push(SkippedFile {
+                    display_path: shown_path.to_string(),
+                    mime: skip_mime(&reason, file_path),
+                    reason,
+                });
+                continue;
+            }
+        };
 
-        // --- Start PDF Handling ---
-        let is_pdf = file_path
-            .extension()
-            .is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"));
+        // Hashed before any transformation, so it matches the file as read.
+        let hash = if wants_hash && !encoded {
+            text.as_deref().map(|text| short_sha256(text.as_bytes()))
+        } else {
+            None
+        };
 
-        if config.convert_pdf && is_pdf {
-            debug!("Attempting PDF text extraction for: {:?}", file_path);
-            match pdf_extract::extract_text(file_path) {
-                Ok(text) => {
-                    if config.add_headers {
-                        let header =
-                            format!("--- FILE: {} (extracted text) ---\n", display_path_ref);
-                        let start = combined_content.len();
-                        combined_content.push_str(&header);
-                        header_range = Some(start..combined_content.len());
+        let (text, annotation) = match text {
+            Some(text) if config.no_body => {
+                let lines = count_lines(&text);
+                let unit = if lines == 1 { "line" } else { "lines" };
+                let summary = format!("{} bytes, {} {}", text.len(), lines, unit);
+                let annotation = match annotation {
+                    Some(note) => format!("{}, {}", note, summary),
+                    None => summary,
+                };
+                (None, Some(annotation))
+            }
+            text => (text, annotation),
+        };
+
+        let annotation = match annotation {
+            Some(note) if config.readme_first && is_readme(file_path) => {
+                Some(format!("{}, {}", note, DIRECTORY_OVERVIEW))
+            }
+            None if config.readme_first && is_readme(file_path) => {
+                Some(DIRECTORY_OVERVIEW.to_string())
+            }
+            other => other,
+        };
+
+        let is_force_added = force_added.contains(display_path_ref.as_ref());
+        let annotation = match annotation {
+            Some(note) if is_force_added => Some(format!("{}, force-added", note)),
+            None if is_force_added => Some("force-added".to_string()),
+            other => other,
+        };
+
+        let metadata = fs::metadata(long_path(file_path)).ok();
+        let mode = file_mode(metadata.as_ref());
+        let permissions = config
+            .show_mode
+            .then(|| mode_annotation(metadata.as_ref(), mode))
+            .flatten();
+        let annotation = match (annotation, permissions) {
+            (Some(note), Some(permissions)) => Some(format!("{}, {}", note, permissions)),
+            (note, permissions) => note.or(permissions),
+        };
+
+        let (header, label) = if config.add_headers && !config.prefix_lines {
+            let language = config
+                .label_language
+                .then(|| language::language_name(file_path))
+                .flatten();
+            match &config.link_base {
+                Some(base) => (
+                    render_link_header(
+                        base,
+                        &shown_path,
+                        language,
+                        hash.as_deref().filter(|_| config.hash_headers),
+                        annotation.as_deref(),
+                    ),
+                    String::new(),
+                ),
+                None => {
+                    let mut label = shown_path.to_string();
+                    if let Some(language) = language {
+                        label.push_str(&format!(" [{}]", language));
                     }
-                    let body_start = combined_content.len();
-                    combined_content.push_str(&text);
-                    if !text.ends_with('\n') {
-                        combined_content.push('\n');
+                    if let Some(hash) = hash.as_deref().filter(|_| config.hash_headers) {
+                        label.push_str(&format!(" [sha256:{}]", hash));
                     }
-                    combined_content.push('\n');
-                    body_range = body_start..combined_content.len();
-                }
-                Err(e) => {
-                    warn!(
-                        "Failed to extract text from PDF {:?}, skipping content: {}",
-                        file_path, e
-                    );
-                    if config.add_headers {
-                        let header = format!(
-                            "--- FILE: {} (PDF extraction failed) ---\n",
-                            display_path_ref
-                        );
-                        let start = combined_content.len();
-                        combined_content.push_str(&header);
-                        header_range = Some(start..combined_content.len());
+                    if let Some(note) = &annotation {
+                        label.push_str(&format!(" ({})", note));
                     }
-                    let body_start = combined_content.len();
-                    combined_content.push('\n');
-                    body_range = body_start..combined_content.len();
+                    (render_header(config, &label), label)
                 }
             }
         } else {
-            // --- Regular File Handling (only if not handled as PDF) ---
-            match fs::read(file_path) {
-                Ok(bytes) => match String::from_utf8(bytes) {
-                    Ok(content) => {
-                        if config.add_headers {
-                            let header = format!("--- FILE: {} ---\n", display_path_ref);
-                            let start = combined_content.len();
-                            combined_content.push_str(&header);
-                            header_range = Some(start..combined_content.len());
-                        }
-                        let body_start = combined_content.len();
-                        combined_content.push_str(&content);
-                        if !content.ends_with('\n') {
-                            combined_content.push('\n');
-                        }
-                        combined_content.push('\n');
-                        body_range = body_start..combined_content.len();
-                    }
-                    Err(_) => {
-                        info!("Skipping non-UTF8 file: {:?}", file_path);
-                        continue;
-                    }
-                },
-                Err(e) => {
-                    warn!("Skipping file due to read error: {:?} - {}", file_path, e);
-                    continue;
+            (String::new(), String::new())
+        };
+
+        let frontmatter = if config.frontmatter {
+            render_frontmatter(&shown_path, metadata.as_ref())
+        } else {
+            String::new()
+        };
+
+        let history = match (history_repo, config.git_history) {
+            (Some(root), Some(count)) => {
+                render_history(config, root, &display_path_ref, &shown_path, count)
+            }
+            _ => String::new(),
+        };
+
+        let body = match text {
+            Some(text) if !encoded => {
+                Some(transforms.apply(file_path, text, &mut savings, &mut anonymized))
+            }
+            text => text,
+        };
+
+        pending.push(PendingFile {
+            display_path: shown_path.to_string(),
+            group_header: String::new(),
+            header,
+            label,
+            frontmatter,
+            history,
+            body,
+            annotation,
+            force_added: is_force_added,
+            mode,
+            hash,
+            section: sections.index.get(file_path).copied(),
+        });
+    } // End of loop through files
+    on_progress(GrabProgress {
+        processed: files.len(),
+        total: files.len(),
+    });
+
+    if let Some(limit) = config.group_under {
+        pending = group_small_files(pending, config, limit);
+    }
+    if !sections.is_empty() {
+        add_partition_banners(&mut pending, sections);
+    }
+
+    Ok(LoadedFiles {
+        pending,
+        skipped,
+        savings,
+        dedupe_stats,
+        duplicates: duplicates
+            .into_iter()
+            .filter(|group| group.paths.len() > 1)
+            .collect(),
+        anonymized,
+    })
+}
+
+/// Per-file body limits that fit `pending` into `budget` bytes, or `None` if
+/// everything already fits (see `GrabConfig::context_budget`).
+fn budget_limits(
+    pending: &[PendingFile],
+    config: &GrabConfig,
+    budget: usize,
+) -> Option<Vec<usize>> {
+    // Headers, front matter, and the blank separator lines are not trimmable,
+    // nor is the newline closing a cut body.
+    let overhead: usize = pending
+        .iter()
+        .map(|file| {
+            let separators = match (file.body.is_some(), config.tight) {
+                (true, true) => 2,
+                (true, false) => 3,
+                (false, true) => 0,
+                (false, false) => 1,
+            };
+            file.group_header.len()
+                + render_wrapper(config.wrap_open.as_deref(), file).len()
+                + render_wrapper(config.wrap_close.as_deref(), file).len()
+                + file.header.len()
+                + file.frontmatter.len()
+                + file.history.len()
+                + separators
+        })
+        .sum();
+    let bodies: Vec<(&str, &str)> = pending
+        .iter()
+        .map(|file| {
+            (
+                file.display_path.as_str(),
+                file.body.as_deref().unwrap_or(""),
+            )
+        })
+        .collect();
+    let sizes: Vec<usize> = bodies
+        .iter()
+        .map(|(path, body)| budget::rendered_len(body, usize::MAX, line_cost(config, path)))
+        .collect();
+    let floors: Vec<usize> = bodies
+        .iter()
+        .map(|(path, body)| {
+            budget::rendered_len(body, config.budget_min_lines, line_cost(config, path))
+        })
+        .collect();
+    // Once anything is cut, the trailer lists it; reserve room for every
+    // file, with its kept line count at most its total.
+    let trailer: usize = TRUNCATED_TRAILER_HEADING.len()
+        + bodies
+            .iter()
+            .filter(|(_, body)| !body.is_empty())
+            .map(|(path, body)| {
+                let total = count_lines(body).to_string();
+                format!("- {}: kept {} of {} lines\n", path, total, total).len()
+            })
+            .sum::<usize>();
+    let available = budget.saturating_sub(overhead);
+    if sizes.iter().sum::<usize>() <= available {
+        return None;
+    }
+    let available = available.saturating_sub(trailer);
+    match config.budget_strategy {
+        BudgetStrategy::Proportional => budget::proportional_limits(&sizes, &floors, available),
+    }
+}
+
+/// Per-transformation byte deltas, in the order the transformations run.
+#[derive(Default)]
+struct SavingsCounter(Vec<TransformSavings>);
+
+impl SavingsCounter {
+    /// Registers an active transformation so it is reported even if it saved nothing.
+    fn enable(&mut self, name: &'static str) {
+        self.0.push(TransformSavings {
+            name,
+            bytes_saved: 0,
+        });
+    }
+
+    fn record(&mut self, name: &'static str, before: usize, after: usize) {
+        if let Some(entry) = self.0.iter_mut().find(|entry| entry.name == name) {
+            entry.bytes_saved += before as isize - after as isize;
+        }
+    }
+}
+
+/// The content transformations every body goes through, in order:
+/// `.editorconfig` normalization, import stripping or folding, content
+/// anonymization, and redaction. Shared by `process_files`, `measure_files`,
+/// and `mirror_files` so they agree on what a file turns into.
+struct BodyTransforms<'a> {
+    config: &'a GrabConfig,
+    redactor: Redactor,
+    /// Also applied to display paths by the callers.
+    anonymizer: Redactor,
+    editorconfigs: Option<EditorConfigs>,
+}
+
+impl<'a> BodyTransforms<'a> {
+    fn new(config: &'a GrabConfig) -> GrabResult<Self> {
+        Ok(Self {
+            config,
+            redactor: Redactor::new(&config.redactions)?,
+            anonymizer: Redactor::new(&config.anonymize)?,
+            editorconfigs: config.respect_editorconfig.then(EditorConfigs::default),
+        })
+    }
+
+    /// Transforms the body of `file_path`, recording each step in `savings`
+    /// and counting anonymized matches into `anonymized`.
+    fn apply(
+        &mut self,
+        file_path: &Path,
+        text: String,
+        savings: &mut SavingsCounter,
+        anonymized: &mut usize,
+    ) -> String {
+        let text = match self.editorconfigs.as_mut() {
+            Some(configs) => {
+                let normalized = configs
+                    .normalization_for(file_path)
+                    .apply(&text)
+                    .into_owned();
+                savings.record(TransformSavings::EDITORCONFIG, text.len(), normalized.len());
+                normalized
+            }
+            None => text,
+        };
+        let text = if self.config.strip_imports {
+            let stripped = imports::strip_imports(file_path, &text).into_owned();
+            savings.record(TransformSavings::STRIP_IMPORTS, text.len(), stripped.len());
+            stripped
+        } else if self.config.fold_imports {
+            let folded = imports::fold_imports(file_path, &text).into_owned();
+            savings.record(TransformSavings::FOLD_IMPORTS, text.len(), folded.len());
+            folded
+        } else {
+            text
+        };
+        let text = if self.config.anonymize_content {
+            let (replaced_text, replaced) = self.anonymizer.apply_counted(&text);
+            *anonymized += replaced;
+            replaced_text.into_owned()
+        } else {
+            text
+        };
+        let redacted = self.redactor.apply(&text).into_owned();
+        savings.record(TransformSavings::REDACT, text.len(), redacted.len());
+        redacted
+    }
+}
+
+/// Loads `files` like the first pass of `process_files` and fits them into
+/// `budget` the same way, then keeps only each body's size, so no output is
+/// assembled. Files without a body (header-only entries, `no_body`) measure as
+/// empty.
+pub(crate) fn measure_files(
+    files: &[PathBuf],
+    config: &GrabConfig,
+    repo_root: Option<&Path>,
+    target_path: &Path,
+    budget: Option<usize>,
+    sections: &Sections,
+) -> GrabResult<ContentMeasure> {
+    debug!("Measuring {} files", files.len());
+    let loaded = load_files(files, config, repo_root, target_path, sections, &mut |_| {})?;
+    let limits = budget.and_then(|budget| budget_limits(&loaded.pending, config, budget));
+    let files = loaded
+        .pending
+        .into_iter()
+        .enumerate()
+        .map(|(index, file)| {
+            let full_body = file.body.unwrap_or_default();
+            let body = limits
+                .as_ref()
+                .and_then(|limits| {
+                    budget::truncate_body(
+                        &full_body,
+                        limits[index],
+                        line_cost(config, &file.display_path),
+                    )
+                })
+                .unwrap_or(&full_body);
+            // The text is dropped here; only its counts are kept.
+            MeasuredFile {
+                bytes: body.len(),
+                chars: body.chars().count(),
+                words: body.split_whitespace().count(),
+                display_path: file.display_path,
+            }
+        })
+        .collect();
+    Ok(ContentMeasure {
+        files,
+        skipped: loaded.skipped,
+    })
+}
+
+/// Writes transformed copies of `files` below `out_dir`, keeping their paths
+/// relative to `base` (anonymized by `GrabConfig::anonymize`). Text goes
+/// through the same loading and content transformations (see `BodyTransforms`)
+/// as `process_files`; text converted from PDFs or extractors is written to
+/// `<name>.txt`. Binary files are copied verbatim if `copy_binaries` is set and
+/// skipped otherwise.
+pub(crate) fn mirror_files(
+    files: &[PathBuf],
+    config: &GrabConfig,
+    base: &Path,
+    out_dir: &Path,
+    copy_binaries: bool,
+) -> GrabResult<MirrorReport> {
+    debug!("Mirroring {} files into {:?}", files.len(), out_dir);
+    let mut transforms = BodyTransforms::new(config)?;
+    // Mirrors report no savings; this only satisfies `BodyTransforms::apply`.
+    let mut savings = SavingsCounter::default();
+    let content_excludes = compile_content_excludes(&config.exclude_matching)?;
+    let mut report = MirrorReport::default();
+    let mut pdf_texts = extract_pdfs(files, config);
+
+    for file_path in files {
+        let Ok(rel_path) = file_path.strip_prefix(base) else {
+            warn!("Skipping {:?}: not below {:?}", file_path, base);
+            continue;
+        };
+        let real_path = posix_path(rel_path);
+        let (display_path, replaced) = transforms.anonymizer.apply_counted(&real_path);
+        let display_path = display_path.into_owned();
+        report.anonymized += replaced;
+        // A replacement may not move the copy out of `out_dir`.
+        let rel_dest: PathBuf = display_path
+            .split('/')
+            .filter(|part| !matches!(*part, "" | "." | ".."))
+            .collect();
+
+        let prefetched = pdf_texts.remove(file_path);
+        let mut loaded = load_file(file_path, config, base, prefetched);
+        if !config.anonymize.is_empty() && is_symlink(file_path) {
+            report.anonymized += anonymize_symlink_target(&mut loaded, &transforms.anonymizer);
+        }
+        let (dest, contents) = match exclude_by_content(loaded, &content_excludes) {
+            LoadedFile::Text {
+                text, converted, ..
+            } => {
+                let mut dest = out_dir.join(&rel_dest);
+                if converted {
+                    dest.as_mut_os_string().push(".txt");
                 }
+                let text = transforms.apply(file_path, text, &mut savings, &mut report.anonymized);
+                (dest, Some(text))
+            }
+            // PDFs that failed to extract and images summarized by metadata are binaries too.
+            LoadedFile::HeaderOnly { .. }
+            | LoadedFile::Encoded { .. }
+            | LoadedFile::Skipped(SkipReason::NonUtf8)
+                if copy_binaries =>
+            {
+                (out_dir.join(&rel_dest), None)
+            }
+            LoadedFile::HeaderOnly { .. } | LoadedFile::Encoded { .. } => {
+                report.skipped.push(SkippedFile {
+                    display_path,
+                    reason: SkipReason::NonUtf8,
+                    mime: crate::mime::sniff(file_path).map(str::to_string),
+                });
+                continue;
             }
+            LoadedFile::Skipped(reason) => {
+                report.skipped.push(SkippedFile {
+                    display_path,
+                    mime: skip_mime(&reason, file_path),
+                    reason,
+                });
+                continue;
+            }
+        };
+
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent).map_err(|e| GrabError::IoError {
+                path: parent.to_path_buf(),
+                source: e,
+            })?;
+        }
+        let result = match &contents {
+            Some(text) => fs::write(&dest, text),
+            None => fs::copy(file_path, &dest).map(|_| ()),
+        };
+        result.map_err(|e| GrabError::IoError {
+            path: dest.clone(),
+            source: e,
+        })?;
+        if contents.is_some() {
+            report.written += 1;
+        } else {
+            report.copied += 1;
         }
+    }
 
-        let full_end = combined_content.len();
-        segments.push(FileSegment {
-            display_path: display_path_ref.to_string(),
-            full_range: file_start..full_end,
-            header_range,
-            body_range,
+    Ok(report)
+}
+
+/// Emits the files of every directory whose selected direct files are all
+/// smaller than `limit` bytes under one `DIR: dir/` header
+/// (`GrabConfig::group_under`), each introduced by a short `name` separator in
+/// the header style instead of a full `FILE:` header. A group needs at least
+/// two files and takes the position of its first member, so later members move
+/// up; other files keep their order. Plain headers only: link headers and
+/// `prefix_lines` output are returned unchanged.
+fn group_small_files(
+    pending: Vec<PendingFile>,
+    config: &GrabConfig,
+    limit: usize,
+) -> Vec<PendingFile> {
+    if !config.add_headers || config.prefix_lines || config.link_base.is_some() {
+        return pending;
+    }
+    let dir_of = |file: &PendingFile| -> String {
+        let path = &file.display_path;
+        path.rfind('/').map_or("", |end| &path[..=end]).to_string()
+    };
+    let mut members: HashMap<String, Vec<usize>> = HashMap::new();
+    for (index, file) in pending.iter().enumerate() {
+        members.entry(dir_of(file)).or_default().push(index);
+    }
+    members.retain(|_, indices| {
+        indices.len() >= 2
+            && indices
+                .iter()
+                .all(|&index| pending[index].body.as_ref().map_or(0, String::len) < limit)
+    });
+    if members.is_empty() {
+        return pending;
+    }
+
+    let style = &config.header_style;
+    let mut slots: Vec<Option<PendingFile>> = pending.into_iter().map(Some).collect();
+    let mut ordered = Vec::with_capacity(slots.len());
+    for index in 0..slots.len() {
+        let Some(file) = slots[index].take() else {
+            continue;
+        };
+        let dir = dir_of(&file);
+        let Some(indices) = members.get(&dir) else {
+            ordered.push(file);
+            continue;
+        };
+        slots[index] = Some(file);
+        let shown_dir = if dir.is_empty() { "./" } else { dir.as_str() };
+        for (position, &member_index) in indices.iter().enumerate() {
+            let Some(mut member) = slots[member_index].take() else {
+                continue;
+            };
+            if position == 0 {
+                member.group_header =
+                    format!("{}DIR: {}{}\n", style.prefix(), shown_dir, style.suffix());
+            }
+            let name = member.label.strip_prefix(&dir).unwrap_or(&member.label);
+            member.header = format!("{}{}{}\n", style.prefix(), name, style.suffix());
+            ordered.push(member);
+        }
+    }
+    ordered
+}
+
+/// Prefixes the first file of each `GrabConfig::partitions` section with the
+/// section's `===== Label =====` banner and a blank line.
+fn add_partition_banners(pending: &mut [PendingFile], sections: &Sections) {
+    let mut current = None;
+    for file in pending.iter_mut() {
+        if file.section == current {
+            continue;
+        }
+        current = file.section;
+        let Some(index) = current else {
+            continue;
+        };
+        file.group_header = format!(
+            "{}\n{}",
+            crate::ordering::partition_banner(sections.label(index)),
+            file.group_header
+        );
+    }
+}
+
+/// Permission bits of a file as `GrabbedFile::mode` reports them.
+fn file_mode(metadata: Option<&fs::Metadata>) -> Option<u32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.map(|meta| meta.permissions().mode() & 0o7777)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+/// The `GrabConfig::show_mode` header note: `mode 755` where a Unix mode is
+/// known, otherwise `read-only` for read-only files.
+fn mode_annotation(metadata: Option<&fs::Metadata>, mode: Option<u32>) -> Option<String> {
+    match mode {
+        Some(mode) => Some(format!("mode {:o}", mode)),
+        None => metadata
+            .filter(|meta| meta.permissions().readonly())
+            .map(|_| "read-only".to_string()),
+    }
+}
+
+/// Bytes `push_prefixed_lines` adds in front of each line of a file (none
+/// without `prefix_lines`), for fitting bodies into `context_budget`.
+fn line_cost<'a>(config: &GrabConfig, display_path: &'a str) -> impl Fn(usize) -> usize + 'a {
+    let prefix_lines = config.prefix_lines;
+    move |index| {
+        if prefix_lines {
+            format!("{}:{}: ", display_path, index + 1).len()
+        } else {
+            0
+        }
+    }
+}
+
+/// Appends `body` with every line prefixed by `path:linenum: ` (1-based), like
+/// `grep -rn` output.
+fn push_prefixed_lines(out: &mut String, display_path: &str, body: &str) {
+    for (index, line) in body.split_inclusive('\n').enumerate() {
+        out.push_str(&format!("{}:{}: ", display_path, index + 1));
+        out.push_str(line);
+    }
+}
+
+/// Number of lines in `text`; a final line without a trailing newline still counts.
+fn count_lines(text: &str) -> usize {
+    text.matches('\n').count() + usize::from(!text.ends_with('\n') && !text.is_empty())
+}
+
+/// Reads one file, running a matching extractor or extracting PDF text or
+/// image metadata when configured. `base` is the root display paths are
+/// relative to. `prefetched` is this file's PDF text from `extract_pdfs`, if any.
+fn load_file(
+    file_path: &Path,
+    config: &GrabConfig,
+    base: &Path,
+    prefetched: Option<PdfText>,
+) -> LoadedFile {
+    // Re-stat instead of trusting the listing: on a busy tree the file may have
+    // been removed since, and extractors would otherwise report a misleading failure.
+    // `symlink_metadata` so that a link is seen as a link, not as its target.
+    let io_path = long_path(file_path);
+    match fs::symlink_metadata(&io_path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            info!(
+                "Skipping file that disappeared during the run: {:?}",
+                file_path
+            );
+            return LoadedFile::Skipped(SkipReason::DisappearedDuringRun);
+        }
+        Ok(metadata) if metadata.file_type().is_symlink() => {
+            if let Some(loaded) = load_symlink(file_path, config, base) {
+                return loaded;
+            }
+        }
+        _ => {}
+    }
+
+    if let Some(extractor) = config.extractors.iter().find(|e| e.matches(file_path)) {
+        if let Some(text) = run_extractor(extractor, file_path) {
+            return LoadedFile::Text {
+                text,
+                annotation: Some(format!("via {}", extractor.name())),
+                converted: true,
+            };
+        }
+    }
+
+    if config.convert_pdf && is_pdf(file_path) {
+        if pdf_too_large(&io_path, config) {
+            info!(
+                "Skipping PDF extraction for {:?}: larger than --max-pdf-bytes",
+                file_path
+            );
+            return LoadedFile::HeaderOnly {
+                annotation: "PDF too large, skipped".to_string(),
+            };
+        }
+        let extracted = prefetched.unwrap_or_else(|| {
+            debug!("Attempting PDF text extraction for: {:?}", file_path);
+            extract_pdf_text(&io_path, config.pdf_max_pages).map_err(|e| e.to_string())
         });
-    } // End of loop through files
+        return match extracted {
+            Ok((text, cut)) => LoadedFile::Text {
+                text,
+                annotation: Some(match cut {
+                    Some((kept, total)) => {
+                        format!("extracted text, first {} of {} pages", kept, total)
+                    }
+                    None => "extracted text".to_string(),
+                }),
+                converted: true,
+            },
+            Err(e) => {
+                warn!(
+                    "Failed to extract text from PDF {:?}, skipping content: {}",
+                    file_path, e
+                );
+                LoadedFile::HeaderOnly {
+                    annotation: "PDF extraction failed".to_string(),
+                }
+            }
+        };
+    }
 
-    Ok(ProcessedFiles {
-        content: combined_content,
-        files: segments,
+    if config.image_metadata {
+        if let Some(summary) = describe_image(&io_path) {
+            return LoadedFile::HeaderOnly {
+                annotation: format!("image: {}", summary),
+            };
+        }
+    }
+
+    // --- Regular File Handling (only if not handled as PDF) ---
+    match retry_transient(config.read_retries, || fs::read(&io_path)) {
+        Ok(bytes) if has_utf16_bom(&bytes) => match decode_utf16_with_bom(&bytes) {
+            Some(text) => LoadedFile::Text {
+                text,
+                annotation: Some("decoded from utf-16".to_string()),
+                converted: false,
+            },
+            None if config.binary_mode == BinaryMode::Base64 => LoadedFile::Encoded {
+                text: encode_base64_lines(&bytes),
+            },
+            None => {
+                info!("Skipping malformed UTF-16 file: {:?}", file_path);
+                LoadedFile::Skipped(SkipReason::NonUtf8)
+            }
+        },
+        Ok(bytes) => match String::from_utf8(bytes) {
+            Ok(text) => LoadedFile::Text {
+                text,
+                annotation: None,
+                converted: false,
+            },
+            Err(e) if config.binary_mode == BinaryMode::Base64 => LoadedFile::Encoded {
+                text: encode_base64_lines(e.as_bytes()),
+            },
+            Err(_) => {
+                info!("Skipping non-UTF8 file: {:?}", file_path);
+                LoadedFile::Skipped(SkipReason::NonUtf8)
+            }
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            info!(
+                "Skipping file that disappeared during the run: {:?}",
+                file_path
+            );
+            LoadedFile::Skipped(SkipReason::DisappearedDuringRun)
+        }
+        Err(e) => {
+            warn!("Skipping file due to read error: {:?} - {}", file_path, e);
+            LoadedFile::Skipped(SkipReason::ReadError(e.to_string()))
+        }
+    }
+}
+
+/// Base delay between read retries; attempt N waits N times this long.
+const READ_RETRY_DELAY_MS: u64 = 50;
+
+/// Runs `read`, retrying up to `retries` times (with a short, growing backoff)
+/// while it fails with a transient error. Any other error, or the last
+/// transient one, is returned as is.
+pub(crate) fn retry_transient<T>(
+    retries: usize,
+    mut read: impl FnMut() -> io::Result<T>,
+) -> io::Result<T> {
+    let mut attempt = 0;
+    loop {
+        match read() {
+            Err(e) if attempt < retries && is_transient(&e) => {
+                attempt += 1;
+                debug!(
+                    "Transient read error ({}); retry {} of {}",
+                    e, attempt, retries
+                );
+                thread::sleep(Duration::from_millis(READ_RETRY_DELAY_MS * attempt as u64));
+            }
+            result => return result,
+        }
+    }
+}
+
+fn is_transient(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+/// A `wrap_open`/`wrap_close` line with `{path}` substituted, or an empty
+/// string when no wrapper is configured.
+/// Binary skips are classified by MIME type so the skip report says what they are.
+fn skip_mime(reason: &SkipReason, file_path: &Path) -> Option<String> {
+    match reason {
+        SkipReason::NonUtf8 => crate::mime::sniff(file_path).map(str::to_string),
+        _ => None,
+    }
+}
+
+fn render_wrapper(template: Option<&str>, file: &PendingFile) -> String {
+    template.map_or_else(String::new, |template| {
+        format!(
+            "{}\n",
+            template
+                .replace("{path}", &file.display_path)
+                .replace(HASH_PLACEHOLDER, file.hash.as_deref().unwrap_or(""))
+        )
+    })
+}
+
+fn compile_content_excludes(patterns: &[String]) -> GrabResult<Vec<Regex>> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Regex::new(pattern).map_err(|source| GrabError::InvalidContentPattern {
+                pattern: pattern.clone(),
+                source,
+            })
+        })
+        .collect()
+}
+
+/// Turns a loaded text file whose content matches one of `excludes`
+/// (`GrabConfig::exclude_matching`) into a `ContentExcluded` skip.
+fn exclude_by_content(loaded: LoadedFile, excludes: &[Regex]) -> LoadedFile {
+    if let LoadedFile::Text { text, .. } = &loaded {
+        if let Some(regex) = excludes.iter().find(|regex| regex.is_match(text)) {
+            debug!("Excluding file whose content matches {:?}", regex.as_str());
+            return LoadedFile::Skipped(SkipReason::ContentExcluded {
+                pattern: regex.as_str().to_string(),
+            });
+        }
+    }
+    loaded
+}
+
+/// Line length of `BinaryMode::Base64` bodies, as in MIME.
+const BASE64_LINE_LEN: usize = 76;
+
+/// Standard (padded) base64 of `bytes`, split into `BASE64_LINE_LEN`-character
+/// lines that each end in a newline.
+fn encode_base64_lines(bytes: &[u8]) -> String {
+    let encoded = BASE64_STANDARD.encode(bytes);
+    let mut text = String::with_capacity(encoded.len() + encoded.len() / BASE64_LINE_LEN + 1);
+    // Base64 output is ASCII, so byte chunks are valid line boundaries.
+    for line in encoded.as_bytes().chunks(BASE64_LINE_LEN) {
+        text.push_str(std::str::from_utf8(line).unwrap_or_default());
+        text.push('\n');
+    }
+    text
+}
+
+fn is_pdf(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"))
+}
+
+/// Extracts the PDFs among `files` ahead of the sequential pass, on up to
+/// `GrabConfig::pdf_jobs` threads (default: the available parallelism). Each
+/// worker pulls the next unclaimed file, so one slow document does not hold up
+/// the others; results are keyed by path and consumed in listing order.
+/// Returns an empty map when there is nothing to run in parallel.
+fn extract_pdfs(files: &[PathBuf], config: &GrabConfig) -> HashMap<PathBuf, PdfText> {
+    if !config.convert_pdf {
+        return HashMap::new();
+    }
+    // Extractors take precedence, and symlinks are resolved by `load_file`.
+    let pdfs: Vec<&PathBuf> = files
+        .iter()
+        .filter(|path| is_pdf(path))
+        .filter(|path| !config.extractors.iter().any(|e| e.matches(path)))
+        .filter(|path| fs::symlink_metadata(long_path(path)).is_ok_and(|m| m.is_file()))
+        .filter(|path| !pdf_too_large(&long_path(path), config))
+        .collect();
+    let jobs = config
+        .pdf_jobs
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, usize::from))
+        .min(pdfs.len());
+    if jobs < 2 {
+        return HashMap::new();
+    }
+    debug!("Extracting {} PDFs on {} threads", pdfs.len(), jobs);
+
+    let next = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut extracted = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(&path) = pdfs.get(index) else {
+                            break;
+                        };
+                        debug!("Attempting PDF text extraction for: {:?}", path);
+                        let text = extract_pdf_text(&long_path(path), config.pdf_max_pages)
+                            .map_err(|e| e.to_string());
+                        extracted.push((path.clone(), text));
+                    }
+                    extracted
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| match worker.join() {
+                Ok(extracted) => extracted,
+                Err(panic) => std::panic::resume_unwind(panic),
+            })
+            .collect()
     })
 }
 
-fn normalized_path(path: &Path) -> Cow<'_, str> {
-    let raw = path.to_string_lossy();
-    if std::path::MAIN_SEPARATOR == '\\' && raw.contains('\\') {
-        Cow::Owned(raw.replace('\\', "/"))
+/// True if the PDF at `path` is over `GrabConfig::pdf_max_bytes` on disk, so
+/// extraction is not even started. Unreadable metadata counts as within limits.
+fn pdf_too_large(path: &Path, config: &GrabConfig) -> bool {
+    config
+        .pdf_max_bytes
+        .is_some_and(|limit| fs::metadata(path).is_ok_and(|meta| meta.len() > limit))
+}
+
+/// Extracts the text of the PDF at `path`. With a non-zero `max_pages`, only
+/// the first N pages are rendered, and `Some((N, total))` is returned alongside
+/// the text when later pages were left out.
+fn extract_pdf_text(
+    path: &Path,
+    max_pages: Option<usize>,
+) -> Result<(String, Option<(usize, usize)>), pdf_extract::OutputError> {
+    let Some(limit) = max_pages.filter(|&limit| limit > 0) else {
+        return pdf_extract::extract_text(path).map(|text| (text, None));
+    };
+    let mut doc = pdf_extract::Document::load(path)?;
+    if doc.is_encrypted() {
+        doc.decrypt("")?;
+    }
+    let pages = doc.get_pages();
+    let mut text = String::new();
+    {
+        let mut output = pdf_extract::PlainTextOutput::new(&mut text);
+        for &page_num in pages.keys().take(limit) {
+            pdf_extract::output_doc_page(&doc, &mut output, page_num)?;
+        }
+    }
+    let cut = (pages.len() > limit).then_some((limit, pages.len()));
+    Ok((text, cut))
+}
+
+/// Handles a symlink that was selected as a file. Unless `follow_symlinks` is
+/// set, the link's target path becomes the body, so nothing outside the tree is
+/// read by accident. Returns `None` to read the target like a regular file.
+fn load_symlink(file_path: &Path, config: &GrabConfig, base: &Path) -> Option<LoadedFile> {
+    let target = match fs::read_link(file_path) {
+        Ok(target) => target,
+        Err(e) => {
+            warn!("Skipping unreadable symlink {:?}: {}", file_path, e);
+            return Some(LoadedFile::Skipped(SkipReason::ReadError(e.to_string())));
+        }
+    };
+    let target_display = if config.relativize_symlink_targets {
+        relativize_symlink_target(file_path, &target, base)
     } else {
-        raw
+        posix_path(&target).into_owned()
+    };
+
+    if !config.follow_symlinks {
+        debug!("Not following symlink {:?} -> {:?}", file_path, target);
+        return Some(LoadedFile::Text {
+            text: format!("{}\n", target_display),
+            annotation: Some(format!("symlink -> {}", target_display)),
+            converted: false,
+        });
+    }
+    if fs::metadata(file_path).is_err() {
+        warn!("Skipping broken symlink {:?} -> {:?}", file_path, target);
+        return Some(LoadedFile::Skipped(SkipReason::ReadError(format!(
+            "broken symlink -> {}",
+            target_display
+        ))));
+    }
+    None
+}
+
+fn is_symlink(file_path: &Path) -> bool {
+    fs::symlink_metadata(long_path(file_path)).is_ok_and(|meta| meta.file_type().is_symlink())
+}
+
+/// Runs the `anonymize` rules over the target path `load_symlink` put in a
+/// symlink's body, header annotation, or broken-link skip reason. Returns the
+/// replacements made; the target counts once, like a display path.
+fn anonymize_symlink_target(loaded: &mut LoadedFile, anonymizer: &Redactor) -> usize {
+    match loaded {
+        LoadedFile::Text {
+            text,
+            annotation: Some(annotation),
+            ..
+        } => {
+            let (rewritten, replaced) = anonymizer.apply_counted(annotation);
+            *annotation = rewritten.into_owned();
+            *text = anonymizer.apply(text).into_owned();
+            replaced
+        }
+        LoadedFile::Skipped(SkipReason::ReadError(reason)) => {
+            let (rewritten, replaced) = anonymizer.apply_counted(reason);
+            *reason = rewritten.into_owned();
+            replaced
+        }
+        _ => 0,
+    }
+}
+
+/// Renders the `HISTORY:` block for `display_path` (relative to `repo_root`),
+/// labelled with `shown_path` (the possibly anonymized path):
+/// one `<hash> <subject>` line per commit, then a blank separator line (unless
+/// `GrabConfig::tight` is set). Returns
+/// an empty string for files without history, e.g. untracked ones.
+fn render_history(
+    config: &GrabConfig,
+    repo_root: &Path,
+    display_path: &str,
+    shown_path: &str,
+    count: usize,
+) -> String {
+    let count_arg = count.to_string();
+    // A literal pathspec, so names like `[ab].txt` do not match other files.
+    let pathspec = format!(":(literal){}", display_path);
+    let args = ["log", "-n", &count_arg, "--format=%h %s", "--", &pathspec];
+    let subjects = match run_command("git", &args, repo_root) {
+        Ok(output) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).into_owned()
+        }
+        Ok(output) => {
+            warn!(
+                "git log failed for {}: {}",
+                display_path,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            return String::new();
+        }
+        Err(e) => {
+            warn!("Could not read git history for {}: {}", display_path, e);
+            return String::new();
+        }
+    };
+    if subjects.trim().is_empty() {
+        return String::new();
+    }
+    let style = &config.header_style;
+    let mut block = format!(
+        "{}HISTORY: {}{}\n",
+        style.prefix(),
+        shown_path,
+        style.suffix()
+    );
+    block.push_str(subjects.trim_end());
+    block.push_str(if config.tight { "\n" } else { "\n\n" });
+    block
+}
+
+/// Lists tracked files under `repo_root` that match an ignore rule, i.e. the
+/// ones `git check-ignore --no-index` would flag, as repo-relative paths.
+/// Failures are logged and yield an empty set.
+fn list_force_added(repo_root: &Path) -> HashSet<String> {
+    let args = [
+        "ls-files",
+        "-z",
+        "--cached",
+        "--ignored",
+        "--exclude-standard",
+    ];
+    match run_command("git", &args, repo_root) {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .split('\0')
+            .filter(|path| !path.is_empty())
+            .map(str::to_string)
+            .collect(),
+        Ok(output) => {
+            warn!(
+                "Could not list force-added files: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            HashSet::new()
+        }
+        Err(e) => {
+            warn!("Could not list force-added files: {}", e);
+            HashSet::new()
+        }
+    }
+}
+
+/// Dedupe key for `dedupe_normalized`: line endings unified to `\n`, trailing
+/// whitespace stripped from every line, and trailing blank lines dropped.
+fn normalize_whitespace(text: &str) -> String {
+    let unified = text.replace("\r\n", "\n").replace('\r', "\n");
+    let mut normalized = String::with_capacity(unified.len());
+    for line in unified.split('\n') {
+        normalized.push_str(line.trim_end());
+        normalized.push('\n');
+    }
+    let kept = normalized.trim_end_matches('\n').len();
+    normalized.truncate(kept);
+    normalized
+}
+
+/// Displays a symlink target relative to `base` when it resolves inside it,
+/// and as a normalized absolute path otherwise. Resolution is lexical, so a
+/// target that is itself a symlink is not followed.
+fn relativize_symlink_target(link: &Path, target: &Path, base: &Path) -> String {
+    let link_dir = link.parent().unwrap_or(base);
+    let resolved = listing::normalize_lexically(&link_dir.join(target));
+    match resolved.strip_prefix(base) {
+        Ok(rel) if !rel.as_os_str().is_empty() => posix_path(rel).into_owned(),
+        _ => posix_path(&resolved).into_owned(),
+    }
+}
+
+fn has_utf16_bom(bytes: &[u8]) -> bool {
+    bytes.starts_with(&[0xFF, 0xFE]) || bytes.starts_with(&[0xFE, 0xFF])
+}
+
+/// Decodes UTF-16 text that starts with a byte-order mark (`FF FE` for
+/// little-endian, `FE FF` for big-endian). The BOM is dropped. Returns `None`
+/// for an odd byte count or unpaired surrogates.
+fn decode_utf16_with_bom(bytes: &[u8]) -> Option<String> {
+    let (units, little_endian) = match bytes {
+        [0xFF, 0xFE, rest @ ..] => (rest, true),
+        [0xFE, 0xFF, rest @ ..] => (rest, false),
+        _ => return None,
+    };
+    if units.len() % 2 != 0 {
+        return None;
+    }
+    let code_units = units.chunks_exact(2).map(|pair| {
+        let pair = [pair[0], pair[1]];
+        if little_endian {
+            u16::from_le_bytes(pair)
+        } else {
+            u16::from_be_bytes(pair)
+        }
+    });
+    char::decode_utf16(code_units)
+        .collect::<Result<String, _>>()
+        .ok()
+}
+
+/// Renders the YAML front-matter block emitted before a body when
+/// `GrabConfig::frontmatter` is set. Size and mtime are omitted if the file
+/// can no longer be stat'ed.
+fn render_frontmatter(display_path: &str, metadata: Option<&fs::Metadata>) -> String {
+    let mut block = format!("---\npath: {}\n", yaml_quote(display_path));
+    if let Some(metadata) = metadata {
+        block.push_str(&format!("bytes: {}\n", metadata.len()));
+        if let Ok(modified) = metadata.modified() {
+            block.push_str(&format!("modified: {}\n", format_rfc3339_utc(modified)));
+        }
+    }
+    block.push_str("---\n");
+    block
+}
+
+/// Double-quotes `value` as a YAML scalar so paths with `:` or `#` stay intact,
+/// escaping control characters so a name with a newline stays on one line.
+fn yaml_quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            // C0, DEL, and C1 controls all fit in `\xNN`.
+            ch if ch.is_control() => quoted.push_str(&format!("\\x{:02X}", u32::from(ch))),
+            // Line and paragraph separators are line breaks to YAML 1.1 readers.
+            '\u{2028}' | '\u{2029}' => quoted.push_str(&format!("\\u{:04X}", u32::from(ch))),
+            _ => quoted.push(ch),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Runs `extractor` on `file_path` and returns its stdout, or `None` (after
+/// logging a warning) if the command could not be run or exited unsuccessfully.
+fn run_extractor(extractor: &Extractor, file_path: &Path) -> Option<String> {
+    let (program, args) = extractor.command.split_first()?;
+    let path_str = file_path.to_string_lossy();
+    let args: Vec<String> = args
+        .iter()
+        .map(|arg| arg.replace(Extractor::PATH_PLACEHOLDER, &path_str))
+        .collect();
+    let arg_refs: Vec<&str> = args.iter().map(String::as_str).collect();
+    let current_dir = file_path.parent().unwrap_or(Path::new("."));
+
+    match run_command(program, &arg_refs, current_dir) {
+        Ok(output) if output.status.success() => {
+            Some(String::from_utf8_lossy(&output.stdout).into_owned())
+        }
+        Ok(output) => {
+            warn!(
+                "Extractor '{}' failed for {:?} ({}); using raw content. stderr: {}",
+                extractor.name(),
+                file_path,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            None
+        }
+        Err(e) => {
+            warn!(
+                "Could not run extractor '{}' for {:?}; using raw content: {}",
+                extractor.name(),
+                file_path,
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Returns e.g. `1920x1080 PNG` for files with a recognized image extension, or
+/// `None` if the extension is not an image or the header cannot be read.
+fn describe_image(file_path: &Path) -> Option<String> {
+    let ext = file_path.extension()?;
+    if !ImageFormat::from_extension(ext).is_some_and(|format| format.reading_enabled()) {
+        return None;
+    }
+    debug!("Reading image dimensions for: {:?}", file_path);
+    let result = ImageReader::open(file_path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(image::ImageError::IoError)
+        .and_then(|reader| {
+            let format = reader.format();
+            reader.into_dimensions().map(|dims| (dims, format))
+        });
+    match result {
+        Ok(((width, height), format)) => {
+            let name = format
+                .map(|format| format!("{:?}", format).to_uppercase())
+                .unwrap_or_else(|| "unknown format".to_string());
+            Some(format!("{}x{} {}", width, height, name))
+        }
+        Err(e) => {
+            info!("Could not read image metadata for {:?}: {}", file_path, e);
+            None
+        }
+    }
+}
+
+/// Splits `files` into those to keep and those whose first kilobyte contains a
+/// generated-code marker. Files that cannot be read are kept so the regular
+/// processing path can report them.
+pub(crate) fn partition_generated(
+    files: Vec<PathBuf>,
+    config: &GrabConfig,
+    repo_root: Option<&Path>,
+    target_path: &Path,
+) -> (Vec<PathBuf>, Vec<SkippedFile>) {
+    let markers: Vec<&str> = if config.generated_markers.is_empty() {
+        DEFAULT_GENERATED_MARKERS.to_vec()
+    } else {
+        config
+            .generated_markers
+            .iter()
+            .map(String::as_str)
+            .filter(|m| !m.is_empty())
+            .collect()
+    };
+
+    let mut kept = Vec::with_capacity(files.len());
+    let mut skipped = Vec::new();
+    for file_path in files {
+        match find_generated_marker(&file_path, &markers) {
+            Some(marker) => {
+                info!(
+                    "Skipping generated file {:?} (matched marker '{}')",
+                    file_path, marker
+                );
+                skipped.push(SkippedFile {
+                    display_path: crate::display_path(&file_path, repo_root, target_path),
+                    reason: SkipReason::Generated {
+                        marker: marker.to_string(),
+                    },
+                    mime: None,
+                });
+            }
+            None => kept.push(file_path),
+        }
+    }
+    (kept, skipped)
+}
+
+fn find_generated_marker<'m>(path: &Path, markers: &[&'m str]) -> Option<&'m str> {
+    let mut head = Vec::with_capacity(GENERATED_SNIFF_BYTES as usize);
+    let file = File::open(long_path(path)).ok()?;
+    if let Err(e) = file.take(GENERATED_SNIFF_BYTES).read_to_end(&mut head) {
+        debug!("Could not sniff {:?} for generated markers: {}", path, e);
+        return None;
+    }
+    let head = String::from_utf8_lossy(&head);
+    markers.iter().copied().find(|marker| head.contains(marker))
+}
+
+/// Renders a full header line, including the trailing newline, using the
+/// configured `HeaderStyle`.
+fn render_header(config: &GrabConfig, label: &str) -> String {
+    let mut header = config.header_style.render(label);
+    header.push('\n');
+    header
+}
+
+/// Renders a `link_base` header: `### [path](<base>path)`, followed by the
+/// `[Language]` tag and the annotation in parentheses if there are any.
+fn render_link_header(
+    base: &str,
+    display_path: &str,
+    language: Option<&str>,
+    hash: Option<&str>,
+    annotation: Option<&str>,
+) -> String {
+    let separator = if base.is_empty() || base.ends_with('/') {
+        ""
+    } else {
+        "/"
+    };
+    // Characters that would end the Markdown link destination early.
+    let encoded = display_path
+        .replace(' ', "%20")
+        .replace('(', "%28")
+        .replace(')', "%29");
+    let mut header = format!("### [{}]({}{}{})", display_path, base, separator, encoded);
+    if let Some(language) = language {
+        header.push_str(&format!(" [{}]", language));
+    }
+    if let Some(hash) = hash {
+        header.push_str(&format!(" [sha256:{}]", hash));
+    }
+    if let Some(note) = annotation {
+        header.push_str(&format!(" ({})", note));
     }
+    header.push('\n');
+    header
 }
@@ -0,0 +1,59 @@
+// --- FILE: dirgrab-lib/src/redact.rs ---
+
+use std::borrow::Cow;
+
+use regex::Regex;
+
+use crate::config::RedactionRule;
+use crate::errors::{GrabError, GrabResult};
+
+/// Compiled form of `GrabConfig::redactions`, built once per run.
+pub(crate) struct Redactor {
+    rules: Vec<(Regex, String)>,
+}
+
+impl Redactor {
+    /// Compiles every rule, failing on the first invalid pattern.
+    pub(crate) fn new(rules: &[RedactionRule]) -> GrabResult<Self> {
+        let rules = rules
+            .iter()
+            .map(|rule| Ok((compile(&rule.pattern)?, rule.replacement.clone())))
+            .collect::<GrabResult<Vec<_>>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Applies all rules in order, borrowing `text` when nothing matched.
+    pub(crate) fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        self.apply_counted(text).0
+    }
+
+    /// Like `apply`, also returning the number of matches replaced.
+    pub(crate) fn apply_counted<'a>(&self, text: &'a str) -> (Cow<'a, str>, usize) {
+        let mut result = Cow::Borrowed(text);
+        let mut count = 0;
+        for (regex, replacement) in &self.rules {
+            let matches = regex.find_iter(&result).count();
+            if matches == 0 {
+                continue;
+            }
+            count += matches;
+            if let Cow::Owned(replaced) = regex.replace_all(&result, replacement.as_str()) {
+                result = Cow::Owned(replaced);
+            }
+        }
+        (result, count)
+    }
+}
+
+fn compile(pattern: &str) -> GrabResult<Regex> {
+    Regex::new(pattern).map_err(|source| GrabError::InvalidRedactionPattern {
+        pattern: pattern.to_string(),
+        source,
+    })
+}
+
+/// Checks that `pattern` compiles, so callers can report errors with their own
+/// context (e.g. the line of a rules file) before a run starts.
+pub fn validate_redaction_pattern(pattern: &str) -> GrabResult<()> {
+    compile(pattern).map(|_| ())
+}
@@ -1,33 +1,151 @@
 // --- FILE: dirgrab-lib/src/tree.rs ---
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::path::{Path, PathBuf};
 
 use log::debug;
 
 // Use crate:: errors because errors.rs is a sibling module declared in lib.rs
+use crate::config::TreeStyle;
 use crate::errors::{GrabError, GrabResult};
 
-/// Generates an indented directory tree string from a list of file paths.
+/// A file to show in the directory tree.
+#[derive(Debug, Clone)]
+pub(crate) struct TreeEntry {
+    /// Absolute path of the file.
+    pub path: PathBuf,
+    /// True if the file was listed but filtered out of the grab; rendered with
+    /// an `(excluded)` marker.
+    pub excluded: bool,
+    /// File size in bytes. When set, the file line shows it and each directory
+    /// line shows the subtotal of the sized files beneath it.
+    pub bytes: Option<u64>,
+}
+
+impl TreeEntry {
+    pub fn selected(path: PathBuf) -> Self {
+        Self {
+            path,
+            excluded: false,
+            bytes: None,
+        }
+    }
+
+    pub fn excluded(path: PathBuf) -> Self {
+        Self {
+            path,
+            excluded: true,
+            bytes: None,
+        }
+    }
+
+    pub fn sized(path: PathBuf, bytes: u64) -> Self {
+        Self {
+            path,
+            excluded: false,
+            bytes: Some(bytes),
+        }
+    }
+}
+
+/// Formats a byte count for size annotations, e.g. `512 B` or `1.5 KB`
+/// (binary multiples).
+pub(crate) fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Renders the directory tree in the given `style`; see `generate_indented_tree`
+/// and `generate_compact_tree`. `collapse_chains` only affects the indented style.
+pub(crate) fn generate_tree(
+    entries: &[TreeEntry],
+    base_path: &Path,
+    max_depth: Option<usize>,
+    style: TreeStyle,
+    collapse_chains: bool,
+) -> GrabResult<String> {
+    match style {
+        TreeStyle::Indented => {
+            generate_indented_tree(entries, base_path, max_depth, collapse_chains)
+        }
+        TreeStyle::Compact => generate_compact_tree(entries, base_path, max_depth),
+    }
+}
+
+/// Keeps the first `max_entries` lines of a rendered tree and replaces the rest
+/// with a single `… (M more entries)` line. `None` or `0` leaves it untouched.
+pub(crate) fn truncate_tree(tree: String, max_entries: Option<usize>) -> String {
+    let Some(limit) = max_entries.filter(|&limit| limit > 0) else {
+        return tree;
+    };
+    let total = tree.lines().count();
+    if total <= limit {
+        return tree;
+    }
+    let mut truncated: String = tree
+        .lines()
+        .take(limit)
+        .flat_map(|line| [line, "\n"])
+        .collect();
+    let hidden = total - limit;
+    truncated.push_str(&format!(
+        "… ({} more {})\n",
+        hidden,
+        if hidden == 1 { "entry" } else { "entries" }
+    ));
+    truncated
+}
+
+/// Generates an indented directory tree string from a list of tagged file entries.
 /// Crate-public as it's only called by grab_contents in lib.rs.
 ///
 /// # Arguments
-/// * `files`: A slice of absolute `PathBuf`s representing the files included
-///   after all filtering.
+/// * `entries`: Absolute file paths to display, each tagged as selected or
+///   excluded. Directories are marked excluded only if every file beneath them is.
 /// * `base_path`: The absolute path (repo root or target path) relative to which
 ///   the tree structure should be displayed.
+/// * `max_depth`: If set, nothing deeper than this many path components is
+///   rendered; each directory at the limit gets a single `…/ (K files)` node
+///   counting the files hidden beneath it.
+/// * `collapse_chains`: If true, a directory whose only child is another
+///   directory is merged into that child's line, so `com/example/app/` renders
+///   as one node; the chain splits where a directory has several children.
 ///
 /// # Returns
 /// * `Ok(String)` containing the formatted tree.
 /// * `Err(GrabError::PathStripError)` if path relativization fails.
-pub(crate) fn generate_indented_tree(files: &[PathBuf], base_path: &Path) -> GrabResult<String> {
+pub(crate) fn generate_indented_tree(
+    entries: &[TreeEntry],
+    base_path: &Path,
+    max_depth: Option<usize>,
+    collapse_chains: bool,
+) -> GrabResult<String> {
     debug!("Generating tree relative to {:?}", base_path);
     let mut tree_output = String::new();
-    // Use BTreeSet to store relative paths for automatic sorting and uniqueness.
-    let mut relative_paths = BTreeSet::new();
+    // Use BTreeMap keyed by relative path for automatic sorting and uniqueness;
+    // the value records whether the entry is excluded.
+    let mut relative_paths: BTreeMap<PathBuf, bool> = BTreeMap::new();
+    // Number of hidden files beneath each directory at `max_depth`.
+    let mut collapsed: BTreeMap<PathBuf, usize> = BTreeMap::new();
+    // Size of each sized file, and subtotal of each directory above one.
+    let mut sizes: BTreeMap<PathBuf, u64> = BTreeMap::new();
+    // Bytes of the hidden files beneath each directory at `max_depth`.
+    let mut collapsed_bytes: BTreeMap<PathBuf, u64> = BTreeMap::new();
+    // Paths rendered as directories: ancestors of at least one listed file.
+    let mut directories: BTreeSet<PathBuf> = BTreeSet::new();
 
     // Collect all unique relative paths (files and their parent directories)
-    for file_path in files {
+    for entry in entries.iter().filter(|entry| !is_directory_entry(entry)) {
+        let file_path = &entry.path;
         // Strip the base_path to get the relative path for display
         let rel_path =
             file_path
@@ -38,37 +156,106 @@ pub(crate) fn generate_indented_tree(files: &[PathBuf], base_path: &Path) -> Gra
                     path: file_path.clone(),
                 })?;
 
-        // Add the file itself
-        relative_paths.insert(rel_path.to_path_buf());
+        // Add the file itself, or count it towards its ancestor at the depth limit
+        let rel_path = match max_depth {
+            Some(limit) if limit > 0 && rel_path.components().count() > limit => {
+                let ancestor: PathBuf = rel_path.components().take(limit).collect();
+                *collapsed.entry(ancestor.clone()).or_insert(0) += 1;
+                if let Some(bytes) = entry.bytes {
+                    *collapsed_bytes.entry(ancestor.clone()).or_insert(0) += bytes;
+                }
+                directories.insert(ancestor.clone());
+                ancestor
+            }
+            _ => rel_path.to_path_buf(),
+        };
+        let rel_path = rel_path.as_path();
+        if let Some(bytes) = entry.bytes {
+            // Credits the file (or its collapsed ancestor) and every parent.
+            let mut current = Some(rel_path);
+            while let Some(path) = current.filter(|p| !p.as_os_str().is_empty()) {
+                *sizes.entry(path.to_path_buf()).or_insert(0) += bytes;
+                current = path.parent();
+            }
+        }
+        let slot = relative_paths
+            .entry(rel_path.to_path_buf())
+            .or_insert(entry.excluded);
+        *slot &= entry.excluded;
 
-        // Add all parent directories of the file
+        // Add all parent directories of the file; a directory stays excluded
+        // only while all of its files are.
         let mut current = rel_path;
         while let Some(parent) = current.parent() {
             if parent.as_os_str().is_empty() {
                 break; // Stop at the root "" path
             }
-            relative_paths.insert(parent.to_path_buf());
+            let slot = relative_paths
+                .entry(parent.to_path_buf())
+                .or_insert(entry.excluded);
+            *slot &= entry.excluded;
+            directories.insert(parent.to_path_buf());
             current = parent;
         }
     }
 
+    // Directories merged into their only child's line (`collapse_chains`).
+    let folded = if collapse_chains {
+        single_dir_chains(&relative_paths, &collapsed, &directories)
+    } else {
+        BTreeSet::new()
+    };
+
     // Build the indented string
-    for rel_path in relative_paths {
-        let depth = rel_path.components().count();
-        let indent = "  ".repeat(depth.saturating_sub(1)); // Indent based on depth
+    for (rel_path, excluded) in relative_paths {
+        if folded.contains(&rel_path) {
+            continue;
+        }
+        // Folded ancestors share this line, so they don't add indentation.
+        let depth = rel_path
+            .ancestors()
+            .skip(1)
+            .filter(|p| !p.as_os_str().is_empty() && !folded.contains(*p))
+            .count();
+        let indent = "  ".repeat(depth); // Indent based on depth
 
         if let Some(name) = rel_path.file_name() {
-            // Determine if it's a directory by checking its absolute path type
-            // This requires the original base_path to reconstruct the absolute path.
-            let abs_path = base_path.join(&rel_path);
-            let is_dir = abs_path.is_dir(); // Relies on filesystem access
+            // Only ancestors of listed files are directories, so a branch
+            // without selected (or shown-as-excluded) files never appears.
+            let is_dir = directories.contains(&rel_path);
+            // Prefix the names of folded ancestors, e.g. `com/example/` + `app`.
+            let mut chain = String::new();
+            let mut current = rel_path.parent();
+            while let Some(parent) = current.filter(|p| folded.contains(*p)) {
+                let parent_name = parent.file_name().unwrap_or_default().to_string_lossy();
+                chain.insert_str(0, &format!("{}/", parent_name));
+                current = parent.parent();
+            }
 
+            let size = sizes
+                .get(&rel_path)
+                .map(|bytes| format!(" ({})", format_size(*bytes)))
+                .unwrap_or_default();
             tree_output.push_str(&format!(
-                "{}- {}{}\n",
+                "{}- {}{}{}{}{}\n",
                 indent,
+                chain,
                 name.to_string_lossy(),
-                if is_dir { "/" } else { "" }
+                if is_dir { "/" } else { "" },
+                size,
+                if excluded { " (excluded)" } else { "" }
             ));
+            if let Some(hidden) = collapsed.get(&rel_path) {
+                let noun = if *hidden == 1 { "file" } else { "files" };
+                let hidden_size = collapsed_bytes
+                    .get(&rel_path)
+                    .map(|bytes| format!(", {}", format_size(*bytes)))
+                    .unwrap_or_default();
+                tree_output.push_str(&format!(
+                    "{}  - …/ ({} {}{})\n",
+                    indent, hidden, noun, hidden_size
+                ));
+            }
         } else {
             // This case should generally not happen for file paths unless base_path itself is processed.
             // If base_path represents the root ".", we might see an empty path here.
@@ -83,5 +270,123 @@ pub(crate) fn generate_indented_tree(files: &[PathBuf], base_path: &Path) -> Gra
     Ok(tree_output)
 }
 
-// No specific tests for this module if covered by integration tests in lib.rs
-// (Although unit testing generate_indented_tree could be beneficial later)
+/// True for a listed path that is a directory rather than a file, such as a Git
+/// submodule reported by `git ls-files`. It holds no grabbed content, so the
+/// trees leave it out instead of drawing it as an empty branch.
+fn is_directory_entry(entry: &TreeEntry) -> bool {
+    let is_dir = entry.path.is_dir();
+    if is_dir {
+        debug!("Leaving directory entry {:?} out of the tree", entry.path);
+    }
+    is_dir
+}
+
+/// Directories among `paths` whose only child is itself a directory and that
+/// have no hidden-file node of their own; each is rendered on its child's line.
+fn single_dir_chains(
+    paths: &BTreeMap<PathBuf, bool>,
+    collapsed: &BTreeMap<PathBuf, usize>,
+    directories: &BTreeSet<PathBuf>,
+) -> BTreeSet<PathBuf> {
+    let mut children: BTreeMap<&Path, Vec<&Path>> = BTreeMap::new();
+    for path in paths.keys() {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            children.entry(parent).or_default().push(path);
+        }
+    }
+    children
+        .into_iter()
+        .filter(|(dir, kids)| {
+            kids.len() == 1
+                && !collapsed.contains_key(*dir)
+                && directories.contains(*dir)
+                && directories.contains(kids[0])
+        })
+        .map(|(dir, _)| dir.to_path_buf())
+        .collect()
+}
+
+/// Generates a compact tree with one line per directory that directly holds
+/// files: `src/: lib.rs, main.rs`, sorted by directory and then file name.
+/// Files at the base are listed under `./`. Subdirectories get their own lines
+/// instead of being nested. Excluded files and sizes are annotated like in
+/// `generate_indented_tree`. With `max_depth`, every file deeper than the limit
+/// is counted on its ancestor's line as `…/ (K files)`.
+pub(crate) fn generate_compact_tree(
+    entries: &[TreeEntry],
+    base_path: &Path,
+    max_depth: Option<usize>,
+) -> GrabResult<String> {
+    debug!("Generating compact tree relative to {:?}", base_path);
+    // Directory -> its files (name, excluded, size), sorted by name.
+    let mut directories: BTreeMap<PathBuf, BTreeMap<String, (bool, Option<u64>)>> = BTreeMap::new();
+    // Hidden file count and bytes beneath each directory at `max_depth`.
+    let mut collapsed: BTreeMap<PathBuf, (usize, Option<u64>)> = BTreeMap::new();
+
+    for entry in entries.iter().filter(|entry| !is_directory_entry(entry)) {
+        let rel_path =
+            entry
+                .path
+                .strip_prefix(base_path)
+                .map_err(|_e| GrabError::PathStripError {
+                    prefix: base_path.to_path_buf(),
+                    path: entry.path.clone(),
+                })?;
+        if let Some(limit) = max_depth.filter(|&limit| limit > 0) {
+            if rel_path.components().count() > limit {
+                let ancestor: PathBuf = rel_path.components().take(limit).collect();
+                let (count, bytes) = collapsed.entry(ancestor.clone()).or_insert((0, None));
+                *count += 1;
+                if let Some(size) = entry.bytes {
+                    *bytes = Some(bytes.unwrap_or(0) + size);
+                }
+                directories.entry(ancestor).or_default();
+                continue;
+            }
+        }
+        let parent = rel_path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let name = rel_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let slot = directories
+            .entry(parent)
+            .or_default()
+            .entry(name)
+            .or_insert((entry.excluded, entry.bytes));
+        slot.0 &= entry.excluded;
+    }
+
+    let mut tree_output = String::new();
+    for (dir, files) in directories {
+        let mut items: Vec<String> = files
+            .into_iter()
+            .map(|(name, (excluded, bytes))| {
+                let size = bytes
+                    .map(|bytes| format!(" ({})", format_size(bytes)))
+                    .unwrap_or_default();
+                format!(
+                    "{}{}{}",
+                    name,
+                    size,
+                    if excluded { " (excluded)" } else { "" }
+                )
+            })
+            .collect();
+        if let Some((hidden, bytes)) = collapsed.get(&dir) {
+            let noun = if *hidden == 1 { "file" } else { "files" };
+            let hidden_size = bytes
+                .map(|bytes| format!(", {}", format_size(bytes)))
+                .unwrap_or_default();
+            items.push(format!("…/ ({} {}{})", hidden, noun, hidden_size));
+        }
+        let label = if dir.as_os_str().is_empty() {
+            ".".to_string()
+        } else {
+            crate::utils::posix_path(&dir).into_owned()
+        };
+        tree_output.push_str(&format!("{}/: {}\n", label, items.join(", ")));
+    }
+
+    Ok(tree_output)
+}
@@ -1,24 +1,94 @@
 // --- FILE: dirgrab-lib/src/utils.rs ---
 
+use std::borrow::Cow;
 use std::io; // Needed for io::ErrorKind::NotFound check
 use std::path::Path;
 use std::process::{Command, Output};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use log::{debug, error};
 
 // Use crate::errors because errors.rs is a sibling module declared in lib.rs
 use crate::errors::{GrabError, GrabResult};
 
+/// On Windows, returns `path` with the `\\?\` long-path prefix when it is
+/// absolute and too long for the legacy `MAX_PATH` limit (260 characters), so
+/// deeply nested files (e.g. under `node_modules`) can still be opened. Such
+/// paths skip Win32 normalization, so `.`/`..` and `/` separators are resolved
+/// here first. Other paths, and all paths on other platforms, are returned as is.
+pub(crate) fn long_path(path: &Path) -> Cow<'_, Path> {
+    #[cfg(windows)]
+    {
+        const MAX_PATH: usize = 260;
+        let raw = path.to_string_lossy();
+        if path.is_absolute() && raw.len() >= MAX_PATH && !raw.starts_with(r"\\?\") {
+            let normalized = crate::listing::normalize_lexically(path);
+            let text = normalized.to_string_lossy().replace('/', "\\");
+            let prefixed = match text.strip_prefix(r"\\") {
+                Some(unc) => format!(r"\\?\UNC\{}", unc),
+                None => format!(r"\\?\{}", text),
+            };
+            return Cow::Owned(std::path::PathBuf::from(prefixed));
+        }
+    }
+    Cow::Borrowed(path)
+}
+
+/// Renders `path` with `/` separators on every platform, so headers, trees, and
+/// structured output read the same on Windows as elsewhere.
+pub(crate) fn posix_path(path: &Path) -> Cow<'_, str> {
+    let raw = path.to_string_lossy();
+    if std::path::MAIN_SEPARATOR == '\\' && raw.contains('\\') {
+        Cow::Owned(raw.replace('\\', "/"))
+    } else {
+        raw
+    }
+}
+
+/// Environment variables that make Git operate on another repository, work
+/// tree, or index than the one containing the working directory. Hooks and CI
+/// jobs often export them (a pre-commit hook sets `GIT_INDEX_FILE`, for
+/// example); dirgrab always locates the repository from the target path, so
+/// they are removed from every Git command it runs.
+const GIT_LOCATION_VARS: &[&str] = &[
+    "GIT_DIR",
+    "GIT_WORK_TREE",
+    "GIT_INDEX_FILE",
+    "GIT_COMMON_DIR",
+    "GIT_OBJECT_DIRECTORY",
+    "GIT_ALTERNATE_OBJECT_DIRECTORIES",
+    "GIT_PREFIX",
+];
+
+/// Builds a `git` command that runs in `current_dir` with the
+/// `GIT_LOCATION_VARS` removed, so the repository is the one found from
+/// `current_dir` regardless of the caller's environment.
+pub(crate) fn git_command(current_dir: &Path) -> Command {
+    let mut command = Command::new("git");
+    command.current_dir(current_dir);
+    for var in GIT_LOCATION_VARS {
+        command.env_remove(var);
+    }
+    command
+}
+
 /// Utility function to run an external command and capture its output.
 /// Made crate-public as it's only needed internally by the listing module.
+/// `git` commands are built with `git_command`.
 pub(crate) fn run_command(cmd: &str, args: &[&str], current_dir: &Path) -> GrabResult<Output> {
     debug!(
         "Running command: {} {:?} in directory: {:?}",
         cmd, args, current_dir
     );
-    let output = Command::new(cmd)
+    let mut command = if cmd == "git" {
+        git_command(current_dir)
+    } else {
+        let mut command = Command::new(cmd);
+        command.current_dir(current_dir); // Execute in the specified directory
+        command
+    };
+    let output = command
         .args(args)
-        .current_dir(current_dir) // Execute in the specified directory
         .output()
         // Map I/O errors during execution (like command not found)
         .map_err(|e| {
@@ -41,4 +111,35 @@ pub(crate) fn run_command(cmd: &str, args: &[&str], current_dir: &Path) -> GrabR
     Ok(output)
 }
 
+/// Formats `time` as an RFC 3339 UTC timestamp with second precision,
+/// e.g. `2024-05-01T12:30:00Z`.
+pub(crate) fn format_rfc3339_utc(time: SystemTime) -> String {
+    let secs = match time.duration_since(UNIX_EPOCH) {
+        Ok(after) => after.as_secs() as i64,
+        Err(before) => -(before.duration().as_secs() as i64),
+    };
+    let (days, day_secs) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+
+    // Civil-from-days conversion (proleptic Gregorian calendar).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        day_secs / 3_600,
+        day_secs % 3_600 / 60,
+        day_secs % 60
+    )
+}
+
 // No tests needed specifically for this module if covered by integration tests in lib.rs
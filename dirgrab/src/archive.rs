@@ -0,0 +1,214 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use flate2::read::GzDecoder;
+use log::info;
+use tempfile::TempDir;
+
+/// Most entries an archive may hold, so a crafted archive cannot exhaust
+/// inodes in the temporary directory.
+const MAX_ENTRIES: u64 = 100_000;
+
+/// Most bytes an archive may extract to, so a zip or tar bomb cannot fill the
+/// disk.
+const MAX_EXTRACTED_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+/// Archive formats accepted as a target path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveKind {
+    Zip,
+    TarGz,
+    Tar,
+}
+
+impl ArchiveKind {
+    fn detect(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_ascii_lowercase();
+        if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+/// If `target` is a `.zip`, `.tar.gz`/`.tgz`, or `.tar` file, extracts it into a
+/// fresh temporary directory and returns that directory, which is deleted when
+/// dropped. Returns `None` for anything else (including directories). Entries
+/// that would land outside the directory (`..`, absolute paths) are rejected
+/// by the zip and tar readers. Archives with more than `MAX_ENTRIES` entries or
+/// `MAX_EXTRACTED_BYTES` of content are refused.
+pub fn extract_if_archive(target: &Path) -> Result<Option<TempDir>> {
+    extract_with_limits(target, MAX_ENTRIES, MAX_EXTRACTED_BYTES)
+}
+
+fn extract_with_limits(target: &Path, max_entries: u64, max_bytes: u64) -> Result<Option<TempDir>> {
+    let Some(kind) = ArchiveKind::detect(target).filter(|_| target.is_file()) else {
+        return Ok(None);
+    };
+    let dir = tempfile::Builder::new()
+        .prefix("dirgrab-archive-")
+        .tempdir()
+        .context("Failed to create a temporary directory for the archive")?;
+    let file =
+        File::open(target).with_context(|| format!("Failed to open archive: {:?}", target))?;
+    let mut limits = Limits::new(target, max_entries, max_bytes);
+    match kind {
+        ArchiveKind::Zip => unpack_zip(file, dir.path(), target, &mut limits)?,
+        ArchiveKind::TarGz => unpack_tar(GzDecoder::new(file), dir.path(), target, &mut limits)?,
+        ArchiveKind::Tar => unpack_tar(file, dir.path(), target, &mut limits)?,
+    }
+    info!("Extracted {:?} to {:?}", target, dir.path());
+    Ok(Some(dir))
+}
+
+/// Running entry and byte totals checked against the extraction limits.
+struct Limits<'a> {
+    target: &'a Path,
+    max_entries: u64,
+    max_bytes: u64,
+    entries: u64,
+    bytes: u64,
+}
+
+impl<'a> Limits<'a> {
+    fn new(target: &'a Path, max_entries: u64, max_bytes: u64) -> Self {
+        Self {
+            target,
+            max_entries,
+            max_bytes,
+            entries: 0,
+            bytes: 0,
+        }
+    }
+
+    /// Counts one entry of `size` bytes, failing once a limit is exceeded.
+    fn add(&mut self, size: u64) -> Result<()> {
+        self.entries += 1;
+        self.bytes = self.bytes.saturating_add(size);
+        if self.entries > self.max_entries {
+            bail!(
+                "Archive {:?} has more than {} entries; refusing to extract it",
+                self.target,
+                self.max_entries
+            );
+        }
+        if self.bytes > self.max_bytes {
+            bail!(
+                "Archive {:?} extracts to more than {} bytes; refusing to extract it",
+                self.target,
+                self.max_bytes
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Checks the sizes in the zip's central directory before extracting; the zip
+/// reader never decompresses an entry past its recorded size.
+fn unpack_zip(file: File, dest: &Path, target: &Path, limits: &mut Limits) -> Result<()> {
+    let mut archive = zip::ZipArchive::new(file)
+        .with_context(|| format!("Failed to read zip archive: {:?}", target))?;
+    for index in 0..archive.len() {
+        let entry = archive
+            .by_index_raw(index)
+            .with_context(|| format!("Failed to read zip archive: {:?}", target))?;
+        limits.add(entry.size())?;
+    }
+    archive
+        .extract(dest)
+        .with_context(|| format!("Failed to extract zip archive: {:?}", target))
+}
+
+/// Unpacks entry by entry, checking each header's size before writing it.
+fn unpack_tar(
+    reader: impl io::Read,
+    dest: &Path,
+    target: &Path,
+    limits: &mut Limits,
+) -> Result<()> {
+    let context = || format!("Failed to extract tar archive: {:?}", target);
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries().with_context(context)? {
+        let mut entry = entry.with_context(context)?;
+        limits.add(entry.size())?;
+        entry.unpack_in(dest).with_context(context)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn extracts_zip_and_tar_gz_archives() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+
+        let zip_path = temp.path().join("bundle.ZIP");
+        let mut writer = zip::ZipWriter::new(File::create(&zip_path)?);
+        writer.start_file("src/lib.rs", zip::write::SimpleFileOptions::default())?;
+        writer.write_all(b"pub fn f() {}\n")?;
+        writer.finish()?;
+        let extracted = extract_if_archive(&zip_path)?.context("zip not detected")?;
+        assert_eq!(
+            std::fs::read_to_string(extracted.path().join("src/lib.rs"))?,
+            "pub fn f() {}\n"
+        );
+
+        let tar_path = temp.path().join("bundle.tar.gz");
+        let encoder =
+            flate2::write::GzEncoder::new(File::create(&tar_path)?, flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(2);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, "docs/a.txt", &b"hi"[..])?;
+        builder.into_inner()?.finish()?;
+        let extracted = extract_if_archive(&tar_path)?.context("tar.gz not detected")?;
+        let dir = extracted.path().to_path_buf();
+        assert_eq!(std::fs::read_to_string(dir.join("docs/a.txt"))?, "hi");
+        drop(extracted);
+        assert!(!dir.exists());
+
+        assert!(extract_if_archive(temp.path())?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_archives_over_the_limits() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let zip_path = temp.path().join("bomb.zip");
+        let mut writer = zip::ZipWriter::new(File::create(&zip_path)?);
+        for name in ["a.txt", "b.txt"] {
+            writer.start_file(name, zip::write::SimpleFileOptions::default())?;
+            writer.write_all(&[b'x'; 100])?;
+        }
+        writer.finish()?;
+        assert!(extract_with_limits(&zip_path, 10, 1000)?.is_some());
+        let err = extract_with_limits(&zip_path, 1, 1000).unwrap_err();
+        assert!(err.to_string().contains("more than 1 entries"));
+        let err = extract_with_limits(&zip_path, 10, 150).unwrap_err();
+        assert!(err.to_string().contains("more than 150 bytes"));
+
+        let tar_path = temp.path().join("bomb.tar");
+        let mut builder = tar::Builder::new(File::create(&tar_path)?);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(100);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, "a.txt", &[b'x'; 100][..])?;
+        builder.into_inner()?;
+        let err = extract_with_limits(&tar_path, 10, 50).unwrap_err();
+        assert!(err.to_string().contains("more than 50 bytes"));
+        Ok(())
+    }
+}
@@ -1,35 +1,61 @@
+use std::borrow::Cow;
 use std::collections::HashSet;
 use std::fs;
-use std::path::Path;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
 
 use anyhow::{bail, Context, Result};
 use directories::BaseDirs;
 use log::{debug, warn};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-use dirgrab_lib::{normalize_glob, GrabConfig};
+use dirgrab_lib::{
+    normalize_glob, validate_redaction_pattern, BinaryMode, BudgetStrategy, Collate, Extractor,
+    FileOrder, GrabConfig, HeaderStyle, IncludePattern, Partition, RedactionRule, TreeStyle,
+};
 
+use crate::models::ContextWindow;
 use crate::Cli;
 
 #[derive(Debug, Clone)]
 pub struct RunSettings {
     pub grab_config: GrabConfig,
     pub stats: StatsSettings,
+    pub output: OutputSettings,
 }
 
-#[derive(Debug, Clone)]
+/// Where and how the final output is written, merged from `[output]` config
+/// sections and the CLI.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OutputSettings {
+    /// Output file; `None` means stdout (or the clipboard with `--clipboard`).
+    pub path: Option<PathBuf>,
+    pub format: OutputFormat,
+    pub encoding: OutputEncoding,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StatsSettings {
     pub enabled: bool,
     pub token_ratio: f64,
     pub exclude_tree: bool,
     pub exclude_headers: bool,
     pub reports: Vec<StatsReport>,
+    /// Window the overview reports the grab's fit against (`--model`/`--model-window`).
+    pub context_window: Option<ContextWindow>,
+    /// JSON file the stats are written to instead of stderr (`--stats-output`).
+    pub output: Option<PathBuf>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum StatsReport {
     Overview,
     TopFiles { count: usize },
+    Savings,
+    LongestLines { count: usize },
+    Duplicates,
+    TreeCost,
 }
 
 #[derive(Debug, Clone)]
@@ -59,6 +85,24 @@ pub fn parse_stats_report_spec(raw: &str) -> Result<StatsReportSpec, String> {
         return Ok(StatsReportSpec::Explicit(StatsReport::Overview));
     }
 
+    if raw.eq_ignore_ascii_case("savings") {
+        return Ok(StatsReportSpec::Explicit(StatsReport::Savings));
+    }
+
+    if raw.eq_ignore_ascii_case("duplicates") {
+        return Ok(StatsReportSpec::Explicit(StatsReport::Duplicates));
+    }
+
+    if raw.eq_ignore_ascii_case("tree-cost") {
+        return Ok(StatsReportSpec::Explicit(StatsReport::TreeCost));
+    }
+
+    if raw.eq_ignore_ascii_case("longest-lines") {
+        return Ok(StatsReportSpec::Explicit(StatsReport::LongestLines {
+            count: DEFAULT_TOP_FILES_COUNT,
+        }));
+    }
+
     if let Some((name, value)) = raw.split_once('=') {
         return match name {
             "top-files" => {
@@ -73,6 +117,20 @@ pub fn parse_stats_report_spec(raw: &str) -> Result<StatsReportSpec, String> {
                 }
                 Ok(StatsReportSpec::Explicit(StatsReport::TopFiles { count }))
             }
+            "longest-lines" => {
+                let count = value.parse::<usize>().map_err(|_| {
+                    format!(
+                        "Invalid longest-lines count '{}'. Expected a positive integer.",
+                        value
+                    )
+                })?;
+                if count == 0 {
+                    return Err("longest-lines count must be greater than 0".to_string());
+                }
+                Ok(StatsReportSpec::Explicit(StatsReport::LongestLines {
+                    count,
+                }))
+            }
             other => Err(format!("Unknown stats report '{}'", other)),
         };
     }
@@ -84,76 +142,675 @@ pub fn parse_stats_report_spec(raw: &str) -> Result<StatsReportSpec, String> {
     Err(format!("Unknown stats report '{}'", raw))
 }
 
+pub fn parse_comment_style(raw: &str) -> Result<HeaderStyle, String> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "plain" | "default" => Ok(HeaderStyle::Plain),
+        "rust" | "c" | "slash" => Ok(HeaderStyle::Rust),
+        "python" | "hash" | "shell" => Ok(HeaderStyle::Python),
+        "html" | "xml" => Ok(HeaderStyle::Html),
+        other => Err(format!(
+            "Unknown comment style '{}'. Expected one of: plain, rust, python, html",
+            other
+        )),
+    }
+}
+
+pub fn parse_file_order(raw: &str) -> Result<FileOrder, String> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "path" => Ok(FileOrder::Path),
+        "size" | "by-size" => Ok(FileOrder::Size),
+        "mtime" | "by-mtime" => Ok(FileOrder::Mtime),
+        "ext" | "extension" | "by-ext" => Ok(FileOrder::Ext),
+        "git-recency" => Ok(FileOrder::GitRecency),
+        other => Err(format!(
+            "Unknown sort order '{}'. Expected one of: path, size, mtime, ext, git-recency",
+            other
+        )),
+    }
+}
+
+pub fn parse_binary_mode(raw: &str) -> Result<BinaryMode, String> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "skip" => Ok(BinaryMode::Skip),
+        "base64" => Ok(BinaryMode::Base64),
+        other => Err(format!(
+            "Unknown binary mode '{}'. Expected one of: skip, base64",
+            other
+        )),
+    }
+}
+
+pub fn parse_tree_style(raw: &str) -> Result<TreeStyle, String> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "indented" => Ok(TreeStyle::Indented),
+        "compact" => Ok(TreeStyle::Compact),
+        other => Err(format!(
+            "Unknown tree style '{}'. Expected one of: indented, compact",
+            other
+        )),
+    }
+}
+
+/// Parses a count with an optional decimal `k`/`m` suffix, e.g. `100k` = 100,000.
+pub fn parse_count(raw: &str) -> Result<usize, String> {
+    let trimmed = raw.trim();
+    let lower = trimmed.to_ascii_lowercase();
+    let (digits, multiplier) = if let Some(rest) = lower.strip_suffix('k') {
+        (rest, 1_000)
+    } else if let Some(rest) = lower.strip_suffix('m') {
+        (rest, 1_000_000)
+    } else {
+        (lower.as_str(), 1)
+    };
+    digits
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .and_then(|value| value.checked_mul(multiplier))
+        .ok_or_else(|| {
+            format!(
+                "Invalid count '{}'. Expected a number with an optional k or m suffix",
+                trimmed
+            )
+        })
+}
+
+pub fn parse_budget_strategy(raw: &str) -> Result<BudgetStrategy, String> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "proportional" => Ok(BudgetStrategy::Proportional),
+        other => Err(format!(
+            "Unknown budget strategy '{}'. Expected one of: proportional",
+            other
+        )),
+    }
+}
+
+/// Parses an extractor of the form `EXT=PROGRAM [ARGS...]`, e.g.
+/// `.rst=rst2txt {path}`. If no argument contains `{path}`, the file path is
+/// appended as the last argument.
+pub fn parse_extractor(raw: &str) -> Result<Extractor, String> {
+    let (extension, command) = raw
+        .split_once('=')
+        .ok_or_else(|| format!("Invalid extractor '{}'. Expected EXT=COMMAND", raw))?;
+    let extension = extension
+        .trim()
+        .trim_start_matches('.')
+        .to_ascii_lowercase();
+    if extension.is_empty() {
+        return Err(format!("Extractor '{}' is missing a file extension", raw));
+    }
+    let mut command: Vec<String> = command.split_whitespace().map(String::from).collect();
+    if command.is_empty() {
+        return Err(format!("Extractor '{}' is missing a command", raw));
+    }
+    if !command
+        .iter()
+        .skip(1)
+        .any(|arg| arg.contains(Extractor::PATH_PLACEHOLDER))
+    {
+        command.push(Extractor::PATH_PLACEHOLDER.to_string());
+    }
+    Ok(Extractor { extension, command })
+}
+
+/// Validates a `.gitattributes` attribute name for `--git-attr`.
+pub fn parse_git_attr(raw: &str) -> Result<String, String> {
+    let attr = raw.trim();
+    if attr.is_empty()
+        || attr.starts_with(['-', '!'])
+        || attr.contains(|c: char| c.is_whitespace() || c == '=')
+    {
+        return Err(format!(
+            "Invalid attribute name '{}'. Expected a bare name such as 'license'",
+            raw
+        ));
+    }
+    Ok(attr.to_string())
+}
+
+/// Validates a glob-style MIME pattern (`image/*`, `application/pdf`, `*`) and
+/// lowercases it.
+pub fn parse_mime_pattern(raw: &str) -> Result<String, String> {
+    let pattern = raw.trim().to_ascii_lowercase();
+    let valid = pattern == "*"
+        || matches!(pattern.split_once('/'), Some((kind, sub)) if !kind.is_empty() && !sub.is_empty() && !sub.contains('/'));
+    if valid {
+        Ok(pattern)
+    } else {
+        Err(format!(
+            "Invalid MIME pattern '{}'. Expected TYPE/SUBTYPE, TYPE/*, or *",
+            raw
+        ))
+    }
+}
+
+fn parse_mime_patterns(values: &[String]) -> Result<Vec<String>> {
+    values
+        .iter()
+        .map(|raw| parse_mime_pattern(raw).map_err(anyhow::Error::msg))
+        .collect()
+}
+
+/// Parses a `--collate` key; `ext` (or `extension`) groups files by extension.
+pub fn parse_collate(raw: &str) -> Result<Collate, String> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "ext" | "extension" => Ok(Collate::Extension),
+        other => Err(format!("Unknown collate key '{}'. Expected: ext", other)),
+    }
+}
+
+/// Parses an output section of the form `PATTERN:LABEL`, e.g. `tests/**:Tests`.
+/// The label follows the last colon.
+pub fn parse_partition(raw: &str) -> Result<Partition, String> {
+    let (pattern, label) = raw
+        .rsplit_once(':')
+        .ok_or_else(|| format!("Invalid partition '{}'. Expected PATTERN:LABEL", raw))?;
+    let (pattern, label) = (pattern.trim(), label.trim());
+    if pattern.is_empty() || label.is_empty() {
+        return Err(format!(
+            "Partition '{}' needs both a pattern and a label (PATTERN:LABEL)",
+            raw
+        ));
+    }
+    Ok(Partition {
+        pattern: pattern.to_string(),
+        label: label.to_string(),
+    })
+}
+
+/// Parses an include pattern of the form `PATTERN[:depth=N]`, e.g.
+/// `src/*.rs:depth=2`, expanding brace groups like exclude patterns do.
+fn parse_include_pattern(raw: &str) -> Result<Vec<IncludePattern>> {
+    let (pattern, max_depth) = match raw.rsplit_once(":depth=") {
+        Some((pattern, depth)) => {
+            let depth = depth.trim().parse::<usize>().with_context(|| {
+                format!("Invalid depth '{}' in include pattern '{}'", depth, raw)
+            })?;
+            if depth == 0 {
+                bail!(
+                    "Depth in include pattern '{}' must be greater than 0 (1 is the top level)",
+                    raw
+                );
+            }
+            (pattern.trim(), Some(depth))
+        }
+        None => (raw.trim(), None),
+    };
+    if pattern.is_empty() {
+        bail!("Include pattern '{}' is missing a pattern", raw);
+    }
+    Ok(expand_braces(pattern)
+        .into_iter()
+        .map(|pattern| IncludePattern {
+            pattern: normalize_glob(&pattern),
+            max_depth,
+        })
+        .collect())
+}
+
+/// Parses a redaction rule of the form `PATTERN` or `PATTERN=>REPLACEMENT`.
+/// Without a replacement, matches become `[REDACTED]`.
+pub fn parse_redaction_rule(raw: &str) -> Result<RedactionRule, String> {
+    let (pattern, replacement) = match raw.split_once("=>") {
+        Some((pattern, replacement)) => (pattern.trim_end(), Some(replacement.trim_start())),
+        None => (raw, None),
+    };
+    if pattern.is_empty() {
+        return Err("Redaction pattern must not be empty".to_string());
+    }
+    validate_redaction_pattern(pattern).map_err(|e| e.to_string())?;
+    let mut rule = RedactionRule::new(pattern);
+    if let Some(replacement) = replacement {
+        rule.replacement = replacement.to_string();
+    }
+    Ok(rule)
+}
+
+/// Parses a `--var NAME=VALUE` template variable. The name must be non-empty and
+/// may not be `tree` or `files`, which the template gets from the grab itself.
+pub fn parse_template_var(raw: &str) -> Result<(String, String), String> {
+    let (name, value) = raw
+        .split_once('=')
+        .ok_or_else(|| format!("Expected NAME=VALUE, got '{}'", raw))?;
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Template variable name must not be empty".to_string());
+    }
+    if matches!(name, "tree" | "files") {
+        return Err(format!(
+            "'{}' is filled in by dirgrab and cannot be set",
+            name
+        ));
+    }
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// Byte encoding used when writing the final output to a file or stdout.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputEncoding {
+    /// UTF-8 without a byte-order mark (the default).
+    #[default]
+    Utf8,
+    /// UTF-8 with a leading `EF BB BF` byte-order mark.
+    Utf8Bom,
+    /// UTF-16 little-endian with a `FF FE` byte-order mark.
+    Utf16Le,
+    /// UTF-16 big-endian with a `FE FF` byte-order mark.
+    Utf16Be,
+}
+
+impl OutputEncoding {
+    /// Encodes `text`, prepending a byte-order mark where the encoding uses one.
+    pub fn encode<'a>(&self, text: &'a str) -> Cow<'a, [u8]> {
+        match self {
+            OutputEncoding::Utf8 => Cow::Borrowed(text.as_bytes()),
+            OutputEncoding::Utf8Bom => {
+                let mut bytes = Vec::with_capacity(text.len() + 3);
+                bytes.extend_from_slice(&[0xEF, 0xBB, 0xBF]);
+                bytes.extend_from_slice(text.as_bytes());
+                Cow::Owned(bytes)
+            }
+            OutputEncoding::Utf16Le => Cow::Owned(
+                std::iter::once(0xFEFF)
+                    .chain(text.encode_utf16())
+                    .flat_map(u16::to_le_bytes)
+                    .collect(),
+            ),
+            OutputEncoding::Utf16Be => Cow::Owned(
+                std::iter::once(0xFEFF)
+                    .chain(text.encode_utf16())
+                    .flat_map(u16::to_be_bytes)
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Shape of the final output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Tree, headers, and file contents as plain text (the default).
+    #[default]
+    Text,
+    /// A single JSON document with a `files` array.
+    Json,
+    /// One JSON object per file, one per line.
+    Jsonl,
+    /// `<documents>` of `<document index>` blocks, as `files-to-prompt --cxml`
+    /// emits for `llm` and Claude prompts.
+    ClaudeXml,
+}
+
+/// Shape of the `--list` output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ListFormat {
+    /// One display path per line (the default).
+    #[default]
+    Text,
+    /// A JSON array of `{path, bytes, tracked}` objects.
+    Json,
+}
+
+pub fn parse_list_format(raw: &str) -> Result<ListFormat, String> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "text" | "txt" => Ok(ListFormat::Text),
+        "json" => Ok(ListFormat::Json),
+        other => Err(format!(
+            "Unknown list format '{}'. Expected one of: text, json",
+            other
+        )),
+    }
+}
+
+pub fn parse_output_format(raw: &str) -> Result<OutputFormat, String> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "text" | "txt" => Ok(OutputFormat::Text),
+        "json" => Ok(OutputFormat::Json),
+        "jsonl" | "ndjson" => Ok(OutputFormat::Jsonl),
+        "claude-xml" | "cxml" => Ok(OutputFormat::ClaudeXml),
+        other => Err(format!(
+            "Unknown output format '{}'. Expected one of: text, json, jsonl, claude-xml",
+            other
+        )),
+    }
+}
+
+pub fn parse_output_encoding(raw: &str) -> Result<OutputEncoding, String> {
+    match raw.trim().to_ascii_lowercase().replace('_', "-").as_str() {
+        "utf8" | "utf-8" => Ok(OutputEncoding::Utf8),
+        "utf8-bom" | "utf-8-bom" => Ok(OutputEncoding::Utf8Bom),
+        "utf16le" | "utf-16le" | "utf-16-le" => Ok(OutputEncoding::Utf16Le),
+        "utf16be" | "utf-16be" | "utf-16-be" => Ok(OutputEncoding::Utf16Be),
+        other => Err(format!(
+            "Unknown output encoding '{}'. Expected one of: utf8, utf8-bom, utf16le, utf16be",
+            other
+        )),
+    }
+}
+
 const DEFAULT_TOKEN_RATIO: f64 = 3.6;
+const DEFAULT_BUDGET_MIN_LINES: usize = 10;
+
+/// A file `build_run_settings` consults before applying CLI flags.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// A TOML config file.
+    Config(PathBuf),
+    /// The target's `.dirgrab.toml`. It ships with the checkout, so keys that
+    /// run commands (`extractors`) are ignored.
+    LocalConfig(PathBuf),
+    /// A gitignore-style file of exclude patterns.
+    Ignore(PathBuf),
+}
+
+impl ConfigSource {
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Config(path) | Self::LocalConfig(path) | Self::Ignore(path) => path,
+        }
+    }
+}
+
+/// The config and ignore files for `target_path`, in the order they are
+/// applied (later files override earlier ones), whether or not they exist:
+/// the global `config.toml` and `ignore`, the target's `.dirgrab.toml` and
+/// `.dirgrabignore`, then `--config`. Empty with `--no-config`.
+pub fn config_sources(cli: &Cli, target_path: &Path) -> Vec<ConfigSource> {
+    if cli.no_config {
+        if let Some(explicit_path) = cli.config_path.as_ref() {
+            debug!(
+                "--no-config specified; skipping explicitly requested config file {:?}",
+                explicit_path
+            );
+        }
+        return Vec::new();
+    }
+
+    let mut sources = Vec::new();
+    if let Some(base_dirs) = BaseDirs::new() {
+        let config_dir = base_dirs.config_dir().join("dirgrab");
+        sources.push(ConfigSource::Config(config_dir.join("config.toml")));
+        sources.push(ConfigSource::Ignore(config_dir.join("ignore")));
+    } else {
+        debug!("No base directories available; skipping global config search");
+    }
+    sources.push(ConfigSource::LocalConfig(target_path.join(".dirgrab.toml")));
+    sources.push(ConfigSource::Ignore(target_path.join(".dirgrabignore")));
+    if let Some(explicit_path) = cli.config_path.as_ref() {
+        sources.push(ConfigSource::Config(explicit_path.clone()));
+    }
+    sources
+}
 
 pub fn build_run_settings(cli: &Cli, target_path: &Path) -> Result<RunSettings> {
     let mut flags = Flags::default();
     let mut stats_acc = StatsAccum::default();
+    let mut output_acc = OutputAccum::default();
     let mut patterns = PatternAccumulator::default();
 
-    if !cli.no_config {
-        if let Some(base_dirs) = BaseDirs::new() {
-            let config_dir = base_dirs.config_dir().join("dirgrab");
-            let global_config_path = config_dir.join("config.toml");
-            apply_config_file(
-                &global_config_path,
+    for source in config_sources(cli, target_path) {
+        match source {
+            ConfigSource::Config(path) => apply_config_file(
+                &path,
+                false,
                 &mut flags,
                 &mut stats_acc,
+                &mut output_acc,
                 &mut patterns,
-            )?;
-
-            let global_ignore_path = config_dir.join("ignore");
-            apply_ignore_file(&global_ignore_path, &mut patterns)?;
-        } else {
-            debug!("No base directories available; skipping global config search");
-        }
-
-        let local_config_path = target_path.join(".dirgrab.toml");
-        apply_config_file(
-            &local_config_path,
-            &mut flags,
-            &mut stats_acc,
-            &mut patterns,
-        )?;
-
-        let local_ignore_path = target_path.join(".dirgrabignore");
-        apply_ignore_file(&local_ignore_path, &mut patterns)?;
-
-        if let Some(explicit_path) = cli.config_path.as_ref() {
-            apply_config_file(explicit_path, &mut flags, &mut stats_acc, &mut patterns)?;
+            )?,
+            ConfigSource::LocalConfig(path) => apply_config_file(
+                &path,
+                true,
+                &mut flags,
+                &mut stats_acc,
+                &mut output_acc,
+                &mut patterns,
+            )?,
+            ConfigSource::Ignore(path) => apply_ignore_file(&path, &mut patterns)?,
         }
-    } else if let Some(explicit_path) = cli.config_path.as_ref() {
-        debug!(
-            "--no-config specified; skipping explicitly requested config file {:?}",
-            explicit_path
-        );
     }
 
     // CLI overrides (highest precedence)
     if cli.no_headers {
         flags.add_headers = false;
     }
+    if cli.frontmatter {
+        flags.frontmatter = true;
+    }
+    if cli.prefix_lines {
+        flags.prefix_lines = true;
+    }
+    if cli.tight {
+        flags.tight = true;
+    }
+    if cli.no_body {
+        flags.no_body = true;
+    }
+    if let Some(bytes) = cli.group_under {
+        flags.group_under = Some(bytes);
+    }
+    if cli.detect_language {
+        flags.detect_language = true;
+    }
+    if cli.explain_selection {
+        flags.explain_selection = true;
+    }
+    if cli.follow_symlinks {
+        flags.follow_symlinks = true;
+    }
+    if cli.relativize_symlink_targets {
+        flags.relativize_symlink_targets = true;
+    }
+    if cli.dedupe_normalized {
+        flags.dedupe_normalized = true;
+    }
+    if cli.respect_editorconfig {
+        flags.respect_editorconfig = true;
+    }
+    if cli.strip_imports {
+        flags.strip_imports = true;
+    }
+    // Folding on the command line wins over `strip_imports` from a config file.
+    if cli.fold_imports {
+        flags.fold_imports = true;
+        flags.strip_imports = false;
+    }
+    if let Some(ref style) = cli.comment_style {
+        flags.header_style = style.clone();
+    }
+    if let Some(ref prefix) = cli.header_prefix {
+        flags.header_prefix = Some(prefix.clone());
+    }
+    if let Some(ref suffix) = cli.header_suffix {
+        flags.header_suffix = Some(suffix.clone());
+    }
+    if let Some(ref base) = cli.link_base {
+        flags.link_base = Some(base.clone());
+    }
+    if let Some(ref text) = cli.wrap_open {
+        flags.wrap_open = Some(text.clone());
+    }
+    if let Some(ref text) = cli.wrap_close {
+        flags.wrap_close = Some(text.clone());
+    }
+    if cli.label_language {
+        flags.label_language = true;
+    }
+    if cli.hash_headers {
+        flags.hash_headers = true;
+    }
     if cli.no_tree {
         flags.include_tree = false;
     }
+    if cli.tree_show_excluded {
+        flags.tree_show_excluded = true;
+    }
+    if cli.tree_collapse_chains {
+        flags.tree_collapse_chains = true;
+    }
+    if let Some(value) = cli.tree_max_depth {
+        flags.tree_max_depth = Some(value);
+    }
+    if let Some(value) = cli.tree_max_entries {
+        flags.tree_max_entries = Some(value);
+    }
+    if let Some(style) = cli.tree_style {
+        flags.tree_style = style;
+    }
+    // `0` lifts a limit set by a config file, as it does in `GrabConfig`.
+    flags.tree_max_depth = flags.tree_max_depth.filter(|&depth| depth > 0);
+    flags.tree_max_entries = flags.tree_max_entries.filter(|&entries| entries > 0);
     if cli.no_pdf {
         flags.convert_pdf = false;
     }
+    if let Some(value) = cli.pdf_max_pages {
+        flags.pdf_max_pages = Some(value);
+    }
+    flags.pdf_max_pages = flags.pdf_max_pages.filter(|&pages| pages > 0);
+    if let Some(value) = cli.max_pdf_bytes {
+        flags.pdf_max_bytes = Some(value as u64);
+    }
+    if let Some(mode) = cli.binary {
+        flags.binary_mode = mode;
+    }
+    if let Some(value) = cli.read_retries {
+        flags.read_retries = value;
+    }
+    if let Some(value) = cli.pdf_jobs {
+        flags.pdf_jobs = Some(value);
+    }
+    if flags.pdf_jobs == Some(0) {
+        bail!("--pdf-jobs must be greater than 0");
+    }
+    if cli.image_metadata {
+        flags.image_metadata = true;
+    }
+    // CLI extractors take precedence over config ones for the same extension.
+    for extractor in cli.extractors.iter().rev() {
+        flags.extractors.insert(0, extractor.clone());
+    }
+    flags.ignore_files.extend(cli.ignore_files.iter().cloned());
+    flags
+        .prepend_files
+        .extend(cli.prepend_files.iter().cloned());
+    flags.append_files.extend(cli.append_files.iter().cloned());
     if cli.include_default_output {
         flags.include_default_output = true;
     }
     if cli.no_git {
         flags.no_git = true;
     }
+    if cli.gitignore_only {
+        flags.gitignore_only = true;
+    }
+    if let Some(marker) = &cli.root_marker {
+        flags.root_marker = Some(marker.clone());
+    }
     if cli.all_repo {
         flags.all_repo = true;
     }
+    if let Some(value) = cli.changed_in_last {
+        flags.changed_in_last = Some(value);
+    }
+    if let Some(refs) = &cli.between {
+        flags.between = Some(parse_between(refs)?);
+    }
+    if let Some(reference) = &cli.since_commit {
+        flags.since_commit = Some(reference.clone());
+    }
+    if let Some(attr) = &cli.git_attr {
+        flags.git_attr = Some(attr.clone());
+    }
+    if let Some(value) = cli.git_history {
+        flags.git_history = Some(value);
+    }
+    if cli.show_force_added {
+        flags.show_force_added = true;
+    }
+    if cli.show_mode {
+        flags.show_mode = true;
+    }
+    if flags.git_history == Some(0) {
+        bail!("--git-history must be greater than 0");
+    }
     if cli.tracked_only {
         flags.include_untracked = false;
     }
+    if cli.warn_unused_excludes {
+        flags.warn_unused_excludes = true;
+    }
+    if cli.skip_generated {
+        flags.skip_generated = true;
+    }
+    if !cli.generated_markers.is_empty() {
+        flags.generated_markers = cli.generated_markers.clone();
+    }
+    flags
+        .exclude_matching
+        .extend(cli.exclude_matching.iter().cloned());
+    if !cli.include_mime.is_empty() {
+        flags.include_mime = cli.include_mime.clone();
+    }
+    flags.exclude_mime.extend(cli.exclude_mime.iter().cloned());
+    if let Some(value) = cli.sniff_only_above {
+        flags.sniff_only_above = Some(value as u64);
+    }
+    if let Some(order) = cli.sort {
+        flags.sort = order;
+    }
+    if cli.sort_desc {
+        flags.sort_desc = true;
+    }
+    flags
+        .deprioritize_patterns
+        .extend(cli.deprioritize.iter().cloned());
+    flags.content_for.extend(cli.content_for.iter().cloned());
+    // CLI sections come before config ones, so they match first.
+    for partition in cli.partition.iter().rev() {
+        flags.partitions.insert(0, partition.clone());
+    }
+    if cli.collate.is_some() {
+        flags.collate = cli.collate;
+    }
+    if cli.tree_partitioned {
+        flags.tree_partitioned = true;
+    }
+    if cli.readme_first {
+        flags.readme_first = true;
+    }
+    if let Some(value) = cli.min_files {
+        flags.min_files = Some(value);
+    }
+    if let Some(value) = cli.max_per_dir {
+        flags.max_per_dir = Some(value);
+    }
+    if let Some(value) = cli.context_budget {
+        flags.context_budget = Some(value);
+    }
+    if let Some(strategy) = cli.budget_strategy {
+        flags.budget_strategy = strategy;
+    }
+    if let Some(value) = cli.budget_min_lines {
+        flags.budget_min_lines = value;
+    }
+    if flags.context_budget == Some(0) {
+        bail!("--context-budget must be greater than 0");
+    }
+    if flags.max_per_dir == Some(0) {
+        bail!("--max-per-dir must be greater than 0");
+    }
     if cli.include_untracked_flag {
         flags.include_untracked = true;
     }
+    // Redaction rules accumulate: config rules, then rule files, then inline rules.
+    for path in &cli.redact_files {
+        flags.redactions.extend(read_redaction_file(path)?);
+    }
+    flags.redactions.extend(cli.redact.iter().cloned());
+    flags.anonymize.extend(cli.anonymize.iter().cloned());
+    if cli.anonymize_content {
+        flags.anonymize_content = true;
+    }
 
     // Warn about git-specific flags that have no effect with --no-git
     if cli.no_git {
@@ -165,26 +822,66 @@ pub fn build_run_settings(cli: &Cli, target_path: &Path) -> Result<RunSettings>
         }
     }
 
-    // CLI excludes — support comma-separated patterns (e.g. -e "*.log,target/")
+    // CLI excludes — support comma-separated patterns (e.g. -e "*.log,target/");
+    // commas inside braces belong to the brace group (`-e "*.{png,jpg}"`).
     for raw in &cli.exclude_patterns {
-        for part in raw.split(',') {
+        for part in split_top_level_commas(raw) {
             let trimmed = part.trim();
             if !trimmed.is_empty() {
                 patterns.push(trimmed);
             }
         }
     }
+    // CLI includes split the same way and add to those from config files.
+    for raw in &cli.include_patterns {
+        for part in split_top_level_commas(raw) {
+            if !part.trim().is_empty() {
+                flags.include_patterns.extend(parse_include_pattern(part)?);
+            }
+        }
+    }
+
+    let output = resolve_output_settings(cli, target_path, output_acc);
 
     // Always auto-exclude the active output file to prevent self-ingestion.
     // This is separate from the default "dirgrab.txt" exclusion controlled by
     // --include-default-output; the active output file is ALWAYS excluded.
-    if let Some(ref output_path) = cli.output {
-        if let Some(name) = output_path.file_name().and_then(|n| n.to_str()) {
-            patterns.push(name);
+    let output_exclude = output.path.as_deref().and_then(|output_path| {
+        let base = exclude_base(target_path, &flags);
+        let pattern = output_exclude_pattern(output_path, &base);
+        if pattern.is_none() {
+            debug!(
+                "Output file {:?} is outside {:?}; no auto-exclude needed",
+                output_path, base
+            );
         }
+        pattern
+    });
+    if let Some(pattern) = output_exclude.as_deref() {
+        patterns.push(pattern);
     }
+    // Like `[output] path`, a configured stats file resolves against the target.
+    stats_acc.output = match &cli.stats_output {
+        Some(path) => Some(path.clone()),
+        None => stats_acc.output.map(|path| target_path.join(path)),
+    };
+    // The stats file is excluded the same way, so a rerun never grabs it.
+    let stats_exclude = stats_acc.output.as_deref().and_then(|stats_path| {
+        output_exclude_pattern(stats_path, &exclude_base(target_path, &flags))
+    });
+    if let Some(pattern) = stats_exclude.as_deref() {
+        patterns.push(pattern);
+    }
+
+    let explicit_files = match cli.files_from.as_ref() {
+        Some(source) => Some(read_file_list(source)?),
+        None => None,
+    };
 
     // Stats merging
+    if cli.stats_output.is_some() {
+        stats_acc.enabled = Some(true);
+    }
     if let Some(cli_specs) = cli.stats.as_ref() {
         stats_acc.enabled = Some(true);
         let mut explicit = Vec::new();
@@ -216,6 +913,16 @@ pub fn build_run_settings(cli: &Cli, target_path: &Path) -> Result<RunSettings>
     if cli.tokens_exclude_headers {
         stats_acc.exclude_headers = Some(true);
     }
+    if let Some(model) = &cli.model {
+        stats_acc.model = Some(model.clone());
+        // A CLI model replaces a configured window meant for another model.
+        stats_acc.model_window = None;
+    }
+    if let Some(window) = cli.model_window {
+        stats_acc.model_window = Some(window);
+    }
+    let context_window = ContextWindow::resolve(stats_acc.model.as_deref(), stats_acc.model_window)
+        .map_err(anyhow::Error::msg)?;
 
     let stats = StatsSettings {
         enabled: stats_acc.enabled.unwrap_or(false),
@@ -226,44 +933,337 @@ pub fn build_run_settings(cli: &Cli, target_path: &Path) -> Result<RunSettings>
             .reports
             .clone()
             .unwrap_or_else(default_stats_reports),
+        context_window,
+        output: stats_acc.output.clone(),
+    };
+
+    let header_style = if flags.header_prefix.is_some() || flags.header_suffix.is_some() {
+        HeaderStyle::Custom {
+            prefix: flags
+                .header_prefix
+                .clone()
+                .unwrap_or_else(|| flags.header_style.prefix().to_string()),
+            suffix: flags
+                .header_suffix
+                .clone()
+                .unwrap_or_else(|| flags.header_style.suffix().to_string()),
+        }
+    } else {
+        flags.header_style.clone()
     };
 
     let grab_config = GrabConfig {
         target_path: target_path.to_path_buf(),
         add_headers: flags.add_headers,
+        frontmatter: flags.frontmatter,
+        prefix_lines: flags.prefix_lines,
+        tight: flags.tight,
+        no_body: flags.no_body,
+        group_under: flags.group_under,
+        detect_language: flags.detect_language,
+        explain_selection: flags.explain_selection,
+        follow_symlinks: flags.follow_symlinks,
+        relativize_symlink_targets: flags.relativize_symlink_targets,
+        dedupe_normalized: flags.dedupe_normalized,
+        find_duplicates: stats.enabled
+            && stats
+                .reports
+                .iter()
+                .any(|report| matches!(report, StatsReport::Duplicates)),
+        respect_editorconfig: flags.respect_editorconfig,
+        strip_imports: flags.strip_imports,
+        fold_imports: flags.fold_imports,
+        header_style,
+        link_base: flags.link_base,
+        wrap_open: flags.wrap_open,
+        wrap_close: flags.wrap_close,
+        label_language: flags.label_language,
+        hash_headers: flags.hash_headers,
         exclude_patterns: patterns.into_vec(),
+        ignore_files: flags.ignore_files,
+        prepend_files: flags.prepend_files,
+        append_files: flags.append_files,
         include_untracked: flags.include_untracked,
         include_default_output: flags.include_default_output,
         no_git: flags.no_git,
+        gitignore_only: flags.gitignore_only,
+        root_marker: flags.root_marker,
         include_tree: flags.include_tree,
+        tree_show_excluded: flags.tree_show_excluded,
+        tree_collapse_chains: flags.tree_collapse_chains,
+        tree_max_depth: flags.tree_max_depth,
+        tree_max_entries: flags.tree_max_entries,
+        tree_style: flags.tree_style,
         convert_pdf: flags.convert_pdf,
+        pdf_max_pages: flags.pdf_max_pages,
+        pdf_max_bytes: flags.pdf_max_bytes,
+        pdf_jobs: flags.pdf_jobs,
+        binary_mode: flags.binary_mode,
+        read_retries: flags.read_retries,
+        image_metadata: flags.image_metadata,
+        extractors: flags.extractors,
         all_repo: flags.all_repo,
+        changed_in_last: flags.changed_in_last,
+        git_history: flags.git_history,
+        show_force_added: flags.show_force_added,
+        show_mode: flags.show_mode,
+        between: flags.between,
+        since_commit: flags.since_commit,
+        git_attr: flags.git_attr,
+        min_files: flags.min_files,
+        max_per_dir: flags.max_per_dir,
+        include_patterns: flags.include_patterns,
+        // The budget is given in tokens; the library works in bytes.
+        context_budget: flags
+            .context_budget
+            .map(|tokens| (tokens as f64 * stats.token_ratio).floor() as usize),
+        budget_strategy: flags.budget_strategy,
+        budget_min_lines: flags.budget_min_lines,
+        skip_generated: flags.skip_generated,
+        generated_markers: flags.generated_markers,
+        exclude_matching: flags.exclude_matching,
+        include_mime: flags.include_mime,
+        exclude_mime: flags.exclude_mime,
+        sniff_only_above: flags.sniff_only_above,
+        redactions: flags.redactions,
+        anonymize: flags.anonymize,
+        anonymize_content: flags.anonymize_content,
+        sort: flags.sort,
+        sort_desc: flags.sort_desc,
+        deprioritize_patterns: flags.deprioritize_patterns,
+        content_for: flags.content_for,
+        partitions: flags.partitions,
+        tree_partitioned: flags.tree_partitioned,
+        collate: flags.collate,
+        readme_first: flags.readme_first,
+        warn_unused_excludes: flags.warn_unused_excludes,
+        explicit_files,
+    };
+
+    Ok(RunSettings {
+        grab_config,
+        stats,
+        output,
+    })
+}
+
+/// The directory exclude patterns are matched against: the target, or with
+/// --all-repo in Git mode the enclosing repository root (the nearest ancestor
+/// holding `.git`).
+fn exclude_base(target_path: &Path, flags: &Flags) -> PathBuf {
+    let target = fs::canonicalize(target_path).unwrap_or_else(|_| target_path.to_path_buf());
+    if flags.all_repo && !flags.no_git && !flags.gitignore_only {
+        if let Some(root) = target.ancestors().find(|dir| dir.join(".git").exists()) {
+            return root.to_path_buf();
+        }
+    }
+    target
+}
+
+/// An exclude pattern matching exactly the output file: its path relative to
+/// `base`, anchored with a leading `/` so a file of the same name elsewhere
+/// is still grabbed. `None` if the output lies outside `base`.
+fn output_exclude_pattern(output_path: &Path, base: &Path) -> Option<String> {
+    let absolute = std::path::absolute(output_path).ok()?;
+    // The file may not exist yet; resolve symlinks through its directory.
+    let resolved = match (absolute.parent(), absolute.file_name()) {
+        (Some(parent), Some(name)) => fs::canonicalize(parent)
+            .map(|parent| parent.join(name))
+            .unwrap_or(absolute),
+        _ => absolute,
     };
+    let relative = resolved.strip_prefix(base).ok()?;
+    let parts: Vec<String> = relative
+        .components()
+        .map(|part| part.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    (!parts.is_empty()).then(|| format!("/{}", parts.join("/")))
+}
 
-    Ok(RunSettings { grab_config, stats })
+/// Merges the CLI output options over the `[output]` config values. `--output -`
+/// forces stdout, and a configured path is ignored when the CLI selects another
+/// destination (`--clipboard`, `--list`, `--plan`, `--mirror`). A relative configured path
+/// resolves against the target directory.
+fn resolve_output_settings(cli: &Cli, target_path: &Path, acc: OutputAccum) -> OutputSettings {
+    let other_destination = cli.clipboard || cli.list || cli.plan || cli.mirror.is_some();
+    let path = match cli.output.as_deref() {
+        Some(_) => cli.output_file().map(Path::to_path_buf),
+        None if other_destination => {
+            if let Some(path) = &acc.path {
+                debug!("Ignoring configured output path {:?} for this run", path);
+            }
+            None
+        }
+        None => acc.path.map(|path| target_path.join(path)),
+    };
+    // The clipboard is always UTF-8; only an explicit --output-encoding conflicts.
+    let config_encoding = acc.encoding.filter(|_| !cli.clipboard);
+    OutputSettings {
+        path,
+        format: cli.format.or(acc.format).unwrap_or_default(),
+        encoding: cli.output_encoding.or(config_encoding).unwrap_or_default(),
+    }
 }
 
 #[derive(Debug)]
 struct Flags {
     add_headers: bool,
+    frontmatter: bool,
+    prefix_lines: bool,
+    tight: bool,
+    no_body: bool,
+    group_under: Option<usize>,
+    detect_language: bool,
+    explain_selection: bool,
+    follow_symlinks: bool,
+    relativize_symlink_targets: bool,
+    dedupe_normalized: bool,
+    respect_editorconfig: bool,
+    strip_imports: bool,
+    fold_imports: bool,
+    header_style: HeaderStyle,
+    header_prefix: Option<String>,
+    header_suffix: Option<String>,
+    link_base: Option<String>,
+    wrap_open: Option<String>,
+    wrap_close: Option<String>,
+    label_language: bool,
+    hash_headers: bool,
     include_tree: bool,
+    tree_show_excluded: bool,
+    tree_collapse_chains: bool,
+    tree_max_depth: Option<usize>,
+    tree_max_entries: Option<usize>,
+    tree_style: TreeStyle,
     convert_pdf: bool,
+    pdf_max_pages: Option<usize>,
+    pdf_max_bytes: Option<u64>,
+    pdf_jobs: Option<usize>,
+    binary_mode: BinaryMode,
+    read_retries: usize,
+    image_metadata: bool,
+    extractors: Vec<Extractor>,
+    ignore_files: Vec<PathBuf>,
+    prepend_files: Vec<PathBuf>,
+    append_files: Vec<PathBuf>,
     include_default_output: bool,
     include_untracked: bool,
     no_git: bool,
+    gitignore_only: bool,
+    root_marker: Option<String>,
     all_repo: bool,
+    changed_in_last: Option<usize>,
+    git_history: Option<usize>,
+    show_force_added: bool,
+    show_mode: bool,
+    between: Option<(String, String)>,
+    since_commit: Option<String>,
+    git_attr: Option<String>,
+    min_files: Option<usize>,
+    max_per_dir: Option<usize>,
+    include_patterns: Vec<IncludePattern>,
+    context_budget: Option<usize>,
+    budget_strategy: BudgetStrategy,
+    budget_min_lines: usize,
+    skip_generated: bool,
+    generated_markers: Vec<String>,
+    exclude_matching: Vec<String>,
+    include_mime: Vec<String>,
+    exclude_mime: Vec<String>,
+    sniff_only_above: Option<u64>,
+    redactions: Vec<RedactionRule>,
+    anonymize: Vec<RedactionRule>,
+    anonymize_content: bool,
+    sort: FileOrder,
+    sort_desc: bool,
+    deprioritize_patterns: Vec<String>,
+    content_for: Vec<String>,
+    partitions: Vec<Partition>,
+    tree_partitioned: bool,
+    collate: Option<Collate>,
+    readme_first: bool,
+    warn_unused_excludes: bool,
 }
 
 impl Default for Flags {
     fn default() -> Self {
         Self {
             add_headers: true,
+            frontmatter: false,
+            prefix_lines: false,
+            tight: false,
+            no_body: false,
+            group_under: None,
+            detect_language: false,
+            explain_selection: false,
+            follow_symlinks: false,
+            relativize_symlink_targets: false,
+            dedupe_normalized: false,
+            respect_editorconfig: false,
+            strip_imports: false,
+            fold_imports: false,
+            header_style: HeaderStyle::Plain,
+            header_prefix: None,
+            header_suffix: None,
+            link_base: None,
+            wrap_open: None,
+            wrap_close: None,
+            label_language: false,
+            hash_headers: false,
             include_tree: true,
+            tree_show_excluded: false,
+            tree_collapse_chains: false,
+            tree_max_depth: None,
+            tree_max_entries: None,
+            tree_style: TreeStyle::Indented,
             convert_pdf: true,
+            pdf_max_pages: None,
+            pdf_max_bytes: None,
+            pdf_jobs: None,
+            binary_mode: BinaryMode::Skip,
+            read_retries: 0,
+            image_metadata: false,
+            extractors: Vec::new(),
+            ignore_files: Vec::new(),
+            prepend_files: Vec::new(),
+            append_files: Vec::new(),
             include_default_output: false,
             include_untracked: true,
             no_git: false,
+            gitignore_only: false,
+            root_marker: None,
             all_repo: false,
+            changed_in_last: None,
+            git_history: None,
+            show_force_added: false,
+            show_mode: false,
+            between: None,
+            since_commit: None,
+            git_attr: None,
+            min_files: None,
+            max_per_dir: None,
+            include_patterns: Vec::new(),
+            context_budget: None,
+            budget_strategy: BudgetStrategy::Proportional,
+            budget_min_lines: DEFAULT_BUDGET_MIN_LINES,
+            skip_generated: false,
+            generated_markers: Vec::new(),
+            exclude_matching: Vec::new(),
+            include_mime: Vec::new(),
+            exclude_mime: Vec::new(),
+            sniff_only_above: None,
+            redactions: Vec::new(),
+            anonymize: Vec::new(),
+            anonymize_content: false,
+            sort: FileOrder::Path,
+            sort_desc: false,
+            deprioritize_patterns: Vec::new(),
+            content_for: Vec::new(),
+            partitions: Vec::new(),
+            tree_partitioned: false,
+            collate: None,
+            readme_first: false,
+            warn_unused_excludes: false,
         }
     }
 }
@@ -275,6 +1275,16 @@ struct StatsAccum {
     exclude_tree: Option<bool>,
     exclude_headers: Option<bool>,
     reports: Option<Vec<StatsReport>>, // None -> defer to default bundle
+    model: Option<String>,
+    model_window: Option<usize>,
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Default)]
+struct OutputAccum {
+    path: Option<PathBuf>,
+    format: Option<OutputFormat>,
+    encoding: Option<OutputEncoding>,
 }
 
 #[derive(Debug, Default)]
@@ -289,15 +1299,17 @@ impl PatternAccumulator {
         if candidate.is_empty() {
             return;
         }
-        let normalized = normalize_glob(candidate);
-        if normalized.is_empty() {
-            return;
-        }
-        if self.seen.insert(normalized.clone()) {
-            debug!("Adding exclude pattern: {}", normalized);
-            self.patterns.push(normalized);
-        } else {
-            debug!("Skipping duplicate exclude pattern: {}", normalized);
+        for expanded in expand_braces(candidate) {
+            let normalized = normalize_glob(&expanded);
+            if normalized.is_empty() {
+                continue;
+            }
+            if self.seen.insert(normalized.clone()) {
+                debug!("Adding exclude pattern: {}", normalized);
+                self.patterns.push(normalized);
+            } else {
+                debug!("Skipping duplicate exclude pattern: {}", normalized);
+            }
         }
     }
 
@@ -316,69 +1328,553 @@ impl PatternAccumulator {
     }
 }
 
-fn apply_config_file(
-    path: &Path,
-    flags: &mut Flags,
-    stats: &mut StatsAccum,
-    patterns: &mut PatternAccumulator,
-) -> Result<()> {
-    if !path.exists() {
-        debug!("Config file {:?} not found; skipping", path);
-        return Ok(());
+/// Upper bound on the patterns one brace pattern may expand into.
+const MAX_BRACE_EXPANSIONS: usize = 1024;
+
+/// Expands shell-style brace groups, e.g. `*.{png,jpg}` into `*.png` and
+/// `*.jpg`; groups may nest (`{a,b{c,d}}`). Braces and commas escaped with a
+/// backslash stay literal, as do unbalanced braces and groups without a
+/// comma. A pattern that would expand past `MAX_BRACE_EXPANSIONS` is kept as is.
+fn expand_braces(pattern: &str) -> Vec<String> {
+    let mut expanded = Vec::new();
+    if expand_braces_into(pattern, &mut expanded) {
+        expanded
+    } else {
+        warn!(
+            "Brace pattern '{}' expands to more than {} patterns; using it literally",
+            pattern, MAX_BRACE_EXPANSIONS
+        );
+        vec![pattern.to_string()]
     }
+}
 
-    debug!("Loading config from {:?}", path);
-    let contents = fs::read_to_string(path)
-        .with_context(|| format!("Failed to read config file {:?}", path))?;
-
-    let parsed: FileConfig = toml::from_str(&contents)
-        .with_context(|| format!("Failed to parse config file {:?}", path))?;
-
-    if let Some(dirgrab_section) = parsed.dirgrab {
-        apply_dirgrab_section(dirgrab_section, flags, patterns);
+/// Appends the expansions of `pattern` to `out`; false once the cap is exceeded.
+fn expand_braces_into(pattern: &str, out: &mut Vec<String>) -> bool {
+    let bytes = pattern.as_bytes();
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'\\' => index += 2,
+            b'{' => {
+                if let Some((close, commas)) = brace_group(pattern, index) {
+                    if !commas.is_empty() {
+                        let prefix = &pattern[..index];
+                        let suffix = &pattern[close + 1..];
+                        let mut start = index + 1;
+                        for end in commas.into_iter().chain(std::iter::once(close)) {
+                            let alternative =
+                                format!("{}{}{}", prefix, &pattern[start..end], suffix);
+                            if !expand_braces_into(&alternative, out) {
+                                return false;
+                            }
+                            start = end + 1;
+                        }
+                        return true;
+                    }
+                }
+                index += 1;
+            }
+            _ => index += 1,
+        }
     }
-    if let Some(stats_section) = parsed.stats {
-        apply_stats_section(stats_section, stats)?;
+    out.push(pattern.to_string());
+    out.len() <= MAX_BRACE_EXPANSIONS
+}
+
+/// For the `{` at `open`, returns the index of its matching `}` and of the
+/// commas directly inside it, or `None` if it is never closed.
+fn brace_group(pattern: &str, open: usize) -> Option<(usize, Vec<usize>)> {
+    let bytes = pattern.as_bytes();
+    let mut depth = 0;
+    let mut commas = Vec::new();
+    let mut index = open + 1;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'\\' => index += 1,
+            b'{' => depth += 1,
+            b'}' if depth == 0 => return Some((index, commas)),
+            b'}' => depth -= 1,
+            b',' if depth == 0 => commas.push(index),
+            _ => {}
+        }
+        index += 1;
     }
+    None
+}
 
-    Ok(())
+/// Splits a `-e` value on commas that are not inside a brace group or escaped.
+fn split_top_level_commas(raw: &str) -> Vec<&str> {
+    let bytes = raw.as_bytes();
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'\\' => index += 1,
+            // An unclosed `{` stays literal, so it must not swallow later commas.
+            b'{' if brace_group(raw, index).is_some() => depth += 1,
+            b'}' if depth > 0 => depth -= 1,
+            b',' if depth == 0 => {
+                parts.push(&raw[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+    parts.push(&raw[start..]);
+    parts
+}
+
+/// Reads a newline-separated file list from `source` (`-` means stdin).
+/// Blank lines are ignored; CRLF line endings are tolerated.
+fn read_file_list(source: &Path) -> Result<Vec<PathBuf>> {
+    let contents = if source == Path::new("-") {
+        let mut buffer = String::new();
+        io::stdin()
+            .read_to_string(&mut buffer)
+            .context("Failed to read file list from stdin")?;
+        buffer
+    } else {
+        fs::read_to_string(source)
+            .with_context(|| format!("Failed to read file list {:?}", source))?
+    };
+    let entries: Vec<PathBuf> = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .collect();
+    debug!("Read {} entries from file list {:?}", entries.len(), source);
+    Ok(entries)
+}
+
+/// Validates a `--between FROM TO` pair (or the `between` config key).
+fn parse_between(refs: &[String]) -> Result<(String, String)> {
+    match refs {
+        [from, to] => Ok((from.clone(), to.clone())),
+        _ => bail!(
+            "between expects exactly two revisions (FROM and TO), got {}",
+            refs.len()
+        ),
+    }
+}
+
+/// Reads redaction rules from `path`, one per line, in the same format as
+/// `--redact`. Blank lines and lines starting with `#` are skipped.
+fn read_redaction_file(path: &Path) -> Result<Vec<RedactionRule>> {
+    debug!("Loading redaction rules from {:?}", path);
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read redaction file {:?}", path))?;
+
+    let mut rules = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        match parse_redaction_rule(trimmed) {
+            Ok(rule) => rules.push(rule),
+            Err(msg) => bail!("{}:{}: {}", path.display(), index + 1, msg),
+        }
+    }
+    Ok(rules)
+}
+
+/// Writes the resolved `GrabConfig` to `path` as pretty-printed JSON.
+pub fn dump_config(path: &Path, config: &GrabConfig) -> Result<()> {
+    let json = serde_json::to_string_pretty(config).context("Failed to serialize config")?;
+    fs::write(path, format!("{}\n", json))
+        .with_context(|| format!("Failed to write config dump to {:?}", path))?;
+    debug!("Wrote resolved config to {:?}", path);
+    Ok(())
+}
+
+/// Applies one config file. `local` marks the target's own `.dirgrab.toml`,
+/// whose `extractors` are dropped with a warning and whose paths must stay
+/// inside the target.
+fn apply_config_file(
+    path: &Path,
+    local: bool,
+    flags: &mut Flags,
+    stats: &mut StatsAccum,
+    output: &mut OutputAccum,
+    patterns: &mut PatternAccumulator,
+) -> Result<()> {
+    if !path.exists() {
+        debug!("Config file {:?} not found; skipping", path);
+        return Ok(());
+    }
+
+    debug!("Loading config from {:?}", path);
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file {:?}", path))?;
+
+    let parsed: FileConfig = toml::from_str(&contents)
+        .with_context(|| format!("Failed to parse config file {:?}", path))?;
+
+    // The target's own config may only read and write inside the target.
+    let local_dir = if local { path.parent() } else { None };
+    if let Some(mut dirgrab_section) = parsed.dirgrab {
+        if local && dirgrab_section.extractors.take().is_some() {
+            warn!(
+                "Ignoring `extractors` in {:?}: extractors run commands, so they are only read from the global config, --config, or --extractor",
+                path
+            );
+        }
+        apply_dirgrab_section(dirgrab_section, local_dir, flags, patterns)
+            .with_context(|| format!("Invalid [dirgrab] section in {:?}", path))?;
+    }
+    if let Some(stats_section) = parsed.stats {
+        apply_stats_section(stats_section, local_dir, stats)
+            .with_context(|| format!("Invalid [stats] section in {:?}", path))?;
+    }
+    if let Some(output_section) = parsed.output {
+        apply_output_section(output_section, local_dir, output)
+            .with_context(|| format!("Invalid [output] section in {:?}", path))?;
+    }
+
+    Ok(())
 }
 
 fn apply_dirgrab_section(
     section: DirgrabSection,
+    local_dir: Option<&Path>,
     flags: &mut Flags,
     patterns: &mut PatternAccumulator,
-) {
+) -> Result<()> {
     if let Some(values) = section.exclude {
         patterns.merge(values);
     }
+    if let Some(values) = section.include {
+        for raw in &values {
+            flags.include_patterns.extend(parse_include_pattern(raw)?);
+        }
+    }
     if let Some(value) = section.include_untracked {
         flags.include_untracked = value;
     }
     if let Some(value) = section.include_tree {
         flags.include_tree = value;
     }
+    if let Some(value) = section.tree_show_excluded {
+        flags.tree_show_excluded = value;
+    }
+    if let Some(value) = section.tree_collapse_chains {
+        flags.tree_collapse_chains = value;
+    }
+    if let Some(value) = section.tree_max_depth {
+        flags.tree_max_depth = Some(value);
+    }
+    if let Some(value) = section.tree_max_entries {
+        flags.tree_max_entries = Some(value);
+    }
+    if let Some(value) = section.tree_style {
+        flags.tree_style = parse_tree_style(&value).map_err(anyhow::Error::msg)?;
+    }
     if let Some(value) = section.add_headers {
         flags.add_headers = value;
     }
+    if let Some(value) = section.frontmatter {
+        flags.frontmatter = value;
+    }
+    if let Some(value) = section.prefix_lines {
+        flags.prefix_lines = value;
+    }
+    if let Some(value) = section.tight {
+        flags.tight = value;
+    }
+    if let Some(value) = section.no_body {
+        flags.no_body = value;
+    }
+    if let Some(value) = section.group_under {
+        flags.group_under = Some(value);
+    }
+    if let Some(value) = section.detect_language {
+        flags.detect_language = value;
+    }
+    if let Some(value) = section.explain_selection {
+        flags.explain_selection = value;
+    }
+    if let Some(value) = section.follow_symlinks {
+        flags.follow_symlinks = value;
+    }
+    if let Some(value) = section.relativize_symlink_targets {
+        flags.relativize_symlink_targets = value;
+    }
+    if let Some(value) = section.dedupe_normalized {
+        flags.dedupe_normalized = value;
+    }
+    if let Some(value) = section.respect_editorconfig {
+        flags.respect_editorconfig = value;
+    }
+    if let Some(value) = section.strip_imports {
+        flags.strip_imports = value;
+    }
+    if let Some(value) = section.fold_imports {
+        flags.fold_imports = value;
+    }
+    if let Some(value) = section.comment_style {
+        flags.header_style = parse_comment_style(&value).map_err(anyhow::Error::msg)?;
+    }
+    if let Some(value) = section.header_prefix {
+        flags.header_prefix = Some(value);
+    }
+    if let Some(value) = section.header_suffix {
+        flags.header_suffix = Some(value);
+    }
+    if let Some(value) = section.link_base {
+        flags.link_base = Some(value);
+    }
+    if let Some(value) = section.wrap_open {
+        flags.wrap_open = Some(value);
+    }
+    if let Some(value) = section.wrap_close {
+        flags.wrap_close = Some(value);
+    }
+    if let Some(value) = section.label_language {
+        flags.label_language = value;
+    }
+    if let Some(value) = section.hash_headers {
+        flags.hash_headers = value;
+    }
     if let Some(value) = section.convert_pdf {
         flags.convert_pdf = value;
     }
+    if let Some(value) = section.pdf_max_pages {
+        flags.pdf_max_pages = Some(value);
+    }
+    if let Some(value) = section.max_pdf_bytes {
+        flags.pdf_max_bytes = Some(value.resolve()? as u64);
+    }
+    if let Some(value) = section.pdf_jobs {
+        flags.pdf_jobs = Some(value);
+    }
+    if let Some(value) = section.binary {
+        flags.binary_mode = parse_binary_mode(&value).map_err(anyhow::Error::msg)?;
+    }
+    if let Some(value) = section.read_retries {
+        flags.read_retries = value;
+    }
+    if let Some(value) = section.image_metadata {
+        flags.image_metadata = value;
+    }
+    if let Some(values) = section.extractors {
+        let mut parsed = values
+            .iter()
+            .map(|raw| parse_extractor(raw))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(anyhow::Error::msg)?;
+        // Later config files take precedence over earlier ones.
+        parsed.append(&mut flags.extractors);
+        flags.extractors = parsed;
+    }
+    if let Some(values) = section.ignore_files {
+        flags.ignore_files.extend(values);
+    }
+    // Injected files bypass every filter, so a checkout may only inject its own.
+    if let Some(values) = section.prepend_files {
+        if let Some(dir) = local_dir {
+            for path in &values {
+                check_local_path(dir, path, "dirgrab.prepend_files")?;
+            }
+        }
+        flags.prepend_files.extend(values);
+    }
+    if let Some(values) = section.append_files {
+        if let Some(dir) = local_dir {
+            for path in &values {
+                check_local_path(dir, path, "dirgrab.append_files")?;
+            }
+        }
+        flags.append_files.extend(values);
+    }
     if let Some(value) = section.include_default_output {
         flags.include_default_output = value;
     }
     if let Some(value) = section.no_git {
         flags.no_git = value;
     }
+    if let Some(value) = section.gitignore_only {
+        flags.gitignore_only = value;
+    }
+    if let Some(value) = section.root_marker {
+        flags.root_marker = Some(value);
+    }
     if let Some(value) = section.tracked_only {
         flags.include_untracked = !value;
     }
     if let Some(value) = section.all_repo {
         flags.all_repo = value;
     }
+    if let Some(value) = section.changed_in_last {
+        flags.changed_in_last = Some(value);
+    }
+    if let Some(value) = section.git_history {
+        flags.git_history = Some(value);
+    }
+    if let Some(value) = section.show_force_added {
+        flags.show_force_added = value;
+    }
+    if let Some(value) = section.show_mode {
+        flags.show_mode = value;
+    }
+    if let Some(refs) = section.between {
+        flags.between = Some(parse_between(&refs)?);
+    }
+    if let Some(reference) = section.since_commit {
+        flags.since_commit = Some(reference);
+    }
+    if let Some(attr) = section.git_attr {
+        flags.git_attr = Some(parse_git_attr(&attr).map_err(anyhow::Error::msg)?);
+    }
+    if let Some(value) = section.min_files {
+        flags.min_files = Some(value);
+    }
+    if let Some(value) = section.max_per_dir {
+        flags.max_per_dir = Some(value);
+    }
+    if let Some(value) = section.context_budget {
+        flags.context_budget = Some(value.resolve()?);
+    }
+    if let Some(value) = section.budget_strategy {
+        flags.budget_strategy = parse_budget_strategy(&value).map_err(anyhow::Error::msg)?;
+    }
+    if let Some(value) = section.budget_min_lines {
+        flags.budget_min_lines = value;
+    }
+    if let Some(value) = section.skip_generated {
+        flags.skip_generated = value;
+    }
+    if let Some(values) = section.generated_markers {
+        flags.generated_markers = values;
+    }
+    if let Some(values) = section.exclude_matching {
+        flags.exclude_matching = values;
+    }
+    if let Some(values) = section.include_mime {
+        flags.include_mime = parse_mime_patterns(&values)?;
+    }
+    if let Some(values) = section.exclude_mime {
+        flags.exclude_mime = parse_mime_patterns(&values)?;
+    }
+    if let Some(value) = section.sniff_only_above {
+        flags.sniff_only_above = Some(value.resolve()? as u64);
+    }
+    if let Some(values) = section.redact {
+        for raw in values {
+            flags
+                .redactions
+                .push(parse_redaction_rule(&raw).map_err(anyhow::Error::msg)?);
+        }
+    }
+    if let Some(values) = section.anonymize {
+        for raw in values {
+            flags
+                .anonymize
+                .push(parse_redaction_rule(&raw).map_err(anyhow::Error::msg)?);
+        }
+    }
+    if let Some(value) = section.anonymize_content {
+        flags.anonymize_content = value;
+    }
+    if let Some(value) = section.sort {
+        flags.sort = parse_file_order(&value).map_err(anyhow::Error::msg)?;
+    }
+    if let Some(value) = section.sort_desc {
+        flags.sort_desc = value;
+    }
+    if let Some(values) = section.deprioritize {
+        flags.deprioritize_patterns = values;
+    }
+    if let Some(values) = section.content_for {
+        flags.content_for = values;
+    }
+    if let Some(values) = section.partition {
+        flags.partitions = values
+            .iter()
+            .map(|raw| parse_partition(raw))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(anyhow::Error::msg)?;
+    }
+    if let Some(value) = section.tree_partitioned {
+        flags.tree_partitioned = value;
+    }
+    if let Some(raw) = section.collate {
+        flags.collate = Some(parse_collate(&raw).map_err(anyhow::Error::msg)?);
+    }
+    if let Some(value) = section.readme_first {
+        flags.readme_first = value;
+    }
+    if let Some(value) = section.warn_unused_excludes {
+        flags.warn_unused_excludes = value;
+    }
+
+    Ok(())
+}
+
+fn apply_output_section(
+    section: OutputSection,
+    local_dir: Option<&Path>,
+    output: &mut OutputAccum,
+) -> Result<()> {
+    if let Some(path) = section.path {
+        if path.as_os_str().is_empty() {
+            bail!("output.path must not be empty");
+        }
+        if let Some(dir) = local_dir {
+            check_local_path(dir, &path, "output.path")?;
+        }
+        output.path = Some(path);
+    }
+    if let Some(value) = section.format {
+        output.format = Some(parse_output_format(&value).map_err(anyhow::Error::msg)?);
+    }
+    if let Some(value) = section.encoding {
+        output.encoding = Some(parse_output_encoding(&value).map_err(anyhow::Error::msg)?);
+    }
+    Ok(())
+}
+
+/// Checks a path from the `.dirgrab.toml` in `dir`: it must be relative and
+/// stay inside `dir`, even through symlinks, so a checkout cannot make dirgrab
+/// overwrite or grab arbitrary files.
+fn check_local_path(dir: &Path, path: &Path, key: &str) -> Result<()> {
+    if path
+        .components()
+        .any(|part| !matches!(part, Component::Normal(_) | Component::CurDir))
+    {
+        bail!(
+            "{} {:?} must be a relative path inside {:?}",
+            key,
+            path,
+            dir
+        );
+    }
+    let joined = dir.join(path);
+    let root = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+    // The file may not exist yet; check the deepest part of it that does.
+    if let Some(existing) = joined.ancestors().find(|ancestor| ancestor.exists()) {
+        let resolved = fs::canonicalize(existing)
+            .with_context(|| format!("Failed to resolve {:?}", existing))?;
+        if !resolved.starts_with(&root) {
+            bail!(
+                "{} {:?} leads outside {:?} through a symlink",
+                key,
+                path,
+                dir
+            );
+        }
+    }
+    Ok(())
 }
 
-fn apply_stats_section(section: StatsSection, stats: &mut StatsAccum) -> Result<()> {
+fn apply_stats_section(
+    section: StatsSection,
+    local_dir: Option<&Path>,
+    stats: &mut StatsAccum,
+) -> Result<()> {
     if let Some(enabled) = section.enabled {
         stats.enabled = Some(enabled);
     }
@@ -388,6 +1884,19 @@ fn apply_stats_section(section: StatsSection, stats: &mut StatsAccum) -> Result<
         }
         stats.token_ratio = Some(ratio);
     }
+    if let Some(model) = section.model {
+        stats.model = Some(model);
+        stats.model_window = None;
+    }
+    if let Some(window) = section.model_window {
+        stats.model_window = Some(window.resolve()?);
+    }
+    if let Some(output) = section.output {
+        if let Some(dir) = local_dir {
+            check_local_path(dir, &output, "stats.output")?;
+        }
+        stats.output = Some(output);
+    }
     if let Some(tokens_exclude) = section.tokens_exclude {
         let mut exclude_tree = false;
         let mut exclude_headers = false;
@@ -458,19 +1967,107 @@ struct FileConfig {
     dirgrab: Option<DirgrabSection>,
     #[serde(default)]
     stats: Option<StatsSection>,
+    #[serde(default)]
+    output: Option<OutputSection>,
 }
 
 #[derive(Debug, Deserialize)]
 struct DirgrabSection {
     exclude: Option<Vec<String>>,
+    include: Option<Vec<String>>,
     include_untracked: Option<bool>,
     include_tree: Option<bool>,
+    tree_show_excluded: Option<bool>,
+    tree_collapse_chains: Option<bool>,
+    tree_max_depth: Option<usize>,
+    tree_max_entries: Option<usize>,
+    tree_style: Option<String>,
     add_headers: Option<bool>,
+    frontmatter: Option<bool>,
+    prefix_lines: Option<bool>,
+    tight: Option<bool>,
+    no_body: Option<bool>,
+    group_under: Option<usize>,
+    detect_language: Option<bool>,
+    explain_selection: Option<bool>,
+    follow_symlinks: Option<bool>,
+    relativize_symlink_targets: Option<bool>,
+    dedupe_normalized: Option<bool>,
+    respect_editorconfig: Option<bool>,
+    strip_imports: Option<bool>,
+    fold_imports: Option<bool>,
+    comment_style: Option<String>,
+    header_prefix: Option<String>,
+    header_suffix: Option<String>,
+    link_base: Option<String>,
+    wrap_open: Option<String>,
+    wrap_close: Option<String>,
+    label_language: Option<bool>,
+    hash_headers: Option<bool>,
     convert_pdf: Option<bool>,
+    pdf_max_pages: Option<usize>,
+    max_pdf_bytes: Option<CountValue>,
+    pdf_jobs: Option<usize>,
+    binary: Option<String>,
+    read_retries: Option<usize>,
+    image_metadata: Option<bool>,
+    extractors: Option<Vec<String>>,
+    ignore_files: Option<Vec<PathBuf>>,
+    prepend_files: Option<Vec<PathBuf>>,
+    append_files: Option<Vec<PathBuf>>,
     include_default_output: Option<bool>,
     no_git: Option<bool>,
+    gitignore_only: Option<bool>,
+    root_marker: Option<String>,
     tracked_only: Option<bool>,
     all_repo: Option<bool>,
+    changed_in_last: Option<usize>,
+    git_history: Option<usize>,
+    show_force_added: Option<bool>,
+    show_mode: Option<bool>,
+    between: Option<Vec<String>>,
+    since_commit: Option<String>,
+    git_attr: Option<String>,
+    min_files: Option<usize>,
+    max_per_dir: Option<usize>,
+    context_budget: Option<CountValue>,
+    budget_strategy: Option<String>,
+    budget_min_lines: Option<usize>,
+    skip_generated: Option<bool>,
+    generated_markers: Option<Vec<String>>,
+    exclude_matching: Option<Vec<String>>,
+    include_mime: Option<Vec<String>>,
+    exclude_mime: Option<Vec<String>>,
+    sniff_only_above: Option<CountValue>,
+    redact: Option<Vec<String>>,
+    anonymize: Option<Vec<String>>,
+    anonymize_content: Option<bool>,
+    sort: Option<String>,
+    sort_desc: Option<bool>,
+    deprioritize: Option<Vec<String>>,
+    content_for: Option<Vec<String>>,
+    partition: Option<Vec<String>>,
+    tree_partitioned: Option<bool>,
+    collate: Option<String>,
+    readme_first: Option<bool>,
+    warn_unused_excludes: Option<bool>,
+}
+
+/// A count given either as a TOML integer or as a string with a `k`/`m` suffix.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum CountValue {
+    Number(usize),
+    Text(String),
+}
+
+impl CountValue {
+    fn resolve(self) -> Result<usize> {
+        match self {
+            CountValue::Number(value) => Ok(value),
+            CountValue::Text(raw) => parse_count(&raw).map_err(anyhow::Error::msg),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -479,6 +2076,16 @@ struct StatsSection {
     token_ratio: Option<f64>,
     tokens_exclude: Option<Vec<String>>,
     reports: Option<Vec<String>>,
+    model: Option<String>,
+    model_window: Option<CountValue>,
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OutputSection {
+    path: Option<PathBuf>,
+    format: Option<String>,
+    encoding: Option<String>,
 }
 
 #[cfg(test)]
@@ -563,7 +2170,7 @@ tokens_exclude = ["tree"]
         cli.tokens_exclude_headers = true;
         cli.token_ratio = Some(5.0);
         cli.stats = Some(vec![StatsReportSpec::Default]);
-        cli.output = Some(PathBuf::from("out.txt"));
+        cli.output = Some(target.join("out.txt"));
 
         let settings = build_run_settings(&cli, &target)?;
         let config = settings.grab_config;
@@ -576,7 +2183,7 @@ tokens_exclude = ["tree"]
         let patterns: HashSet<_> = config.exclude_patterns.iter().cloned().collect();
         assert!(patterns.contains("Cargo.lock"));
         assert!(patterns.contains("node_modules/"));
-        assert!(patterns.contains("out.txt"));
+        assert!(patterns.contains("/out.txt"));
 
         assert!(stats.enabled);
         assert!(stats.exclude_tree);
@@ -611,7 +2218,7 @@ tokens_exclude = ["tree"]
 
         let mut cli = Cli::test_default();
         cli.no_config = true;
-        cli.output = Some(PathBuf::from("out.txt"));
+        cli.output = Some(target.join("out.txt"));
 
         let settings = build_run_settings(&cli, &target)?;
         let config = settings.grab_config;
@@ -622,7 +2229,7 @@ tokens_exclude = ["tree"]
         assert!(config.include_untracked);
 
         let patterns: HashSet<_> = config.exclude_patterns.iter().cloned().collect();
-        assert!(patterns.contains("out.txt"));
+        assert!(patterns.contains("/out.txt"));
         assert!(!patterns.contains("test-ignore/"));
 
         assert!(!stats.enabled);
@@ -668,6 +2275,59 @@ tokens_exclude = ["tree"]
         Ok(())
     }
 
+    #[test]
+    fn brace_patterns_expand() {
+        assert_eq!(
+            expand_braces("*.{png,jpg,gif}"),
+            ["*.png", "*.jpg", "*.gif"]
+        );
+        assert_eq!(
+            expand_braces("{src,docs}/{a,b{c,d}}"),
+            ["src/a", "src/bc", "src/bd", "docs/a", "docs/bc", "docs/bd"]
+        );
+        assert_eq!(expand_braces("*.{,bak}"), ["*.", "*.bak"]);
+        // Escaped, unbalanced, and comma-less braces stay literal.
+        assert_eq!(expand_braces(r"\{a,b\}"), [r"\{a,b\}"]);
+        assert_eq!(expand_braces(r"{a\,b}"), [r"{a\,b}"]);
+        assert_eq!(expand_braces("{a,b"), ["{a,b"]);
+        assert_eq!(expand_braces("a}b"), ["a}b"]);
+        assert_eq!(expand_braces("{a}"), ["{a}"]);
+        assert_eq!(expand_braces("{x{a,b}}"), ["{xa}", "{xb}"]);
+        // Runaway products are kept literally.
+        let huge = "{a,b}{a,b}{a,b}{a,b}{a,b}{a,b}{a,b}{a,b}{a,b}{a,b}{a,b}";
+        assert_eq!(expand_braces(huge), [huge]);
+
+        assert_eq!(
+            split_top_level_commas("*.{png,jpg},target/,{a,b"),
+            ["*.{png,jpg}", "target/", "{a", "b"]
+        );
+    }
+
+    #[test]
+    fn brace_patterns_expand_from_cli_and_ignore_files() -> Result<()> {
+        let temp = tempdir()?;
+        let target = temp.path().join("project");
+        fs::create_dir_all(&target)?;
+        let _guards = isolate_env(temp.path());
+        fs::write(target.join(".dirgrabignore"), "build/{debug,release}/\n")?;
+
+        let mut cli = Cli::test_default();
+        cli.exclude_patterns = vec!["*.{png,jpg},*.log".to_string()];
+        let patterns = build_run_settings(&cli, &target)?
+            .grab_config
+            .exclude_patterns;
+        for expected in ["*.png", "*.jpg", "*.log", "build/debug/", "build/release/"] {
+            assert!(
+                patterns.iter().any(|p| p == expected),
+                "missing {} in {:?}",
+                expected,
+                patterns
+            );
+        }
+        assert!(!patterns.iter().any(|p| p.contains('{')));
+        Ok(())
+    }
+
     #[test]
     fn multiple_e_flags_still_work() -> Result<()> {
         let temp = tempdir()?;
@@ -703,7 +2363,7 @@ tokens_exclude = ["tree"]
 
         let mut cli = Cli::test_default();
         cli.include_default_output = true;
-        cli.output = Some(PathBuf::from("dirgrab.txt"));
+        cli.output = Some(target.join("dirgrab.txt"));
 
         let settings = build_run_settings(&cli, &target)?;
         let patterns: HashSet<_> = settings
@@ -714,13 +2374,41 @@ tokens_exclude = ["tree"]
             .collect();
 
         assert!(
-            patterns.contains("dirgrab.txt"),
+            patterns.contains("/dirgrab.txt"),
             "active output file should always be excluded, even with --include-default-output"
         );
 
         Ok(())
     }
 
+    #[test]
+    fn output_auto_exclude_uses_the_exact_path_inside_the_target() -> Result<()> {
+        let temp = tempdir()?;
+        let target = temp.path().join("project");
+        fs::create_dir_all(target.join("out"))?;
+        let target = target.canonicalize()?;
+
+        assert_eq!(
+            output_exclude_pattern(&target.join("out/dirgrab.txt"), &target).as_deref(),
+            Some("/out/dirgrab.txt")
+        );
+        assert_eq!(
+            output_exclude_pattern(&temp.path().join("elsewhere.txt"), &target),
+            None
+        );
+
+        let _guards = isolate_env(temp.path());
+        let mut cli = Cli::test_default();
+        cli.output = Some(temp.path().join("outside.txt"));
+        let settings = build_run_settings(&cli, &target)?;
+        assert!(!settings
+            .grab_config
+            .exclude_patterns
+            .iter()
+            .any(|p| p.contains("outside.txt")));
+        Ok(())
+    }
+
     #[test]
     fn no_git_with_git_flags_still_builds_successfully() -> Result<()> {
         let temp = tempdir()?;
@@ -746,4 +2434,707 @@ tokens_exclude = ["tree"]
 
         Ok(())
     }
+
+    #[test]
+    fn dump_config_round_trips_resolved_config() -> Result<()> {
+        let temp = tempdir()?;
+        let target = temp.path().join("project");
+        fs::create_dir_all(&target)?;
+
+        let _guards = isolate_env(temp.path());
+
+        let mut cli = Cli::test_default();
+        cli.no_tree = true;
+        cli.exclude_patterns = vec!["*.log".to_string()];
+
+        let settings = build_run_settings(&cli, &target)?;
+        let dump_path = temp.path().join("config.json");
+        dump_config(&dump_path, &settings.grab_config)?;
+
+        let raw = fs::read_to_string(&dump_path)?;
+        let loaded: GrabConfig = serde_json::from_str(&raw)?;
+        assert_eq!(loaded.target_path, settings.grab_config.target_path);
+        assert_eq!(loaded.exclude_patterns, vec!["*.log".to_string()]);
+        assert!(!loaded.include_tree);
+        assert!(loaded.add_headers);
+
+        let stats_json = serde_json::to_string(&settings.stats)?;
+        let stats: StatsSettings = serde_json::from_str(&stats_json)?;
+        assert_eq!(stats.reports.len(), settings.stats.reports.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn header_prefix_overrides_comment_style() -> Result<()> {
+        let temp = tempdir()?;
+        let target = temp.path().join("project");
+        fs::create_dir_all(&target)?;
+
+        let _guards = isolate_env(temp.path());
+
+        fs::write(
+            target.join(".dirgrab.toml"),
+            "[dirgrab]\ncomment_style = \"html\"\n",
+        )?;
+
+        let cli = Cli::test_default();
+        let settings = build_run_settings(&cli, &target)?;
+        assert_eq!(settings.grab_config.header_style, HeaderStyle::Html);
+
+        let mut cli = Cli::test_default();
+        cli.header_prefix = Some("<!-- >> ".to_string());
+        let settings = build_run_settings(&cli, &target)?;
+        assert_eq!(
+            settings.grab_config.header_style,
+            HeaderStyle::Custom {
+                prefix: "<!-- >> ".to_string(),
+                suffix: " -->".to_string(),
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn files_from_reads_list_into_explicit_files() -> Result<()> {
+        let temp = tempdir()?;
+        let target = temp.path().join("project");
+        fs::create_dir_all(&target)?;
+
+        let _guards = isolate_env(temp.path());
+
+        let list_path = temp.path().join("changed.txt");
+        fs::write(&list_path, "src/main.rs\r\n\n  README.md  \n")?;
+
+        let mut cli = Cli::test_default();
+        cli.files_from = Some(list_path);
+        let settings = build_run_settings(&cli, &target)?;
+        assert_eq!(
+            settings.grab_config.explicit_files,
+            Some(vec![
+                PathBuf::from("src/main.rs"),
+                PathBuf::from("README.md")
+            ])
+        );
+
+        let settings = build_run_settings(&Cli::test_default(), &target)?;
+        assert!(settings.grab_config.explicit_files.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn redact_file_rules_merge_with_config_and_cli() -> Result<()> {
+        let temp = tempdir()?;
+        let target = temp.path().join("project");
+        fs::create_dir_all(&target)?;
+
+        let _guards = isolate_env(temp.path());
+
+        fs::write(
+            target.join(".dirgrab.toml"),
+            "[dirgrab]\nredact = [\"internal\\\\.corp\"]\n",
+        )?;
+        let rules_path = temp.path().join("rules.txt");
+        fs::write(
+            &rules_path,
+            "# shared secrets\n\nghp_[A-Za-z0-9]+ => <github-token>\nAKIA[0-9A-Z]{16}\n",
+        )?;
+
+        let mut cli = Cli::test_default();
+        cli.redact_files = vec![rules_path];
+        cli.redact = vec![parse_redaction_rule("secret=>***").map_err(anyhow::Error::msg)?];
+        let settings = build_run_settings(&cli, &target)?;
+        assert_eq!(
+            settings.grab_config.redactions,
+            vec![
+                RedactionRule::new(r"internal\.corp"),
+                RedactionRule {
+                    pattern: "ghp_[A-Za-z0-9]+".to_string(),
+                    replacement: "<github-token>".to_string(),
+                },
+                RedactionRule::new("AKIA[0-9A-Z]{16}"),
+                RedactionRule {
+                    pattern: "secret".to_string(),
+                    replacement: "***".to_string(),
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn redact_file_reports_invalid_regex_line() -> Result<()> {
+        let temp = tempdir()?;
+        let target = temp.path().join("project");
+        fs::create_dir_all(&target)?;
+
+        let _guards = isolate_env(temp.path());
+
+        let rules_path = temp.path().join("rules.txt");
+        fs::write(&rules_path, "# header\nvalid\n(unclosed\n")?;
+
+        let mut cli = Cli::test_default();
+        cli.redact_files = vec![rules_path];
+        let err = build_run_settings(&cli, &target).unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(message.contains("rules.txt:3:"), "{}", message);
+        assert!(message.contains("(unclosed"), "{}", message);
+        Ok(())
+    }
+
+    #[test]
+    fn output_encoding_adds_bom_and_transcodes() {
+        let text = "hé\n";
+        assert_eq!(OutputEncoding::Utf8.encode(text).as_ref(), text.as_bytes());
+        assert_eq!(
+            OutputEncoding::Utf8Bom.encode(text).as_ref(),
+            [0xEF, 0xBB, 0xBF, b'h', 0xC3, 0xA9, b'\n']
+        );
+        assert_eq!(
+            OutputEncoding::Utf16Le.encode(text).as_ref(),
+            [0xFF, 0xFE, b'h', 0x00, 0xE9, 0x00, b'\n', 0x00]
+        );
+        assert_eq!(
+            OutputEncoding::Utf16Be.encode(text).as_ref(),
+            [0xFE, 0xFF, 0x00, b'h', 0x00, 0xE9, 0x00, b'\n']
+        );
+        assert_eq!(
+            parse_output_encoding("UTF-16LE"),
+            Ok(OutputEncoding::Utf16Le)
+        );
+        assert!(parse_output_encoding("latin1").is_err());
+    }
+
+    #[test]
+    fn model_window_resolves_from_config_and_cli() -> Result<()> {
+        let temp = tempdir()?;
+        let target = temp.path().join("project");
+        fs::create_dir_all(&target)?;
+        let _guards = isolate_env(temp.path());
+
+        fs::write(
+            target.join(".dirgrab.toml"),
+            "[stats]\nmodel = \"local-llm\"\nmodel_window = \"32k\"\n",
+        )?;
+        let cli = Cli::test_default();
+        let window = build_run_settings(&cli, &target)?.stats.context_window;
+        assert_eq!(
+            window,
+            Some(ContextWindow {
+                model: Some("local-llm".to_string()),
+                tokens: 32_000,
+            })
+        );
+
+        // A CLI model drops the configured window and uses the built-in table.
+        let mut cli = Cli::test_default();
+        cli.model = Some("gpt-4o".to_string());
+        let window = build_run_settings(&cli, &target)?.stats.context_window;
+        assert_eq!(window.map(|window| window.tokens), Some(128_000));
+
+        cli.model = Some("unknown-model".to_string());
+        assert!(build_run_settings(&cli, &target).is_err());
+        cli.model_window = Some(8_000);
+        let window = build_run_settings(&cli, &target)?.stats.context_window;
+        assert_eq!(window.map(|window| window.tokens), Some(8_000));
+        Ok(())
+    }
+
+    #[test]
+    fn git_attr_names_are_validated() {
+        assert_eq!(parse_git_attr(" license "), Ok("license".to_string()));
+        for bad in ["", "-license", "!license", "license=MIT", "two words"] {
+            assert!(parse_git_attr(bad).is_err(), "accepted {:?}", bad);
+        }
+    }
+
+    #[test]
+    fn mime_patterns_validate_and_merge() -> Result<()> {
+        let temp = tempdir()?;
+        let target = temp.path().join("project");
+        fs::create_dir_all(&target)?;
+        let _guards = isolate_env(temp.path());
+
+        assert_eq!(parse_mime_pattern(" Image/* "), Ok("image/*".to_string()));
+        assert_eq!(parse_mime_pattern("*"), Ok("*".to_string()));
+        assert!(parse_mime_pattern("image").is_err());
+        assert!(parse_mime_pattern("image/").is_err());
+        assert!(parse_mime_pattern("a/b/c").is_err());
+
+        fs::write(
+            target.join(".dirgrab.toml"),
+            "[dirgrab]\ninclude_mime = [\"text/*\"]\nexclude_mime = [\"Image/*\"]\n",
+        )?;
+        let mut cli = Cli::test_default();
+        cli.exclude_mime = vec!["application/pdf".to_string()];
+        let config = build_run_settings(&cli, &target)?.grab_config;
+        assert_eq!(config.include_mime, vec!["text/*"]);
+        assert_eq!(config.exclude_mime, vec!["image/*", "application/pdf"]);
+        assert_eq!(config.sniff_only_above, None);
+
+        fs::write(
+            target.join(".dirgrab.toml"),
+            "[dirgrab]\nsniff_only_above = \"4k\"\n",
+        )?;
+        let config = build_run_settings(&Cli::test_default(), &target)?.grab_config;
+        assert_eq!(config.sniff_only_above, Some(4_000));
+        cli.sniff_only_above = Some(512);
+        let config = build_run_settings(&cli, &target)?.grab_config;
+        assert_eq!(config.sniff_only_above, Some(512));
+
+        fs::write(
+            target.join(".dirgrab.toml"),
+            "[dirgrab]\nexclude_mime = [\"image\"]\n",
+        )?;
+        assert!(build_run_settings(&Cli::test_default(), &target).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn partition_specs_parse_and_cli_sections_come_first() -> Result<()> {
+        let temp = tempdir()?;
+        let target = temp.path().join("project");
+        fs::create_dir_all(&target)?;
+        let _guards = isolate_env(temp.path());
+
+        let parsed = parse_partition("tests/**: Tests").map_err(anyhow::Error::msg)?;
+        assert_eq!(parsed.pattern, "tests/**");
+        assert_eq!(parsed.label, "Tests");
+        assert_eq!(
+            parse_partition("C:/src/**:Source").map(|p| p.pattern),
+            Ok("C:/src/**".to_string())
+        );
+        assert!(parse_partition("tests/**").is_err());
+        assert!(parse_partition("tests/**:").is_err());
+
+        fs::write(
+            target.join(".dirgrab.toml"),
+            "[dirgrab]\npartition = [\"src/**:Source\"]\ntree_partitioned = true\n",
+        )?;
+        let mut cli = Cli::test_default();
+        cli.partition = vec![parsed];
+        let config = build_run_settings(&cli, &target)?.grab_config;
+        let labels: Vec<&str> = config
+            .partitions
+            .iter()
+            .map(|partition| partition.label.as_str())
+            .collect();
+        assert_eq!(labels, vec!["Tests", "Source"]);
+        assert!(config.tree_partitioned);
+        Ok(())
+    }
+
+    #[test]
+    fn include_patterns_parse_depth_suffix_and_merge_config_with_cli() -> Result<()> {
+        let temp = tempdir()?;
+        let target = temp.path().join("project");
+        fs::create_dir_all(&target)?;
+        let _guards = isolate_env(temp.path());
+
+        assert!(parse_include_pattern("*.rs:depth=0").is_err());
+        assert!(parse_include_pattern("*.rs:depth=x").is_err());
+        assert!(parse_include_pattern(":depth=2").is_err());
+
+        fs::write(
+            target.join(".dirgrab.toml"),
+            "[dirgrab]\ninclude = [\"*.md\"]\n",
+        )?;
+        let mut cli = Cli::test_default();
+        cli.include_patterns = vec!["src/*.rs:depth=2,*.{toml,lock}".to_string()];
+        let includes = build_run_settings(&cli, &target)?
+            .grab_config
+            .include_patterns;
+        let parsed: Vec<(&str, Option<usize>)> = includes
+            .iter()
+            .map(|include| (include.pattern.as_str(), include.max_depth))
+            .collect();
+        assert_eq!(
+            parsed,
+            vec![
+                ("*.md", None),
+                ("src/*.rs", Some(2)),
+                ("*.toml", None),
+                ("*.lock", None)
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn extractor_specs_parse_and_cli_wins_over_config() -> Result<()> {
+        let temp = tempdir()?;
+        let target = temp.path().join("project");
+        fs::create_dir_all(&target)?;
+
+        let _guards = isolate_env(temp.path());
+
+        let parsed = parse_extractor(".RST=rst2txt --quiet").map_err(anyhow::Error::msg)?;
+        assert_eq!(parsed.extension, "rst");
+        assert_eq!(parsed.command, vec!["rst2txt", "--quiet", "{path}"]);
+        assert_eq!(parsed.name(), "rst2txt");
+        assert!(parse_extractor("rst2txt").is_err());
+        assert!(parse_extractor(".rst=").is_err());
+
+        let config_path = temp.path().join("extractors.toml");
+        fs::write(
+            &config_path,
+            "[dirgrab]\nextractors = [\"rst=pandoc -t plain {path}\"]\n",
+        )?;
+        let mut cli = Cli::test_default();
+        cli.config_path = Some(config_path);
+        cli.extractors = vec![parsed];
+        let settings = build_run_settings(&cli, &target)?;
+        let commands: Vec<_> = settings
+            .grab_config
+            .extractors
+            .iter()
+            .map(|e| e.command[0].as_str())
+            .collect();
+        assert_eq!(commands, vec!["rst2txt", "pandoc"]);
+        Ok(())
+    }
+
+    #[test]
+    fn local_config_cannot_register_extractors() -> Result<()> {
+        let temp = tempdir()?;
+        let target = temp.path().join("project");
+        fs::create_dir_all(&target)?;
+
+        let _guards = isolate_env(temp.path());
+
+        fs::write(
+            target.join(".dirgrab.toml"),
+            "[dirgrab]\nextractors = [\"rs=touch pwned\"]\nadd_headers = false\n",
+        )?;
+        let settings = build_run_settings(&Cli::test_default(), &target)?;
+        assert!(settings.grab_config.extractors.is_empty());
+        // The rest of the file still applies.
+        assert!(!settings.grab_config.add_headers);
+        Ok(())
+    }
+
+    #[test]
+    fn output_dash_is_stdout_and_not_auto_excluded() -> Result<()> {
+        let temp = tempdir()?;
+        let target = temp.path().join("project");
+        fs::create_dir_all(&target)?;
+
+        let _guards = isolate_env(temp.path());
+
+        let mut cli = Cli::test_default();
+        cli.output = Some(PathBuf::from("-"));
+        assert_eq!(cli.output_file(), None);
+
+        let settings = build_run_settings(&cli, &target)?;
+        assert!(!settings
+            .grab_config
+            .exclude_patterns
+            .iter()
+            .any(|p| p == "-"));
+        Ok(())
+    }
+
+    #[test]
+    fn context_budget_accepts_suffixes_and_converts_tokens_to_bytes() -> Result<()> {
+        let temp = tempdir()?;
+        let target = temp.path().join("project");
+        fs::create_dir_all(&target)?;
+
+        let _guards = isolate_env(temp.path());
+
+        assert_eq!(parse_count("100k"), Ok(100_000));
+        assert_eq!(parse_count("2M"), Ok(2_000_000));
+        assert_eq!(parse_count("512"), Ok(512));
+        assert!(parse_count("1.5k").is_err());
+
+        fs::write(
+            target.join(".dirgrab.toml"),
+            "[dirgrab]\ncontext_budget = \"2k\"\nbudget_min_lines = 4\n",
+        )?;
+        let mut cli = Cli::test_default();
+        cli.token_ratio = Some(4.0);
+        let settings = build_run_settings(&cli, &target)?;
+        assert_eq!(settings.grab_config.context_budget, Some(8_000));
+        assert_eq!(settings.grab_config.budget_min_lines, 4);
+
+        cli.context_budget = Some(1_000);
+        let settings = build_run_settings(&cli, &target)?;
+        assert_eq!(settings.grab_config.context_budget, Some(4_000));
+
+        cli.context_budget = Some(0);
+        assert!(build_run_settings(&cli, &target).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn zero_tree_and_pdf_limits_lift_config_limits() -> Result<()> {
+        let temp = tempdir()?;
+        let target = temp.path().join("project");
+        fs::create_dir_all(&target)?;
+
+        let _guards = isolate_env(temp.path());
+
+        fs::write(
+            target.join(".dirgrab.toml"),
+            "[dirgrab]\ntree_max_depth = 3\ntree_max_entries = 50\npdf_max_pages = 2\n",
+        )?;
+        let mut cli = Cli::test_default();
+        let settings = build_run_settings(&cli, &target)?;
+        assert_eq!(settings.grab_config.tree_max_depth, Some(3));
+        assert_eq!(settings.grab_config.tree_max_entries, Some(50));
+        assert_eq!(settings.grab_config.pdf_max_pages, Some(2));
+
+        cli.tree_max_depth = Some(0);
+        cli.tree_max_entries = Some(0);
+        cli.pdf_max_pages = Some(0);
+        let settings = build_run_settings(&cli, &target)?;
+        assert_eq!(settings.grab_config.tree_max_depth, None);
+        assert_eq!(settings.grab_config.tree_max_entries, None);
+        assert_eq!(settings.grab_config.pdf_max_pages, None);
+        Ok(())
+    }
+
+    #[test]
+    fn ignore_files_accumulate_from_config_and_cli() -> Result<()> {
+        let temp = tempdir()?;
+        let target = temp.path().join("project");
+        fs::create_dir_all(&target)?;
+
+        let _guards = isolate_env(temp.path());
+
+        fs::write(
+            target.join(".dirgrab.toml"),
+            "[dirgrab]\nignore_files = [\".aiignore\"]\n",
+        )?;
+        let mut cli = Cli::test_default();
+        cli.ignore_files = vec![PathBuf::from("extra.ignore")];
+        let settings = build_run_settings(&cli, &target)?;
+        assert_eq!(
+            settings.grab_config.ignore_files,
+            vec![PathBuf::from(".aiignore"), PathBuf::from("extra.ignore")]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn between_requires_two_revisions() -> Result<()> {
+        let temp = tempdir()?;
+        let target = temp.path().join("project");
+        fs::create_dir_all(&target)?;
+
+        let _guards = isolate_env(temp.path());
+
+        fs::write(
+            target.join(".dirgrab.toml"),
+            "[dirgrab]\nbetween = [\"v1.0\", \"v2.0\"]\n",
+        )?;
+        let mut cli = Cli::test_default();
+        let settings = build_run_settings(&cli, &target)?;
+        assert_eq!(
+            settings.grab_config.between,
+            Some(("v1.0".to_string(), "v2.0".to_string()))
+        );
+
+        cli.between = Some(vec!["main".to_string(), "HEAD".to_string()]);
+        let settings = build_run_settings(&cli, &target)?;
+        assert_eq!(
+            settings.grab_config.between,
+            Some(("main".to_string(), "HEAD".to_string()))
+        );
+
+        fs::write(
+            target.join(".dirgrab.toml"),
+            "[dirgrab]\nbetween = [\"v1.0\"]\n",
+        )?;
+        assert!(build_run_settings(&Cli::test_default(), &target).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn output_section_provides_defaults_that_cli_overrides() -> Result<()> {
+        let temp = tempdir()?;
+        let target = temp.path().join("project");
+        fs::create_dir_all(&target)?;
+
+        let _guards = isolate_env(temp.path());
+
+        fs::write(
+            target.join(".dirgrab.toml"),
+            "[output]\npath = \"context/llm.json\"\nformat = \"json\"\nencoding = \"utf8-bom\"\n",
+        )?;
+
+        let settings = build_run_settings(&Cli::test_default(), &target)?;
+        assert_eq!(
+            settings.output,
+            OutputSettings {
+                path: Some(target.join("context/llm.json")),
+                format: OutputFormat::Json,
+                encoding: OutputEncoding::Utf8Bom,
+            }
+        );
+        assert!(settings
+            .grab_config
+            .exclude_patterns
+            .contains(&"/context/llm.json".to_string()));
+
+        let mut cli = Cli::test_default();
+        cli.output = Some(PathBuf::from("out.txt"));
+        cli.format = Some(OutputFormat::Jsonl);
+        let settings = build_run_settings(&cli, &target)?;
+        assert_eq!(settings.output.path, Some(PathBuf::from("out.txt")));
+        assert_eq!(settings.output.format, OutputFormat::Jsonl);
+        assert_eq!(settings.output.encoding, OutputEncoding::Utf8Bom);
+
+        cli.output = Some(PathBuf::from("-"));
+        assert_eq!(build_run_settings(&cli, &target)?.output.path, None);
+
+        let mut cli = Cli::test_default();
+        cli.clipboard = true;
+        let settings = build_run_settings(&cli, &target)?;
+        assert_eq!(settings.output.path, None);
+        assert_eq!(settings.output.encoding, OutputEncoding::Utf8);
+        assert!(!settings
+            .grab_config
+            .exclude_patterns
+            .contains(&"/context/llm.json".to_string()));
+
+        fs::write(target.join(".dirgrab.toml"), "[output]\nformat = \"xml\"\n")?;
+        let err = build_run_settings(&Cli::test_default(), &target).unwrap_err();
+        assert!(format!("{:#}", err).contains("Unknown output format 'xml'"));
+        Ok(())
+    }
+
+    #[test]
+    fn local_output_path_must_stay_inside_the_target() -> Result<()> {
+        let temp = tempdir()?;
+        let target = temp.path().join("project");
+        fs::create_dir_all(&target)?;
+
+        let _guards = isolate_env(temp.path());
+
+        let outside = temp.path().join("home/.bashrc");
+        for path in [outside.to_string_lossy().into_owned(), "../.bashrc".into()] {
+            fs::write(
+                target.join(".dirgrab.toml"),
+                format!("[output]\npath = {:?}\n", path),
+            )?;
+            let err = build_run_settings(&Cli::test_default(), &target).unwrap_err();
+            assert!(format!("{:#}", err).contains("must be a relative path"));
+        }
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(temp.path().join("home"), target.join("out"))?;
+            fs::write(
+                target.join(".dirgrab.toml"),
+                "[output]\npath = \"out/.bashrc\"\n",
+            )?;
+            let err = build_run_settings(&Cli::test_default(), &target).unwrap_err();
+            assert!(format!("{:#}", err).contains("through a symlink"));
+        }
+
+        // The global config is trusted with any destination.
+        fs::write(target.join(".dirgrab.toml"), "")?;
+        let config_path = temp.path().join("trusted.toml");
+        fs::write(&config_path, "[output]\npath = \"../shared/out.txt\"\n")?;
+        let mut cli = Cli::test_default();
+        cli.config_path = Some(config_path);
+        let settings = build_run_settings(&cli, &target)?;
+        assert_eq!(settings.output.path, Some(target.join("../shared/out.txt")));
+        Ok(())
+    }
+
+    #[test]
+    fn local_config_cannot_inject_files_from_outside_the_target() -> Result<()> {
+        let temp = tempdir()?;
+        let target = temp.path().join("project");
+        fs::create_dir_all(&target)?;
+
+        let _guards = isolate_env(temp.path());
+
+        let secret = temp.path().join("home/.ssh/id_rsa");
+        for key in ["prepend_files", "append_files"] {
+            for path in [
+                secret.to_string_lossy().into_owned(),
+                "../home/.ssh/id_rsa".into(),
+            ] {
+                fs::write(
+                    target.join(".dirgrab.toml"),
+                    format!("[dirgrab]\n{} = [{:?}]\n", key, path),
+                )?;
+                let err = build_run_settings(&Cli::test_default(), &target).unwrap_err();
+                assert!(format!("{:#}", err).contains("must be a relative path"));
+            }
+        }
+
+        // Files inside the checkout are fine.
+        fs::write(
+            target.join(".dirgrab.toml"),
+            "[dirgrab]\nprepend_files = [\"docs/PROMPT.md\"]\n",
+        )?;
+        let settings = build_run_settings(&Cli::test_default(), &target)?;
+        assert_eq!(
+            settings.grab_config.prepend_files,
+            vec![PathBuf::from("docs/PROMPT.md")]
+        );
+
+        // The CLI and --config may point anywhere.
+        fs::write(target.join(".dirgrab.toml"), "")?;
+        let config_path = temp.path().join("trusted.toml");
+        fs::write(
+            &config_path,
+            format!("[dirgrab]\nappend_files = [{:?}]\n", secret),
+        )?;
+        let mut cli = Cli::test_default();
+        cli.config_path = Some(config_path);
+        cli.prepend_files = vec![PathBuf::from("../notes.md")];
+        let settings = build_run_settings(&cli, &target)?;
+        assert_eq!(
+            settings.grab_config.prepend_files,
+            vec![PathBuf::from("../notes.md")]
+        );
+        assert_eq!(settings.grab_config.append_files, vec![secret]);
+        Ok(())
+    }
+
+    #[test]
+    fn stats_output_enables_stats_and_is_auto_excluded() -> Result<()> {
+        let temp = tempdir()?;
+        let target = temp.path().join("project");
+        fs::create_dir_all(&target)?;
+        let target = target.canonicalize()?;
+
+        let _guards = isolate_env(temp.path());
+        let mut cli = Cli::test_default();
+        cli.stats_output = Some(target.join("stats.json"));
+
+        let settings = build_run_settings(&cli, &target)?;
+        assert!(settings.stats.enabled);
+        assert_eq!(settings.stats.output, Some(target.join("stats.json")));
+        assert!(settings
+            .grab_config
+            .exclude_patterns
+            .iter()
+            .any(|p| p == "/stats.json"));
+
+        fs::write(
+            target.join(".dirgrab.toml"),
+            "[stats]\nenabled = true\noutput = \"reports/stats.json\"\n",
+        )?;
+        let settings = build_run_settings(&Cli::test_default(), &target)?;
+        assert_eq!(
+            settings.stats.output,
+            Some(target.join("reports/stats.json"))
+        );
+
+        let outside = temp.path().join("home/.bashrc");
+        fs::write(
+            target.join(".dirgrab.toml"),
+            format!("[stats]\nenabled = true\noutput = {:?}\n", outside),
+        )?;
+        let err = build_run_settings(&Cli::test_default(), &target).unwrap_err();
+        assert!(format!("{:#}", err).contains("stats.output"));
+        Ok(())
+    }
 }
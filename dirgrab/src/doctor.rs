@@ -0,0 +1,125 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use arboard::Clipboard;
+use dirgrab_lib::{detect_git_repo, git_version};
+
+use crate::config_loader::{build_run_settings, config_sources, ConfigSource};
+use crate::Cli;
+
+/// Runs `dirgrab doctor`: prints the environment details a bug report needs
+/// to stdout. Config files are read, but nothing is grabbed.
+pub fn run(cli: &Cli) -> Result<()> {
+    let dir = std::env::current_dir().context("Failed to get current working directory")?;
+    let text = report(cli, &dir, &clipboard_status());
+    io::stdout()
+        .write_all(text.as_bytes())
+        .context("Failed to write diagnostics to stdout")?;
+    Ok(())
+}
+
+/// `available`, or `unavailable (reason)` when no clipboard can be opened
+/// (e.g. a headless session).
+fn clipboard_status() -> String {
+    match Clipboard::new() {
+        Ok(_) => "available".to_string(),
+        Err(e) => format!("unavailable ({})", e),
+    }
+}
+
+fn report(cli: &Cli, dir: &Path, clipboard: &str) -> String {
+    let settings = build_run_settings(cli, dir);
+    let mut out = format!(
+        "dirgrab {}\nPlatform: {} ({})\n",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+
+    let git = git_version();
+    out.push_str(&format!(
+        "Git: {}\n",
+        git.as_deref().unwrap_or("not found on PATH")
+    ));
+    if git.is_some() {
+        let repo = match detect_git_repo(dir) {
+            Ok(Some(root)) => root.display().to_string(),
+            Ok(None) => "none (plain directory mode)".to_string(),
+            Err(e) => format!("error ({})", e),
+        };
+        out.push_str(&format!("Git repository: {}\n", repo));
+    }
+
+    let pdf = match &settings {
+        Ok(settings) if settings.grab_config.convert_pdf => ", enabled",
+        Ok(_) => ", disabled",
+        Err(_) => "",
+    };
+    out.push_str(&format!("PDF extraction: built in (pdf-extract){}\n", pdf));
+    out.push_str(&format!("Clipboard: {}\n", clipboard));
+
+    out.push_str("Config files:\n");
+    let sources = config_sources(cli, dir);
+    if sources.is_empty() {
+        out.push_str("  none (--no-config)\n");
+    }
+    for source in &sources {
+        let kind = match source {
+            ConfigSource::Config(_) | ConfigSource::LocalConfig(_) => "config",
+            ConfigSource::Ignore(_) => "ignore",
+        };
+        let status = if source.path().is_file() {
+            "found"
+        } else {
+            "missing"
+        };
+        out.push_str(&format!(
+            "  {:<7} {} {}\n",
+            status,
+            kind,
+            source.path().display()
+        ));
+    }
+
+    out.push_str(&format!("Resolved config for {}:\n", dir.display()));
+    match settings.and_then(|settings| {
+        serde_json::to_string_pretty(&settings.grab_config).context("Failed to serialize config")
+    }) {
+        Ok(json) => out.push_str(&format!("{}\n", json)),
+        Err(e) => out.push_str(&format!("  error: {:#}\n", e)),
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_lists_config_files_and_resolved_config() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        std::fs::write(
+            temp.path().join(".dirgrab.toml"),
+            "[dirgrab]\nconvert_pdf = false\n",
+        )?;
+
+        let mut cli = Cli::test_default();
+        let text = report(&cli, temp.path(), "unavailable (test)");
+        assert!(text.starts_with(&format!("dirgrab {}\n", env!("CARGO_PKG_VERSION"))));
+        assert!(text.contains("\nGit: "));
+        assert!(text.contains("PDF extraction: built in (pdf-extract), disabled\n"));
+        assert!(text.contains("Clipboard: unavailable (test)\n"));
+        let local = temp.path().join(".dirgrab.toml");
+        assert!(text.contains(&format!("  found   config {}\n", local.display())));
+        let ignore = temp.path().join(".dirgrabignore");
+        assert!(text.contains(&format!("  missing ignore {}\n", ignore.display())));
+        assert!(text.contains("\"convert_pdf\": false"));
+
+        cli.no_config = true;
+        let text = report(&cli, temp.path(), "available");
+        assert!(text.contains("Config files:\n  none (--no-config)\n"));
+        assert!(text.contains("\"convert_pdf\": true"));
+        Ok(())
+    }
+}
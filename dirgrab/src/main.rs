@@ -2,18 +2,41 @@
 
 use anyhow::{Context, Result};
 use arboard::Clipboard;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use config_loader::{
-    build_run_settings, parse_stats_report_spec, StatsReport, StatsReportSpec, StatsSettings,
+    build_run_settings, dump_config, parse_binary_mode, parse_budget_strategy, parse_collate,
+    parse_comment_style, parse_count, parse_extractor, parse_file_order, parse_git_attr,
+    parse_list_format, parse_mime_pattern, parse_output_encoding, parse_output_format,
+    parse_partition, parse_redaction_rule, parse_stats_report_spec, parse_template_var,
+    parse_tree_style, ListFormat, OutputEncoding, OutputFormat, StatsReport, StatsReportSpec,
+    StatsSettings, DEFAULT_TOP_FILES_COUNT,
 };
-use dirgrab_lib::{grab_contents_detailed, list_files, GrabConfig, GrabOutput, GrabbedFile};
-use log::{debug, error, info, LevelFilter};
+use dirgrab_lib::{
+    explain_ignored, grab_contents_with_progress, list_files, list_files_detailed,
+    list_files_with_metadata, measure_contents, mirror_contents, plan_contents, sha256_hex,
+    BinaryMode, BudgetStrategy, Collate, ContentMeasure, DedupeStats, DuplicateGroup, Extractor,
+    FileOrder, GrabConfig, GrabError, GrabOutput, GrabPlan, GrabbedFile, HeaderStyle, IgnoredPath,
+    ListedFile, Partition, RedactionRule, SkippedFile, TransformSavings, TreeStyle,
+};
+use log::{debug, error, info, warn, LevelFilter};
+use serde::Serialize;
 use std::borrow::Cow;
-use std::fs::File;
-use std::io::{self, Write};
-use std::path::PathBuf;
-
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::thread;
+use std::time::Duration;
+
+mod archive;
 mod config_loader;
+mod doctor;
+mod models;
+mod progress;
+
+/// Exit code returned when the `--min-files` guardrail fails.
+const EXIT_TOO_FEW_FILES: u8 = 3;
 
 #[derive(Parser, Debug)]
 #[command(
@@ -27,11 +50,17 @@ mod config_loader;
 pub(crate) struct Cli {
     /// Optional path to the repository or directory to process.
     /// If not provided, the current working directory is used.
+    /// A `.zip`, `.tar.gz`/`.tgz`, or `.tar` file is extracted to a temporary
+    /// directory and grabbed from there.
     #[arg(index = 1)]
     target_path: Option<PathBuf>,
 
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Write output to a file instead of stdout.
     /// If the flag is provided without a filename (e.g., `-o`), defaults to 'dirgrab.txt'.
+    /// `-o -` writes to stdout.
     #[arg(
         short = 'o',
         long,
@@ -42,42 +71,539 @@ pub(crate) struct Cli {
     )]
     output: Option<PathBuf>,
 
+    /// Refuse to overwrite an existing output file (from -o/--output or the
+    /// `[output]` config section).
+    #[arg(long = "no-clobber")]
+    no_clobber: bool,
+
+    /// Print the SHA-256 of the generated content to stderr (`sha256: <hex>`), so
+    /// CI can assert a grab has not changed. Volatile with --frontmatter and
+    /// --git-history.
+    #[arg(long = "print-checksum", conflicts_with_all = ["list", "plan", "mirror"])]
+    print_checksum: bool,
+
+    /// Debug aid: print each file's full/header/front-matter/body byte ranges
+    /// and the length of the text they slice to stderr.
+    #[arg(long = "dump-ranges", hide = true, conflicts_with_all = ["list", "plan", "mirror"])]
+    dump_ranges: bool,
+
     /// Copy output to the system clipboard instead of stdout or a file.
     #[arg(short = 'c', long, conflicts_with = "output")]
     clipboard: bool,
 
+    /// With --clipboard on Linux, keep running after copying so the clipboard
+    /// contents survive until something else is copied (or dirgrab is interrupted).
+    /// X11 and Wayland clear a selection when the process that owns it exits.
+    #[arg(long = "clipboard-wait", requires = "clipboard")]
+    clipboard_wait: bool,
+
+    /// With --clipboard, also put an HTML rendering on the clipboard (each file
+    /// under its own heading, code in `<pre>` blocks) so rich-text editors keep
+    /// the formatting. Plain text is copied alongside and used on its own when
+    /// the platform clipboard has no HTML support.
+    #[arg(long = "clip-html", requires = "clipboard")]
+    clip_html: bool,
+
+    /// Output format: `text` (default), `json` (one document with a `files` array),
+    /// `jsonl` (one JSON object per file), or `claude-xml` (the `<documents>`
+    /// layout of `files-to-prompt --cxml`). JSON entries carry `path`, `lines`,
+    /// and `content`; the tree and headers are omitted.
+    #[arg(long, value_name = "FORMAT", value_parser = parse_output_format)]
+    format: Option<OutputFormat>,
+
+    /// Render the text output through the template in FILE: `{{tree}}` becomes
+    /// the directory tree (without its banners), `{{files}}` the file sections,
+    /// and `{{NAME}}` the value of `--var NAME=VALUE`. A placeholder without a
+    /// value is an error.
+    #[arg(
+        long,
+        value_name = "FILE",
+        value_hint = clap::ValueHint::FilePath,
+        conflicts_with_all = ["list", "plan", "mirror"]
+    )]
+    template: Option<PathBuf>,
+
+    /// Set a `{{NAME}}` placeholder for --template. Can be used multiple times.
+    #[arg(long = "var", value_name = "NAME=VALUE", value_parser = parse_template_var, requires = "template")]
+    vars: Vec<(String, String)>,
+
+    /// Encoding for output written to a file or stdout: `utf8` (default, no BOM),
+    /// `utf8-bom`, `utf16le`, or `utf16be` (UTF-16 variants include a BOM).
+    /// The clipboard always receives UTF-8.
+    #[arg(long = "output-encoding", value_name = "ENCODING", value_parser = parse_output_encoding)]
+    output_encoding: Option<OutputEncoding>,
+
     /// List the files that would be included, one per line, without content.
     /// Useful for previewing file selection before a full grab.
     #[arg(short = 'l', long, conflicts_with_all = ["clipboard", "output"])]
     list: bool,
 
+    /// Format for --list: `text` (default, one path per line) or `json` (an array
+    /// of `{path, bytes, tracked}` objects). Only stats files; never reads contents.
+    #[arg(long = "list-format", value_name = "FORMAT", value_parser = parse_list_format, requires = "list")]
+    list_format: Option<ListFormat>,
+
+    /// Print the directory tree of the selected files annotated with file sizes and
+    /// per-directory subtotals, plus a total, then exit without reading contents.
+    /// Use it to decide what to exclude before a full grab.
+    #[arg(long, conflicts_with_all = ["list", "clipboard", "output", "mirror", "no_tree"])]
+    plan: bool,
+
+    /// Read every selected file and print its character and token counts, then
+    /// exit without assembling the output. Slower than --plan, which only looks
+    /// at sizes on disk, but counts what the files would actually contribute.
+    #[arg(
+        long = "dry-run-stats",
+        conflicts_with_all = ["list", "clipboard", "output", "mirror", "plan"]
+    )]
+    dry_run_stats: bool,
+
+    /// Write a transformed copy of every selected file into OUTDIR, preserving the
+    /// directory structure (the content transformations of a normal grab apply;
+    /// --anonymize also renames copied paths). Binary files are skipped unless
+    /// --mirror-copy-binaries is given.
+    #[arg(
+        long,
+        value_name = "OUTDIR",
+        value_hint = clap::ValueHint::DirPath,
+        conflicts_with_all = ["clipboard", "output", "list"]
+    )]
+    mirror: Option<PathBuf>,
+
+    /// With --mirror, copy binary files verbatim instead of skipping them.
+    #[arg(long = "mirror-copy-binaries", requires = "mirror")]
+    mirror_copy_binaries: bool,
+
+    /// Grab the files listed in FILE (one path per line; `-` reads stdin) instead of
+    /// listing the target. In Git mode, relative paths resolve against the repo root,
+    /// so `git diff --name-only | dirgrab --files-from -` works from any subdirectory.
+    #[arg(long = "files-from", value_name = "FILE")]
+    files_from: Option<PathBuf>,
+
+    /// Choose which of the selected files to grab from a terminal checklist
+    /// (space toggles, enter confirms). Requires stdout to be a terminal.
+    #[arg(long, conflicts_with_all = ["list", "plan", "files_from"])]
+    interactive: bool,
+
     /// Disable the default inclusion of '--- FILE: `<filename>` ---' headers.
     #[arg(long)]
     no_headers: bool,
 
+    /// Emit a YAML front-matter block (`path`, `bytes`, `modified`) before each file
+    /// body, for front-matter-aware loaders. Combine with --no-headers to emit only
+    /// the front matter.
+    #[arg(long)]
+    frontmatter: bool,
+
+    /// Prefix every body line with `path:linenum: ` (like `grep -rn`) instead of
+    /// emitting `--- FILE: ---` headers, so the output can be grepped line by line.
+    /// Replaces the header mechanism, so --comment-style has no effect.
+    #[arg(long = "prefix-lines")]
+    prefix_lines: bool,
+
+    /// Separate files with a single newline instead of a blank line, so every
+    /// file body ends with exactly one `\n`. Saves a token per file.
+    #[arg(long)]
+    tight: bool,
+
+    /// Emit only a header per file, annotated with its size and line count
+    /// (`--- FILE: a.rs (2048 bytes, 64 lines) ---`), and no bodies: a compact
+    /// manifest of the repo that keeps the tree and --stats.
+    #[arg(long = "no-body", conflicts_with_all = ["no_headers", "prefix_lines"])]
+    no_body: bool,
+
+    /// Emit the files of a directory under one `--- DIR: dir/ ---` header, with a
+    /// short `--- name ---` separator per file, when every selected file directly
+    /// in it is smaller than BYTES (accepts `k`/`m` suffixes). Saves header
+    /// tokens in codebases of many tiny files; larger files keep their headers.
+    #[arg(
+        long = "group-under",
+        value_name = "BYTES",
+        value_parser = parse_count,
+        conflicts_with_all = ["no_headers", "prefix_lines"]
+    )]
+    group_under: Option<usize>,
+
+    /// Prepend a `Primary language: Rust (68% of files)` line before the tree,
+    /// counted from the extensions of the selected files.
+    #[arg(long = "detect-language")]
+    detect_language: bool,
+
+    /// Prepend a `SELECTION` block describing how the files were chosen:
+    /// target path, Git mode, untracked files, excludes, change filters, and
+    /// the file count.
+    #[arg(long = "explain-selection")]
+    explain_selection: bool,
+
+    /// Read symlinked files through to their target. By default a symlink is
+    /// emitted as `--- FILE: link (symlink -> target) ---` with the target path
+    /// as its body.
+    #[arg(long = "follow-symlinks")]
+    follow_symlinks: bool,
+
+    /// Show symlink targets relative to the repo root (or target directory)
+    /// when they point inside it; external targets are shown as absolute paths.
+    #[arg(long = "relativize-symlink-targets")]
+    relativize_symlink_targets: bool,
+
+    /// Emit files whose content matches an earlier file once line endings,
+    /// trailing whitespace, and trailing blank lines are normalized as header-only
+    /// `(duplicate of PATH, whitespace-insensitive)` entries. The first copy is
+    /// kept verbatim.
+    #[arg(long = "dedupe-normalized")]
+    dedupe_normalized: bool,
+
+    /// Normalize each file per the project's `.editorconfig` (`end_of_line`,
+    /// `trim_trailing_whitespace`, `insert_final_newline`). Unmatched files pass through.
+    #[arg(long = "respect-editorconfig")]
+    respect_editorconfig: bool,
+
+    /// Remove the leading import block (`use`, `import`, `require`, ...) from
+    /// Rust, Python, and JS/TS files. Imports below the top are kept.
+    #[arg(long = "strip-imports")]
+    strip_imports: bool,
+
+    /// Replace the leading import block of Rust, Python, and JS/TS files with a
+    /// one-line summary comment, e.g. `// imports: serde, tokio, anyhow (+3 more)`.
+    #[arg(long = "fold-imports", conflicts_with = "strip_imports")]
+    fold_imports: bool,
+
+    /// Render file headers as comments for a language family: `plain` (default),
+    /// `rust` (`// --- FILE: x ---`), `python` (`# --- FILE: x ---`), or
+    /// `html` (`<!-- FILE: x -->`).
+    #[arg(long = "comment-style", value_name = "STYLE", value_parser = parse_comment_style)]
+    comment_style: Option<HeaderStyle>,
+
+    /// Custom text placed before `FILE: <path>` in headers (overrides the style's prefix).
+    #[arg(
+        long = "header-prefix",
+        value_name = "TEXT",
+        allow_hyphen_values = true
+    )]
+    header_prefix: Option<String>,
+
+    /// Custom text placed after the path in headers (overrides the style's suffix).
+    #[arg(
+        long = "header-suffix",
+        value_name = "TEXT",
+        allow_hyphen_values = true
+    )]
+    header_suffix: Option<String>,
+
+    /// Render file headers as Markdown links for publishing, e.g. with
+    /// `--link-base https://github.com/org/repo/blob/main/` a header becomes
+    /// `### [src/lib.rs](https://github.com/org/repo/blob/main/src/lib.rs)`.
+    #[arg(
+        long = "link-base",
+        value_name = "URL",
+        conflicts_with_all = ["comment_style", "header_prefix", "header_suffix", "prefix_lines"]
+    )]
+    link_base: Option<String>,
+
+    /// A line emitted before each file, ahead of its header, with `{path}`
+    /// replaced by the file's path, e.g. `--wrap-open "<file path='{path}'>"`.
+    /// Combine with --no-headers to replace headers entirely.
+    #[arg(long = "wrap-open", value_name = "TEXT", allow_hyphen_values = true)]
+    wrap_open: Option<String>,
+
+    /// A line emitted after each file's body (`{path}` is substituted), e.g.
+    /// `--wrap-close "</file>"`.
+    #[arg(long = "wrap-close", value_name = "TEXT", allow_hyphen_values = true)]
+    wrap_close: Option<String>,
+
+    /// Name each file's language in its header, e.g. `--- FILE: main.rs [Rust] ---`.
+    /// Files with unknown extensions get no tag.
+    #[arg(long = "label-language", conflicts_with = "prefix_lines")]
+    label_language: bool,
+
+    /// Add a short SHA-256 of each file's text as read to its header, e.g.
+    /// `--- FILE: main.rs [sha256:3f2a9c01b7de] ---`, for provenance checks.
+    /// `--wrap-open`/`--wrap-close` also accept a `{hash}` placeholder.
+    #[arg(long = "hash-headers", conflicts_with = "prefix_lines")]
+    hash_headers: bool,
+
     /// Disable the default inclusion of the directory structure overview.
     #[arg(long, action = clap::ArgAction::SetTrue)]
     no_tree: bool,
 
+    /// Also list files removed by exclude patterns in the directory tree, marked
+    /// `(excluded)`. The contents section still only includes selected files.
+    #[arg(long = "tree-show-excluded", conflicts_with = "no_tree")]
+    tree_show_excluded: bool,
+
+    /// Render chains of single-child directories on one tree line, e.g.
+    /// `- com/example/app/`, splitting only where a directory has several
+    /// children. Indented tree only; file contents are unaffected.
+    #[arg(
+        long = "tree-collapse-chains",
+        alias = "collapse-single-child-dirs",
+        conflicts_with = "no_tree"
+    )]
+    tree_collapse_chains: bool,
+
+    /// Render at most N levels of the directory tree; deeper entries collapse into
+    /// a `…/ (K files)` node. File contents still include every file. `0` means
+    /// unlimited, e.g. to lift a config file's limit.
+    #[arg(long = "tree-max-depth", value_name = "N", conflicts_with = "no_tree")]
+    tree_max_depth: Option<usize>,
+
+    /// Render at most N lines of the directory tree, then a `… (M more entries)`
+    /// marker. A blunt cap for huge repos; file contents are unaffected. `0`
+    /// means unlimited.
+    #[arg(
+        long = "tree-max-entries",
+        value_name = "N",
+        conflicts_with = "no_tree"
+    )]
+    tree_max_entries: Option<usize>,
+
+    /// Directory tree layout: `indented` (default) or `compact`, which prints one
+    /// `dir/: a.rs, b.rs` line per directory for very wide repos.
+    #[arg(
+        long = "tree-style",
+        value_name = "STYLE",
+        value_parser = parse_tree_style,
+        conflicts_with = "no_tree"
+    )]
+    tree_style: Option<TreeStyle>,
+
     /// Disable the default extraction of text content from PDF files.
     #[arg(long, action = clap::ArgAction::SetTrue)] // New flag to disable PDF extraction
     no_pdf: bool,
 
+    /// Extract at most the first N pages of each PDF; the header notes
+    /// `first N of M pages` when later pages are left out. `0` means unlimited.
+    #[arg(long = "pdf-max-pages", value_name = "N", conflicts_with = "no_pdf")]
+    pdf_max_pages: Option<usize>,
+
+    /// Don't parse PDFs larger than N bytes on disk (k/m suffixes, e.g. `10m`);
+    /// they are listed with a `(PDF too large, skipped)` header instead.
+    #[arg(
+        long = "max-pdf-bytes",
+        value_name = "N",
+        value_parser = parse_count,
+        conflicts_with = "no_pdf"
+    )]
+    max_pdf_bytes: Option<usize>,
+
+    /// Extract PDF text on up to N threads (default: one per CPU). Output order
+    /// is unaffected; `--pdf-jobs 1` extracts one PDF at a time.
+    #[arg(long = "pdf-jobs", value_name = "N", conflicts_with = "no_pdf")]
+    pdf_jobs: Option<usize>,
+
+    /// How to handle binary (non-UTF-8) files: `skip` (default) or `base64`, which
+    /// includes them base64-encoded under a `(base64)` header note. Base64 is about
+    /// a third larger than the original bytes.
+    #[arg(long, value_name = "MODE", value_parser = parse_binary_mode)]
+    binary: Option<BinaryMode>,
+
+    /// Retry a file read up to N times after a transient error (interrupted,
+    /// would block, timed out), e.g. on NFS or sshfs. Default 0: no retries.
+    #[arg(long = "read-retries", value_name = "N")]
+    read_retries: Option<usize>,
+
+    /// Register an external text extractor as `EXT=COMMAND`, e.g.
+    /// `--extractor '.rst=rst2txt {path}'`. The command's stdout becomes the file
+    /// body; on failure the raw content is used. Can be used multiple times.
+    #[arg(long = "extractor", value_name = "EXT=COMMAND", value_parser = parse_extractor)]
+    extractors: Vec<Extractor>,
+
+    /// Note image dimensions and format in the header of recognized image files
+    /// (e.g. `--- FILE: shot.png (image: 1920x1080 PNG) ---`) instead of skipping them.
+    #[arg(long = "image-metadata")]
+    image_metadata: bool,
+
     /// Add patterns to exclude files or directories. Can be used multiple times.
     /// Supports comma-separated patterns: -e "*.log,target/,*.tmp"
-    /// Uses .gitignore glob syntax. Quote patterns to prevent shell expansion.
+    /// Uses .gitignore glob syntax, relative to the target directory in every mode.
+    /// Quote patterns to prevent shell expansion.
     #[arg(short = 'e', long = "exclude", value_name = "PATTERN")]
     exclude_patterns: Vec<String>,
 
+    /// Keep only files matching PATTERN (repeatable, comma-separated like -e),
+    /// after excludes apply. Append `:depth=N` to match only files at most N
+    /// levels deep (1 = directly in the target), e.g. -i '*.md' -i '*.rs:depth=2'.
+    #[arg(short = 'i', long = "include", value_name = "PATTERN[:depth=N]")]
+    include_patterns: Vec<String>,
+
+    /// Read additional exclude patterns from a gitignore-style FILE (e.g. `.aiignore`),
+    /// resolved against the target directory. Missing files are skipped. Can be used
+    /// multiple times.
+    #[arg(long = "ignore-file", value_name = "FILE", value_hint = clap::ValueHint::FilePath)]
+    ignore_files: Vec<PathBuf>,
+
+    /// Grab FILE first, with a header and a tree entry like any other file, even if
+    /// selection filters would drop it. Resolved like --files-from entries (against
+    /// the repo root in Git mode). Can be used multiple times.
+    #[arg(long = "prepend-file", value_name = "FILE", value_hint = clap::ValueHint::FilePath)]
+    prepend_files: Vec<PathBuf>,
+
+    /// Like --prepend-file, but FILE is grabbed after every selected file.
+    #[arg(long = "append-file", value_name = "FILE", value_hint = clap::ValueHint::FilePath)]
+    append_files: Vec<PathBuf>,
+
+    /// Skip generated files: any file whose first kilobyte contains a generated-code
+    /// marker (default: `@generated`, `DO NOT EDIT`). Skipped files are left out of
+    /// both the content and the tree.
+    #[arg(long = "skip-generated")]
+    skip_generated: bool,
+
+    /// Marker text identifying generated files for --skip-generated. Can be used
+    /// multiple times; replaces the default markers.
+    #[arg(long = "generated-marker", value_name = "TEXT")]
+    generated_markers: Vec<String>,
+
+    /// Drop files whose content (after decoding and PDF/extractor conversion)
+    /// matches REGEX; they are reported as skipped. Every candidate file has to be
+    /// read, and binary files never match. Can be used multiple times.
+    #[arg(long = "exclude-matching", value_name = "REGEX")]
+    exclude_matching: Vec<String>,
+
+    /// Only grab files whose MIME type, sniffed from their first 8 KiB, matches
+    /// PATTERN (`image/*`, `application/pdf`, `text/plain`). Unsignatured UTF-8
+    /// files are `text/plain`. Reads every candidate file. Can be used multiple times.
+    #[arg(long = "include-mime", value_name = "PATTERN", value_parser = parse_mime_pattern)]
+    include_mime: Vec<String>,
+
+    /// Drop files whose sniffed MIME type matches PATTERN, e.g. `image/*`; they are
+    /// reported as skipped. Reads every candidate file. Can be used multiple times.
+    #[arg(long = "exclude-mime", value_name = "PATTERN", value_parser = parse_mime_pattern)]
+    exclude_mime: Vec<String>,
+
+    /// With --include-mime/--exclude-mime, only sniff files larger than N bytes
+    /// (k/m suffixes, e.g. `4k`); smaller files are assumed to be text/plain.
+    /// Faster on huge trees, but small binaries can slip through.
+    #[arg(long = "sniff-only-above", value_name = "N", value_parser = parse_count)]
+    sniff_only_above: Option<usize>,
+
+    /// Redact matches of REGEX in file contents. Accepts `REGEX` (replaced with
+    /// `[REDACTED]`) or `REGEX=>REPLACEMENT`, where the replacement may use `$1`.
+    /// Can be used multiple times.
+    #[arg(long = "redact", value_name = "REGEX", value_parser = parse_redaction_rule)]
+    redact: Vec<RedactionRule>,
+
+    /// Load redaction rules from FILE, one `--redact` rule per line. Blank lines and
+    /// lines starting with `#` are skipped. Can be used multiple times.
+    #[arg(long = "redact-file", value_name = "FILE", value_hint = clap::ValueHint::FilePath)]
+    redact_files: Vec<PathBuf>,
+
+    /// Rewrite matches of REGEX in displayed paths (headers, tree, listings) as
+    /// `REGEX=>REPLACEMENT`, e.g. `acme-internal=>project`. Can be used multiple times.
+    #[arg(long = "anonymize", value_name = "REGEX=>REPLACEMENT", value_parser = parse_redaction_rule)]
+    anonymize: Vec<RedactionRule>,
+
+    /// Also apply `--anonymize` rules to file contents.
+    #[arg(long = "anonymize-content")]
+    anonymize_content: bool,
+
+    /// Warn (on stderr) about exclude patterns that did not exclude any file.
+    /// Costs one extra, unfiltered listing pass.
+    #[arg(long = "warn-unused-excludes")]
+    warn_unused_excludes: bool,
+
     /// Include the default output file ('dirgrab.txt') if it exists and isn't otherwise excluded.
     #[arg(long)]
     include_default_output: bool,
 
+    /// Order files in the output by `path` (default), `size`, `mtime`, `ext`, or
+    /// `git-recency` (last commit time, most recent last).
+    #[arg(long, value_name = "ORDER", value_parser = parse_file_order)]
+    sort: Option<FileOrder>,
+
+    /// Reverse the --sort order (ties still fall back to path order).
+    #[arg(long = "sort-desc")]
+    sort_desc: bool,
+
+    /// Move files matching PATTERN (exclude glob syntax) to the end of the
+    /// output, after every other file (repeatable), e.g. `--deprioritize
+    /// '*.lock' --deprioritize 'vendor/**'`.
+    #[arg(long = "deprioritize", value_name = "PATTERN")]
+    deprioritize: Vec<String>,
+
+    /// Emit full content only for files matching PATTERN (exclude glob syntax,
+    /// repeatable); other selected files appear in the tree without a body,
+    /// e.g. `--content-for 'src/**'`.
+    #[arg(
+        long = "content-for",
+        alias = "content-only-for",
+        value_name = "PATTERN"
+    )]
+    content_for: Vec<String>,
+
+    /// Put each directory's README* files right before that directory's other
+    /// files, with a `(directory overview)` note in their headers.
+    #[arg(long = "readme-first")]
+    readme_first: bool,
+
+    /// Group files into a named output section opened by a `===== LABEL =====`
+    /// banner (repeatable; sections appear in flag order, each file joins the
+    /// first matching one), e.g. `--partition 'src/**:Source' --partition
+    /// 'tests/**:Tests'`. Unmatched files go to a final `Other` section.
+    #[arg(
+        long = "partition",
+        value_name = "PATTERN:LABEL",
+        value_parser = parse_partition,
+        group = "sections"
+    )]
+    partition: Vec<Partition>,
+
+    /// Group files into generated sections instead: `ext` opens one
+    /// `===== .rs =====` section per extension, each path-sorted. The tree stays
+    /// hierarchical unless --tree-partitioned is set.
+    #[arg(long = "collate", value_name = "KEY", value_parser = parse_collate, group = "sections")]
+    collate: Option<Collate>,
+
+    /// With --partition or --collate, split the directory tree into one subtree
+    /// per section.
+    #[arg(
+        long = "tree-partitioned",
+        requires = "sections",
+        conflicts_with = "no_tree"
+    )]
+    tree_partitioned: bool,
+
+    /// Fail (exit code 3) if fewer than N files are selected. Checked after listing,
+    /// before any file contents are read.
+    #[arg(long = "min-files", value_name = "N")]
+    min_files: Option<usize>,
+
+    /// Keep at most N files per directory (the first N in output order). Omitted
+    /// files are summarized in a trailer after the file contents.
+    #[arg(long = "max-per-dir", value_name = "N")]
+    max_per_dir: Option<usize>,
+
+    /// Trim every file body so the whole output fits in about N tokens (estimated
+    /// with the token ratio). Accepts k/m suffixes, e.g. `100k`. Every file stays
+    /// in the output; trimmed files are listed in a trailer.
+    #[arg(long = "context-budget", value_name = "TOKENS", value_parser = parse_count)]
+    context_budget: Option<usize>,
+
+    /// How --context-budget is shared between files. `proportional` (the only
+    /// strategy) shrinks every body by the same fraction.
+    #[arg(long = "budget-strategy", value_name = "STRATEGY", value_parser = parse_budget_strategy)]
+    budget_strategy: Option<BudgetStrategy>,
+
+    /// Lines always kept from the start of each file under --context-budget (default 10).
+    #[arg(long = "budget-min-lines", value_name = "K")]
+    budget_min_lines: Option<usize>,
+
     /// Ignore Git context and treat the target as a plain directory.
     /// This disables .gitignore processing and the effect of -u/--include-untracked.
     #[arg(long)]
     no_git: bool,
 
+    /// Select files using the full .gitignore ruleset (nested, info/exclude, global)
+    /// without running git. Works with or without --no-git; paths are relative to
+    /// the target.
+    #[arg(long = "gitignore-only")]
+    gitignore_only: bool,
+
+    /// Outside Git mode, make paths relative to the nearest directory at or
+    /// above the target that contains NAME (e.g. `.project-root`) instead of
+    /// the target itself. Ignored in Git mode.
+    #[arg(long = "root-marker", value_name = "NAME")]
+    root_marker: Option<String>,
+
     /// Limit Git mode to tracked files only.
     #[arg(long, conflicts_with = "include_untracked_flag")]
     tracked_only: bool,
@@ -86,7 +612,52 @@ pub(crate) struct Cli {
     #[arg(long)]
     all_repo: bool,
 
-    /// Print statistics to stderr. Accepts reports such as `overview` and `top-files=N`.
+    /// Git mode: keep only files changed in the last N commits (deleted files are
+    /// dropped, excludes still apply).
+    #[arg(long = "changed-in-last", value_name = "N")]
+    changed_in_last: Option<usize>,
+
+    /// Git mode: append a `--- HISTORY: path ---` block after each file listing its
+    /// last N commit subjects. Runs one `git log` per file, so it is slow on large
+    /// selections.
+    #[arg(long = "git-history", value_name = "N")]
+    git_history: Option<usize>,
+
+    /// Git mode: note tracked files that match an ignore rule (force-added
+    /// with `git add -f`) as "force-added" in their headers.
+    #[arg(long = "show-force-added")]
+    show_force_added: bool,
+
+    /// Append each file's permissions to its header, e.g. `(mode 755)`; on Windows,
+    /// read-only files are marked `(read-only)` instead.
+    #[arg(long = "show-mode")]
+    show_mode: bool,
+
+    /// Git mode: print to stderr every untracked file or directory that Git ignores
+    /// within the target, with the rule that matched (`.gitignore:3: *.log`, per
+    /// `git check-ignore -v`). Diagnostic only; the selection is unchanged.
+    #[arg(long = "why-ignored")]
+    why_ignored: bool,
+
+    /// Git mode: keep only files that differ between two revisions
+    /// (`git diff --name-only FROM..TO`, deleted files dropped). This compares the
+    /// two trees directly; it is not the `FROM...TO` merge-base form.
+    #[arg(long, num_args = 2, value_names = ["FROM", "TO"])]
+    between: Option<Vec<String>>,
+
+    /// Git mode: keep only files changed after COMMIT up to HEAD
+    /// (`git diff --name-only COMMIT..HEAD`, deleted files dropped), plus untracked
+    /// files. COMMIT itself is the base: its own changes are not included.
+    #[arg(long = "since-commit", value_name = "COMMIT")]
+    since_commit: Option<String>,
+
+    /// Git mode: keep only files whose `.gitattributes` set ATTR (e.g. `license`),
+    /// per `git check-attr`. No effect outside Git mode.
+    #[arg(long = "git-attr", value_name = "ATTR", value_parser = parse_git_attr)]
+    git_attr: Option<String>,
+
+    /// Print statistics to stderr. Accepts reports such as `overview`, `top-files=N`,
+    /// `savings`, `longest-lines[=N]`, `duplicates`, and `tree-cost`.
     /// With no values, prints the default bundle (`overview` plus `top-files=5`).
     #[arg(
         short = 's',
@@ -106,6 +677,11 @@ pub(crate) struct Cli {
     #[arg(long = "config", value_name = "FILE", value_hint = clap::ValueHint::FilePath)]
     config_path: Option<PathBuf>,
 
+    /// Write the fully resolved configuration (after merging config files and flags)
+    /// to FILE as JSON before running.
+    #[arg(long = "dump-config", value_name = "FILE", value_hint = clap::ValueHint::FilePath)]
+    dump_config: Option<PathBuf>,
+
     /// Token ratio override for approximate token counting used with --stats.
     #[arg(long = "token-ratio", value_name = "FLOAT")]
     token_ratio: Option<f64>,
@@ -118,6 +694,20 @@ pub(crate) struct Cli {
     #[arg(long = "tokens-exclude-headers")]
     tokens_exclude_headers: bool,
 
+    /// Report in the --stats overview how much of MODEL's context window the grab
+    /// fills, e.g. `--model gpt-4o`. Unknown models need --model-window.
+    #[arg(long = "model", value_name = "MODEL")]
+    model: Option<String>,
+
+    /// Context window size in tokens for --model (or on its own), e.g. `200k`.
+    #[arg(long = "model-window", value_name = "N", value_parser = parse_count)]
+    model_window: Option<usize>,
+
+    /// Write the stats as JSON to FILE instead of stderr, leaving stderr for logs.
+    /// Implies --stats; the file is auto-excluded from the grab like -o.
+    #[arg(long = "stats-output", value_name = "FILE", value_hint = clap::ValueHint::FilePath)]
+    stats_output: Option<PathBuf>,
+
     /// Legacy flag to force including untracked files (now default). Hidden for compatibility.
     #[arg(
         short = 'u',
@@ -131,12 +721,57 @@ pub(crate) struct Cli {
     // /// Optionally extract text content from PDF files using pdf-extract.
     // #[arg(long, action = clap::ArgAction::SetTrue)]
     // convert_pdf: bool,
+    /// Never show the progress bar that appears on a terminal while the contents
+    /// of large grabs are read.
+    #[arg(long = "no-progress")]
+    no_progress: bool,
+
     /// Enable verbose output. Use -v for info, -vv for debug, -vvv for trace.
     #[arg(short, long, action = clap::ArgAction::Count)]
     verbose: u8,
 }
 
-fn main() -> Result<()> {
+impl Cli {
+    /// The file to write output to, or `None` for stdout (no `-o`, or `-o -`).
+    pub(crate) fn output_file(&self) -> Option<&Path> {
+        self.output
+            .as_deref()
+            .filter(|path| *path != Path::new(STDOUT_PATH))
+    }
+}
+
+/// Conventional `--output` value meaning standard output.
+const STDOUT_PATH: &str = "-";
+
+/// Subcommands; without one, dirgrab grabs the target. A directory that shares
+/// a subcommand's name can still be grabbed as `./doctor`.
+#[derive(Subcommand, Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    /// Print environment diagnostics for bug reports: the Git version and
+    /// repository, PDF and clipboard support, the config files consulted, and
+    /// the resolved config for the current directory. Grabs nothing.
+    Doctor,
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        // Reported once `run` has returned, so its temporaries (such as an
+        // extracted archive) have been cleaned up.
+        Err(err) => match err.downcast_ref::<GrabError>() {
+            Some(guardrail @ GrabError::TooFewFiles { .. }) => {
+                eprintln!("Error: {}", guardrail);
+                ExitCode::from(EXIT_TOO_FEW_FILES)
+            }
+            _ => {
+                eprintln!("Error: {:?}", err);
+                ExitCode::FAILURE
+            }
+        },
+    }
+}
+
+fn run() -> Result<()> {
     let cli = Cli::parse();
 
     // Initialize Logging
@@ -151,6 +786,10 @@ fn main() -> Result<()> {
     info!("Log level set to: {}", log_level);
     debug!("Parsed arguments: {:?}", cli);
 
+    if cli.command == Some(Command::Doctor) {
+        return doctor::run(&cli);
+    }
+
     // Determine Target Path
     let target_path = match &cli.target_path {
         Some(path) => path.clone(),
@@ -158,9 +797,53 @@ fn main() -> Result<()> {
     };
     info!("Target path determined as: {:?}", target_path);
 
-    let run_settings = build_run_settings(&cli, &target_path)?;
-    let config = run_settings.grab_config;
+    // An archive target is extracted to a temp dir (removed when `extracted`
+    // drops at the end of run) and grabbed from there. Config files are still
+    // looked up next to the archive, never inside it.
+    let extracted = archive::extract_if_archive(&target_path)?;
+    let config_base = match &extracted {
+        Some(_) => target_path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .map_or_else(|| PathBuf::from("."), Path::to_path_buf),
+        None => target_path.clone(),
+    };
+
+    if cli.clipboard
+        && cli
+            .output_encoding
+            .is_some_and(|e| e != OutputEncoding::Utf8)
+    {
+        anyhow::bail!(
+            "--output-encoding cannot be combined with --clipboard (the clipboard is always UTF-8)"
+        );
+    }
+
+    let run_settings = build_run_settings(&cli, &config_base)?;
+    let mut config = run_settings.grab_config;
+    if let Some(dir) = &extracted {
+        config.target_path = dir.path().to_path_buf();
+    }
     let stats_settings = run_settings.stats;
+    let output_settings = run_settings.output;
+
+    let template = match cli.template.as_deref() {
+        Some(_) if output_settings.format != OutputFormat::Text => {
+            anyhow::bail!(
+                "--template only applies to text output, not --format json/jsonl/claude-xml"
+            );
+        }
+        Some(path) => Some(
+            fs::read_to_string(path)
+                .with_context(|| format!("Failed to read template file: {:?}", path))?,
+        ),
+        None => None,
+    };
+
+    if let Some(ref dump_path) = cli.dump_config {
+        dump_config(dump_path, &config)?;
+        info!("Resolved configuration written to {:?}", dump_path);
+    }
 
     if config.add_headers {
         info!("File headers will be included.");
@@ -192,60 +875,233 @@ fn main() -> Result<()> {
         info!("Git scope set to entire repository (--all-repo).");
     }
 
+    if cli.why_ignored {
+        let ignored = explain_ignored(&config).context("Failed to explain ignored files")?;
+        for line in why_ignored_lines(&ignored) {
+            eprintln!("{}", line);
+        }
+    }
+
+    let config = if cli.interactive {
+        select_interactively(config)?
+    } else {
+        config
+    };
+
     // Handle --list mode: print file paths and exit early
+    if cli.list && cli.list_format == Some(ListFormat::Json) {
+        let listing = match list_files_with_metadata(&config) {
+            Ok(listing) => listing,
+            Err(e) => {
+                return Err(anyhow::Error::new(e).context("Failed to list files"));
+            }
+        };
+        report_unused_excludes(output_settings.path.as_deref(), &listing.unused_excludes);
+        println!("{}", render_listing_json(&listing.files)?);
+        return Ok(());
+    }
     if cli.list {
-        let paths = list_files(&config).context("Failed to list files")?;
-        for path in &paths {
+        let listing = match list_files_detailed(&config) {
+            Ok(listing) => listing,
+            Err(e) => {
+                return Err(anyhow::Error::new(e).context("Failed to list files"));
+            }
+        };
+        report_unused_excludes(output_settings.path.as_deref(), &listing.unused_excludes);
+        for path in &listing.files {
             println!("{}", path);
         }
         return Ok(());
     }
 
-    // Call Library
-    let grab_output = match grab_contents_detailed(&config) {
+    if cli.plan {
+        let plan = match plan_contents(&config) {
+            Ok(plan) => plan,
+            Err(e) => {
+                return Err(anyhow::Error::new(e).context("Failed to plan grab"));
+            }
+        };
+        print!("{}", plan.tree);
+        println!("{}", plan_total_line(&plan, &stats_settings));
+        return Ok(());
+    }
+
+    if cli.dry_run_stats {
+        let measure = match measure_contents(&config) {
+            Ok(measure) => measure,
+            Err(e) => {
+                return Err(anyhow::Error::new(e).context("Failed to measure files"));
+            }
+        };
+        for line in dry_run_stats_lines(&measure, &stats_settings) {
+            println!("{}", line);
+        }
+        return Ok(());
+    }
+
+    if let Some(out_dir) = cli.mirror.as_deref() {
+        let report = match mirror_contents(&config, out_dir, cli.mirror_copy_binaries) {
+            Ok(report) => report,
+            Err(e) => {
+                return Err(anyhow::Error::new(e).context("Failed to mirror files"));
+            }
+        };
+        for entry in &report.skipped {
+            info!(
+                "  Not mirrored: {} — {}{}",
+                entry.display_path,
+                entry.reason,
+                mime_suffix(entry)
+            );
+        }
+        eprintln!(
+            "Mirrored into {}: {} file(s) written, {} binary file(s) copied, {} skipped",
+            out_dir.display(),
+            report.written,
+            report.copied,
+            report.skipped.len()
+        );
+        if !config.anonymize.is_empty() {
+            eprintln!("Anonymized {} occurrence(s).", report.anonymized);
+        }
+        return Ok(());
+    }
+
+    // Call Library. The bar would garble interleaved log lines, so -v disables it.
+    let mut progress = progress::ProgressReporter::new(!cli.no_progress && cli.verbose == 0);
+    let result = grab_contents_with_progress(&config, |event| progress.update(event));
+    drop(progress);
+    let grab_output = match result {
         Ok(output) => output,
         Err(e) => {
-            error!("Error during dirgrab operation: {}", e);
+            if !matches!(e, GrabError::TooFewFiles { .. }) {
+                error!("Error during dirgrab operation: {}", e);
+            }
             return Err(e.into());
         }
     };
     let GrabOutput {
-        content: combined_content,
+        selection,
+        language_summary,
+        tree,
+        content: file_content,
         files: file_segments,
-    } = grab_output;
+        skipped,
+        unused_excludes,
+        truncated,
+        anonymized,
+        ..
+    } = &grab_output;
+    report_unused_excludes(output_settings.path.as_deref(), unused_excludes);
+
+    if !config.anonymize.is_empty() {
+        eprintln!("Anonymized {} occurrence(s).", anonymized);
+    }
+
+    if !truncated.is_empty() {
+        info!(
+            "Trimmed {} file(s) to fit --context-budget.",
+            truncated.len()
+        );
+    }
+
+    if !skipped.is_empty() {
+        info!("Skipped {} selected file(s):", skipped.len());
+        for entry in skipped {
+            info!(
+                "  {} — {}{}",
+                entry.display_path,
+                entry.reason,
+                mime_suffix(entry)
+            );
+        }
+    }
+
+    // The tree is kept apart from the file sections; text output shows both,
+    // structured formats and per-file stats only need the file sections.
+    let preamble = format!(
+        "{}{}",
+        selection.as_deref().unwrap_or(""),
+        language_summary.as_deref().unwrap_or("")
+    );
+    let tree_text = tree.as_deref().unwrap_or("");
+    let combined_content = match template.as_deref() {
+        Some(template) => {
+            let tree = format!("{}{}", preamble, bare_tree(tree_text));
+            render_template(template, &tree, file_content, &cli.vars)?
+        }
+        None => format!("{}{}{}", preamble, tree_text, file_content),
+    };
+
+    if cli.dump_ranges {
+        for line in dump_ranges_lines(file_content, file_segments) {
+            eprintln!("{}", line);
+        }
+    }
+
+    if cli.print_checksum {
+        if let Some(flag) = volatile_content_flag(&config) {
+            warn!(
+                "{} embeds content that changes without the files changing; the checksum is volatile.",
+                flag
+            );
+        }
+        eprintln!("{}", checksum_line(&combined_content));
+    }
+
+    let output_format = output_settings.format;
 
     // Check if content is empty *after* potential tree generation
-    if combined_content.is_empty() {
+    if combined_content.is_empty() && output_format == OutputFormat::Text {
         info!("No content was generated.");
         // Print stats even if empty, but only if requested
-        if stats_settings.enabled {
+        if stats_settings.enabled && stats_settings.output.is_some() {
+            print_stats_reports(&grab_output, &config, &stats_settings, "none")?;
+        } else if stats_settings.enabled {
             eprintln!("Output Size: 0 bytes, 0 words, tokens≈0");
         }
         return Ok(());
     }
 
+    let rendered = match output_format {
+        OutputFormat::Text => Cow::Borrowed(combined_content.as_str()),
+        format => Cow::Owned(render_structured(
+            format,
+            file_content,
+            file_segments,
+            skipped,
+            config.tight,
+        )?),
+    };
+
     // Handle Output
     let output_destination = if cli.clipboard {
         info!("Copying output to clipboard...");
-        let mut clipboard = Clipboard::new().context("Failed to initialize clipboard")?;
-        clipboard
-            .set_text(&combined_content)
-            .context("Failed to copy content to clipboard")?;
+        let html = clipboard_html(
+            &cli,
+            output_format,
+            template.is_some(),
+            &format!("{}{}", preamble, tree_text),
+            file_content,
+            file_segments,
+        );
+        copy_to_clipboard(rendered.as_ref(), html.as_deref(), cli.clipboard_wait)?;
         info!("Successfully copied content to clipboard.");
         "Clipboard".to_string()
-    } else if let Some(ref output_path) = cli.output {
+    } else if let Some(output_path) = output_settings.path.as_deref() {
         info!("Writing output to file: {:?}", output_path);
-        let mut file = File::create(output_path)
-            .with_context(|| format!("Failed to create output file: {:?}", output_path))?;
-        file.write_all(combined_content.as_bytes())
-            .with_context(|| format!("Failed to write content to file: {:?}", output_path))?;
+        write_output_file(
+            output_path,
+            &output_settings.encoding.encode(&rendered),
+            cli.no_clobber,
+        )?;
         info!("Successfully wrote content to {:?}", output_path);
         format!("File ({})", output_path.display())
     } else {
         // Default to stdout
         debug!("Writing output to stdout...");
         io::stdout()
-            .write_all(combined_content.as_bytes())
+            .write_all(&output_settings.encoding.encode(&rendered))
             .context("Failed to write content to stdout")?;
         io::stdout().flush().context("Failed to flush stdout")?;
         debug!("Finished writing to stdout.");
@@ -254,136 +1110,1019 @@ fn main() -> Result<()> {
 
     // Calculate and print stats to stderr *only if requested*
     if stats_settings.enabled {
-        print_stats_reports(
-            &combined_content,
-            &file_segments,
-            &config,
-            &stats_settings,
-            &output_destination,
-        );
+        print_stats_reports(&grab_output, &config, &stats_settings, &output_destination)?;
     }
 
     Ok(())
 }
 
-fn build_token_basis<'a>(
-    full_output: &'a str,
-    config: &GrabConfig,
-    stats: &StatsSettings,
-) -> Cow<'a, str> {
-    let mut current = Cow::Borrowed(full_output);
+const CLIPBOARD_ATTEMPTS: u32 = 3;
 
-    if stats.exclude_tree && config.include_tree {
-        let trimmed = strip_tree_section(current.as_ref());
-        current = Cow::Owned(trimmed);
+/// The `--clip-html` rendering, or `None` when plain text should be copied:
+/// structured formats and templates have no per-file HTML equivalent.
+fn clipboard_html(
+    cli: &Cli,
+    format: OutputFormat,
+    templated: bool,
+    preamble: &str,
+    content: &str,
+    file_segments: &[GrabbedFile],
+) -> Option<String> {
+    if !cli.clip_html {
+        return None;
+    }
+    if format != OutputFormat::Text || templated {
+        warn!(
+            "--clip-html only applies to plain text output without a template; copying plain text."
+        );
+        return None;
     }
+    Some(render_html(preamble, content, file_segments))
+}
 
-    if stats.exclude_headers && config.add_headers {
-        let without_headers = strip_header_lines(current.as_ref());
-        current = Cow::Owned(without_headers);
+/// Renders the grab as an HTML fragment for rich-text paste targets: the
+/// selection/tree preamble in one `<pre>`, then each file as a heading and a
+/// `<pre><code>` block tagged `language-<ext>` for editors that highlight.
+fn render_html(preamble: &str, content: &str, file_segments: &[GrabbedFile]) -> String {
+    let mut html = String::from("<meta charset=\"utf-8\">\n");
+    if !preamble.trim().is_empty() {
+        html.push_str(&format!(
+            "<pre>{}</pre>\n",
+            escape_html(preamble.trim_end())
+        ));
+    }
+    for file in file_segments {
+        let body = &content[file.body_range.clone()];
+        let class = Path::new(&file.display_path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| format!(" class=\"language-{}\"", escape_html(ext)))
+            .unwrap_or_default();
+        html.push_str(&format!(
+            "<h3><code>{}</code></h3>\n<pre><code{}>{}</code></pre>\n",
+            escape_html(&file.display_path),
+            class,
+            escape_html(body.trim_end_matches('\n'))
+        ));
     }
+    html
+}
 
-    current
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
 }
 
-fn strip_tree_section(content: &str) -> String {
-    const FILE_CONTENTS_HEADER: &str = "---\nFILE CONTENTS\n---\n\n";
-    if let Some(idx) = content.find(FILE_CONTENTS_HEADER) {
-        content[idx + FILE_CONTENTS_HEADER.len()..].to_string()
-    } else {
-        content.to_string()
+/// Copies `text` (plus `html`, if given) to the clipboard, retrying with a
+/// short backoff because X11 and Wayland clipboards intermittently refuse
+/// access while another app holds them.
+fn copy_to_clipboard(text: &str, html: Option<&str>, wait: bool) -> Result<()> {
+    let mut attempt = 1;
+    loop {
+        let result = match html {
+            Some(html) => set_clipboard_html(html, text, wait).or_else(|e| {
+                warn!(
+                    "Clipboard rejected HTML ({}); copying plain text instead.",
+                    e
+                );
+                set_clipboard_text(text, wait)
+            }),
+            None => set_clipboard_text(text, wait),
+        };
+        match result {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt < CLIPBOARD_ATTEMPTS => {
+                debug!("Clipboard attempt {} failed: {}", attempt, e);
+                thread::sleep(Duration::from_millis(100 * u64::from(attempt)));
+                attempt += 1;
+            }
+            Err(e) => {
+                return Err(anyhow::Error::new(e).context(format!(
+                    "Failed to copy content to clipboard after {} attempts. On Linux this usually \
+                     means no X11/Wayland display is reachable (check DISPLAY / WAYLAND_DISPLAY) \
+                     or another application is holding the clipboard",
+                    CLIPBOARD_ATTEMPTS
+                )));
+            }
+        }
     }
 }
 
-fn strip_header_lines(content: &str) -> String {
-    content
-        .split_inclusive('\n')
-        .filter(|chunk| {
-            let line = chunk.trim_end_matches('\n');
-            !(line.starts_with("--- FILE: ") && line.ends_with(" ---"))
-        })
-        .collect()
+fn set_clipboard_text(text: &str, wait: bool) -> Result<(), arboard::Error> {
+    let mut clipboard = Clipboard::new()?;
+    #[cfg(target_os = "linux")]
+    if wait {
+        use arboard::SetExtLinux;
+        eprintln!("Serving clipboard contents until they are replaced (Ctrl-C to stop)...");
+        return clipboard.set().wait().text(text);
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = wait; // Other platforms keep the contents after dirgrab exits.
+    clipboard.set_text(text)
 }
 
-fn format_ratio(ratio: f64) -> String {
-    let mut s = format!("{:.3}", ratio);
-    while s.contains('.') && s.ends_with('0') {
-        s.pop();
+fn set_clipboard_html(html: &str, alt_text: &str, wait: bool) -> Result<(), arboard::Error> {
+    let mut clipboard = Clipboard::new()?;
+    #[cfg(target_os = "linux")]
+    if wait {
+        use arboard::SetExtLinux;
+        eprintln!("Serving clipboard contents until they are replaced (Ctrl-C to stop)...");
+        return clipboard.set().wait().html(html, Some(alt_text));
     }
-    if s.ends_with('.') {
-        s.pop();
+    #[cfg(not(target_os = "linux"))]
+    let _ = wait;
+    clipboard.set_html(html, Some(alt_text))
+}
+
+/// Writes the output file atomically: `bytes` go to a hidden temp file next to
+/// `path`, which is then renamed over it, so a reader (or a killed run) never
+/// sees a half-written file. The whole output is already buffered in memory.
+/// With `no_clobber` the temp file is hard-linked into place instead, which
+/// fails if `path` exists. Where neither works (a rename across filesystems,
+/// e.g. through a bind mount, or no hard-link support) the temp file is copied
+/// over `path` non-atomically. The temp file is removed on every error path.
+fn write_output_file(path: &Path, bytes: &[u8], no_clobber: bool) -> Result<()> {
+    // Replace a symlinked output's target rather than the link itself.
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let temp_path = temp_output_path(&path);
+    let result = write_temp_output(&temp_path, &path, bytes)
+        .and_then(|()| persist_temp_output(&temp_path, &path, no_clobber));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
     }
-    if s.is_empty() {
-        "0".to_string()
-    } else {
-        s
+    result
+}
+
+/// `.NAME.dirgrab-PID.tmp` in the same directory as `path`, so the final
+/// rename stays on one filesystem.
+fn temp_output_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "dirgrab".to_string());
+    path.with_file_name(format!(".{}.dirgrab-{}.tmp", name, std::process::id()))
+}
+
+fn write_temp_output(temp_path: &Path, path: &Path, bytes: &[u8]) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(temp_path)
+        .with_context(|| format!("Failed to create temporary output file: {:?}", temp_path))?;
+    file.write_all(bytes)
+        .and_then(|()| file.sync_all())
+        .with_context(|| format!("Failed to write content to file: {:?}", temp_path))?;
+    // Keep the mode of the file being replaced.
+    if let Ok(metadata) = fs::metadata(path) {
+        let _ = fs::set_permissions(temp_path, metadata.permissions());
     }
+    Ok(())
 }
 
-fn print_stats_reports(
-    combined_content: &str,
-    file_segments: &[GrabbedFile],
-    config: &GrabConfig,
-    stats: &StatsSettings,
-    output_destination: &str,
-) {
-    let byte_count = combined_content.len();
-    let word_count = combined_content.split_whitespace().count();
-    let token_basis = build_token_basis(combined_content, config, stats);
-    let char_count = token_basis.chars().count();
-    let approx_tokens = if char_count == 0 {
-        0
+fn persist_temp_output(temp_path: &Path, path: &Path, no_clobber: bool) -> Result<()> {
+    let linked = if no_clobber {
+        fs::hard_link(temp_path, path).and_then(|()| fs::remove_file(temp_path))
     } else {
-        (char_count as f64 / stats.token_ratio).ceil() as usize
+        fs::rename(temp_path, path)
     };
-    let ratio_display = format_ratio(stats.token_ratio);
-
-    let mut first_report = true;
-    for report in &stats.reports {
-        if !first_report {
-            eprintln!();
-        }
-        match report {
-            StatsReport::Overview => {
-                eprintln!(
-                    "Output Size (to {}): {} bytes, {} words, tokens≈{} (ratio={})",
-                    output_destination, byte_count, word_count, approx_tokens, ratio_display
-                );
-            }
-            StatsReport::TopFiles { count } => {
-                print_top_files_report(combined_content, file_segments, stats, *count);
-            }
+    match linked {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => anyhow::bail!(
+            "Output file {:?} already exists; refusing to overwrite it (--no-clobber)",
+            path
+        ),
+        Err(e) if no_clobber || e.kind() == io::ErrorKind::CrossesDevices => {
+            debug!(
+                "Atomic replace of {:?} failed ({}); copying the temporary file instead",
+                path, e
+            );
+            let mut source = File::open(temp_path).with_context(|| {
+                format!("Failed to reopen temporary output file: {:?}", temp_path)
+            })?;
+            let mut file = create_output_file(path, no_clobber)?;
+            io::copy(&mut source, &mut file)
+                .with_context(|| format!("Failed to write content to file: {:?}", path))?;
+            fs::remove_file(temp_path)
+                .with_context(|| format!("Failed to remove temporary output file: {:?}", temp_path))
         }
-        first_report = false;
+        Err(e) => Err(e).with_context(|| format!("Failed to replace output file: {:?}", path)),
     }
 }
 
-fn print_top_files_report(
-    combined_content: &str,
-    file_segments: &[GrabbedFile],
-    stats: &StatsSettings,
-    max_files: usize,
-) {
-    let mut entries = compute_file_token_stats(combined_content, file_segments, stats);
-    if entries.is_empty() {
-        eprintln!(
-            "Top {} files by tokens: no file content captured.",
-            max_files
-        );
-        return;
+/// Creates (or truncates) the output file. With `no_clobber`, an existing file is
+/// an error; `create_new` makes the existence check and the creation atomic.
+fn create_output_file(path: &Path, no_clobber: bool) -> Result<File> {
+    if !no_clobber {
+        return File::create(path)
+            .with_context(|| format!("Failed to create output file: {:?}", path));
     }
+    match OpenOptions::new().write(true).create_new(true).open(path) {
+        Ok(file) => Ok(file),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => anyhow::bail!(
+            "Output file {:?} already exists; refusing to overwrite it (--no-clobber)",
+            path
+        ),
+        Err(e) => Err(e).with_context(|| format!("Failed to create output file: {:?}", path)),
+    }
+}
 
-    entries.sort_by(|a, b| {
-        b.approx_tokens
-            .cmp(&a.approx_tokens)
-            .then_with(|| b.char_count.cmp(&a.char_count))
-            .then_with(|| a.path.cmp(b.path))
-    });
+#[derive(Serialize)]
+struct JsonFile<'a> {
+    path: &'a str,
+    lines: usize,
+    content: &'a str,
+}
 
-    let display_count = entries.len().min(max_files);
-    eprintln!(
-        "Top {} files by tokens (ratio={}):",
-        display_count,
+#[derive(Serialize)]
+struct JsonListedFile<'a> {
+    path: &'a str,
+    bytes: u64,
+    tracked: bool,
+}
+
+#[derive(Serialize)]
+struct JsonSkipped<'a> {
+    path: &'a str,
+    reason: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mime: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct JsonDocument<'a> {
+    files: Vec<JsonFile<'a>>,
+    skipped: Vec<JsonSkipped<'a>>,
+}
+
+/// ` [image/png]` for skip entries classified by MIME type.
+fn mime_suffix(entry: &SkippedFile) -> String {
+    entry
+        .mime
+        .as_deref()
+        .map(|mime| format!(" [{}]", mime))
+        .unwrap_or_default()
+}
+
+/// Renders the --list-format json array.
+fn render_listing_json(files: &[ListedFile]) -> Result<String> {
+    let entries: Vec<JsonListedFile> = files
+        .iter()
+        .map(|file| JsonListedFile {
+            path: &file.display_path,
+            bytes: file.bytes,
+            tracked: file.tracked,
+        })
+        .collect();
+    serde_json::to_string_pretty(&entries).context("Failed to serialize file list")
+}
+
+/// Renders the grabbed files as a JSON document or as JSON Lines. File content is
+/// the body without its header and trailing separator line (there is none with
+/// `tight`).
+fn render_structured(
+    format: OutputFormat,
+    content: &str,
+    file_segments: &[GrabbedFile],
+    skipped: &[SkippedFile],
+    tight: bool,
+) -> Result<String> {
+    let files: Vec<JsonFile> = file_segments
+        .iter()
+        .map(|file| {
+            let body = &content[file.body_range.clone()];
+            JsonFile {
+                path: &file.display_path,
+                lines: file.lines,
+                content: if tight {
+                    body
+                } else {
+                    body.strip_suffix('\n').unwrap_or(body)
+                },
+            }
+        })
+        .collect();
+
+    let mut rendered = String::new();
+    if format == OutputFormat::Jsonl {
+        for file in &files {
+            rendered.push_str(&serde_json::to_string(file).context("Failed to serialize file")?);
+            rendered.push('\n');
+        }
+    } else if format == OutputFormat::ClaudeXml {
+        rendered = render_claude_xml(&files);
+    } else {
+        let document = JsonDocument {
+            files,
+            skipped: skipped
+                .iter()
+                .map(|entry| JsonSkipped {
+                    path: &entry.display_path,
+                    reason: entry.reason.to_string(),
+                    mime: entry.mime.as_deref(),
+                })
+                .collect(),
+        };
+        rendered = serde_json::to_string_pretty(&document).context("Failed to serialize output")?;
+        rendered.push('\n');
+    }
+    Ok(rendered)
+}
+
+/// `--format claude-xml`: numbered `<document>` blocks inside `<documents>`,
+/// byte-for-byte the layout of `files-to-prompt --cxml`, which also leaves paths
+/// and contents unescaped.
+fn render_claude_xml(files: &[JsonFile]) -> String {
+    let mut xml = String::from("<documents>\n");
+    for (index, file) in files.iter().enumerate() {
+        xml.push_str(&format!(
+            "<document index=\"{}\">\n<source>{}</source>\n<document_content>\n",
+            index + 1,
+            file.path
+        ));
+        xml.push_str(file.content);
+        if !file.content.is_empty() && !file.content.ends_with('\n') {
+            xml.push('\n');
+        }
+        xml.push_str("</document_content>\n</document>\n");
+    }
+    xml.push_str("</documents>\n");
+    xml
+}
+
+/// Warns about exclude patterns that matched nothing. The auto-excluded active
+/// output file is ignored since it legitimately may not exist yet.
+fn report_unused_excludes(output_path: Option<&Path>, unused: &[String]) {
+    let output_name = output_path
+        .and_then(|p| p.file_name())
+        .and_then(|n| n.to_str());
+    let unused: Vec<&str> = unused
+        .iter()
+        .map(String::as_str)
+        .filter(|pattern| Some(*pattern) != output_name)
+        .collect();
+    if !unused.is_empty() {
+        warn!(
+            "{} exclude pattern(s) did not match any files: {}",
+            unused.len(),
+            unused.join(", ")
+        );
+    }
+}
+
+/// Exits with a dedicated status code for selection guardrail failures so CI
+/// can tell them apart from ordinary errors.
+/// Lists the selected files, lets the user tick a subset in a checklist drawn on
+/// stdout, and narrows `config` to the chosen files via `explicit_files`.
+fn select_interactively(mut config: GrabConfig) -> Result<GrabConfig> {
+    if !io::stdout().is_terminal() {
+        anyhow::bail!(
+            "--interactive needs a terminal on stdout; use --files-from to grab a fixed list instead"
+        );
+    }
+    // Real display paths, even with --anonymize, so the choice maps back to files.
+    let listing_config = GrabConfig {
+        anonymize: Vec::new(),
+        ..config.clone()
+    };
+    let files = match list_files(&listing_config) {
+        Ok(files) => files,
+        Err(e) => {
+            return Err(anyhow::Error::new(e).context("Failed to list files"));
+        }
+    };
+    if files.is_empty() {
+        anyhow::bail!("--interactive found no files to choose from");
+    }
+    let chosen = dialoguer::MultiSelect::new()
+        .with_prompt("Select files to grab (space toggles, enter confirms)")
+        .items(&files)
+        .interact_on(&dialoguer::console::Term::stdout())
+        .context("Interactive selection failed")?;
+    if chosen.is_empty() {
+        anyhow::bail!("No files were selected");
+    }
+    info!(
+        "{} of {} file(s) selected interactively.",
+        chosen.len(),
+        files.len()
+    );
+    config.explicit_files = Some(
+        chosen
+            .into_iter()
+            .map(|index| PathBuf::from(&files[index]))
+            .collect(),
+    );
+    Ok(config)
+}
+
+fn build_token_basis<'a>(
+    tree: &str,
+    file_content: &'a str,
+    file_segments: &[GrabbedFile],
+    config: &GrabConfig,
+    stats: &StatsSettings,
+) -> Cow<'a, str> {
+    let mut current = Cow::Borrowed(file_content);
+
+    if stats.exclude_headers && (config.add_headers || config.frontmatter) {
+        let without_headers = strip_header_ranges(current.as_ref(), file_segments);
+        current = Cow::Owned(without_headers);
+    }
+
+    if stats.exclude_tree || tree.is_empty() {
+        current
+    } else {
+        Cow::Owned(format!("{}{}", tree, current))
+    }
+}
+
+/// Removes each file's `header_range` from `content`. Using the recorded ranges
+/// (rather than matching header text) works for every header style and never
+/// strips file content that happens to look like a header.
+fn strip_header_ranges(content: &str, file_segments: &[GrabbedFile]) -> String {
+    let mut result = String::with_capacity(content.len());
+    let mut cursor = 0;
+    for range in file_segments.iter().flat_map(|segment| {
+        segment
+            .header_range
+            .iter()
+            .chain(segment.frontmatter_range.iter())
+    }) {
+        if range.start < cursor || range.end > content.len() {
+            continue;
+        }
+        result.push_str(&content[cursor..range.start]);
+        cursor = range.end;
+    }
+    result.push_str(&content[cursor..]);
+    result
+}
+
+fn format_ratio(ratio: f64) -> String {
+    let mut s = format!("{:.3}", ratio);
+    while s.contains('.') && s.ends_with('0') {
+        s.pop();
+    }
+    if s.ends_with('.') {
+        s.pop();
+    }
+    if s.is_empty() {
+        "0".to_string()
+    } else {
+        s
+    }
+}
+
+fn print_stats_reports(
+    output: &GrabOutput,
+    config: &GrabConfig,
+    stats: &StatsSettings,
+    output_destination: &str,
+) -> Result<()> {
+    let tree = output.tree.as_deref().unwrap_or_default();
+    let file_content = output.content.as_str();
+    let file_segments = output.files.as_slice();
+    let byte_count = tree.len() + file_content.len();
+    let word_count = tree.split_whitespace().count() + file_content.split_whitespace().count();
+    let token_basis = build_token_basis(tree, file_content, file_segments, config, stats);
+    let char_count = token_basis.chars().count();
+    let approx_tokens = if char_count == 0 {
+        0
+    } else {
+        (char_count as f64 / stats.token_ratio).ceil() as usize
+    };
+    if let Some(path) = stats.output.as_deref() {
+        let document = JsonStats {
+            destination: output_destination,
+            bytes: byte_count,
+            words: word_count,
+            approx_tokens,
+            token_ratio: stats.token_ratio,
+            context_window: stats
+                .context_window
+                .as_ref()
+                .map(|window| JsonContextWindow {
+                    model: window.model.as_deref(),
+                    tokens: window.tokens,
+                    percent: (approx_tokens as f64 * 100.0 / window.tokens as f64).round() as usize,
+                }),
+            files: ranked_file_token_stats(file_content, file_segments, stats)
+                .into_iter()
+                .map(|entry| JsonFileTokens {
+                    path: entry.path,
+                    approx_tokens: entry.approx_tokens,
+                    chars: entry.char_count,
+                })
+                .collect(),
+            savings: output
+                .savings
+                .iter()
+                .map(|entry| JsonSavings {
+                    name: entry.name,
+                    bytes_saved: entry.bytes_saved,
+                })
+                .collect(),
+            dedupe: output.dedupe_stats.as_ref().map(|dedupe| JsonDedupe {
+                files: dedupe.files,
+                bytes_saved: dedupe.bytes_saved,
+            }),
+        };
+        let mut json =
+            serde_json::to_string_pretty(&document).context("Failed to serialize stats to JSON")?;
+        json.push('\n');
+        let omitted: Vec<&str> = stats
+            .reports
+            .iter()
+            .filter_map(|report| match report {
+                StatsReport::LongestLines { .. } => Some("longest-lines"),
+                StatsReport::Duplicates => Some("duplicates"),
+                StatsReport::TreeCost => Some("tree-cost"),
+                StatsReport::Overview | StatsReport::TopFiles { .. } | StatsReport::Savings => None,
+            })
+            .collect();
+        if !omitted.is_empty() {
+            warn!(
+                "--stats-output does not include the {} report(s); they are skipped.",
+                omitted.join(", ")
+            );
+        }
+        info!("Writing stats to {:?}", path);
+        return write_output_file(path, json.as_bytes(), false)
+            .with_context(|| format!("Failed to write stats to {}", path.display()));
+    }
+    let ratio_display = format_ratio(stats.token_ratio);
+
+    let mut first_report = true;
+    for report in &stats.reports {
+        if !first_report {
+            eprintln!();
+        }
+        match report {
+            StatsReport::Overview => {
+                eprintln!(
+                    "Output Size (to {}): {} bytes, {} words, tokens≈{} (ratio={})",
+                    output_destination, byte_count, word_count, approx_tokens, ratio_display
+                );
+                if let Some(window) = &stats.context_window {
+                    eprintln!("Context window: {}", window.fit_line(approx_tokens));
+                }
+                if let Some(dedupe) = &output.dedupe_stats {
+                    eprintln!("{}", dedupe_overview_line(dedupe, stats));
+                }
+            }
+            StatsReport::TopFiles { count } => {
+                print_top_files_report(file_content, file_segments, stats, *count);
+            }
+            StatsReport::Savings => {
+                for line in savings_report_lines(&output.savings, stats) {
+                    eprintln!("{}", line);
+                }
+            }
+            StatsReport::LongestLines { count } => {
+                for line in longest_lines_report(file_content, file_segments, *count) {
+                    eprintln!("{}", line);
+                }
+            }
+            StatsReport::Duplicates => {
+                for line in duplicates_report_lines(&output.duplicates, stats) {
+                    eprintln!("{}", line);
+                }
+            }
+            StatsReport::TreeCost => {
+                for line in tree_cost_lines(tree, file_content, stats) {
+                    eprintln!("{}", line);
+                }
+            }
+        }
+        first_report = false;
+    }
+    Ok(())
+}
+
+/// The `--stats-output` document: the overview numbers plus every file's
+/// token estimate, largest first.
+#[derive(Serialize)]
+struct JsonStats<'a> {
+    destination: &'a str,
+    bytes: usize,
+    words: usize,
+    approx_tokens: usize,
+    token_ratio: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    context_window: Option<JsonContextWindow<'a>>,
+    files: Vec<JsonFileTokens<'a>>,
+    savings: Vec<JsonSavings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dedupe: Option<JsonDedupe>,
+}
+
+#[derive(Serialize)]
+struct JsonContextWindow<'a> {
+    model: Option<&'a str>,
+    tokens: usize,
+    percent: usize,
+}
+
+#[derive(Serialize)]
+struct JsonFileTokens<'a> {
+    path: &'a str,
+    approx_tokens: usize,
+    chars: usize,
+}
+
+#[derive(Serialize)]
+struct JsonSavings {
+    name: &'static str,
+    bytes_saved: isize,
+}
+
+#[derive(Serialize)]
+struct JsonDedupe {
+    files: usize,
+    bytes_saved: usize,
+}
+
+/// One line per enabled transformation, e.g. `redact saved ~1.2k tokens (4320 bytes)`.
+fn savings_report_lines(savings: &[TransformSavings], stats: &StatsSettings) -> Vec<String> {
+    if savings.is_empty() {
+        return vec!["Savings: no content transformations were enabled.".to_string()];
+    }
+    savings
+        .iter()
+        .map(|entry| {
+            let bytes = entry.bytes_saved.unsigned_abs();
+            let tokens = (bytes as f64 / stats.token_ratio).round() as usize;
+            let verb = if entry.bytes_saved < 0 {
+                "added"
+            } else {
+                "saved"
+            };
+            format!(
+                "{} {} ~{} tokens ({} bytes)",
+                entry.name,
+                verb,
+                format_token_estimate(tokens),
+                bytes
+            )
+        })
+        .collect()
+}
+
+/// Overview addendum for --dedupe-normalized, e.g. `Deduped 8 files, saved ~40.0k tokens (144000 bytes)`.
+fn dedupe_overview_line(dedupe: &DedupeStats, stats: &StatsSettings) -> String {
+    let tokens = (dedupe.bytes_saved as f64 / stats.token_ratio).round() as usize;
+    format!(
+        "Deduped {} file{}, saved ~{} tokens ({} bytes)",
+        dedupe.files,
+        if dedupe.files == 1 { "" } else { "s" },
+        format_token_estimate(tokens),
+        dedupe.bytes_saved
+    )
+}
+
+/// The --why-ignored report, e.g. `  build/: .gitignore:2: /build`.
+fn why_ignored_lines(ignored: &[IgnoredPath]) -> Vec<String> {
+    if ignored.is_empty() {
+        return vec!["Why ignored: Git ignores no untracked files here.".to_string()];
+    }
+    let mut lines = vec![format!(
+        "Why ignored: {} path{} ignored by Git (path: source:line: pattern):",
+        ignored.len(),
+        if ignored.len() == 1 { "" } else { "s" }
+    )];
+    lines.extend(ignored.iter().map(|entry| {
+        format!(
+            "  {}: {}:{}: {}",
+            entry.display_path, entry.source, entry.line, entry.pattern
+        )
+    }));
+    lines
+}
+
+/// Closing line of --plan, e.g. `Total: 12 files, 35328 bytes, tokens≈9814`.
+fn plan_total_line(plan: &GrabPlan, stats: &StatsSettings) -> String {
+    let tokens = (plan.total_bytes as f64 / stats.token_ratio).ceil() as u64;
+    format!(
+        "Total: {} file{}, {} bytes, tokens≈{}",
+        plan.file_count,
+        if plan.file_count == 1 { "" } else { "s" },
+        plan.total_bytes,
+        tokens
+    )
+}
+
+/// Output of --dry-run-stats: totals, the context-window fit if configured, the
+/// largest files by tokens (as many as a `top-files` report asks for), and the
+/// number of selected files that would be skipped.
+fn dry_run_stats_lines(measure: &ContentMeasure, stats: &StatsSettings) -> Vec<String> {
+    let tokens = |chars: usize| (chars as f64 / stats.token_ratio).ceil() as usize;
+    let bytes: usize = measure.files.iter().map(|file| file.bytes).sum();
+    let words: usize = measure.files.iter().map(|file| file.words).sum();
+    let chars: usize = measure.files.iter().map(|file| file.chars).sum();
+    let count = measure.files.len();
+    let mut lines = vec![format!(
+        "Dry run: {} file{} read, {} bytes, {} words, tokens≈{} (ratio={}); headers and tree not counted",
+        count,
+        if count == 1 { "" } else { "s" },
+        bytes,
+        words,
+        tokens(chars),
+        format_ratio(stats.token_ratio)
+    )];
+    if let Some(window) = &stats.context_window {
+        lines.push(format!(
+            "Context window: {}",
+            window.fit_line(tokens(chars))
+        ));
+    }
+
+    let top = stats
+        .reports
+        .iter()
+        .find_map(|report| match report {
+            StatsReport::TopFiles { count } => Some(*count),
+            _ => None,
+        })
+        .unwrap_or(DEFAULT_TOP_FILES_COUNT);
+    let mut ranked: Vec<_> = measure.files.iter().collect();
+    ranked.sort_by(|a, b| {
+        b.chars
+            .cmp(&a.chars)
+            .then_with(|| a.display_path.cmp(&b.display_path))
+    });
+    if top > 0 && !ranked.is_empty() {
+        lines.push(format!("Top {} files by tokens:", top.min(ranked.len())));
+        for (idx, file) in ranked.into_iter().take(top).enumerate() {
+            lines.push(format!(
+                "{}. {} — tokens≈{} (chars={})",
+                idx + 1,
+                file.display_path,
+                tokens(file.chars),
+                file.chars
+            ));
+        }
+    }
+    if !measure.skipped.is_empty() {
+        lines.push(format!(
+            "Skipped: {} selected file{} would be left out of the output",
+            measure.skipped.len(),
+            if measure.skipped.len() == 1 { "" } else { "s" }
+        ));
+    }
+    lines
+}
+
+/// The tree listing without the `DIRECTORY STRUCTURE` / `FILE CONTENTS` banners
+/// the library wraps it in, for templates that supply their own framing.
+fn bare_tree(tree: &str) -> &str {
+    tree.strip_prefix("---\nDIRECTORY STRUCTURE\n---\n")
+        .and_then(|rest| rest.strip_suffix("---\nFILE CONTENTS\n---\n\n"))
+        .unwrap_or(tree)
+}
+
+/// Fills the `{{tree}}`, `{{files}}`, and `{{NAME}}` placeholders of a --template.
+/// Substituted values are not scanned again, so file contents may contain `{{`.
+/// Errors on a placeholder without a value; warns about unused `--var`s.
+fn render_template(
+    template: &str,
+    tree: &str,
+    files: &str,
+    vars: &[(String, String)],
+) -> Result<String> {
+    let mut rendered = String::with_capacity(template.len() + tree.len() + files.len());
+    let mut used = HashSet::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let name = rest[start + 2..start + 2 + len].trim();
+        let value = match name {
+            "tree" => tree,
+            "files" => files,
+            _ => match vars.iter().rev().find(|(var, _)| var == name) {
+                Some((_, value)) => value,
+                None => anyhow::bail!(
+                    "Template placeholder {{{{{}}}}} has no value; pass --var {}=VALUE",
+                    name,
+                    name
+                ),
+            },
+        };
+        used.insert(name);
+        rendered.push_str(&rest[..start]);
+        rendered.push_str(value);
+        rest = &rest[start + 2 + len + 2..];
+    }
+    rendered.push_str(rest);
+
+    for (name, _) in vars {
+        if !used.contains(name.as_str()) {
+            warn!("--var {} is not used by the template.", name);
+        }
+    }
+    Ok(rendered)
+}
+
+/// --print-checksum line for the generated text content (before `--format` and
+/// `--encoding` are applied), e.g. `sha256: 9f86d0…`.
+fn checksum_line(content: &str) -> String {
+    format!("sha256: {}", sha256_hex(content.as_bytes()))
+}
+
+/// The first enabled option whose output depends on more than file contents
+/// and paths (modification times, Git history).
+fn volatile_content_flag(config: &GrabConfig) -> Option<&'static str> {
+    if config.frontmatter {
+        Some("--frontmatter")
+    } else if config.git_history.is_some() {
+        Some("--git-history")
+    } else {
+        None
+    }
+}
+
+fn format_token_estimate(tokens: usize) -> String {
+    if tokens < 1000 {
+        tokens.to_string()
+    } else if tokens < 1_000_000 {
+        format!("{:.1}k", tokens as f64 / 1000.0)
+    } else {
+        format!("{:.1}m", tokens as f64 / 1_000_000.0)
+    }
+}
+
+/// One line per file for `--dump-ranges`, e.g.
+/// `a.rs: full=0..40 (40 bytes) header=0..18 (18 bytes) frontmatter=- body=18..39 (21 bytes)`.
+/// Ranges are offsets into the file sections (`GrabOutput::content`); a range
+/// that is out of bounds or splits a UTF-8 character is flagged `INVALID`.
+fn dump_ranges_lines(file_content: &str, file_segments: &[GrabbedFile]) -> Vec<String> {
+    let describe = |range: &std::ops::Range<usize>| match file_content.get(range.clone()) {
+        Some(text) => format!("{}..{} ({} bytes)", range.start, range.end, text.len()),
+        None => format!("{}..{} (INVALID)", range.start, range.end),
+    };
+    let describe_opt = |range: &Option<std::ops::Range<usize>>| {
+        range.as_ref().map_or_else(|| "-".to_string(), describe)
+    };
+    let mut lines = vec![format!(
+        "Ranges ({} files, {} bytes of file sections):",
+        file_segments.len(),
+        file_content.len()
+    )];
+    for segment in file_segments {
+        lines.push(format!(
+            "{}: full={} header={} frontmatter={} body={}",
+            segment.display_path,
+            describe(&segment.full_range),
+            describe_opt(&segment.header_range),
+            describe_opt(&segment.frontmatter_range),
+            describe(&segment.body_range)
+        ));
+    }
+    lines
+}
+
+/// Size of the directory tree section next to the file sections, e.g.
+/// `Tree: 2048 bytes, tokens≈569 (12% of tokens)`, to judge whether `--no-tree`
+/// is worth it. Counted like the overview, ignoring `--tokens-exclude-*`.
+fn tree_cost_lines(tree: &str, file_content: &str, stats: &StatsSettings) -> Vec<String> {
+    if tree.is_empty() {
+        return vec!["Tree cost: no directory tree in this output.".to_string()];
+    }
+    let tokens = |text: &str| (text.chars().count() as f64 / stats.token_ratio).ceil() as usize;
+    let tree_tokens = tokens(tree);
+    let content_tokens = tokens(file_content);
+    let share = tree_tokens as f64 * 100.0 / (tree_tokens + content_tokens) as f64;
+    vec![
+        format!(
+            "Tree: {} bytes, tokens≈{} ({:.0}% of tokens)",
+            tree.len(),
+            tree_tokens,
+            share
+        ),
+        format!(
+            "File contents: {} bytes, tokens≈{}",
+            file_content.len(),
+            content_tokens
+        ),
+    ]
+}
+
+/// Groups of files whose content was byte-identical as read (hashed by the
+/// library during processing, so `--dedupe-normalized`, line prefixes, and
+/// other transformations do not hide or break them), largest waste first,
+/// e.g. `- a.txt, b.txt (120 bytes each, 120 bytes redundant)`. Every copy
+/// after the first counts as redundant.
+fn duplicates_report_lines(groups: &[DuplicateGroup], stats: &StatsSettings) -> Vec<String> {
+    if groups.is_empty() {
+        return vec!["Duplicates: no files with identical content.".to_string()];
+    }
+    let redundant = |group: &DuplicateGroup| group.bytes * (group.paths.len() - 1);
+    let mut groups: Vec<&DuplicateGroup> = groups.iter().collect();
+    groups.sort_by(|a, b| {
+        redundant(b)
+            .cmp(&redundant(a))
+            .then_with(|| a.paths.cmp(&b.paths))
+    });
+
+    let total_bytes: usize = groups.iter().map(|group| redundant(group)).sum();
+    let total_files: usize = groups.iter().map(|group| group.paths.len() - 1).sum();
+    let tokens = (total_bytes as f64 / stats.token_ratio).round() as usize;
+    let mut lines = vec![format!(
+        "Duplicates: {} group(s), {} redundant file(s), ~{} tokens ({} bytes)",
+        groups.len(),
+        total_files,
+        format_token_estimate(tokens),
+        total_bytes
+    )];
+    for group in groups {
+        lines.push(format!(
+            "- {} ({} bytes each, {} bytes redundant)",
+            group.paths.join(", "),
+            group.bytes,
+            redundant(group)
+        ));
+    }
+    lines
+}
+
+/// Files ranked by their longest line (in characters), to surface minified or
+/// blob-like files that are small overall, e.g.
+/// `1. dist/app.min.js — longest line 48213 chars (line 1)`.
+fn longest_lines_report(
+    file_content: &str,
+    file_segments: &[GrabbedFile],
+    max_files: usize,
+) -> Vec<String> {
+    let mut entries: Vec<(&str, usize, usize)> = file_segments
+        .iter()
+        .filter_map(|segment| {
+            let body = &file_content[segment.body_range.clone()];
+            body.lines()
+                .enumerate()
+                .map(|(index, line)| (line.chars().count(), index + 1))
+                // Keep the first of equally long lines.
+                .fold(
+                    None,
+                    |best: Option<(usize, usize)>, (len, line)| match best {
+                        Some((best_len, _)) if best_len >= len => best,
+                        _ => Some((len, line)),
+                    },
+                )
+                .filter(|&(len, _)| len > 0)
+                .map(|(len, line)| (segment.display_path.as_str(), len, line))
+        })
+        .collect();
+    if entries.is_empty() {
+        return vec![format!(
+            "Top {} files by longest line: no file content captured.",
+            max_files
+        )];
+    }
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+    let display_count = entries.len().min(max_files);
+    let mut lines = vec![format!("Top {} files by longest line:", display_count)];
+    for (idx, (path, len, line)) in entries.into_iter().take(display_count).enumerate() {
+        lines.push(format!(
+            "{}. {} — longest line {} chars (line {})",
+            idx + 1,
+            path,
+            len,
+            line
+        ));
+    }
+    lines
+}
+
+fn print_top_files_report(
+    combined_content: &str,
+    file_segments: &[GrabbedFile],
+    stats: &StatsSettings,
+    max_files: usize,
+) {
+    let entries = ranked_file_token_stats(combined_content, file_segments, stats);
+    if entries.is_empty() {
+        eprintln!(
+            "Top {} files by tokens: no file content captured.",
+            max_files
+        );
+        return;
+    }
+
+    let display_count = entries.len().min(max_files);
+    eprintln!(
+        "Top {} files by tokens (ratio={}):",
+        display_count,
         format_ratio(stats.token_ratio)
     );
     for (idx, entry) in entries.into_iter().take(display_count).enumerate() {
@@ -397,6 +2136,22 @@ fn print_top_files_report(
     }
 }
 
+/// Per-file token estimates, most tokens first.
+fn ranked_file_token_stats<'a>(
+    combined_content: &'a str,
+    file_segments: &'a [GrabbedFile],
+    stats: &StatsSettings,
+) -> Vec<FileTokenStat<'a>> {
+    let mut entries = compute_file_token_stats(combined_content, file_segments, stats);
+    entries.sort_by(|a, b| {
+        b.approx_tokens
+            .cmp(&a.approx_tokens)
+            .then_with(|| b.char_count.cmp(&a.char_count))
+            .then_with(|| a.path.cmp(b.path))
+    });
+    entries
+}
+
 struct FileTokenStat<'a> {
     path: &'a str,
     approx_tokens: usize,
@@ -442,24 +2197,117 @@ impl Cli {
     fn test_default() -> Self {
         Self {
             target_path: None,
+            command: None,
             output: None,
+            no_clobber: false,
+            print_checksum: false,
+            dump_ranges: false,
+            template: None,
+            vars: Vec::new(),
+            clipboard_wait: false,
+            mirror: None,
+            mirror_copy_binaries: false,
             clipboard: false,
+            clip_html: false,
+            output_encoding: None,
+            format: None,
             list: false,
+            list_format: None,
+            plan: false,
+            dry_run_stats: false,
+            files_from: None,
+            interactive: false,
             no_headers: false,
+            frontmatter: false,
+            prefix_lines: false,
+            tight: false,
+            no_body: false,
+            group_under: None,
+            detect_language: false,
+            explain_selection: false,
+            follow_symlinks: false,
+            relativize_symlink_targets: false,
+            dedupe_normalized: false,
+            respect_editorconfig: false,
+            strip_imports: false,
+            fold_imports: false,
+            comment_style: None,
+            header_prefix: None,
+            header_suffix: None,
+            link_base: None,
+            wrap_open: None,
+            wrap_close: None,
+            label_language: false,
+            hash_headers: false,
             no_tree: false,
+            tree_show_excluded: false,
+            tree_collapse_chains: false,
+            tree_max_depth: None,
+            tree_max_entries: None,
+            tree_style: None,
             no_pdf: false,
+            pdf_max_pages: None,
+            max_pdf_bytes: None,
+            pdf_jobs: None,
+            binary: None,
+            read_retries: None,
+            image_metadata: false,
+            extractors: Vec::new(),
             exclude_patterns: Vec::new(),
+            ignore_files: Vec::new(),
+            prepend_files: Vec::new(),
+            append_files: Vec::new(),
+            warn_unused_excludes: false,
+            skip_generated: false,
+            generated_markers: Vec::new(),
+            exclude_matching: Vec::new(),
+            include_mime: Vec::new(),
+            exclude_mime: Vec::new(),
+            sniff_only_above: None,
+            redact: Vec::new(),
+            redact_files: Vec::new(),
+            anonymize: Vec::new(),
+            anonymize_content: false,
             include_default_output: false,
+            sort: None,
+            sort_desc: false,
+            deprioritize: Vec::new(),
+            content_for: Vec::new(),
+            readme_first: false,
+            partition: Vec::new(),
+            collate: None,
+            tree_partitioned: false,
+            min_files: None,
+            max_per_dir: None,
+            include_patterns: Vec::new(),
+            context_budget: None,
+            budget_strategy: None,
+            budget_min_lines: None,
             no_git: false,
+            gitignore_only: false,
+            root_marker: None,
             tracked_only: false,
             all_repo: false,
+            changed_in_last: None,
+            git_history: None,
+            show_force_added: false,
+            show_mode: false,
+            why_ignored: false,
+            between: None,
+            since_commit: None,
+            git_attr: None,
             stats: None,
             no_config: false,
             config_path: None,
+            dump_config: None,
             token_ratio: None,
             tokens_exclude_tree: false,
             tokens_exclude_headers: false,
+            model: None,
+            model_window: None,
+            stats_output: None,
             include_untracked_flag: false,
+            no_progress: false,
             verbose: 0,
         }
     }
@@ -478,7 +2326,11 @@ mod tests {
             display_path: "foo.txt".to_string(),
             full_range: 0..content.len(),
             header_range: Some(0..header.len()),
+            frontmatter_range: None,
             body_range: header.len()..content.len(),
+            lines: 1,
+            force_added: false,
+            mode: None,
         };
 
         let mut stats = StatsSettings {
@@ -487,6 +2339,8 @@ mod tests {
             exclude_tree: false,
             exclude_headers: false,
             reports: vec![StatsReport::Overview],
+            context_window: None,
+            output: None,
         };
 
         let files = [file.clone()];
@@ -501,6 +2355,48 @@ mod tests {
         assert_eq!(without_headers[0].char_count, body.chars().count());
     }
 
+    #[test]
+    fn render_template_fills_placeholders() -> Result<()> {
+        let vars = vec![("task".to_string(), "Review {{files}}".to_string())];
+        let rendered = render_template(
+            "Context:\n{{tree}}\nFiles:\n{{ files }}\nTask: {{task}}{{",
+            "TREE",
+            "a {{b}} c",
+            &vars,
+        )?;
+        assert_eq!(
+            rendered,
+            "Context:\nTREE\nFiles:\na {{b}} c\nTask: Review {{files}}{{"
+        );
+
+        let err = render_template("{{missing}}", "", "", &vars).unwrap_err();
+        assert!(err.to_string().contains("--var missing=VALUE"));
+
+        assert_eq!(
+            parse_template_var("task=fix the bug"),
+            Ok(("task".to_string(), "fix the bug".to_string()))
+        );
+        assert!(parse_template_var("files=x").is_err());
+        assert!(parse_template_var("novalue").is_err());
+        assert!(Cli::try_parse_from(["dirgrab", "--var", "a=b"]).is_err());
+        assert_eq!(
+            bare_tree("---\nDIRECTORY STRUCTURE\n---\n- a.txt\n\n---\nFILE CONTENTS\n---\n\n"),
+            "- a.txt\n\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn interactive_conflicts_with_other_selection_modes() {
+        assert!(Cli::parse_from(["dirgrab", "--interactive"]).interactive);
+        for other in ["--list", "--plan"] {
+            assert!(Cli::try_parse_from(["dirgrab", "--interactive", other]).is_err());
+        }
+        assert!(
+            Cli::try_parse_from(["dirgrab", "--interactive", "--files-from", "list.txt"]).is_err()
+        );
+    }
+
     #[test]
     fn exclude_flag_each_e_takes_one_value() {
         let cli = Cli::parse_from(["dirgrab", "-e", "foo", "-e", "bar"]);
@@ -521,27 +2417,587 @@ mod tests {
         assert!(result.is_err(), "--tracked-only and -u should conflict");
     }
 
+    /// Builds segments for `(header, body)` pairs laid out back to back.
+    fn segments_for(parts: &[(Option<&str>, &str)]) -> (String, Vec<GrabbedFile>) {
+        let mut content = String::new();
+        let mut files = Vec::new();
+        for (idx, (header, body)) in parts.iter().enumerate() {
+            let start = content.len();
+            let header_range = header.map(|h| {
+                content.push_str(h);
+                start..content.len()
+            });
+            let body_start = content.len();
+            content.push_str(body);
+            files.push(GrabbedFile {
+                display_path: format!("file{}", idx),
+                full_range: start..content.len(),
+                header_range,
+                frontmatter_range: None,
+                body_range: body_start..content.len(),
+                lines: body.matches('\n').count().saturating_sub(1),
+                force_added: false,
+                mode: None,
+            });
+        }
+        (content, files)
+    }
+
     #[test]
-    fn strip_header_lines_removes_real_headers() {
-        let content = "--- FILE: foo.txt ---\nhello world\n--- FILE: bar.rs ---\nfn main() {}\n";
-        let result = strip_header_lines(content);
+    fn strip_header_ranges_removes_real_headers() {
+        let (content, files) = segments_for(&[
+            (Some("--- FILE: foo.txt ---\n"), "hello world\n"),
+            (Some("--- FILE: bar.rs ---\n"), "fn main() {}\n"),
+        ]);
+        let result = strip_header_ranges(&content, &files);
         assert_eq!(result, "hello world\nfn main() {}\n");
     }
 
     #[test]
-    fn strip_header_lines_keeps_prefix_only_lines() {
-        // A line that starts with "--- FILE: " but doesn't end with " ---"
-        // should NOT be stripped (it's file content, not a header)
-        let content = "--- FILE: this is not a header\nhello\n--- FILE: real.txt ---\nworld\n";
-        let result = strip_header_lines(content);
-        assert_eq!(result, "--- FILE: this is not a header\nhello\nworld\n");
+    fn strip_header_ranges_keeps_header_like_content() {
+        // Body text that looks like a header is not inside a header_range,
+        // so it must be preserved.
+        let (content, files) = segments_for(&[
+            (
+                Some("--- FILE: real.txt ---\n"),
+                "--- FILE: this is not a header ---\nhello\n",
+            ),
+            (Some("--- FILE: other.txt ---\n"), "world\n"),
+        ]);
+        let result = strip_header_ranges(&content, &files);
+        assert_eq!(result, "--- FILE: this is not a header ---\nhello\nworld\n");
+    }
+
+    #[test]
+    fn strip_header_ranges_handles_pdf_and_comment_headers() {
+        let (content, files) = segments_for(&[
+            (
+                Some("--- FILE: doc.pdf (extracted text) ---\n"),
+                "pdf content\n",
+            ),
+            (Some("<!-- FILE: index.html -->\n"), "<p>hi</p>\n"),
+            (None, "headerless\n"),
+        ]);
+        let result = strip_header_ranges(&content, &files);
+        assert_eq!(result, "pdf content\n<p>hi</p>\nheaderless\n");
+    }
+
+    #[test]
+    fn comment_style_flags_parse() {
+        let cli = Cli::parse_from(["dirgrab", "--comment-style", "python"]);
+        assert_eq!(cli.comment_style, Some(HeaderStyle::Python));
+        let result = Cli::try_parse_from(["dirgrab", "--comment-style", "cobol"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn structured_formats_include_line_counts() -> Result<()> {
+        let (content, mut files) = segments_for(&[
+            (Some("--- FILE: a.txt ---\n"), "one\ntwo\n\n"),
+            (
+                Some("--- FILE: empty.pdf (PDF extraction failed) ---\n"),
+                "\n",
+            ),
+        ]);
+        files[0].lines = 2;
+        let skipped = [SkippedFile {
+            display_path: "bin.dat".to_string(),
+            reason: dirgrab_lib::SkipReason::NonUtf8,
+            mime: Some("application/zip".to_string()),
+        }];
+
+        let json = render_structured(OutputFormat::Json, &content, &files, &skipped, false)?;
+        let parsed: serde_json::Value = serde_json::from_str(&json)?;
+        assert_eq!(parsed["files"][0]["path"], "file0");
+        assert_eq!(parsed["files"][0]["lines"], 2);
+        assert_eq!(parsed["files"][0]["content"], "one\ntwo\n");
+        assert_eq!(parsed["files"][1]["content"], "");
+        assert_eq!(parsed["skipped"][0]["reason"], "not valid UTF-8");
+        assert_eq!(parsed["skipped"][0]["mime"], "application/zip");
+
+        let jsonl = render_structured(OutputFormat::Jsonl, &content, &files, &skipped, false)?;
+        let lines: Vec<_> = jsonl.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            r#"{"path":"file0","lines":2,"content":"one\ntwo\n"}"#
+        );
+
+        let xml = render_structured(OutputFormat::ClaudeXml, &content, &files, &skipped, false)?;
+        assert_eq!(
+            xml,
+            "<documents>\n\
+             <document index=\"1\">\n<source>file0</source>\n\
+             <document_content>\none\ntwo\n</document_content>\n</document>\n\
+             <document index=\"2\">\n<source>file1</source>\n\
+             <document_content>\n</document_content>\n</document>\n\
+             </documents>\n"
+        );
+        Ok(())
     }
 
     #[test]
-    fn strip_header_lines_handles_pdf_headers() {
-        let content = "--- FILE: doc.pdf (extracted text) ---\npdf content\n";
-        let result = strip_header_lines(content);
-        assert_eq!(result, "pdf content\n");
+    fn savings_report_formats_token_estimates() {
+        let stats = StatsSettings {
+            enabled: true,
+            token_ratio: 4.0,
+            exclude_tree: false,
+            exclude_headers: false,
+            reports: vec![StatsReport::Savings],
+            context_window: None,
+            output: None,
+        };
+        let savings = [
+            TransformSavings {
+                name: TransformSavings::REDACT,
+                bytes_saved: -8,
+            },
+            TransformSavings {
+                name: TransformSavings::CONTEXT_BUDGET,
+                bytes_saved: 4800,
+            },
+        ];
+        assert_eq!(
+            savings_report_lines(&savings, &stats),
+            vec![
+                "redact added ~2 tokens (8 bytes)",
+                "context-budget saved ~1.2k tokens (4800 bytes)",
+            ]
+        );
+        assert_eq!(savings_report_lines(&[], &stats).len(), 1);
+        assert!(matches!(
+            parse_stats_report_spec("savings"),
+            Ok(StatsReportSpec::Explicit(StatsReport::Savings))
+        ));
+    }
+
+    #[test]
+    fn longest_lines_report_ranks_files_by_longest_line() {
+        let content = "--- FILE: a.js ---\nshort\nxxxxxxxxxx\n\n--- FILE: b.txt ---\nabc\n\n";
+        let a_body = 19..36;
+        let b_body = 57..61;
+        assert_eq!(&content[a_body.clone()], "short\nxxxxxxxxxx\n");
+        assert_eq!(&content[b_body.clone()], "abc\n");
+        let files = vec![
+            GrabbedFile {
+                display_path: "b.txt".to_string(),
+                full_range: 37..62,
+                header_range: Some(37..57),
+                frontmatter_range: None,
+                body_range: b_body,
+                lines: 1,
+                force_added: false,
+                mode: None,
+            },
+            GrabbedFile {
+                display_path: "a.js".to_string(),
+                full_range: 0..37,
+                header_range: Some(0..19),
+                frontmatter_range: None,
+                body_range: a_body,
+                lines: 2,
+                force_added: false,
+                mode: None,
+            },
+        ];
+        assert_eq!(
+            longest_lines_report(content, &files, 1),
+            vec![
+                "Top 1 files by longest line:",
+                "1. a.js — longest line 10 chars (line 2)",
+            ]
+        );
+        assert!(matches!(
+            parse_stats_report_spec("longest-lines=3"),
+            Ok(StatsReportSpec::Explicit(StatsReport::LongestLines {
+                count: 3
+            }))
+        ));
+        assert!(parse_stats_report_spec("longest-lines=0").is_err());
+    }
+
+    #[test]
+    fn duplicates_report_groups_identical_bodies() {
+        let groups = vec![
+            DuplicateGroup {
+                paths: vec!["c".to_string(), "d".to_string()],
+                bytes: 2,
+            },
+            DuplicateGroup {
+                paths: vec!["a".to_string(), "b".to_string()],
+                bytes: 5,
+            },
+        ];
+        let stats = StatsSettings {
+            enabled: true,
+            token_ratio: 1.0,
+            exclude_tree: false,
+            exclude_headers: false,
+            reports: vec![StatsReport::Duplicates],
+            context_window: None,
+            output: None,
+        };
+        assert_eq!(
+            duplicates_report_lines(&groups, &stats),
+            vec![
+                "Duplicates: 2 group(s), 2 redundant file(s), ~7 tokens (7 bytes)",
+                "- a, b (5 bytes each, 5 bytes redundant)",
+                "- c, d (2 bytes each, 2 bytes redundant)",
+            ]
+        );
+        assert_eq!(
+            duplicates_report_lines(&[], &stats),
+            vec!["Duplicates: no files with identical content."]
+        );
+        assert!(matches!(
+            parse_stats_report_spec("duplicates"),
+            Ok(StatsReportSpec::Explicit(StatsReport::Duplicates))
+        ));
+    }
+
+    #[test]
+    fn tree_cost_compares_tree_and_file_sections() {
+        let stats = StatsSettings {
+            enabled: true,
+            token_ratio: 4.0,
+            exclude_tree: true,
+            exclude_headers: false,
+            reports: vec![StatsReport::TreeCost],
+            context_window: None,
+            output: None,
+        };
+        let tree = "---\nDIRECTORY STRUCTURE\n---\n- a.rs\n";
+        let content = "--- FILE: a.rs ---\nfn main() {}\n\n".repeat(3);
+        assert_eq!(
+            tree_cost_lines(tree, &content, &stats),
+            vec![
+                "Tree: 35 bytes, tokens≈9 (26% of tokens)",
+                "File contents: 99 bytes, tokens≈25",
+            ]
+        );
+        assert_eq!(
+            tree_cost_lines("", &content, &stats),
+            vec!["Tree cost: no directory tree in this output."]
+        );
+        assert!(matches!(
+            parse_stats_report_spec("tree-cost"),
+            Ok(StatsReportSpec::Explicit(StatsReport::TreeCost))
+        ));
+    }
+
+    #[test]
+    fn dump_ranges_lists_ranges_and_flags_invalid_ones() {
+        let content = "--- FILE: a.rs ---\nfn a() {}\n\n";
+        let mut files = vec![GrabbedFile {
+            display_path: "a.rs".to_string(),
+            full_range: 0..30,
+            header_range: Some(0..19),
+            frontmatter_range: None,
+            body_range: 19..29,
+            lines: 1,
+            force_added: false,
+            mode: None,
+        }];
+        assert_eq!(
+            dump_ranges_lines(content, &files),
+            vec![
+                "Ranges (1 files, 30 bytes of file sections):",
+                "a.rs: full=0..30 (30 bytes) header=0..19 (19 bytes) frontmatter=- body=19..29 (10 bytes)",
+            ]
+        );
+        files[0].body_range = 19..31;
+        assert!(dump_ranges_lines(content, &files)[1].ends_with("body=19..31 (INVALID)"));
+    }
+
+    #[test]
+    fn no_clobber_refuses_existing_output_file() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let existing = temp.path().join("dirgrab.txt");
+        std::fs::write(&existing, "important")?;
+
+        let err = create_output_file(&existing, true).unwrap_err();
+        assert!(err.to_string().contains("already exists"));
+        assert_eq!(std::fs::read_to_string(&existing)?, "important");
+
+        let fresh = temp.path().join("fresh.txt");
+        create_output_file(&fresh, true)?.write_all(b"new")?;
+        assert_eq!(std::fs::read_to_string(&fresh)?, "new");
+
+        create_output_file(&existing, false)?;
+        assert_eq!(std::fs::read_to_string(&existing)?, "");
+        Ok(())
+    }
+
+    #[test]
+    fn write_output_file_replaces_atomically_and_cleans_up() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let output = temp.path().join("dirgrab.txt");
+        std::fs::write(&output, "old")?;
+
+        write_output_file(&output, b"new", false)?;
+        assert_eq!(std::fs::read_to_string(&output)?, "new");
+
+        let err = write_output_file(&output, b"newer", true).unwrap_err();
+        assert!(err.to_string().contains("already exists"));
+        assert_eq!(std::fs::read_to_string(&output)?, "new");
+
+        let fresh = temp.path().join("fresh.txt");
+        write_output_file(&fresh, b"fresh", true)?;
+        assert_eq!(std::fs::read_to_string(&fresh)?, "fresh");
+
+        let leftovers: Vec<_> = std::fs::read_dir(temp.path())?
+            .map(|entry| entry.map(|entry| entry.file_name()))
+            .collect::<io::Result<_>>()?;
+        assert_eq!(
+            leftovers.len(),
+            2,
+            "temp files left behind: {:?}",
+            leftovers
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn clipboard_wait_requires_clipboard() {
+        assert!(Cli::try_parse_from(["dirgrab", "--clipboard-wait"]).is_err());
+        let cli = Cli::parse_from(["dirgrab", "-c", "--clipboard-wait"]);
+        assert!(cli.clipboard && cli.clipboard_wait);
+    }
+
+    #[test]
+    fn list_format_json_renders_path_bytes_and_tracked() -> Result<()> {
+        let files = vec![dirgrab_lib::ListedFile {
+            display_path: "src/main.rs".to_string(),
+            bytes: 42,
+            tracked: true,
+        }];
+        let parsed: serde_json::Value = serde_json::from_str(&render_listing_json(&files)?)?;
+        assert_eq!(
+            parsed,
+            serde_json::json!([{ "path": "src/main.rs", "bytes": 42, "tracked": true }])
+        );
+        assert!(Cli::try_parse_from(["dirgrab", "--list-format", "json"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn dedupe_overview_line_reports_files_and_tokens() {
+        let stats = StatsSettings {
+            enabled: true,
+            token_ratio: 4.0,
+            exclude_tree: false,
+            exclude_headers: false,
+            reports: vec![StatsReport::Overview],
+            context_window: None,
+            output: None,
+        };
+        let dedupe = DedupeStats {
+            files: 8,
+            bytes_saved: 160_000,
+        };
+        assert_eq!(
+            dedupe_overview_line(&dedupe, &stats),
+            "Deduped 8 files, saved ~40.0k tokens (160000 bytes)"
+        );
+        let single = DedupeStats {
+            files: 1,
+            bytes_saved: 12,
+        };
+        assert_eq!(
+            dedupe_overview_line(&single, &stats),
+            "Deduped 1 file, saved ~3 tokens (12 bytes)"
+        );
+    }
+
+    #[test]
+    fn plan_total_line_reports_files_bytes_and_tokens() {
+        let stats = StatsSettings {
+            enabled: false,
+            token_ratio: 4.0,
+            exclude_tree: false,
+            exclude_headers: false,
+            reports: Vec::new(),
+            context_window: None,
+            output: None,
+        };
+        let plan = GrabPlan {
+            tree: String::new(),
+            file_count: 12,
+            total_bytes: 35_330,
+        };
+        assert_eq!(
+            plan_total_line(&plan, &stats),
+            "Total: 12 files, 35330 bytes, tokens≈8833"
+        );
+        assert!(Cli::try_parse_from(["dirgrab", "--plan", "--list"]).is_err());
+    }
+
+    #[test]
+    fn checksum_line_hashes_content_and_flags_volatile_options() {
+        assert_eq!(
+            checksum_line("abc"),
+            "sha256: ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let mut config = GrabConfig::default();
+        assert_eq!(volatile_content_flag(&config), None);
+        config.git_history = Some(3);
+        assert_eq!(volatile_content_flag(&config), Some("--git-history"));
+        config.frontmatter = true;
+        assert_eq!(volatile_content_flag(&config), Some("--frontmatter"));
+    }
+
+    #[test]
+    fn render_html_escapes_files_into_pre_blocks() {
+        let header = "--- FILE: a<b>.rs ---\n";
+        let body = "if a < b && c > d { \"x\" }\n\n";
+        let content = format!("{}{}", header, body);
+        let files = vec![GrabbedFile {
+            display_path: "a<b>.rs".to_string(),
+            full_range: 0..content.len(),
+            header_range: Some(0..header.len()),
+            frontmatter_range: None,
+            body_range: header.len()..content.len(),
+            lines: 1,
+            force_added: false,
+            mode: None,
+        }];
+        assert_eq!(
+            render_html("- a<b>.rs\n\n", &content, &files),
+            "<meta charset=\"utf-8\">\n<pre>- a&lt;b&gt;.rs</pre>\n\
+             <h3><code>a&lt;b&gt;.rs</code></h3>\n\
+             <pre><code class=\"language-rs\">if a &lt; b &amp;&amp; c &gt; d { &quot;x&quot; }</code></pre>\n"
+        );
+        assert!(Cli::try_parse_from(["dirgrab", "--clip-html"]).is_err());
+        assert!(Cli::try_parse_from(["dirgrab", "-c", "--clip-html"]).is_ok());
+    }
+
+    #[test]
+    fn stats_output_writes_json_instead_of_stderr() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let path = temp.path().join("stats.json");
+        let header = "--- FILE: a.txt ---\n";
+        let content = format!("{}hello world\n", header);
+        let output = GrabOutput {
+            selection: None,
+            language_summary: None,
+            tree: None,
+            content: content.clone(),
+            files: vec![GrabbedFile {
+                display_path: "a.txt".to_string(),
+                full_range: 0..content.len(),
+                header_range: Some(0..header.len()),
+                frontmatter_range: None,
+                body_range: header.len()..content.len(),
+                lines: 1,
+                force_added: false,
+                mode: None,
+            }],
+            skipped: Vec::new(),
+            unused_excludes: Vec::new(),
+            truncated: Vec::new(),
+            savings: Vec::new(),
+            duplicates: Vec::new(),
+            dedupe_stats: None,
+            anonymized: 0,
+        };
+        let stats = StatsSettings {
+            enabled: true,
+            token_ratio: 4.0,
+            exclude_tree: false,
+            exclude_headers: true,
+            reports: vec![StatsReport::Overview],
+            context_window: None,
+            output: Some(path.clone()),
+        };
+        let config = GrabConfig {
+            add_headers: true,
+            ..GrabConfig::default()
+        };
+
+        print_stats_reports(&output, &config, &stats, "stdout")?;
+        let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path)?)?;
+        assert_eq!(json["destination"], "stdout");
+        assert_eq!(json["bytes"], content.len());
+        assert_eq!(json["words"], 6);
+        assert_eq!(json["approx_tokens"], 3);
+        assert_eq!(json["files"][0]["path"], "a.txt");
+        assert_eq!(json["files"][0]["chars"], 12);
+        assert!(json.get("context_window").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn collate_conflicts_with_partition_and_enables_tree_partitioned() {
+        assert!(Cli::try_parse_from(["dirgrab", "--collate", "ext"]).is_ok());
+        assert!(Cli::try_parse_from(["dirgrab", "--collate", "lang"]).is_err());
+        assert!(Cli::try_parse_from([
+            "dirgrab",
+            "--collate",
+            "ext",
+            "--partition",
+            "src/**:Source"
+        ])
+        .is_err());
+        assert!(Cli::try_parse_from(["dirgrab", "--collate", "ext", "--tree-partitioned"]).is_ok());
+        assert!(Cli::try_parse_from(["dirgrab", "--tree-partitioned"]).is_err());
+    }
+
+    #[test]
+    fn why_ignored_lines_name_rule_per_path() {
+        assert_eq!(
+            why_ignored_lines(&[]),
+            vec!["Why ignored: Git ignores no untracked files here."]
+        );
+        let ignored = [IgnoredPath {
+            display_path: "build/".to_string(),
+            source: ".gitignore".to_string(),
+            line: 2,
+            pattern: "/build".to_string(),
+        }];
+        assert_eq!(
+            why_ignored_lines(&ignored),
+            vec![
+                "Why ignored: 1 path ignored by Git (path: source:line: pattern):",
+                "  build/: .gitignore:2: /build",
+            ]
+        );
+    }
+
+    #[test]
+    fn dry_run_stats_lines_total_and_rank_files() {
+        let stats = StatsSettings {
+            enabled: false,
+            token_ratio: 4.0,
+            exclude_tree: false,
+            exclude_headers: false,
+            reports: vec![StatsReport::TopFiles { count: 1 }],
+            context_window: None,
+            output: None,
+        };
+        let file = |path: &str, chars: usize| dirgrab_lib::MeasuredFile {
+            display_path: path.to_string(),
+            bytes: chars,
+            chars,
+            words: chars / 5,
+        };
+        let measure = ContentMeasure {
+            files: vec![file("a.rs", 40), file("big.rs", 400)],
+            skipped: vec![SkippedFile {
+                display_path: "bin.dat".to_string(),
+                reason: dirgrab_lib::SkipReason::NonUtf8,
+                mime: None,
+            }],
+        };
+        assert_eq!(
+            dry_run_stats_lines(&measure, &stats),
+            vec![
+                "Dry run: 2 files read, 440 bytes, 88 words, tokens≈110 (ratio=4); headers and tree not counted",
+                "Top 1 files by tokens:",
+                "1. big.rs — tokens≈100 (chars=400)",
+                "Skipped: 1 selected file would be left out of the output",
+            ]
+        );
+        assert!(Cli::try_parse_from(["dirgrab", "--dry-run-stats", "--plan"]).is_err());
     }
 }
 
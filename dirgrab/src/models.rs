@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+
+/// Context-window sizes (in tokens) for commonly used models, matched
+/// case-insensitively by name. Use `--model-window` for anything not listed.
+const MODEL_WINDOWS: &[(&str, usize)] = &[
+    ("gpt-4o", 128_000),
+    ("gpt-4o-mini", 128_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4", 8_000),
+    ("gpt-3.5-turbo", 16_000),
+    ("o1", 200_000),
+    ("o3", 200_000),
+    ("o3-mini", 200_000),
+    ("o4-mini", 200_000),
+    ("claude-3-haiku", 200_000),
+    ("claude-3-opus", 200_000),
+    ("claude-3-5-sonnet", 200_000),
+    ("claude-3-7-sonnet", 200_000),
+    ("claude-sonnet-4", 200_000),
+    ("claude-opus-4", 200_000),
+    ("gemini-1.5-flash", 1_000_000),
+    ("gemini-1.5-pro", 2_000_000),
+    ("gemini-2.0-flash", 1_000_000),
+    ("gemini-2.5-pro", 1_000_000),
+    ("llama-3.1", 128_000),
+    ("mistral-large", 128_000),
+];
+
+/// Looks up the context window of a known model.
+pub fn known_window(model: &str) -> Option<usize> {
+    let wanted = model.trim().to_ascii_lowercase();
+    MODEL_WINDOWS
+        .iter()
+        .find(|(name, _)| *name == wanted)
+        .map(|(_, tokens)| *tokens)
+}
+
+/// The context window the stats overview measures the grab against.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContextWindow {
+    /// Model name as given by the user; `None` when only a size was supplied.
+    pub model: Option<String>,
+    pub tokens: usize,
+}
+
+impl ContextWindow {
+    /// Resolves `--model`/`--model-window`; an explicit window always wins over
+    /// the table. Errors on an unknown model without an explicit window.
+    pub fn resolve(model: Option<&str>, window: Option<usize>) -> Result<Option<Self>, String> {
+        let model = model.map(str::trim).filter(|name| !name.is_empty());
+        let tokens = match (model, window) {
+            (_, Some(0)) => return Err("The model window must be greater than 0".to_string()),
+            (_, Some(tokens)) => tokens,
+            (Some(name), None) => known_window(name).ok_or_else(|| {
+                format!(
+                    "Unknown model '{}'; pass its size with --model-window N",
+                    name
+                )
+            })?,
+            (None, None) => return Ok(None),
+        };
+        Ok(Some(Self {
+            model: model.map(str::to_string),
+            tokens,
+        }))
+    }
+
+    /// E.g. `fits 42% of gpt-4o's 128k window` or
+    /// `exceeds gpt-4o's 128k window (135%)`.
+    pub fn fit_line(&self, approx_tokens: usize) -> String {
+        let percent = (approx_tokens as f64 * 100.0 / self.tokens as f64).round() as usize;
+        let window = match &self.model {
+            Some(name) => format!("{}'s {} window", name, format_window(self.tokens)),
+            None => format!("the {} window", format_window(self.tokens)),
+        };
+        if approx_tokens <= self.tokens {
+            format!("fits {}% of {}", percent, window)
+        } else {
+            format!("exceeds {} ({}%)", window, percent)
+        }
+    }
+}
+
+/// `128000` -> `128k`, `2000000` -> `2m`; other sizes keep one decimal.
+fn format_window(tokens: usize) -> String {
+    if tokens >= 1_000_000 && tokens.is_multiple_of(1_000_000) {
+        format!("{}m", tokens / 1_000_000)
+    } else if tokens >= 1_000_000 {
+        format!("{:.1}m", tokens as f64 / 1_000_000.0)
+    } else if tokens >= 1000 && tokens.is_multiple_of(1000) {
+        format!("{}k", tokens / 1000)
+    } else if tokens >= 1000 {
+        format!("{:.1}k", tokens as f64 / 1000.0)
+    } else {
+        tokens.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_known_models_and_formats_fit() {
+        let window = ContextWindow::resolve(Some("GPT-4o"), None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(window.tokens, 128_000);
+        assert_eq!(window.fit_line(53_760), "fits 42% of GPT-4o's 128k window");
+        assert_eq!(
+            window.fit_line(172_800),
+            "exceeds GPT-4o's 128k window (135%)"
+        );
+
+        assert!(ContextWindow::resolve(Some("my-local-model"), None).is_err());
+        let custom = ContextWindow::resolve(Some("my-local-model"), Some(32_768))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            custom.fit_line(16_384),
+            "fits 50% of my-local-model's 32.8k window"
+        );
+        let bare = ContextWindow::resolve(None, Some(2_000_000))
+            .unwrap()
+            .unwrap();
+        assert_eq!(bare.fit_line(0), "fits 0% of the 2m window");
+        assert_eq!(ContextWindow::resolve(None, None).unwrap(), None);
+        assert!(ContextWindow::resolve(None, Some(0)).is_err());
+    }
+}
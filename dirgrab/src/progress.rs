@@ -0,0 +1,86 @@
+use std::io::{self, IsTerminal};
+use std::time::Duration;
+
+use dirgrab_lib::GrabProgress;
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// Runs with fewer files than this finish quickly enough not to need a bar.
+const MIN_FILES: usize = 200;
+
+/// Draws a `files processed / total` bar on stderr from library progress
+/// events, so stdout stays clean. The bar only appears on a terminal and for
+/// runs of at least `MIN_FILES` files, and is cleared when the reporter is
+/// dropped, whether the run succeeded or not.
+pub struct ProgressReporter {
+    enabled: bool,
+    bar: Option<ProgressBar>,
+}
+
+impl ProgressReporter {
+    /// `wanted` is false for `--no-progress` or when logs would interleave
+    /// with the bar.
+    pub fn new(wanted: bool) -> Self {
+        Self {
+            enabled: wanted && io::stderr().is_terminal(),
+            bar: None,
+        }
+    }
+
+    pub fn update(&mut self, event: GrabProgress) {
+        if !self.enabled || event.total < MIN_FILES {
+            return;
+        }
+        let bar = self.bar.get_or_insert_with(|| {
+            let bar = ProgressBar::new(event.total as u64);
+            bar.set_style(
+                ProgressStyle::with_template("{spinner} [{bar:40}] {pos}/{len} files ({eta})")
+                    .unwrap_or_else(|_| ProgressStyle::default_bar())
+                    .progress_chars("=> "),
+            );
+            bar.enable_steady_tick(Duration::from_millis(120));
+            bar
+        });
+        bar.set_position(event.processed as u64);
+    }
+}
+
+impl Drop for ProgressReporter {
+    fn drop(&mut self) {
+        if let Some(bar) = self.bar.take() {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bar_only_starts_for_large_enabled_runs() {
+        let event = |processed, total| GrabProgress { processed, total };
+
+        let mut small = ProgressReporter {
+            enabled: true,
+            bar: None,
+        };
+        small.update(event(0, MIN_FILES - 1));
+        assert!(small.bar.is_none());
+
+        let mut disabled = ProgressReporter {
+            enabled: false,
+            bar: None,
+        };
+        disabled.update(event(0, MIN_FILES));
+        assert!(disabled.bar.is_none());
+
+        let mut large = ProgressReporter {
+            enabled: true,
+            bar: None,
+        };
+        large.update(event(0, MIN_FILES));
+        large.update(event(7, MIN_FILES));
+        assert_eq!(large.bar.as_ref().map(ProgressBar::position), Some(7));
+        drop(large);
+    }
+}